@@ -0,0 +1,308 @@
+#version 330 core
+in vec2 position;
+layout(location = 0) out vec4 FragColor;
+layout(location = 1) out vec4 DerivativeState; // with write_state
+
+uniform float time;
+uniform vec2 scale;
+uniform vec2 rotation; // (cos, sin) of the view rotation
+
+uniform int substeps;
+
+uniform vec2 offset;
+
+uniform vec2 resolution; // size of the whole view in pixels
+uniform vec2 pixel_offset; // of this render target within the view, for tiles
+uniform bool frag_coord_mapping; // derive the screen position from gl_FragCoord instead of the vertex output
+uniform bool log_polar; // exponential map around offset, as Viewport::log_polar
+
+uniform bool transparent_interior;
+uniform bool julia; // iterate from the point with julia_c added instead of from 0
+uniform vec2 julia_c;
+uniform float morph; // with julia, below 1 the Mandelbrot set is mixed in by 1 - morph
+uniform int color_mode; // 0 escape time, 1 angle, 2 blend, as palette::ColorMode
+uniform int color_span; // iterations the palette spreads over, 0 for substeps
+uniform bool checkerboard;
+uniform bool backdrop; // composite over backdrop_color, leaving nothing see-through
+uniform vec3 backdrop_color;
+uniform bool premultiplied; // for a see-through window, which takes the alpha premultiplied
+uniform bool slow_tint; // color interior points that ended with |z| over slow_threshold
+uniform vec3 slow_color;
+uniform float slow_threshold;
+uniform bool relief; // light the exterior by the slope of the distance estimate
+uniform vec2 light; // (cos, sin) of the direction the light comes from, on screen
+uniform float light_intensity;
+uniform float brightness; // added to the finished color, as palette::Tone
+uniform float contrast; // around mid grey
+
+uniform int ssaa; // supersamples per axis
+#ifndef LEGACY_GLSL
+uniform uint seed; // picks the supersample jitter
+#endif
+
+uniform sampler1D palette;
+
+uniform bool background; // composite over background_image
+uniform sampler2D background_image; // rows from the top
+uniform int background_fit; // 0 fit, 1 fill, 2 stretch, as background::BackgroundFit
+uniform int blend_mode; // 0 over, 1 multiply, 2 screen, as background::BlendMode
+
+// write the pre-palette values at the pixel center to a float target instead
+// of a color: escape iteration, smooth iteration, final |z|, interior mask
+uniform bool raw_iterations;
+
+// one of several draws the iterations are split across, as
+// Renderer::set_iterations_per_draw has them: all but the last write where
+// the orbit got to after `substeps` instead of a color, z and the escape
+// iteration (-1 while it runs) to FragColor, dz and the derivative's scale
+// to DerivativeState, and each after the first picks up from the last
+// one's at iteration_first
+uniform bool write_state;
+uniform bool resume;
+uniform int iteration_first;
+uniform sampler2D iteration_state;
+uniform sampler2D derivative_state;
+uniform vec2 state_size; // of the state targets, which gl_FragCoord indexes
+
+const float light_height = 1.5; // same as palette::LIGHT_HEIGHT
+const float derivative_rescale = 1e10; // same as cpu::DERIVATIVE_RESCALE
+
+// iteration at which the orbit escaped, -1 if it stayed bounded; z is
+// left at its value on escape, and with relief dz at its derivative in
+// the direction cpu::iterate_derivative takes it, dc its scale
+int escape(vec2 pos, bool as_julia, out vec2 z, out vec2 dz, out float dc){
+    vec2 p = pos * scale;
+    if (log_polar){
+        // scale * exp(pi * p / scale), as complex numbers: across the screen
+        // the radius grows by e^pi per half height, up it goes once around
+        vec2 u = 3.14159265 * p / scale.y;
+        p = scale.y * exp(u.x) * vec2(cos(u.y), sin(u.y));
+    }
+    vec2 point = offset + vec2(p.x * rotation.x - p.y * rotation.y, p.x * rotation.y + p.y * rotation.x);
+    z = as_julia ? point : vec2(0.);
+    vec2 c = as_julia ? julia_c : point;
+    dz = as_julia ? vec2(1., 0.) : vec2(0.);
+    dc = as_julia ? 0. : 1.;
+
+    int first = 0;
+    if (resume){
+        vec2 uv = gl_FragCoord.xy / state_size;
+        vec4 state = texture(iteration_state, uv);
+        vec4 derivative = texture(derivative_state, uv);
+        z = state.xy;
+        dz = derivative.xy;
+        dc = derivative.z;
+        if (state.z >= 0.){
+            return int(state.z);
+        }
+        first = iteration_first;
+    }
+    for (int i = first; i <= substeps; i++){
+        if (relief){
+            dz = 2. * vec2(z.x * dz.x - z.y * dz.y, z.x * dz.y + z.y * dz.x) + vec2(dc, 0.);
+            if (length(dz) > derivative_rescale){
+                dz /= derivative_rescale;
+                dc /= derivative_rescale;
+            }
+        }
+        z = vec2(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+        if (length(z) > 4.){
+            return i;
+        }
+    }
+    return -1;
+}
+
+// same as cpu::smooth_iteration
+float smooth_iteration(int i, vec2 z){
+    return float(i) + 1. - log2(log(length(z)) / log(4.));
+}
+
+// same as ColorMode::position in palette.rs
+float palette_position(int i, vec2 z){
+    float span = float(color_span > 0 ? color_span : substeps);
+    float angle = atan(z.y, z.x) / 6.28318531 + 0.5;
+    if (color_mode == 1){
+        return angle;
+    }
+    if (color_mode == 2){
+        return fract(angle + smooth_iteration(i, z) / span);
+    }
+    return float(i) / span;
+}
+
+// same as Lighting::shade
+float relief_shade(vec2 z, vec2 dz){
+    vec2 normal = vec2(z.x * dz.x + z.y * dz.y, z.y * dz.x - z.x * dz.y);
+    float len = length(normal);
+    if (!(len > 0.) || isinf(len)){
+        return 1.;
+    }
+    // the light turns with the view
+    vec2 from = vec2(light.x * rotation.x - light.y * rotation.y, light.x * rotation.y + light.y * rotation.x);
+    float lit = (dot(normal / len, from) + light_height) / (1. + light_height);
+    return max(mix(1., max(lit, 0.), clamp(light_intensity, 0., 1.)), 0.);
+}
+
+vec4 mandelbrot(vec2 pos, bool as_julia){
+    vec2 z;
+    vec2 dz;
+    float dc;
+    int i = escape(pos, as_julia, z, dz, dc);
+    if (i >= 0){
+        vec3 color = texture(palette, palette_position(i, z)).rgb;
+        if (relief){
+            color *= relief_shade(z, dz);
+        }
+        return vec4(color, 1.);
+    }
+    // interior (non-escaped) points
+    if (slow_tint && length(z) > slow_threshold){
+        return vec4(slow_color, 1.);
+    }
+    if (transparent_interior){
+        return vec4(1., 1., 1., 0.);
+    }
+    return vec4(1.);
+}
+
+// same as the cross-fade in cpu::shade_tile: both sets are only computed
+// while switching between them
+vec4 fractal(vec2 pos){
+    if (!julia || morph >= 1.){
+        return mandelbrot(pos, julia);
+    }
+    if (morph <= 0.){
+        return mandelbrot(pos, false);
+    }
+    return mix(mandelbrot(pos, false), mandelbrot(pos, true), morph);
+}
+
+#ifndef LEGACY_GLSL
+// same hash as rng.rs
+uint hash(uint x){
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return x;
+}
+
+vec2 jitter(vec2 frag, int index){
+    uint h = hash(uint(frag.x) ^ hash(uint(frag.y) ^ hash(uint(index) ^ hash(seed))));
+    return vec2(float(h >> 16), float(h & 0xffffu)) / 65536.;
+}
+#else
+// GLSL 1.20 has no integers to hash: a sine hash, the same for every seed
+// and not the CPU renderer's
+vec2 jitter(vec2 frag, int index){
+    vec2 p = frag + float(index) * vec2(0.618034, 0.414214);
+    return fract(sin(vec2(dot(p, vec2(12.9898, 78.233)), dot(p, vec2(39.3468, 11.1351)))) * 43758.5453);
+}
+#endif
+
+// same as Background::sample and background::stretch
+vec4 background_at(vec2 pos){
+    vec2 size = vec2(textureSize(background_image, 0));
+    float ratio = (resolution.x / resolution.y) / (size.x / size.y);
+    vec2 stretch = vec2(1.);
+    if (background_fit == 0){
+        stretch = ratio > 1. ? vec2(ratio, 1.) : vec2(1., 1. / ratio);
+    } else if (background_fit == 1){
+        stretch = ratio > 1. ? vec2(1., 1. / ratio) : vec2(ratio, 1.);
+    }
+    vec2 uv = 0.5 + vec2(pos.x, -pos.y) * 0.5 * stretch;
+    if (any(lessThan(uv, vec2(0.))) || any(greaterThan(uv, vec2(1.)))){
+        return vec4(0.);
+    }
+    return texture(background_image, uv);
+}
+
+// same as background::composite
+vec4 composite(vec4 fractal, vec4 under){
+    vec3 blended = fractal.rgb;
+    if (blend_mode == 1){
+        blended = fractal.rgb * under.rgb;
+    } else if (blend_mode == 2){
+        blended = 1. - (1. - fractal.rgb) * (1. - under.rgb);
+    }
+    vec3 mixed = mix(fractal.rgb, blended, under.a);
+    float alpha = fractal.a + under.a * (1. - fractal.a);
+    if (alpha <= 0.){
+        return vec4(1., 1., 1., 0.);
+    }
+    return vec4((mixed * fractal.a + under.rgb * under.a * (1. - fractal.a)) / alpha, alpha);
+}
+
+// grey/white squares shown behind transparent regions in the interactive preview
+vec3 checker(){
+    vec2 cell = floor(gl_FragCoord.xy / 8.);
+    return mix(vec3(0.6), vec3(0.85), mod(cell.x + cell.y, 2.));
+}
+
+void main() {
+    // quad coordinates of this pixel's center and the size of one pixel in them
+    vec2 frag = gl_FragCoord.xy + pixel_offset;
+    vec2 pos;
+    vec2 pixel;
+    if (frag_coord_mapping){
+        pos = frag / resolution * 2. - 1.;
+        pixel = 2. / resolution;
+    } else {
+        pos = position;
+        pixel = vec2(dFdx(position.x), dFdy(position.y));
+    }
+
+    if (write_state){
+        vec2 z;
+        vec2 dz;
+        float dc;
+        int i = escape(pos, julia && morph >= 0.5, z, dz, dc);
+        FragColor = vec4(z, float(i), 0.);
+        DerivativeState = vec4(dz, dc, 0.);
+        return;
+    }
+
+    if (raw_iterations){
+        // same as cpu::raw_sample
+        vec2 z;
+        vec2 dz;
+        float dc;
+        int i = escape(pos, julia && morph >= 0.5, z, dz, dc);
+        FragColor = i >= 0
+            ? vec4(float(i), smooth_iteration(i, z), length(z), 0.)
+            : vec4(-1., -1., length(z), 1.);
+        return;
+    }
+
+    int n = ssaa > 1 ? ssaa : 1;
+
+    vec4 color = vec4(0.);
+    for (int sx = 0; sx < n; sx++){
+        for (int sy = 0; sy < n; sy++){
+            // one randomly placed sample per cell of an n x n grid
+            vec2 cell = n > 1 ? jitter(frag, sx * n + sy) : vec2(0.5);
+            vec2 sub = (vec2(sx, sy) + cell) / float(n) - 0.5;
+            color += fractal(pos + sub * pixel);
+        }
+    }
+    color /= float(n * n);
+
+    if (background){
+        color = composite(color, background_at(pos));
+    }
+
+    // any color adjustments must only touch color.rgb so the alpha cut-out survives them
+    color.rgb = clamp((color.rgb - 0.5) * contrast + 0.5 + brightness, 0., 1.);
+
+    if (checkerboard){
+        color = vec4(mix(checker(), color.rgb, color.a), 1.);
+    } else if (backdrop){
+        color = vec4(mix(backdrop_color, color.rgb, color.a), 1.);
+    } else if (premultiplied){
+        color.rgb *= color.a;
+    }
+
+    FragColor = color;
+}
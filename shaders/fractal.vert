@@ -0,0 +1,10 @@
+#version 330 core
+
+layout(location = 0) in vec2 in_position; // Define input position attribute
+
+out vec2 position; // Define output position varying variable
+
+void main() {
+    gl_Position = vec4(in_position.xy, 0.0, 1.0);
+    position = in_position; // Pass input position to the fragment shader
+}
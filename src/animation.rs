@@ -0,0 +1,298 @@
+// Zoom videos. The camera moves through a list of keyframes (see keyframes.rs
+// for the interpolation). In the window every frame is
+// rendered offscreen through the same tiles as a poster export and written as
+// a numbered PNG (with its view in the PNG metadata), with `frames.json` next
+// to the frames recording the parameters of each one so a video can be re-rendered or matched up with
+// other data later. `render --export-video` walks the same path headlessly.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use image::DynamicImage;
+use log::warn;
+use serde::Serialize;
+
+use crate::export::{self, BitDepth, Export, Tile};
+use crate::job::Job;
+use crate::keyframes::{self, Keyframe};
+use crate::metadata::{self, ViewMetadata};
+use crate::viewport::Viewport;
+
+/// Iterations added for every factor of ten zoomed in, so detail keeps up
+/// with the depth of each frame.
+const ITERATIONS_PER_DECADE: f64 = 250.;
+/// Fewest iterations the zoom rule hands out to shallow frames.
+const MIN_ITERATIONS: i32 = 100;
+
+/// Iteration count for a frame at `scale`, given `base` iterations at
+/// `base_scale`, for keyframes that weren't given one.
+pub fn auto_iterations(base: i32, base_scale: f64, scale: f64) -> i32 {
+    let decades = (base_scale / scale).log10();
+    (base as f64 + ITERATIONS_PER_DECADE * decades).round().max(MIN_ITERATIONS as f64) as i32
+}
+
+#[derive(Serialize)]
+struct FrameInfo {
+    file: String,
+    time: f64,
+    center: [f64; 2],
+    scale: f64,
+    rotation: f64,
+    iterations: i32,
+}
+
+#[derive(Serialize)]
+struct Sidecar<'a> {
+    width: u32,
+    height: u32,
+    fps: f64,
+    ssaa: i32,
+    /// Frames planned; fewer are listed when the export was cancelled.
+    frame_count: usize,
+    frames: &'a [FrameInfo],
+}
+
+/// The camera over time: keyframes with the time each one is reached.
+pub struct KeyframePath {
+    keyframes: Vec<Keyframe>,
+    /// When each keyframe is reached, in seconds from the first.
+    times: Vec<f64>,
+}
+
+impl KeyframePath {
+    /// Needs at least two keyframes; segments without a duration of their own
+    /// take `default_duration` seconds.
+    pub fn new(keyframes: Vec<Keyframe>, default_duration: f64) -> Result<KeyframePath, String> {
+        if keyframes.len() < 2 {
+            return Err("an animation needs at least two keyframes".to_string());
+        }
+        let mut times = vec![0.];
+        for keyframe in &keyframes[..keyframes.len() - 1] {
+            let last = *times.last().expect("starts with 0");
+            times.push(last + keyframe.duration.unwrap_or(default_duration));
+        }
+        Ok(KeyframePath { keyframes, times })
+    }
+
+    /// Total length in seconds.
+    pub fn duration(&self) -> f64 {
+        *self.times.last().expect("at least two keyframes")
+    }
+
+    /// Frames in a video of the whole path at `fps`, at least two.
+    pub fn frame_count(&self, fps: f64) -> usize {
+        ((self.duration() * fps).round() as usize).max(2)
+    }
+
+    /// When `frame` of `frame_count` is shown; the first and last frames land
+    /// exactly on the first and last keyframes.
+    pub fn frame_time(&self, frame: usize, frame_count: usize) -> f64 {
+        self.duration() * frame as f64 / (frame_count - 1) as f64
+    }
+
+    /// The interpolated keyframe at `time` seconds, eased per segment.
+    pub fn at(&self, time: f64) -> Keyframe {
+        let time = time.clamp(0., self.duration());
+        let segment = self.times[1..].iter().position(|&end| time < end).unwrap_or(self.times.len() - 2);
+        let (start, end) = (self.times[segment], self.times[segment + 1]);
+        let (from, to) = (&self.keyframes[segment], &self.keyframes[segment + 1]);
+        let u = if end > start { (time - start) / (end - start) } else { 1. };
+        keyframes::interpolate(from, to, from.easing.apply(u))
+    }
+
+    /// The camera at `time` on a `width x height` target.
+    pub fn view_at(&self, time: f64, width: u32, height: u32) -> Viewport {
+        self.at(time).view(width, height)
+    }
+}
+
+pub struct Animation {
+    path: KeyframePath,
+    frame_count: usize,
+    width: u32,
+    height: u32,
+    fps: f64,
+    pub ssaa: i32,
+    depth: BitDepth,
+    /// Fractal and coloring of every frame, for the PNG metadata.
+    metadata: ViewMetadata,
+    dir: PathBuf,
+    /// The frame being rendered, its iteration count and its pixels so far.
+    current: Option<(Export, i32, DynamicImage)>,
+    written: Vec<FrameInfo>,
+    job: Job,
+}
+
+impl Animation {
+    /// Plans `width x height` frames at `fps` along `path` and creates `dir`
+    /// for them. Each frame's PNG carries `metadata` with its own view.
+    pub fn new(
+        path: KeyframePath,
+        (width, height): (u32, u32),
+        fps: f64,
+        (ssaa, depth): (i32, BitDepth),
+        metadata: ViewMetadata,
+        dir: &Path,
+    ) -> Result<Animation, String> {
+        let frame_count = path.frame_count(fps);
+        let mut job = Job::new("animation", "frames", frame_count);
+        job.create_dir(dir)?;
+        Ok(Animation {
+            frame_count,
+            path,
+            width,
+            height,
+            fps,
+            ssaa,
+            depth,
+            metadata,
+            dir: dir.to_path_buf(),
+            current: None,
+            written: Vec::new(),
+            job,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The next tile to render with the export it belongs to and the frame's
+    /// iteration count, or `None` once every frame is written.
+    pub fn next_tile(&mut self) -> Option<(&Export, Tile, i32)> {
+        if self.current.is_none() {
+            let frame = self.written.len();
+            if frame == self.frame_count {
+                return None;
+            }
+            let keyframe = self.path.at(self.path.frame_time(frame, self.frame_count));
+            let export = Export::new(&keyframe.view(self.width, self.height), self.width, self.height, self.ssaa, self.depth);
+            let image = self.depth.blank(self.width, self.height);
+            self.current = Some((export, keyframe.iterations, image));
+        }
+        let (export, iterations, _) = self.current.as_ref().expect("started above");
+        export.next_tile().map(|tile| (export, tile, *iterations))
+    }
+
+    /// Takes the pixels of the tile `next_tile` returned and writes the frame
+    /// once all its tiles are in.
+    pub fn store(&mut self, tile: Tile, pixels: &DynamicImage) -> Result<(), String> {
+        let (export, _, image) = self.current.as_mut().expect("next_tile starts a frame");
+        if let Some((y, band)) = export.store(tile, pixels) {
+            export::paste(image, &band, 0, y);
+        }
+        let (tiles, tile_count) = export.progress();
+        let frame = self.written.len();
+        self.job.update(frame, (frame as f64 + tiles as f64 / tile_count as f64) / self.frame_count as f64);
+        if !export.finished() {
+            return Ok(());
+        }
+
+        let (export, iterations, image) = self.current.take().expect("checked above");
+        let frame = self.written.len();
+        let file = format!("frame_{:06}.png", frame + 1);
+        let path = self.dir.join(&file);
+        let view = export.viewport;
+        self.job.wrote(&path);
+        metadata::save_png(&path, &image, &[self.metadata.with_view(&view, iterations).text_chunk()])?;
+        self.job.update(frame + 1, (frame + 1) as f64 / self.frame_count as f64);
+        self.written.push(FrameInfo {
+            file,
+            time: self.path.frame_time(frame, self.frame_count),
+            center: [view.center.0, view.center.1],
+            scale: view.scale,
+            rotation: view.rotation,
+            iterations,
+        });
+        Ok(())
+    }
+
+    /// `(written, total)` frames.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.written.len(), self.frame_count)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.written.len() == self.frame_count
+    }
+
+    /// Progress counted in tiles as well as frames, for the title bar.
+    pub fn job(&self) -> &Job {
+        &self.job
+    }
+
+    pub fn report(&mut self) -> Option<String> {
+        self.job.report()
+    }
+
+    /// Stops rendering: keeps the frames so far with their `frames.json` if
+    /// `keep_partial`, removes them otherwise. Says what happened.
+    pub fn cancel(self, keep_partial: bool) -> String {
+        if keep_partial {
+            if let Err(err) = self.write_sidecar() {
+                warn!("failed to describe the frames: {err}");
+            }
+        }
+        self.job.cancel(keep_partial)
+    }
+
+    /// Writes `frames.json` describing every frame written so far.
+    pub fn write_sidecar(&self) -> Result<(), String> {
+        let path = self.dir.join("frames.json");
+        let sidecar = Sidecar {
+            width: self.width,
+            height: self.height,
+            fps: self.fps,
+            ssaa: self.ssaa,
+            frame_count: self.frame_count,
+            frames: &self.written,
+        };
+        File::create(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|file| serde_json::to_writer_pretty(file, &sidecar).map_err(|err| err.to_string()))
+            .map_err(|err| format!("{}: {err}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyframes::Easing;
+
+    fn keyframe(scale: f64, duration: Option<f64>, easing: Easing) -> Keyframe {
+        Keyframe { duration, easing, ..Keyframe::from_view(&Viewport { center: (-0.75, 0.1), scale, ..Viewport::new(1, 1) }, 500) }
+    }
+
+    #[test]
+    fn frames_span_the_whole_path() {
+        let path = KeyframePath::new(vec![keyframe(1., Some(3.), Easing::Linear), keyframe(1e-3, None, Easing::Smooth), keyframe(1e-6, None, Easing::Linear)], 2.)
+            .expect("three keyframes");
+        assert_eq!(path.duration(), 5.);
+        assert_eq!(path.frame_count(30.), 150);
+        assert_eq!(path.frame_count(0.1), 2);
+        assert_eq!(path.frame_time(0, 150), 0.);
+        assert_eq!(path.frame_time(149, 150), 5.);
+        assert_eq!(path.at(path.frame_time(149, 150)).scale, 1e-6);
+        assert!(KeyframePath::new(vec![keyframe(1., None, Easing::Linear)], 2.).is_err());
+    }
+
+    #[test]
+    fn zoom_only_goes_deeper() {
+        for easing in [Easing::Linear, Easing::Smooth, Easing::EaseIn, Easing::EaseOut] {
+            let path = KeyframePath::new(vec![keyframe(2., None, easing), keyframe(1e-4, None, easing), keyframe(1e-9, None, easing)], 1.5)
+                .expect("three keyframes");
+            let count = path.frame_count(24.);
+            let scales: Vec<f64> = (0..count).map(|frame| path.view_at(path.frame_time(frame, count), 64, 48).scale).collect();
+            // eased segments stand still at their keyframes, but never back out
+            assert!(scales.windows(2).all(|pair| pair[1] <= pair[0]), "{easing:?}");
+            assert!(scales[count / 2] < scales[0] && scales[count - 1] < scales[count / 2]);
+        }
+    }
+
+    #[test]
+    fn iterations_grow_with_depth() {
+        assert_eq!(auto_iterations(1000, 1., 1.), 1000);
+        assert_eq!(auto_iterations(1000, 1., 1e-4), 2000);
+        assert_eq!(auto_iterations(100, 1., 1e3), MIN_ITERATIONS);
+    }
+}
@@ -0,0 +1,102 @@
+// Monte Carlo estimate of how much of the current view belongs to the set.
+// Points are drawn uniformly over the view and classified with the CPU kernel
+// at the current iteration cap, on a background thread that keeps refining
+// until it is dropped.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::cpu;
+use crate::rng::Rng;
+use crate::viewport::Viewport;
+
+/// Samples classified between two checks of the cancel flag.
+const BATCH: u64 = 10_000;
+/// How often the worker reports progress.
+const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug)]
+pub struct AreaEstimate {
+    pub samples: u64,
+    pub inside: u64,
+    /// Area of the sampled view in complex units squared.
+    pub view_area: f64,
+}
+
+impl AreaEstimate {
+    pub fn area(&self) -> f64 {
+        self.fraction() * self.view_area
+    }
+
+    /// Half width of the 95% confidence interval of `area()`, from the normal
+    /// approximation of the binomial proportion.
+    pub fn margin(&self) -> f64 {
+        let p = self.fraction();
+        1.96 * (p * (1. - p) / self.samples.max(1) as f64).sqrt() * self.view_area
+    }
+
+    fn fraction(&self) -> f64 {
+        self.inside as f64 / self.samples.max(1) as f64
+    }
+}
+
+/// A running estimate. Dropping it stops the worker.
+pub struct AreaJob {
+    viewport: Viewport,
+    substeps: i32,
+    cancel: Arc<AtomicBool>,
+    updates: Receiver<AreaEstimate>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl AreaJob {
+    pub fn start(viewport: Viewport, substeps: i32, seed: u64) -> AreaJob {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, updates) = mpsc::channel();
+        let flag = cancel.clone();
+        let worker = thread::spawn(move || {
+            let (half_w, half_h) = viewport.half_extents();
+            let mut estimate = AreaEstimate { samples: 0, inside: 0, view_area: 4. * half_w * half_h };
+            let mut rng = Rng::new(seed);
+            let mut last_report = Instant::now();
+            while !flag.load(Ordering::Relaxed) {
+                for _ in 0..BATCH {
+                    let px = (rng.next_f64() * viewport.width as f64, rng.next_f64() * viewport.height as f64);
+                    if cpu::escape_time(viewport.screen_to_complex(px), substeps).is_none() {
+                        estimate.inside += 1;
+                    }
+                }
+                estimate.samples += BATCH;
+                if last_report.elapsed() >= REPORT_INTERVAL {
+                    last_report = Instant::now();
+                    if sender.send(estimate).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        AreaJob { viewport, substeps, cancel, updates, worker: Some(worker) }
+    }
+
+    /// Whether the estimate still describes this view.
+    pub fn matches(&self, viewport: &Viewport, substeps: i32) -> bool {
+        self.viewport == *viewport && self.substeps == substeps
+    }
+
+    /// The most recent progress report, if a new one arrived.
+    pub fn poll(&self) -> Option<AreaEstimate> {
+        self.updates.try_iter().last()
+    }
+}
+
+impl Drop for AreaJob {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
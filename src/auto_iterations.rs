@@ -0,0 +1,46 @@
+// Iterations that follow the zoom (Shift+U, or `--auto-iterations`): the
+// count the quality preset gives at the home framing, plus the zoom rule of
+// animations for every factor of ten deeper. Changing the count recolors the
+// image, so it is left alone while the view moves and only eased to the new
+// count over a few frames once the view has been still for `SETTLE_SECONDS`.
+// Counts high enough for progressive refinement are set in one step instead,
+// as every change restarts it from the coarse pass.
+
+use crate::animation;
+use crate::viewport::Viewport;
+
+/// How long the view has to stay put before the count changes.
+const SETTLE_SECONDS: f64 = 0.3;
+/// Time constant of the ease to the new count; it is there in about four.
+const RAMP_SECONDS: f64 = 0.12;
+
+pub struct AutoIterations {
+    pub on: bool,
+    /// The view last frame, and how long it has been the same.
+    last: Option<Viewport>,
+    still: f64,
+}
+
+impl AutoIterations {
+    pub fn new(on: bool) -> AutoIterations {
+        AutoIterations { on, last: None, still: 0. }
+    }
+
+    /// The count for this frame, from `current`: `base` at `home_scale` plus
+    /// the zoom rule for `viewport`, approached once the view is settled.
+    /// From `progressive_above` iterations (unless 0) it jumps there.
+    pub fn step(&mut self, viewport: &Viewport, home_scale: f64, base: i32, current: i32, dt: f64, progressive_above: i32) -> i32 {
+        self.still = if self.last == Some(*viewport) { self.still + dt } else { 0. };
+        self.last = Some(*viewport);
+        let target = animation::auto_iterations(base, home_scale, viewport.scale);
+        if self.still < SETTLE_SECONDS || target == current {
+            return current;
+        }
+        if progressive_above > 0 && target.max(current) >= progressive_above {
+            return target;
+        }
+        let eased = (current as f64 + (target - current) as f64 * (1. - (-dt / RAMP_SECONDS).exp())).round() as i32;
+        // at least one iteration a frame, so it gets there
+        if eased == current { current + (target - current).signum() } else { eased }
+    }
+}
@@ -0,0 +1,113 @@
+// Background images for compositing: the fractal is blended over a loaded
+// picture, which shows through wherever the interior is transparent. The GL
+// renderer samples it from a texture and the CPU renderer from the image, with
+// the same placement and blend math so both produce the same picture.
+
+use std::path::Path;
+
+use image::RgbaImage;
+
+/// How the background is placed when its aspect ratio differs from the view's.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum BackgroundFit {
+    /// All of the image, letterboxed with transparency.
+    Fit,
+    /// Covers the whole view, cropping the image.
+    #[default]
+    Fill,
+    /// Covers the view exactly, distorting the image.
+    Stretch,
+}
+
+/// How escaped (colored) points combine with the background under them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum BlendMode {
+    /// The fractal's color replaces the background.
+    #[default]
+    Over,
+    Multiply,
+    Screen,
+}
+
+/// Fit and blend, what the shader needs besides the texture itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BackgroundStyle {
+    pub fit: BackgroundFit,
+    pub blend: BlendMode,
+}
+
+pub struct Background {
+    pub image: RgbaImage,
+    pub style: BackgroundStyle,
+}
+
+impl Background {
+    /// Any format `image` reads, PNG and JPEG included.
+    pub fn load(path: &Path, style: BackgroundStyle) -> Result<Background, String> {
+        let image = image::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        Ok(Background { image: image.to_rgba8(), style })
+    }
+
+    /// The background at `(u, v)` of a `width x height` view, both from 0 to 1
+    /// with `v` growing downwards, filtered like the shader's linear sampling;
+    /// transparent where `Fit` leaves the view uncovered.
+    pub fn sample(&self, (u, v): (f64, f64), (width, height): (u32, u32)) -> [f32; 4] {
+        let (iw, ih) = self.image.dimensions();
+        let stretch = stretch(self.style.fit, width as f64 / height as f64, iw as f64 / ih as f64);
+        let uv = (0.5 + (u - 0.5) * stretch.0, 0.5 + (v - 0.5) * stretch.1);
+        if !(0. ..=1.).contains(&uv.0) || !(0. ..=1.).contains(&uv.1) {
+            return [0.; 4];
+        }
+
+        let texel = |t: f64, n: u32| {
+            let x = (t * n as f64 - 0.5).clamp(0., (n - 1) as f64);
+            let i = x.floor() as u32;
+            (i, (i + 1).min(n - 1), (x - i as f64) as f32)
+        };
+        let (x0, x1, fx) = texel(uv.0, iw);
+        let (y0, y1, fy) = texel(uv.1, ih);
+        let at = |x, y| self.image.get_pixel(x, y).0.map(|c| c as f32 / 255.);
+        let (a, b, c, d) = (at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1));
+        [0, 1, 2, 3].map(|k| {
+            let top = a[k] + (b[k] - a[k]) * fx;
+            let bottom = c[k] + (d[k] - c[k]) * fx;
+            top + (bottom - top) * fy
+        })
+    }
+}
+
+/// How much the view's texture coordinates are stretched around the middle,
+/// per axis, to place an image of aspect ratio `image` in a view of `view`.
+/// Same as `background_at` in the shader.
+pub fn stretch(fit: BackgroundFit, view: f64, image: f64) -> (f64, f64) {
+    let ratio = view / image;
+    match fit {
+        BackgroundFit::Fit if ratio > 1. => (ratio, 1.),
+        BackgroundFit::Fit => (1., 1. / ratio),
+        BackgroundFit::Fill if ratio > 1. => (1., 1. / ratio),
+        BackgroundFit::Fill => (ratio, 1.),
+        BackgroundFit::Stretch => (1., 1.),
+    }
+}
+
+/// `fractal` over `background`, both straight (not premultiplied) RGBA. Same
+/// as `composite` in the shader.
+pub fn composite(blend: BlendMode, fractal: [f32; 4], background: [f32; 4]) -> [f32; 4] {
+    let mixed = |k: usize| {
+        let (f, b) = (fractal[k], background[k]);
+        let blended = match blend {
+            BlendMode::Over => f,
+            BlendMode::Multiply => f * b,
+            BlendMode::Screen => 1. - (1. - f) * (1. - b),
+        };
+        // where the background is itself transparent, there is nothing to blend with
+        f + (blended - f) * background[3]
+    };
+    let (fa, ba) = (fractal[3], background[3]);
+    let alpha = fa + ba * (1. - fa);
+    if alpha <= 0. {
+        return [1., 1., 1., 0.];
+    }
+    let [r, g, b] = [0, 1, 2].map(|k| (mixed(k) * fa + background[k] * ba * (1. - fa)) / alpha);
+    [r, g, b, alpha]
+}
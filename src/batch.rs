@@ -0,0 +1,155 @@
+// `--batch jobs.json`: renders a list of stills one after the other without
+// opening a window, for leaving a night of wallpapers to render.
+//
+//     [
+//       {"location": "mb2:Ey0wLjc0...", "out": "seahorse.png", "size": "3840x2160"},
+//       {"location": "spots/deep.kfr", "out": "deep.png", "iterations": 20000, "palette": "fire.json"},
+//       {"location": {"version": 1, "camera": {...}, "iterations": 4000}, "out": "inline.png"}
+//     ]
+//
+// A location is a location string, a path to a `.kfr`, a saved state or a PNG
+// written by the explorer, or a saved state inline. Paths are relative to the
+// job file. A job that fails is reported and the next one starts, unless
+// `--fail-fast`; the timings of all of them end up in `<jobs>.summary.json`.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::{self, Options};
+use crate::headless::{self, EXIT_BAD_ARGS, EXIT_INTERRUPTED, EXIT_RENDER_FAILED};
+use crate::job;
+use crate::load;
+use crate::location;
+use crate::metadata::Fractal;
+use crate::palette::Palette;
+use crate::session::Session;
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Location {
+    Inline(Box<Session>),
+    /// A location string or a file.
+    Text(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Job {
+    location: Location,
+    out: PathBuf,
+    /// `WIDTHxHEIGHT`, `render`'s default when missing.
+    size: Option<String>,
+    /// Overrides the location's.
+    iterations: Option<i32>,
+    /// Palette file, overriding the location's.
+    palette: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct JobSummary {
+    out: PathBuf,
+    ok: bool,
+    seconds: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Runs every job in `path` and returns the process exit status.
+pub fn run(path: &Path, options: &Options) -> i32 {
+    job::catch_interrupt();
+    let jobs: Vec<Job> = match File::open(path)
+        .map_err(|err| err.to_string())
+        .and_then(|file| serde_json::from_reader(file).map_err(|err| err.to_string()))
+    {
+        Ok(jobs) => jobs,
+        Err(err) => {
+            eprintln!("{}: {err}", path.display());
+            return EXIT_BAD_ARGS;
+        }
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let started = Instant::now();
+    let mut summary = Vec::new();
+    for (index, job) in jobs.iter().enumerate() {
+        let out = dir.join(&job.out);
+        println!("job {}/{}: {}", index + 1, jobs.len(), out.display());
+        let job_started = Instant::now();
+        let error = render(job, dir, &out, options).err();
+        let seconds = job_started.elapsed().as_secs_f64();
+        match &error {
+            None => println!("job {}/{} done in {seconds:.1}s", index + 1, jobs.len()),
+            Some(err) => eprintln!("job {}/{} failed: {err}", index + 1, jobs.len()),
+        }
+        summary.push(JobSummary { out, ok: error.is_none(), seconds, error });
+        if job::interrupted() || options.fail_fast && summary.last().is_some_and(|job| !job.ok) {
+            break;
+        }
+    }
+
+    let failed = summary.iter().filter(|job| !job.ok).count();
+    let skipped = jobs.len() - summary.len();
+    println!(
+        "{} of {} jobs rendered in {:.1}s{}",
+        summary.len() - failed,
+        jobs.len(),
+        started.elapsed().as_secs_f64(),
+        match skipped {
+            0 => String::new(),
+            _ if job::interrupted() => format!(", {skipped} skipped after Ctrl+C"),
+            _ => format!(", {skipped} skipped after a failure"),
+        }
+    );
+    for job in &summary {
+        println!("  {:>8.1}s  {}  {}", job.seconds, if job.ok { "ok    " } else { "failed" }, job.out.display());
+    }
+    let summary_path = path.with_extension("summary.json");
+    let written = File::create(&summary_path)
+        .map_err(|err| err.to_string())
+        .and_then(|file| serde_json::to_writer_pretty(file, &summary).map_err(|err| err.to_string()));
+    if let Err(err) = written {
+        eprintln!("failed to write {}: {err}", summary_path.display());
+    }
+
+    if job::interrupted() {
+        EXIT_INTERRUPTED
+    } else if failed > 0 {
+        EXIT_RENDER_FAILED
+    } else {
+        0
+    }
+}
+
+fn render(job: &Job, dir: &Path, out: &Path, options: &Options) -> Result<(), String> {
+    let session = match &job.location {
+        Location::Inline(session) => (**session).clone(),
+        Location::Text(text) if text.starts_with("mb") && text.contains(':') => location::decode(text)?,
+        Location::Text(file) => load::file(&dir.join(file))?.session()?,
+    };
+    let palette = match &job.palette {
+        Some(file) => Palette::load(&dir.join(file))?,
+        None => session.palette.clone(),
+    };
+
+    let mut args = cli::render_defaults(out.to_path_buf(), options.quality);
+    if let Some(size) = &job.size {
+        args.size = cli::parse_size(size)?;
+    }
+    args.iter = job.iterations.unwrap_or(session.iterations).max(1);
+    args.color_mode = session.color_mode;
+    args.transparent = session.transparent_interior;
+    args.lighting = session.lighting;
+    args.color_span = session.color_span;
+    args.tone = session.tone;
+    if let Fractal::Julia { c } = session.fractal {
+        args.julia = Some(c.to_vec());
+    }
+    let viewport = session.camera.view(args.size.0, args.size.1)?;
+    match headless::render(viewport, &palette, &args, options) {
+        0 => Ok(()),
+        status => Err(format!("render exited with {status}")),
+    }
+}
@@ -0,0 +1,220 @@
+// `--bench`: fixed scenarios timed on the GPU and the CPU, so performance
+// changes can be compared with numbers instead of impressions.
+
+use std::fs::File;
+use std::path::Path;
+use std::time::Instant;
+
+use gl::types::*;
+use serde::Serialize;
+
+use crate::caps::{Feature, GpuCapabilities};
+use crate::cpu;
+use crate::gl_object::Query;
+use crate::headless;
+use crate::offscreen;
+use crate::palette::{ColorMode, Tone};
+use crate::renderer::{CoordinateMapping, DrawParams, Framebuffer, Renderer};
+use crate::viewport::Viewport;
+
+const WIDTH: u32 = 600;
+const HEIGHT: u32 = 600;
+const GPU_WARMUP_FRAMES: usize = 5;
+const GPU_FRAMES: usize = 30;
+const CPU_FRAMES: usize = 3;
+
+struct Scenario {
+    name: &'static str,
+    center: (f64, f64),
+    scale: f64,
+    substeps: i32,
+    ssaa: i32,
+}
+
+const GPU_SCENARIOS: &[Scenario] = &[
+    Scenario { name: "default view, 1k iterations", center: (0., 0.), scale: 1., substeps: 1000, ssaa: 1 },
+    Scenario { name: "default view, 1k iterations, 3x3 SSAA", center: (0., 0.), scale: 1., substeps: 1000, ssaa: 3 },
+    Scenario { name: "deep zoom, 50k iterations", center: (-0.743643887, 0.131825904), scale: 2e-5, substeps: 50000, ssaa: 1 },
+    Scenario { name: "deep zoom, 50k iterations, 3x3 SSAA", center: (-0.743643887, 0.131825904), scale: 2e-5, substeps: 50000, ssaa: 3 },
+];
+
+const CPU_SCENARIO: Scenario =
+    Scenario { name: "CPU, default view, 1k iterations", center: (0., 0.), scale: 1., substeps: 1000, ssaa: 1 };
+
+#[derive(Serialize)]
+struct BenchResult {
+    scenario: String,
+    threads: Option<usize>,
+    frames: usize,
+    mean_ms: f64,
+    p50_ms: f64,
+    p95_ms: f64,
+    /// Estimated from a sparse CPU sample of the same view.
+    iterations_per_second: f64,
+}
+
+/// Runs every scenario and returns the process exit status.
+pub fn run(json_path: Option<&Path>) -> i32 {
+    let mut results = Vec::new();
+
+    match bench_gpu() {
+        Ok(gpu) => results.extend(gpu),
+        Err(err) => eprintln!("skipping GPU scenarios: {err}"),
+    }
+    results.extend(bench_cpu());
+
+    print_table(&results);
+
+    if let Some(path) = json_path {
+        let written = File::create(path)
+            .map_err(|err| err.to_string())
+            .and_then(|file| serde_json::to_writer_pretty(file, &results).map_err(|err| err.to_string()));
+        if let Err(err) = written {
+            eprintln!("failed to write {}: {err}", path.display());
+            return headless::EXIT_RENDER_FAILED;
+        }
+    }
+    0
+}
+
+fn viewport(scenario: &Scenario) -> Viewport {
+    let mut viewport = Viewport::new(WIDTH, HEIGHT);
+    viewport.center = scenario.center;
+    viewport.scale = scenario.scale;
+    viewport
+}
+
+fn bench_gpu() -> Result<Vec<BenchResult>, String> {
+    let _context = offscreen::create()?;
+    let renderer = Renderer::new()?;
+    let target = Framebuffer::new(WIDTH as i32, HEIGHT as i32)?;
+    target.bind();
+
+    GpuCapabilities::current().require(Feature::TimerQueries).map_err(|reason| format!("can't time the GPU: {reason}"))?;
+    let query = Query::new()?;
+
+    let mut results = Vec::new();
+    for scenario in GPU_SCENARIOS {
+        let params = DrawParams {
+            view: viewport(scenario).uniforms(),
+            substeps: scenario.substeps,
+            ssaa: scenario.ssaa,
+            seed: 0,
+            time: 0.,
+            julia: None,
+            morph: 1.,
+            transparent_interior: false,
+            color_mode: ColorMode::EscapeTime,
+            color_span: None,
+            checkerboard: false,
+            backdrop: None,
+            premultiplied: false,
+            slow_tint: None,
+            lighting: None,
+            tone: Tone::default(),
+            mapping: CoordinateMapping::FragCoord,
+            log_polar: false,
+            background: None,
+        };
+
+        for _ in 0..GPU_WARMUP_FRAMES {
+            renderer.draw(&params);
+        }
+        unsafe {
+            gl::Finish();
+        }
+
+        let mut times = Vec::with_capacity(GPU_FRAMES);
+        for _ in 0..GPU_FRAMES {
+            let mut nanos: GLuint64 = 0;
+            unsafe {
+                gl::BeginQuery(gl::TIME_ELAPSED, query.id());
+                renderer.draw(&params);
+                gl::EndQuery(gl::TIME_ELAPSED);
+                // blocks until the GPU has finished the draw
+                gl::GetQueryObjectui64v(query.id(), gl::QUERY_RESULT, &mut nanos);
+            }
+            times.push(nanos as f64 / 1e6);
+        }
+        results.push(summarize(scenario, None, times));
+    }
+    Ok(results)
+}
+
+fn bench_cpu() -> Vec<BenchResult> {
+    let scenario = &CPU_SCENARIO;
+    let viewport = viewport(scenario);
+
+    // 1, 2, 4, ... up to every core (or --threads), to see how the renderer scales
+    let cores = rayon::current_num_threads();
+    let mut counts: Vec<usize> = std::iter::successors(Some(1), |&n| Some(n * 2)).take_while(|&n| n < cores).collect();
+    counts.push(cores);
+
+    let mut results = Vec::new();
+    for threads in counts {
+        let pool = match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+            Ok(pool) => pool,
+            Err(err) => {
+                eprintln!("could not start {threads} threads: {err}");
+                continue;
+            }
+        };
+        let times = (0..CPU_FRAMES)
+            .map(|_| {
+                let start = Instant::now();
+                pool.install(|| cpu::render_escapes(&viewport, scenario.substeps));
+                start.elapsed().as_secs_f64() * 1e3
+            })
+            .collect();
+        results.push(summarize(scenario, Some(threads), times));
+    }
+    results
+}
+
+fn summarize(scenario: &Scenario, threads: Option<usize>, mut times: Vec<f64>) -> BenchResult {
+    times.sort_by(f64::total_cmp);
+    let mean_ms = times.iter().sum::<f64>() / times.len() as f64;
+    let iterations = estimate_iterations(&viewport(scenario), scenario.substeps) * (scenario.ssaa * scenario.ssaa) as f64;
+    BenchResult {
+        scenario: scenario.name.to_string(),
+        threads,
+        frames: times.len(),
+        mean_ms,
+        p50_ms: percentile(&times, 0.5),
+        p95_ms: percentile(&times, 0.95),
+        iterations_per_second: iterations / (mean_ms / 1e3),
+    }
+}
+
+/// Nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Total iterations for one frame, extrapolated from every 8th pixel in each direction.
+fn estimate_iterations(viewport: &Viewport, substeps: i32) -> f64 {
+    const STRIDE: u32 = 8;
+    let mut total = 0f64;
+    for y in (0..viewport.height).step_by(STRIDE as usize) {
+        for x in (0..viewport.width).step_by(STRIDE as usize) {
+            let c = viewport.screen_to_complex((x as f64 + 0.5, y as f64 + 0.5));
+            total += match cpu::escape_time(c, substeps) {
+                Some(i) => i + 1,
+                None => substeps + 1,
+            } as f64;
+        }
+    }
+    total * (STRIDE * STRIDE) as f64
+}
+
+fn print_table(results: &[BenchResult]) {
+    println!("{:<42} {:>7} {:>6} {:>10} {:>10} {:>10} {:>10}", "scenario", "threads", "frames", "mean ms", "p50 ms", "p95 ms", "Giter/s");
+    for r in results {
+        let threads = r.threads.map_or("gpu".to_string(), |t| t.to_string());
+        println!(
+            "{:<42} {:>7} {:>6} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+            r.scenario, threads, r.frames, r.mean_ms, r.p50_ms, r.p95_ms, r.iterations_per_second / 1e9
+        );
+    }
+}
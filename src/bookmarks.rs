@@ -0,0 +1,185 @@
+// Views kept to come back to (F1), each a saved state with a note saying
+// what it was, in `bookmarks.json` (or `--bookmarks`):
+//
+//     {"version": 1, "bookmarks": [{"note": "nice spiral", "session": {...}}, ...]}
+//
+// `session` is the same as a state saved with Ctrl+S. A bookmark without a
+// note gets an empty one. The file is written again after every change; one
+// that can't be read is left alone rather than replaced with an empty list.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use imgui::{Condition, Ui};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::session::Session;
+
+/// The schema this build writes and the only one it reads.
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    #[serde(default)]
+    pub note: String,
+    pub session: Session,
+}
+
+impl Bookmark {
+    /// What the list shows for it.
+    pub fn label(&self) -> &str {
+        if self.note.is_empty() {
+            "(no note)"
+        } else {
+            &self.note
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BookmarkFile {
+    version: u32,
+    bookmarks: Vec<Bookmark>,
+}
+
+/// What the window asks of the explorer.
+pub enum Picked {
+    /// Keep the current view with this note.
+    Add(String),
+    /// Go to this view.
+    Open(Box<Bookmark>),
+}
+
+pub struct Bookmarks {
+    /// Whether the F1 window is showing.
+    pub open: bool,
+    path: PathBuf,
+    list: Vec<Bookmark>,
+    /// Set when the file was there but couldn't be read, so it isn't
+    /// overwritten.
+    unreadable: bool,
+    selected: Option<usize>,
+    /// The note for the next bookmark, as typed.
+    note: String,
+}
+
+impl Bookmarks {
+    /// The bookmarks in `path`, none if there is no such file yet; says why
+    /// when there is one that can't be used.
+    pub fn load(path: &Path) -> Bookmarks {
+        let mut bookmarks =
+            Bookmarks { open: false, path: path.to_path_buf(), list: Vec::new(), unreadable: false, selected: None, note: String::new() };
+        if !path.exists() {
+            return bookmarks;
+        }
+        match read(path) {
+            Ok(list) => bookmarks.list = list,
+            Err(err) => {
+                warn!("not using the bookmarks in {}: {err}", path.display());
+                bookmarks.unreadable = true;
+            }
+        }
+        bookmarks
+    }
+
+    /// Adds the view in `session` and saves the list.
+    pub fn add(&mut self, note: String, session: Session) {
+        self.list.push(Bookmark { note, session });
+        self.selected = Some(self.list.len() - 1);
+        self.save();
+    }
+
+    fn save(&self) {
+        if self.unreadable {
+            warn!("not saving the bookmarks over {}, which could not be read; fix or move it first", self.path.display());
+            return;
+        }
+        let written = File::create(&self.path)
+            .map_err(|err| err.to_string())
+            .and_then(|file| {
+                serde_json::to_writer_pretty(file, &BookmarkFile { version: VERSION, bookmarks: self.list.clone() })
+                    .map_err(|err| err.to_string())
+            });
+        if let Err(err) = written {
+            warn!("failed to save the bookmarks to {}: {err}", self.path.display());
+        }
+    }
+
+    /// The window: a line for the note of a new bookmark, then the list,
+    /// each showing its note and, when hovered, where it is. Clicking one goes
+    /// there and lets its note be edited.
+    pub fn draw(&mut self, ui: &Ui) -> Option<Picked> {
+        if !self.open {
+            return None;
+        }
+        let mut open = true;
+        let mut picked = None;
+        let mut changed = false;
+        ui.window("Bookmarks")
+            .opened(&mut open)
+            .size([360., 300.], Condition::FirstUseEver)
+            .build(|| {
+                let entered = ui.input_text("##note", &mut self.note).hint("note").enter_returns_true(true).build();
+                ui.same_line();
+                if ui.button("add this view") || entered {
+                    picked = Some(Picked::Add(std::mem::take(&mut self.note)));
+                }
+                ui.separator();
+                if self.list.is_empty() {
+                    ui.text_disabled("no bookmarks yet");
+                }
+                for (index, bookmark) in self.list.iter().enumerate() {
+                    let _id = ui.push_id_usize(index);
+                    if ui.selectable_config(bookmark.label()).selected(self.selected == Some(index)).build() {
+                        self.selected = Some(index);
+                        picked = Some(Picked::Open(Box::new(bookmark.clone())));
+                    }
+                    if ui.is_item_hovered() {
+                        let camera = &bookmark.session.camera;
+                        ui.tooltip(|| {
+                            if !bookmark.note.is_empty() {
+                                ui.text_wrapped(&bookmark.note);
+                            }
+                            ui.text_disabled(format!(
+                                "center {} {}, scale {}, {} iterations",
+                                camera.center[0], camera.center[1], camera.scale, bookmark.session.iterations
+                            ));
+                        });
+                    }
+                }
+
+                let Some(index) = self.selected.filter(|&index| index < self.list.len()) else {
+                    return;
+                };
+                ui.separator();
+                ui.input_text("note", &mut self.list[index].note).build();
+                changed |= ui.is_item_deactivated_after_edit();
+                if ui.button("delete") {
+                    self.list.remove(index);
+                    self.selected = None;
+                    changed = true;
+                }
+            });
+        self.open = open;
+        if changed {
+            self.save();
+        }
+        picked
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<Bookmark>, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    let value: serde_json::Value = serde_json::from_reader(file).map_err(|err| err.to_string())?;
+    match value.get("version").and_then(|v| v.as_u64()) {
+        Some(version) if version == VERSION as u64 => {}
+        Some(version) => return Err(format!("version {version} is not the {VERSION} this build reads")),
+        None => return Err("no version".to_string()),
+    }
+    let file: BookmarkFile = serde_json::from_value(value).map_err(|err| err.to_string())?;
+    for bookmark in &file.bookmarks {
+        bookmark.session.camera.view(1, 1)?;
+    }
+    Ok(file.bookmarks)
+}
@@ -0,0 +1,363 @@
+// What the current GL context can do past what the renderer needs anyway:
+// probed once per context (after `load_gl`) from its version string,
+// extensions and limits, and asked by everything that depends on one of
+// them, so a missing capability comes back as a message naming it instead
+// of a GL error. Contexts are current per thread, and so is the probe.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::fmt;
+use std::rc::Rc;
+
+use gl::types::*;
+
+thread_local! {
+    /// The probe of the context current on this thread, once asked for.
+    static CURRENT: RefCell<Option<Rc<GpuCapabilities>>> = const { RefCell::new(None) };
+}
+
+/// A capability something needs and not every context has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    /// Doubles in shaders.
+    Fp64,
+    ComputeShaders,
+    /// Debug output from the driver (KHR_debug).
+    Debug,
+    /// Compiling shaders on the driver's threads.
+    ParallelShaderCompile,
+    /// Drawing into 32-bit float textures.
+    FloatTargets,
+    /// Drawing into 16-bit normalized textures.
+    Norm16Targets,
+    /// Offscreen framebuffer objects.
+    Framebuffers,
+    /// Timing draws on the GPU.
+    TimerQueries,
+    /// Reading pixels back through fences and mapped buffers, without
+    /// waiting for the GPU.
+    AsyncReadback,
+}
+
+impl Feature {
+    const ALL: [Feature; 9] = [
+        Feature::Fp64,
+        Feature::ComputeShaders,
+        Feature::Debug,
+        Feature::ParallelShaderCompile,
+        Feature::FloatTargets,
+        Feature::Norm16Targets,
+        Feature::Framebuffers,
+        Feature::TimerQueries,
+        Feature::AsyncReadback,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Fp64 => "fp64 shaders",
+            Feature::ComputeShaders => "compute shaders",
+            Feature::Debug => "debug callbacks",
+            Feature::ParallelShaderCompile => "parallel shader compiles",
+            Feature::FloatTargets => "float render targets",
+            Feature::Norm16Targets => "16-bit render targets",
+            Feature::Framebuffers => "framebuffer objects",
+            Feature::TimerQueries => "timer queries",
+            Feature::AsyncReadback => "asynchronous readbacks",
+        }
+    }
+
+    /// What provides it, in a context of that kind.
+    fn provided_by(self, gles: bool) -> &'static str {
+        match (self, gles) {
+            (Feature::Fp64, false) => "OpenGL 4.0 or GL_ARB_gpu_shader_fp64",
+            (Feature::Fp64, true) => "desktop OpenGL 4.0 or GL_ARB_gpu_shader_fp64",
+            (Feature::ComputeShaders, false) => "OpenGL 4.3 or GL_ARB_compute_shader",
+            (Feature::ComputeShaders, true) => "OpenGL ES 3.1",
+            (Feature::Debug, false) => "OpenGL 4.3 or GL_KHR_debug",
+            (Feature::Debug, true) => "OpenGL ES 3.2 or GL_KHR_debug",
+            (Feature::ParallelShaderCompile, _) => "GL_KHR_parallel_shader_compile",
+            (Feature::FloatTargets, false) => "OpenGL 3.0 or GL_ARB_texture_float",
+            (Feature::FloatTargets, true) => "GL_EXT_color_buffer_float",
+            (Feature::Norm16Targets, false) => "OpenGL 3.0",
+            (Feature::Norm16Targets, true) => "GL_EXT_texture_norm16",
+            (Feature::Framebuffers, false) => "OpenGL 3.0 or GL_ARB_framebuffer_object",
+            (Feature::Framebuffers, true) => "OpenGL ES 2.0",
+            (Feature::TimerQueries, false) => "OpenGL 3.3 or GL_ARB_timer_query",
+            (Feature::TimerQueries, true) => "GL_EXT_disjoint_timer_query",
+            (Feature::AsyncReadback, false) => "OpenGL 3.2 or GL_ARB_sync",
+            (Feature::AsyncReadback, true) => "OpenGL ES 3.0",
+        }
+    }
+}
+
+/// The probe of one context.
+#[derive(Clone, Debug)]
+pub struct GpuCapabilities {
+    /// `GL_VERSION` as the driver gives it.
+    pub version_string: String,
+    pub gles: bool,
+    pub version: (u32, u32),
+    pub glsl_version: String,
+    pub vendor: String,
+    pub renderer: String,
+    /// Of either edge of a texture, and so of an offscreen target.
+    pub max_texture_size: i32,
+    extensions: HashSet<String>,
+    /// Of `Feature::ALL`, in order.
+    features: [bool; 9],
+}
+
+impl GpuCapabilities {
+    /// Of the context current on this thread, probed the first time it is
+    /// asked for after `load_gl`.
+    pub fn current() -> Rc<GpuCapabilities> {
+        CURRENT.with(|current| current.borrow_mut().get_or_insert_with(|| Rc::new(GpuCapabilities::probe())).clone())
+    }
+
+    /// Drops the probe of an earlier context, for `load_gl`.
+    pub(crate) fn forget() {
+        CURRENT.with(|current| current.borrow_mut().take());
+    }
+
+    /// Asks the current context. Needs the GL functions loaded.
+    pub fn probe() -> GpuCapabilities {
+        let version_string = gl_string(gl::VERSION);
+        let (gles, version) = parse_version(&version_string);
+        let extensions = if version.0 < 3 || !gl::GetStringi::is_loaded() {
+            // before 3.0 they come as one string
+            parse_extensions(&gl_string(gl::EXTENSIONS))
+        } else {
+            let mut count = 0;
+            unsafe {
+                gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+            }
+            (0..count.max(0) as GLuint)
+                .filter_map(|index| {
+                    let extension = unsafe { gl::GetStringi(gl::EXTENSIONS, index) };
+                    (!extension.is_null()).then(|| unsafe { CStr::from_ptr(extension.cast()) }.to_string_lossy().into_owned())
+                })
+                .collect()
+        };
+        let mut max_texture_size = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_texture_size);
+        }
+        let loaded = Loaded {
+            framebuffers: gl::GenFramebuffers::is_loaded(),
+            timer_queries: gl::GetQueryObjectui64v::is_loaded(),
+            async_readback: gl::FenceSync::is_loaded() && gl::MapBufferRange::is_loaded(),
+        };
+        GpuCapabilities {
+            features: features(gles, version, &extensions, loaded),
+            version_string,
+            gles,
+            version,
+            glsl_version: gl_string(gl::SHADING_LANGUAGE_VERSION),
+            vendor: gl_string(gl::VENDOR),
+            renderer: gl_string(gl::RENDERER),
+            max_texture_size,
+            extensions,
+        }
+    }
+
+    pub fn has(&self, feature: Feature) -> bool {
+        self.features[Feature::ALL.iter().position(|&f| f == feature).expect("every feature is in ALL")]
+    }
+
+    /// `Ok` with `feature`, otherwise what it needs, such as "float render
+    /// targets need GL_EXT_color_buffer_float, which this OpenGL ES driver
+    /// lacks", for the caller to say what it does instead.
+    pub fn require(&self, feature: Feature) -> Result<(), String> {
+        if self.has(feature) {
+            return Ok(());
+        }
+        let driver = if self.gles { "OpenGL ES driver" } else { "driver" };
+        Err(format!("{} need {}, which this {driver} lacks", feature.name(), feature.provided_by(self.gles)))
+    }
+
+    /// Whether the context has the extension `name`, such as
+    /// "GL_EXT_color_buffer_float".
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+
+    /// `Ok` if a `width x height` texture fits the texture size limit.
+    pub fn check_texture_size(&self, width: i32, height: i32) -> Result<(), String> {
+        if width > self.max_texture_size || height > self.max_texture_size {
+            return Err(format!("{width}x{height} exceeds the GPU texture size limit of {}", self.max_texture_size));
+        }
+        Ok(())
+    }
+
+    /// One line for the log at startup: the context and what it lacks.
+    pub fn summary(&self) -> String {
+        let api = if self.gles { "OpenGL ES" } else { "OpenGL" };
+        let missing: Vec<_> = Feature::ALL.into_iter().filter(|&feature| !self.has(feature)).map(Feature::name).collect();
+        let mut summary = format!("{api} {}.{} on {}, textures up to {}", self.version.0, self.version.1, self.renderer, self.max_texture_size);
+        if !missing.is_empty() {
+            summary += &format!("; no {}", missing.join(", "));
+        }
+        summary
+    }
+}
+
+/// Everything probed, for `--print-caps`.
+impl fmt::Display for GpuCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "version:                  {}", self.version_string)?;
+        writeln!(f, "GLSL:                     {}", self.glsl_version)?;
+        writeln!(f, "vendor:                   {}", self.vendor)?;
+        writeln!(f, "renderer:                 {}", self.renderer)?;
+        writeln!(f, "max texture size:         {}", self.max_texture_size)?;
+        for feature in Feature::ALL {
+            let has = if self.has(feature) { "yes" } else { "no" };
+            writeln!(f, "{:<26}{has}", format!("{}:", feature.name()))?;
+        }
+        let mut extensions: Vec<_> = self.extensions.iter().map(String::as_str).collect();
+        extensions.sort_unstable();
+        writeln!(f, "extensions ({}):", extensions.len())?;
+        for extension in extensions {
+            writeln!(f, "  {extension}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What the probe finds out from the loaded function pointers.
+#[derive(Clone, Copy)]
+struct Loaded {
+    framebuffers: bool,
+    timer_queries: bool,
+    async_readback: bool,
+}
+
+/// Of `Feature::ALL`, in order, given what the context says it is.
+fn features(gles: bool, version: (u32, u32), extensions: &HashSet<String>, loaded: Loaded) -> [bool; 9] {
+    let has = |name: &str| extensions.contains(name);
+    let desktop = |at_least: (u32, u32)| !gles && version >= at_least;
+    let es = |at_least: (u32, u32)| gles && version >= at_least;
+    Feature::ALL.map(|feature| match feature {
+        Feature::Fp64 => desktop((4, 0)) || has("GL_ARB_gpu_shader_fp64"),
+        Feature::ComputeShaders => desktop((4, 3)) || es((3, 1)) || has("GL_ARB_compute_shader"),
+        Feature::Debug => desktop((4, 3)) || es((3, 2)) || has("GL_KHR_debug"),
+        Feature::ParallelShaderCompile => has("GL_KHR_parallel_shader_compile") || has("GL_ARB_parallel_shader_compile"),
+        Feature::FloatTargets if gles => has("GL_EXT_color_buffer_float"),
+        Feature::FloatTargets => desktop((3, 0)) || has("GL_ARB_texture_float"),
+        Feature::Norm16Targets => !gles || has("GL_EXT_texture_norm16"),
+        Feature::Framebuffers => loaded.framebuffers,
+        Feature::TimerQueries => loaded.timer_queries,
+        Feature::AsyncReadback => loaded.async_readback,
+    })
+}
+
+/// Whether a `GL_VERSION` string is OpenGL ES's, and the version in it:
+/// desktop ones start with it ("4.6.0 NVIDIA 535.54", "3.3 (Core Profile)
+/// Mesa 23.1"), ES ones after "OpenGL ES" ("OpenGL ES 3.1 Mesa 23.2.1").
+pub fn parse_version(version: &str) -> (bool, (u32, u32)) {
+    let gles = version.starts_with("OpenGL ES");
+    let mut numbers = version.split(|c: char| !c.is_ascii_digit()).filter(|number| !number.is_empty()).map(|number| number.parse().unwrap_or(0));
+    (gles, (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0)))
+}
+
+/// The extensions in a pre-3.0 `GL_EXTENSIONS` string, separated by spaces.
+pub fn parse_extensions(extensions: &str) -> HashSet<String> {
+    extensions.split_whitespace().map(str::to_string).collect()
+}
+
+/// `name` of the current context, empty where it has none.
+fn gl_string(name: GLenum) -> String {
+    let string = unsafe { gl::GetString(name) };
+    if string.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(string.cast()) }.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTHING_LOADED: Loaded = Loaded { framebuffers: false, timer_queries: false, async_readback: false };
+
+    /// A context that says it is `version`, with `extensions`.
+    fn caps(version: &str, extensions: &str) -> GpuCapabilities {
+        let (gles, parsed) = parse_version(version);
+        let extensions = parse_extensions(extensions);
+        GpuCapabilities {
+            features: features(gles, parsed, &extensions, NOTHING_LOADED),
+            version_string: version.to_string(),
+            gles,
+            version: parsed,
+            glsl_version: String::new(),
+            vendor: String::new(),
+            renderer: "llvmpipe".to_string(),
+            max_texture_size: 8192,
+            extensions,
+        }
+    }
+
+    #[test]
+    fn versions_from_each_driver() {
+        assert_eq!(parse_version("4.6.0 NVIDIA 535.54.03"), (false, (4, 6)));
+        assert_eq!(parse_version("3.3 (Core Profile) Mesa 23.1.4"), (false, (3, 3)));
+        assert_eq!(parse_version("4.6.14761 Compatibility Profile Context 21.30.44.01"), (false, (4, 6)));
+        assert_eq!(parse_version("OpenGL ES 3.1 Mesa 23.2.1"), (true, (3, 1)));
+        assert_eq!(parse_version("OpenGL ES 3.2 v1.r38p1"), (true, (3, 2)));
+        // what a broken or missing string comes to
+        assert_eq!(parse_version(""), (false, (0, 0)));
+        assert_eq!(parse_version("4"), (false, (4, 0)));
+        assert_eq!(parse_version("99999999999.1"), (false, (0, 1)));
+    }
+
+    #[test]
+    fn extensions_split_on_any_space() {
+        let extensions = parse_extensions(" GL_KHR_debug  GL_ARB_sync\tGL_EXT_texture_norm16 ");
+        assert_eq!(extensions.len(), 3);
+        assert!(["GL_KHR_debug", "GL_ARB_sync", "GL_EXT_texture_norm16"].iter().all(|name| extensions.contains(*name)));
+        assert!(parse_extensions("").is_empty());
+    }
+
+    #[test]
+    fn features_from_the_version_or_an_extension() {
+        let old = caps("3.3 (Core Profile) Mesa 23.1.4", "");
+        assert!(!old.has(Feature::Fp64) && !old.has(Feature::Debug) && !old.has(Feature::ComputeShaders));
+        assert!(old.has(Feature::FloatTargets) && old.has(Feature::Norm16Targets));
+        let extended = caps("3.3 (Core Profile) Mesa 23.1.4", "GL_ARB_gpu_shader_fp64 GL_KHR_debug GL_ARB_parallel_shader_compile");
+        assert!(extended.has(Feature::Fp64) && extended.has(Feature::Debug) && extended.has(Feature::ParallelShaderCompile));
+        assert!(extended.has_extension("GL_KHR_debug") && !extended.has_extension("GL_KHR"));
+        let new = caps("4.6.0 NVIDIA 535.54.03", "");
+        assert!(new.has(Feature::Fp64) && new.has(Feature::Debug) && new.has(Feature::ComputeShaders));
+        // ES versions don't count towards desktop ones, nor the other way round
+        let es = caps("OpenGL ES 3.1 Mesa 23.2.1", "");
+        assert!(es.has(Feature::ComputeShaders) && !es.has(Feature::Fp64) && !es.has(Feature::Debug));
+        assert!(!es.has(Feature::FloatTargets) && !es.has(Feature::Norm16Targets));
+        // and what comes from the loaded functions comes from them only
+        assert!(!new.has(Feature::Framebuffers) && !new.has(Feature::TimerQueries) && !new.has(Feature::AsyncReadback));
+    }
+
+    #[test]
+    fn missing_features_say_what_provides_them() {
+        assert_eq!(caps("4.6.0 NVIDIA 535.54.03", "").require(Feature::Fp64), Ok(()));
+        assert_eq!(
+            caps("OpenGL ES 3.1 Mesa 23.2.1", "").require(Feature::FloatTargets),
+            Err("float render targets need GL_EXT_color_buffer_float, which this OpenGL ES driver lacks".into())
+        );
+        assert_eq!(
+            caps("3.3 (Core Profile) Mesa 23.1.4", "").require(Feature::Fp64),
+            Err("fp64 shaders need OpenGL 4.0 or GL_ARB_gpu_shader_fp64, which this driver lacks".into())
+        );
+    }
+
+    #[test]
+    fn texture_sizes_and_the_summary() {
+        let caps = caps("4.6.0 NVIDIA 535.54.03", "");
+        assert!(caps.check_texture_size(8192, 8192).is_ok());
+        assert_eq!(caps.check_texture_size(8193, 10), Err("8193x10 exceeds the GPU texture size limit of 8192".into()));
+        assert_eq!(
+            caps.summary(),
+            "OpenGL 4.6 on llvmpipe, textures up to 8192; no parallel shader compiles, framebuffer objects, timer queries, asynchronous readbacks"
+        );
+        assert!(caps.to_string().contains("fp64 shaders:             yes\n"));
+    }
+}
@@ -0,0 +1,206 @@
+// Screenshots without a frame-time hitch. The GPU copies the frame into one
+// of two pixel buffer objects, a fence tells when the copy is done a frame or
+// two later, and only then is the buffer mapped; PNG encoding happens on a
+// worker thread. The worker is fed through a bounded channel so a burst of
+// captures applies back pressure instead of piling up frames in memory.
+// Frames for the clipboard take the same way, and are copied by the worker.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::ptr;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use gl::types::*;
+use image::{DynamicImage, RgbaImage};
+use log::{info, warn};
+
+use crate::caps::{Feature, GpuCapabilities};
+use crate::clipboard::Clipboard;
+use crate::gl_object::Buffer;
+use crate::metadata;
+use crate::renderer;
+
+/// Frames that may wait for the encoder before new captures block.
+const QUEUE_DEPTH: usize = 4;
+
+/// Where a capture goes.
+pub enum Target {
+    /// A PNG at this path.
+    File(String),
+    Clipboard,
+}
+
+struct Encode {
+    image: DynamicImage,
+    /// GL rows start at the bottom.
+    flip: bool,
+    target: Target,
+    /// PNG text chunks, `(keyword, text)`.
+    text: Vec<(String, String)>,
+}
+
+struct Readback {
+    pbo: usize,
+    fence: GLsync,
+    width: i32,
+    height: i32,
+    target: Target,
+    text: Vec<(String, String)>,
+}
+
+pub struct Capturer {
+    pbos: [Buffer; 2],
+    /// Oldest first.
+    in_flight: VecDeque<Readback>,
+    sender: Option<SyncSender<Encode>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Capturer {
+    /// Needs a current GL context.
+    pub fn new() -> Result<Capturer, String> {
+        let pbos = [Buffer::new(gl::PIXEL_PACK_BUFFER)?, Buffer::new(gl::PIXEL_PACK_BUFFER)?];
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        let (sender, jobs) = mpsc::sync_channel::<Encode>(QUEUE_DEPTH);
+        let worker = thread::spawn(move || {
+            let mut clipboard = Clipboard::new();
+            for job in jobs {
+                let image = if job.flip { job.image.flipv() } else { job.image };
+                match job.target {
+                    Target::File(path) => match metadata::save_png(Path::new(&path), &image, &job.text) {
+                        Ok(()) => info!("saved {path}"),
+                        Err(err) => warn!("failed to save {err}"),
+                    },
+                    Target::Clipboard => match clipboard.copy(&image, &job.text) {
+                        Ok(done) => info!("{done}"),
+                        Err(err) => warn!("{err}"),
+                    },
+                }
+            }
+        });
+        Ok(Capturer { pbos, in_flight: VecDeque::new(), sender: Some(sender), worker: Some(worker) })
+    }
+
+    /// Starts copying the bound read framebuffer into a PBO. Call right after
+    /// drawing the frame to capture; the pixels are picked up by `poll` and
+    /// sent to `target`, with `text` as PNG text chunks.
+    pub fn read_framebuffer(&mut self, width: i32, height: i32, target: Target, text: Vec<(String, String)>) {
+        // OpenGL 2.1 may have neither fences nor mapping by range: read it now, hitch and all
+        if !GpuCapabilities::current().has(Feature::AsyncReadback) {
+            self.save(renderer::read_pixels(width, height).into(), target, text);
+            return;
+        }
+        // both buffers busy: wait for the oldest rather than overwrite it
+        if self.in_flight.len() == self.pbos.len() {
+            self.collect_oldest(true);
+        }
+        let pbo = (0..self.pbos.len())
+            .find(|i| self.in_flight.iter().all(|r| r.pbo != *i))
+            .expect("a free buffer after collecting");
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.pbos[pbo].id());
+            renderer::tight_rows();
+            gl::BufferData(gl::PIXEL_PACK_BUFFER, (width * height * 4) as GLsizeiptr, ptr::null(), gl::STREAM_READ);
+            // with a pack buffer bound the pointer is an offset into it, and this returns immediately
+            gl::ReadPixels(0, 0, width, height, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null_mut());
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            self.in_flight.push_back(Readback { pbo, fence, width, height, target, text });
+        }
+    }
+
+    /// Hands an image that is already in memory (e.g. from the CPU renderer)
+    /// straight to the encoder.
+    pub fn save(&mut self, image: DynamicImage, target: Target, text: Vec<(String, String)>) {
+        self.encode(Encode { image, flip: false, target, text });
+    }
+
+    /// Picks up every readback the GPU has finished, without waiting. Call once per frame.
+    pub fn poll(&mut self) {
+        while !self.in_flight.is_empty() && self.collect_oldest(false) {}
+    }
+
+    /// Finishes pending readbacks and waits for the encoder, so no capture is
+    /// lost on exit. Needs the GL context to still be current.
+    pub fn shutdown(mut self) {
+        while !self.in_flight.is_empty() {
+            self.collect_oldest(true);
+        }
+        drop(self.sender.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        // the buffers go with `self`, here
+    }
+
+    /// Maps the oldest readback and queues it for encoding. Without `wait`
+    /// it gives up (returning false) if the GPU is not done with it yet. A
+    /// readback that fails is dropped with a warning, which frees its buffer
+    /// all the same.
+    fn collect_oldest(&mut self, wait: bool) -> bool {
+        let Some(readback) = self.in_flight.front() else {
+            return false;
+        };
+        let status = unsafe {
+            let timeout = if wait { GLuint64::MAX } else { 0 };
+            gl::ClientWaitSync(readback.fence, gl::SYNC_FLUSH_COMMANDS_BIT, timeout)
+        };
+        let signaled = status == gl::ALREADY_SIGNALED || status == gl::CONDITION_SATISFIED;
+        if !signaled && !wait && status == gl::TIMEOUT_EXPIRED {
+            return false;
+        }
+
+        let readback = self.in_flight.pop_front().expect("checked above");
+        unsafe {
+            gl::DeleteSync(readback.fence);
+        }
+        if !signaled {
+            warn!("dropped a capture: waiting for the GPU to copy it failed");
+            return true;
+        }
+        let len = (readback.width * readback.height * 4) as usize;
+        let mut pixels = vec![0u8; len];
+        let mapped = unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.pbos[readback.pbo].id());
+            // OpenGL ES only maps ranges
+            let mapped = gl::MapBufferRange(gl::PIXEL_PACK_BUFFER, 0, len as GLsizeiptr, gl::MAP_READ_BIT) as *const u8;
+            if !mapped.is_null() {
+                ptr::copy_nonoverlapping(mapped, pixels.as_mut_ptr(), len);
+                gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            }
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            !mapped.is_null()
+        };
+        if !mapped {
+            warn!("dropped a capture: its pixels could not be mapped");
+            return true;
+        }
+
+        let image = RgbaImage::from_raw(readback.width as u32, readback.height as u32, pixels)
+            .expect("one pixel per four bytes");
+        self.encode(Encode { image: image.into(), flip: true, target: readback.target, text: readback.text });
+        true
+    }
+
+    fn encode(&mut self, job: Encode) {
+        if let Some(sender) = &self.sender {
+            // blocks while the queue is full
+            let _ = sender.send(job);
+        }
+    }
+}
+
+/// Timestamped PNG name in the working directory.
+pub fn capture_path(prefix: &str) -> String {
+    format!("{}.png", capture_name(prefix))
+}
+
+/// `prefix` with a millisecond timestamp, so captures never overwrite each other.
+pub fn capture_name(prefix: &str) -> String {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+    format!("{prefix}_{stamp}")
+}
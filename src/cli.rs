@@ -0,0 +1,869 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+
+use crate::background::{BackgroundFit, BlendMode};
+use crate::data::DataChannel;
+use crate::export::BitDepth;
+use crate::keyframes::Easing;
+use crate::logging;
+use crate::palette::{ColorMode, Lighting, Palette, Tone};
+use crate::recovery;
+use crate::sensitivity;
+use crate::session::Session;
+use crate::settings::{self, Settings};
+use crate::tileset;
+use crate::viewport;
+
+/// Interactive Mandelbrot set explorer.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Options {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Log more: `-v` adds debug messages, `-vv` per-frame traces too (in
+    /// debug builds). `RUST_LOG`, when set, overrides it.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only log warnings and errors.
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Render to the terminal with the CPU renderer instead of opening a window.
+    #[arg(long)]
+    pub terminal: bool,
+
+    /// In terminal mode, use a plain ASCII gradient even if the terminal
+    /// advertises 24-bit color support.
+    #[arg(long, requires = "terminal")]
+    pub ascii: bool,
+
+    /// Size of the window. Without it, the last session's size is restored.
+    #[arg(long, default_value = "600x600", value_name = "WxH", value_parser = parse_size)]
+    pub window_size: (u32, u32),
+
+    /// Open fullscreen on the primary monitor, at its current resolution.
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Let the desktop show through the window where the interior is
+    /// transparent (T), for an ambient display over it; the interior starts
+    /// out transparent. Where the platform can't, the window stays opaque.
+    #[arg(long)]
+    pub transparent_window: bool,
+
+    /// Keep the window above the others.
+    #[arg(long)]
+    pub always_on_top: bool,
+
+    /// Wait for the display's refresh between frames; `--vsync false` draws
+    /// as fast as it can, e.g. to watch frame times with `-vv`.
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set, value_name = "BOOL")]
+    pub vsync: bool,
+
+    /// Ask for an OpenGL ES 3.0 context instead of desktop OpenGL, as on a
+    /// Raspberry Pi. ES is also tried when there is no desktop 3.3 context.
+    #[arg(long)]
+    pub gles: bool,
+
+    /// Complex coordinate the window starts centered on.
+    #[arg(long, num_args = 2, value_names = ["RE", "IM"], allow_negative_numbers = true, default_values_t = [0., 0.])]
+    pub center: Vec<f64>,
+
+    /// Half of the visible vertical extent the window starts at (smaller is
+    /// deeper).
+    #[arg(long, default_value_t = 1., value_name = "UNITS", value_parser = positive_f64)]
+    pub zoom: f64,
+
+    /// Iterations the window starts with. Defaults to what `--quality` picks.
+    #[arg(long, default_value_t = 1000, hide_default_value = true, value_name = "N", value_parser = clap::value_parser!(i32).range(1..))]
+    pub iter: i32,
+
+    /// Start on the Julia set of this constant instead of the Mandelbrot set.
+    #[arg(long, num_args = 2, value_names = ["RE", "IM"], allow_negative_numbers = true)]
+    pub julia: Option<Vec<f64>>,
+
+    /// What places escaped points along the palette at startup; H cycles it.
+    #[arg(long, value_enum, default_value_t = ColorMode::EscapeTime)]
+    pub color_mode: ColorMode,
+
+    /// Palette to start with, a JSON file as the palette editor (G) saves.
+    #[arg(long, value_name = "PATH", value_parser = parse_palette)]
+    pub palette: Option<Palette>,
+
+    /// Multisample the window's framebuffer with this many samples. This only
+    /// smooths geometry edges (overlays); use supersampling (X) for the fractal.
+    #[arg(long, default_value_t = 0, value_name = "SAMPLES", value_parser = clap::value_parser!(u32).range(0..=32))]
+    pub msaa: u32,
+
+    /// Size of the high-resolution export started with P. The vertical extent of
+    /// the view is kept, the horizontal one follows this aspect ratio.
+    #[arg(long, default_value = "7680x4320", value_name = "WxH", value_parser = parse_size)]
+    pub export_size: (u32, u32),
+
+    /// Iterations and supersampling in one setting; `--iter`, `--ssaa` and
+    /// `--export-ssaa` still win when given. U in the window cycles it.
+    #[arg(long, global = true, value_enum, default_value_t = Quality::Medium, value_name = "LEVEL")]
+    pub quality: Quality,
+
+    /// Supersamples per pixel axis for the high-resolution export. Defaults to
+    /// what `--quality` picks.
+    #[arg(long, default_value_t = 2, hide_default_value = true, value_name = "N", value_parser = clap::value_parser!(i32).range(1..=8))]
+    pub export_ssaa: i32,
+
+    /// Longer edge of a region export (Shift+drag a rectangle, then P); the
+    /// other edge follows the selection's aspect ratio.
+    #[arg(long, default_value_t = 7680, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(1..=viewport::MAX_EDGE as i64))]
+    pub region_edge: u32,
+
+    /// Frame size of the zoom animation started with L.
+    #[arg(long, default_value = "1920x1080", value_name = "WxH", value_parser = parse_size)]
+    pub animation_size: (u32, u32),
+
+    /// Keyframe file to start the explorer with (and that F5 saves to), or to
+    /// render with `render --export-video`.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub keyframes: Option<PathBuf>,
+
+    /// How closely keyframes recorded with R follow the flight: the largest
+    /// allowed drift in view heights (center), ln(scale) (zoom) and radians.
+    #[arg(long, default_value_t = 0.02, value_name = "ERROR", value_parser = positive_f64)]
+    pub record_tolerance: f64,
+
+    /// Frames per second of the zoom animation (L, or `render --export-video`).
+    #[arg(long, global = true, default_value_t = 30., value_name = "FPS", value_parser = positive_f64)]
+    pub animation_fps: f64,
+
+    /// Bits per channel of screenshots, exports and animation frames (PNG, or
+    /// TIFF for `render --out`). Videos stay 8-bit.
+    #[arg(long, global = true, value_enum, default_value_t = BitDepth::Eight, value_name = "BITS")]
+    pub bit_depth: BitDepth,
+
+    /// Seconds between keyframes that don't set their own duration.
+    #[arg(long, global = true, default_value_t = 10., value_name = "SECONDS", value_parser = positive_f64)]
+    pub animation_seconds: f64,
+
+    /// Color F paints points that used up every iteration but ended far out,
+    /// as `RRGGBB` hex.
+    #[arg(long, default_value = "ff8000", value_name = "RRGGBB", value_parser = parse_hex_color)]
+    pub slow_color: [f32; 3],
+
+    /// Final |z| above which F marks a bounded point as slow: past 2 it is
+    /// sure to escape, lower marks more points that are likely to.
+    #[arg(long, default_value_t = 2., value_name = "ABS_Z", value_parser = positive_f64)]
+    pub slow_threshold: f64,
+
+    /// Light the exterior by its slope, so the bands look embossed, as
+    /// Shift+H toggles in the window.
+    #[arg(long, global = true)]
+    pub relief: bool,
+
+    /// Remap the plane logarithmically around the center, so the set tiles
+    /// endlessly inward as a tunnel, as Shift+C toggles in the window.
+    #[arg(long, global = true)]
+    pub log_polar: bool,
+
+    /// Where the light of `--relief` comes from, in degrees counter-clockwise
+    /// from the right.
+    #[arg(long, global = true, default_value_t = 135., value_name = "DEGREES", allow_negative_numbers = true)]
+    pub light_angle: f32,
+
+    /// How much `--relief` darkens the slopes facing away from the light,
+    /// from 0 (not at all) to 1.
+    #[arg(long, global = true, default_value_t = 0.75, value_name = "AMOUNT", value_parser = unit_f32)]
+    pub light_intensity: f32,
+
+    /// Seconds between the screenshots of a timelapse (Shift+F12).
+    #[arg(long, default_value_t = 5., value_name = "SECONDS", value_parser = positive_f64)]
+    pub timelapse_seconds: f64,
+
+    /// Take a timelapse screenshot every this many frames instead.
+    #[arg(long, value_name = "FRAMES", conflicts_with = "timelapse_seconds", value_parser = clap::value_parser!(u32).range(1..))]
+    pub timelapse_frames: Option<u32>,
+
+    /// Stop a timelapse after this many screenshots.
+    #[arg(long, value_name = "COUNT")]
+    pub timelapse_max_images: Option<usize>,
+
+    /// Stop a timelapse once its screenshots take this many megabytes.
+    #[arg(long, value_name = "MB", value_parser = positive_f64)]
+    pub timelapse_max_mb: Option<f64>,
+
+    /// Seconds photo mode (F11) counts down after Enter before it takes the
+    /// photo; 0 takes it right away.
+    #[arg(long, default_value_t = 3., value_name = "SECONDS", value_parser = non_negative_f64)]
+    pub photo_countdown: f64,
+
+    /// How many times the window's size photo mode's photos are.
+    #[arg(long, default_value_t = 2, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=8))]
+    pub photo_scale: u32,
+
+    /// How much held navigation keys and fast scrolls speed up: the exponent
+    /// of a `(1 + x)^curve` gain, 0 for constant speeds. F2 opens a slider.
+    #[arg(long, default_value_t = 0., value_name = "CURVE", value_parser = sensitivity_curve)]
+    pub sensitivity: f64,
+
+    /// Iterations one GPU draw runs at most; views with more draw them over
+    /// several, so a deep view doesn't hang the desktop or get the GPU reset
+    /// by the driver's watchdog (two seconds on Windows). 0 never splits.
+    #[arg(long, global = true, default_value_t = 0, value_name = "N", value_parser = clap::value_parser!(i32).range(0..))]
+    pub iterations_per_draw: i32,
+
+    /// Threads the CPU renderer uses: the software fallback, `render` without
+    /// `--gpu`, `--terminal` and `--batch`. All cores by default.
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=1024))]
+    pub threads: Option<u32>,
+
+    /// Color the window shows through a transparent interior (T) while the
+    /// checkerboard is off, as `RRGGBB` hex.
+    #[arg(long, default_value = "000000", value_name = "RRGGBB", value_parser = parse_hex_color)]
+    pub clear_color: [f32; 3],
+
+    /// Navigate with a gamepad as well: left stick pans, triggers zoom,
+    /// bumpers change the iterations, A takes a screenshot, Start resets.
+    #[arg(long)]
+    pub gamepad: bool,
+
+    /// From this many iterations on, the GPU draws a changed view coarse
+    /// first and refines it in tiles over the next frames; 0 never does.
+    /// Off with `--msaa`.
+    #[arg(long, default_value_t = 10000, value_name = "ITERATIONS", value_parser = clap::value_parser!(i32).range(0..))]
+    pub progressive_above: i32,
+
+    /// Start with the iteration count following the zoom, as Shift+U toggles.
+    #[arg(long)]
+    pub auto_iterations: bool,
+
+    /// Run every line on stdin as navigation commands, as typed into the F4
+    /// window (`pan 0.001 -0.002, zoom 0.5`, see the README).
+    #[arg(long)]
+    pub stdin_commands: bool,
+
+    /// Seconds Y takes to cross-fade between the Mandelbrot set and the
+    /// Julia set under the cursor; 0 switches at once.
+    #[arg(long, default_value_t = 0.5, value_name = "SECONDS", value_parser = non_negative_f64)]
+    pub morph_seconds: f64,
+
+    /// Start at the view saved in this PNG's metadata (written into every
+    /// screenshot, export and frame), or at a Kalles Fraktaler location
+    /// (`.kfr`). Dropping either on the window does the same.
+    #[arg(long, value_name = "FILE")]
+    pub open: Option<PathBuf>,
+
+    /// Saved explorer state to start from, if the file exists, and where
+    /// Ctrl+S saves and Ctrl+O loads it [default: state.json].
+    #[arg(long, value_name = "PATH", conflicts_with = "open")]
+    pub state: Option<PathBuf>,
+
+    /// Where the views kept in the F1 window are read from and saved to
+    /// [default: bookmarks.json].
+    #[arg(long, value_name = "PATH")]
+    pub bookmarks: Option<PathBuf>,
+
+    /// Start at the view in a saved state, a `.kfr`, a PNG written by the
+    /// explorer or a location string, told apart by extension or content,
+    /// with its palette and coloring when it has them.
+    #[arg(long, value_name = "PATH|STRING", conflicts_with_all = ["open", "state", "location"])]
+    pub load: Option<String>,
+
+    /// Start at a location string (`mb2:...`), as copied with Ctrl+Shift+C.
+    #[arg(long, value_name = "STRING", conflicts_with = "open")]
+    pub location: Option<String>,
+
+    /// Image (PNG, JPEG, ...) to composite the fractal over. The interior
+    /// turns transparent to show it.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub background: Option<PathBuf>,
+
+    /// How the background covers a view of another aspect ratio.
+    #[arg(long, global = true, value_enum, default_value_t = BackgroundFit::Fill, requires = "background")]
+    pub background_fit: BackgroundFit,
+
+    /// How escaped points combine with the background under them.
+    #[arg(long, global = true, value_enum, default_value_t = BlendMode::Over, requires = "background")]
+    pub blend: BlendMode,
+
+    /// Write the palette (`--palette`, or the grayscale one) as a PNG strip
+    /// to this file, or to `palette_<name>.png` like F10 without one, and exit.
+    #[arg(long, value_name = "PATH", num_args = 0..=1)]
+    pub export_palette: Option<Option<PathBuf>>,
+
+    /// Write the fragment shader source as compiled to this file (or to
+    /// stdout without one) and exit. F9 in the window does the same.
+    #[arg(long, value_name = "PATH", num_args = 0..=1)]
+    pub dump_shader: Option<Option<PathBuf>>,
+
+    /// Compile the fractal shaders from `fractal.vert` and `fractal.frag` in
+    /// this directory instead of the built-in ones. Without it, a `shaders`
+    /// directory next to the executable is used if there is one.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub shader_dir: Option<PathBuf>,
+
+    /// Render every job in this JSON file, one after the other, without
+    /// opening a window (see the README for the format), then exit.
+    #[arg(long, value_name = "JOBS")]
+    pub batch: Option<PathBuf>,
+
+    /// Keep what a cancelled export, animation or render wrote so far (the
+    /// rows done, the frames done) instead of removing it.
+    #[arg(long, global = true)]
+    pub keep_partial: bool,
+
+    /// Render the view the explorer was left at (or `--open`, `--state`,
+    /// `--location`) at the primary monitor's resolution with `--export-ssaa`,
+    /// make it the desktop wallpaper and exit.
+    #[arg(long)]
+    pub set_wallpaper: bool,
+
+    /// With `--set-wallpaper`, the view of the day instead: a spot near the
+    /// edge of the set and a palette, picked from the date.
+    #[arg(long, requires = "set_wallpaper", conflicts_with_all = ["open", "state", "location"])]
+    pub daily: bool,
+
+    /// Stop a batch at the first job that fails instead of going on.
+    #[arg(long, requires = "batch")]
+    pub fail_fast: bool,
+
+    /// Answer HTTP GETs on this local port with a PNG of the view in the
+    /// query (see the README), until killed. Needs the `serve` feature.
+    #[arg(long, value_name = "PORT")]
+    pub serve: Option<u16>,
+
+    /// Start with the defaults instead of the camera, window and options the
+    /// last session left behind (see the README).
+    #[arg(long)]
+    pub fresh: bool,
+
+    /// The last session's settings, unless `--fresh`; `parse` already merged
+    /// the options the command line didn't give.
+    #[arg(skip)]
+    pub restored: Option<Settings>,
+
+    /// The view saved when the explorer last crashed, unless `--fresh` or
+    /// the command line picks one.
+    #[arg(skip)]
+    pub recovered: Option<Session>,
+
+    /// Run the fixed benchmark scenarios, print a timing table and exit.
+    #[arg(long)]
+    pub bench: bool,
+
+    /// Print what an offscreen GL context here can do (version, limits,
+    /// extensions, the optional capabilities features check for) and exit.
+    #[arg(long)]
+    pub print_caps: bool,
+
+    /// Also write the benchmark results to this JSON file.
+    #[arg(long, value_name = "PATH", requires = "bench")]
+    pub bench_json: Option<PathBuf>,
+
+    /// Seed for everything random (supersample jitter, the Monte Carlo area
+    /// estimate) so runs can be reproduced. Offline renders default to 0, the
+    /// interactive window to a time-based seed that it prints at startup.
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+
+    /// Half of the visible horizontal extent in complex units. Defaults to
+    /// whatever keeps pixels square.
+    #[arg(long, global = true, value_name = "UNITS")]
+    pub scale_x: Option<f64>,
+
+    /// Half of the visible vertical extent in complex units [default: 1].
+    #[arg(long, global = true, value_name = "UNITS")]
+    pub scale_y: Option<f64>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Render a single frame to an image file and exit without opening a window.
+    Render(Box<RenderArgs>),
+    /// List or edit a keyframe file.
+    Keyframes(KeyframesArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct KeyframesArgs {
+    /// Keyframe file, as saved by the explorer with F5.
+    pub file: PathBuf,
+
+    #[command(subcommand)]
+    pub action: Option<KeyframesAction>,
+}
+
+/// Keyframes are numbered from 1; the file is rewritten after an edit.
+#[derive(Subcommand, Debug)]
+pub enum KeyframesAction {
+    /// Print every keyframe (the default).
+    List,
+    /// Remove a keyframe.
+    Delete { index: usize },
+    /// Move a keyframe to another position.
+    Move { from: usize, to: usize },
+    /// Change how the segment from a keyframe to the next one plays.
+    Set {
+        index: usize,
+        /// Seconds to the next keyframe.
+        #[arg(long, value_parser = positive_f64)]
+        duration: Option<f64>,
+        #[arg(long, value_enum)]
+        easing: Option<Easing>,
+    },
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct RenderArgs {
+    /// Complex coordinate at the middle of the image.
+    #[arg(long, num_args = 2, value_names = ["RE", "IM"], allow_negative_numbers = true, default_values_t = [0., 0.])]
+    pub center: Vec<f64>,
+
+    /// Half of the visible vertical extent in complex units (smaller is deeper).
+    #[arg(long, default_value_t = 1., value_parser = positive_f64)]
+    pub zoom: f64,
+
+    /// Maximum number of iterations. Defaults to what `--quality` picks.
+    #[arg(long, default_value_t = 1000, hide_default_value = true, value_parser = clap::value_parser!(i32).range(1..))]
+    pub iter: i32,
+
+    /// Take the center, zoom and iterations from a Kalles Fraktaler
+    /// location file (`.kfr`) instead.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["center", "zoom", "iter"])]
+    pub kfr: Option<PathBuf>,
+
+    /// Image size in pixels.
+    #[arg(long, default_value = "600x600", value_parser = parse_size)]
+    pub size: (u32, u32),
+
+    /// Output image; the format is taken from the extension.
+    #[arg(
+        long,
+        required_unless_present_any = ["export_data", "export_contours", "export_video", "export_frames", "export_tiles"],
+        conflicts_with_all = ["export_data", "export_contours", "export_video", "export_frames", "export_tiles"]
+    )]
+    pub out: Option<PathBuf>,
+
+    /// Write the pre-palette values of every pixel (see `--data-channels`)
+    /// instead of an image, as `.npy`, `.f32`, `.exr` or `.csv`, plus a `.json`
+    /// file with the view parameters.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["export_contours", "export_video", "export_frames", "export_tiles"])]
+    pub export_data: Option<PathBuf>,
+
+    /// Values per pixel for `--export-data`, comma-separated, in this order.
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "escape", requires = "export_data")]
+    pub data_channels: Vec<DataChannel>,
+
+    /// Trace iso-iteration contours of the view (see `--contour-levels`) and
+    /// write them as SVG paths, for plotters and laser cutters.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["export_video", "export_frames", "export_tiles", "julia"])]
+    pub export_contours: Option<PathBuf>,
+
+    /// Smooth escape iterations to trace, comma-separated. Defaults to
+    /// `--iter`, the outline of the set.
+    #[arg(long, value_name = "ITERATIONS", value_delimiter = ',', value_parser = positive_f64, requires = "export_contours")]
+    pub contour_levels: Vec<f64>,
+
+    /// Samples of the contour grid per image pixel along each axis.
+    #[arg(long, default_value_t = 1., value_name = "FACTOR", value_parser = positive_f64, requires = "export_contours")]
+    pub contour_resolution: f64,
+
+    /// How far, in image pixels, simplified contours may stray from the
+    /// traced ones; 0 keeps every point.
+    #[arg(long, default_value_t = 0.25, value_name = "PIXELS", value_parser = non_negative_f64, requires = "export_contours")]
+    pub contour_tolerance: f64,
+
+    /// Stroke width in image pixels, one for every level or one per level.
+    #[arg(long, default_value = "1", value_name = "PIXELS", value_delimiter = ',', value_parser = positive_f64, requires = "export_contours")]
+    pub contour_stroke: Vec<f64>,
+
+    /// Render a zoom from `--center`/`--zoom` to `--to-center`/`--to-zoom` (or
+    /// along `--keyframes`, or a `--julia-path` morph) and pipe the frames into
+    /// ffmpeg, which must be installed, to write this video.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["export_frames", "export_tiles"])]
+    pub export_video: Option<PathBuf>,
+
+    /// Like `--export-video`, but save the frames as numbered PNGs in this
+    /// directory.
+    #[arg(long, value_name = "DIR", conflicts_with = "export_tiles")]
+    pub export_frames: Option<PathBuf>,
+
+    /// Render a pyramid of map tiles around `--center` into this directory,
+    /// as `LEVEL/X/Y.png` with a Leaflet page to browse them, for putting a
+    /// deep zoom on the web. Level 0 is one tile of `--zoom`, and each level
+    /// after it has twice the resolution; of each, only the tiles within
+    /// `--size` of the center are rendered.
+    #[arg(long, value_name = "DIR", conflicts_with = "julia_path")]
+    pub export_tiles: Option<PathBuf>,
+
+    /// Shallowest level of `--export-tiles`.
+    #[arg(long, default_value_t = 0, value_name = "LEVEL", value_parser = clap::value_parser!(u32).range(0..=tileset::MAX_LEVEL as i64), requires = "export_tiles")]
+    pub min_level: u32,
+
+    /// Deepest level of `--export-tiles`.
+    #[arg(long, default_value_t = 8, value_name = "LEVEL", value_parser = clap::value_parser!(u32).range(0..=tileset::MAX_LEVEL as i64), requires = "export_tiles")]
+    pub max_level: u32,
+
+    /// Edge of the tiles of `--export-tiles`, in pixels.
+    #[arg(long, default_value_t = 256, value_name = "PIXELS", value_parser = clap::value_parser!(u32).range(16..=4096), requires = "export_tiles")]
+    pub tile_size: u32,
+
+    /// Where the video ends; defaults to `--center`.
+    #[arg(long, num_args = 2, value_names = ["RE", "IM"], allow_negative_numbers = true, conflicts_with = "julia_path")]
+    pub to_center: Option<Vec<f64>>,
+
+    /// Zoom at the end of the video.
+    #[arg(long, value_parser = positive_f64, conflicts_with = "julia_path")]
+    pub to_zoom: Option<f64>,
+
+    /// Zoom to `--to-zoom` at this many factors of ten per second, instead of
+    /// over `--animation-seconds`: every frame then zooms by the same factor,
+    /// so the video keeps one steady pace at `--animation-fps`.
+    #[arg(long, value_name = "DECADES", value_parser = positive_f64, requires = "to_zoom")]
+    pub decades_per_second: Option<f64>,
+
+    /// Render the Julia set of this constant instead of the Mandelbrot set.
+    #[arg(long, num_args = 2, value_names = ["RE", "IM"], allow_negative_numbers = true, conflicts_with = "export_data")]
+    pub julia: Option<Vec<f64>>,
+
+    /// Keep the camera still and move the Julia constant along this path file
+    /// (a circle, a line or a trace recorded with F6) over `--animation-seconds`,
+    /// into `--export-video` or `--export-frames`.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["out", "export_data", "julia", "keyframes"])]
+    pub julia_path: Option<PathBuf>,
+
+    /// Encoder arguments passed to ffmpeg between the input and the output, as
+    /// one space-separated string, e.g. "-c:v libvpx-vp9 -crf 30 -b:v 0".
+    #[arg(
+        long,
+        value_name = "ARGS",
+        allow_hyphen_values = true,
+        value_delimiter = ' ',
+        default_value = "-c:v libx264 -pix_fmt yuv420p -crf 18"
+    )]
+    pub ffmpeg_args: Vec<String>,
+
+    /// Supersamples per pixel axis (1 disables anti-aliasing). Defaults to what
+    /// `--quality` picks.
+    #[arg(long, default_value_t = 1, hide_default_value = true, value_parser = clap::value_parser!(i32).range(1..=8))]
+    pub ssaa: i32,
+
+    /// Render with OpenGL in an invisible window instead of on the CPU.
+    #[arg(long)]
+    pub gpu: bool,
+
+    /// Leave the interior of the set transparent.
+    #[arg(long)]
+    pub transparent: bool,
+
+    /// What places escaped points along the palette.
+    #[arg(long, value_enum, default_value_t = ColorMode::EscapeTime)]
+    pub color_mode: ColorMode,
+
+    /// The relief of a saved session, which `--relief` can't express; unset
+    /// leaves it to `--relief`.
+    #[arg(skip)]
+    pub lighting: Option<Lighting>,
+
+    /// The iterations a saved session locked the palette to.
+    #[arg(skip)]
+    pub color_span: Option<i32>,
+
+    /// The brightness and contrast of a saved session.
+    #[arg(skip)]
+    pub tone: Tone,
+}
+
+/// Presets for casual use. Each sets the iteration count, the supersampling of
+/// the view (or of `render`) and that of exports:
+///
+/// | level  | iterations | view | exports |
+/// |--------|-----------:|-----:|--------:|
+/// | low    |        250 |   1x |      1x |
+/// | medium |       1000 |   1x |      2x |
+/// | high   |       2500 |   2x |      3x |
+/// | ultra  |      10000 |   4x |      4x |
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+impl Quality {
+    pub fn next(self) -> Quality {
+        match self {
+            Quality::Low => Quality::Medium,
+            Quality::Medium => Quality::High,
+            Quality::High => Quality::Ultra,
+            Quality::Ultra => Quality::Low,
+        }
+    }
+
+    pub fn iterations(self) -> i32 {
+        match self {
+            Quality::Low => 250,
+            Quality::Medium => 1000,
+            Quality::High => 2500,
+            Quality::Ultra => 10000,
+        }
+    }
+
+    /// Supersamples per pixel axis of the view and of `render`.
+    pub fn ssaa(self) -> i32 {
+        match self {
+            Quality::Low | Quality::Medium => 1,
+            Quality::High => 2,
+            Quality::Ultra => 4,
+        }
+    }
+
+    /// Supersamples per pixel axis of P exports.
+    pub fn export_ssaa(self) -> i32 {
+        match self {
+            Quality::Low => 1,
+            Quality::Medium => 2,
+            Quality::High => 3,
+            Quality::Ultra => 4,
+        }
+    }
+}
+
+/// Parses the command line, then fills in what `--quality` picks for every
+/// finer option that wasn't given.
+pub fn parse() -> Options {
+    let matches = Options::command().get_matches();
+    let mut options = Options::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    // before anything that might log, such as a broken settings file
+    logging::init(options.verbose, options.quiet);
+    if (options.opens_window() || options.set_wallpaper) && !options.fresh {
+        options.restored = settings::load().map(|restored| restore(&mut options, &matches, restored));
+    }
+    if options.opens_window() {
+        // removed either way, so it doesn't turn up much later
+        let recovered = recovery::take();
+        if !options.fresh && !picks_view(&matches) {
+            options.recovered = recovered;
+        }
+    }
+    let quality = options.quality;
+    let defaulted = |matches: &ArgMatches, id: &str| matches.value_source(id) == Some(ValueSource::DefaultValue);
+    if defaulted(&matches, "export_ssaa") {
+        options.export_ssaa = quality.export_ssaa();
+    }
+    if defaulted(&matches, "iter") {
+        options.iter = quality.iterations();
+    }
+    if let (Some(Command::Render(args)), Some(("render", render))) = (&mut options.command, matches.subcommand()) {
+        if defaulted(render, "iter") {
+            args.iter = quality.iterations();
+        }
+        if defaulted(render, "ssaa") {
+            args.ssaa = quality.ssaa();
+        }
+    }
+    options
+}
+
+impl Options {
+    /// The light of `--relief`, whether or not it is on.
+    pub fn lighting(&self) -> Lighting {
+        Lighting { angle: self.light_angle, intensity: self.light_intensity }
+    }
+
+    /// Whether this run is the explorer, not one of the modes that exit.
+    fn opens_window(&self) -> bool {
+        self.command.is_none()
+            && self.batch.is_none()
+            && self.serve.is_none()
+            && self.export_palette.is_none()
+            && self.dump_shader.is_none()
+            && !self.set_wallpaper
+            && !self.bench
+            && !self.terminal
+    }
+}
+
+/// Whether the command line says where to start, over what was saved.
+fn picks_view(matches: &ArgMatches) -> bool {
+    let given = |id: &str| matches.value_source(id).is_some_and(|source| source != ValueSource::DefaultValue);
+    ["load", "open", "state", "location", "scale_x", "scale_y", "center", "zoom", "julia"].into_iter().any(given)
+}
+
+/// Takes the options the command line didn't give from `restored`, and
+/// drops its view if the command line picks one.
+fn restore(options: &mut Options, matches: &ArgMatches, mut restored: Settings) -> Settings {
+    let given = |id: &str| matches.value_source(id).is_some_and(|source| source != ValueSource::DefaultValue);
+    if !given("msaa") {
+        options.msaa = restored.msaa;
+    }
+    if !given("iterations_per_draw") {
+        options.iterations_per_draw = restored.iterations_per_draw;
+    }
+    if !given("quality") {
+        options.quality = restored.quality;
+    } else if let Some(session) = &mut restored.session {
+        session.iterations = options.quality.iterations();
+        session.ssaa = options.quality.ssaa();
+    }
+    if !given("export_size") {
+        options.export_size = restored.export_size;
+    }
+    if !given("animation_size") {
+        options.animation_size = restored.animation_size;
+    }
+    if !given("sensitivity") {
+        options.sensitivity = restored.sensitivity.clamp(0., sensitivity::MAX_CURVE);
+    }
+    if !given("clear_color") {
+        options.clear_color = restored.clear_color;
+    }
+    if given("window_size") {
+        restored.window.size = (options.window_size.0 as i32, options.window_size.1 as i32);
+    }
+    if picks_view(matches) {
+        restored.session = None;
+    }
+    if let Some(session) = &mut restored.session {
+        if given("iter") {
+            session.iterations = options.iter;
+        }
+        if given("color_mode") {
+            session.color_mode = options.color_mode;
+        }
+        if let Some(palette) = &options.palette {
+            session.palette = palette.clone();
+        }
+    }
+    restored
+}
+
+/// `render --out <out>` with everything else at its default for `quality`,
+/// for batch jobs to fill in.
+pub fn render_defaults(out: PathBuf, quality: Quality) -> RenderArgs {
+    // through the whole command, which the global arguments `render` refers to live in
+    let argv = [OsString::from(env!("CARGO_PKG_NAME")), "render".into(), "--out".into(), out.into_os_string()];
+    let Some(Command::Render(args)) = Options::parse_from(argv).command else {
+        unreachable!("parsed as render");
+    };
+    let mut args = *args;
+    args.iter = quality.iterations();
+    args.ssaa = quality.ssaa();
+    args
+}
+
+/// Loads the palette file `s` names.
+fn parse_palette(s: &str) -> Result<Palette, String> {
+    Palette::load(std::path::Path::new(s))
+}
+
+/// Parses `RRGGBB`, with or without a leading `#`, into 0..1 channels.
+fn parse_hex_color(s: &str) -> Result<[f32; 3], String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    // digits only: `from_str_radix` would take a sign as well
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("expected RRGGBB, got `{s}`"));
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_or(0., |v| v as f32 / 255.);
+    Ok([channel(0), channel(2), channel(4)])
+}
+
+/// Parses `WIDTHxHEIGHT`, e.g. `1920x1080`.
+pub fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let (w, h) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got `{s}`"))?;
+    let w: u32 = w.trim().parse().map_err(|_| format!("invalid width `{w}`"))?;
+    let h: u32 = h.trim().parse().map_err(|_| format!("invalid height `{h}`"))?;
+    if w == 0 || h == 0 {
+        return Err("width and height must be at least 1".to_string());
+    }
+    if w.max(h) > viewport::MAX_EDGE {
+        return Err(format!("width and height can be at most {}", viewport::MAX_EDGE));
+    }
+    Ok((w, h))
+}
+
+fn positive_f64(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v > 0. && v.is_finite() => Ok(v),
+        Ok(_) => Err("must be a positive number".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn sensitivity_curve(s: &str) -> Result<f64, String> {
+    match non_negative_f64(s)? {
+        curve if curve <= sensitivity::MAX_CURVE => Ok(curve),
+        _ => Err(format!("must be at most {}", sensitivity::MAX_CURVE)),
+    }
+}
+
+fn unit_f32(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if (0. ..=1.).contains(&v) => Ok(v),
+        Ok(_) => Err("must be between 0 and 1".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn non_negative_f64(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(v) if v >= 0. && v.is_finite() => Ok(v),
+        Ok(_) => Err("must be zero or more".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1920x1080"), Ok((1920, 1080)));
+        assert_eq!(parse_size("64X48"), Ok((64, 48)));
+        assert_eq!(parse_size(" 3 x 2 "), Ok((3, 2)));
+        assert_eq!(parse_size("1920"), Err("expected WIDTHxHEIGHT, got `1920`".into()));
+        assert_eq!(parse_size("wx10"), Err("invalid width `w`".into()));
+        assert_eq!(parse_size("10x-1"), Err("invalid height `-1`".into()));
+        assert_eq!(parse_size("0x10"), Err("width and height must be at least 1".into()));
+        assert!(parse_size(&format!("{}x1", viewport::MAX_EDGE)).is_ok());
+        assert!(parse_size(&format!("1x{}", viewport::MAX_EDGE + 1)).is_err());
+    }
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(parse_hex_color("#ff0080"), Ok([1., 0., 128. / 255.]));
+        assert_eq!(parse_hex_color("00FF00"), Ok([0., 1., 0.]));
+        for bad in ["#fff", "ff00800", "gg0000", "+f0000", "ff00é"] {
+            assert_eq!(parse_hex_color(bad), Err(format!("expected RRGGBB, got `{bad}`")));
+        }
+    }
+
+    #[test]
+    fn numbers_in_range() {
+        assert_eq!(positive_f64("0.5"), Ok(0.5));
+        assert!(positive_f64("0").is_err() && positive_f64("inf").is_err() && positive_f64("NaN").is_err());
+        assert_eq!(non_negative_f64("0"), Ok(0.));
+        assert!(non_negative_f64("-1").is_err() && non_negative_f64("x").is_err());
+        assert_eq!(unit_f32("1"), Ok(1.));
+        assert!(unit_f32("1.01").is_err() && unit_f32("NaN").is_err());
+        assert_eq!(sensitivity_curve(&sensitivity::MAX_CURVE.to_string()), Ok(sensitivity::MAX_CURVE));
+        assert!(sensitivity_curve(&(sensitivity::MAX_CURVE * 2.).to_string()).is_err());
+    }
+
+    #[test]
+    fn bad_values_are_reported_by_option() {
+        let err = Options::try_parse_from(["Mandelplotter1", "render", "--size", "0x10", "--out", "x.png"]).expect_err("a bad size");
+        assert!(err.to_string().contains("width and height must be at least 1"), "{err}");
+        let Some(Command::Render(args)) = Options::try_parse_from(["Mandelplotter1", "render", "--size", "64x48", "--out", "x.png"]).expect("valid").command else {
+            panic!("not the render subcommand");
+        };
+        assert_eq!(args.size, (64, 48));
+    }
+}
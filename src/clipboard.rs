@@ -0,0 +1,60 @@
+// Ctrl+Shift+S: the frame on the system clipboard, to paste straight into a
+// chat or a document. Where the clipboard takes no images (a headless X
+// server, a Wayland compositor without XWayland, ...) the frame is saved as a
+// PNG in the temporary directory instead and its path is copied as text, which
+// most places that take images accept too.
+
+use std::borrow::Cow;
+use std::env;
+
+use arboard::ImageData;
+use image::DynamicImage;
+
+use crate::capture;
+use crate::metadata;
+
+pub struct Clipboard {
+    /// Kept open, as on X11 the copy is only there while it is.
+    system: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    pub fn new() -> Clipboard {
+        Clipboard { system: None }
+    }
+
+    /// Puts `image` on the clipboard, or the path of a PNG of it with `text`
+    /// chunks where images can't go; says which it did.
+    pub fn copy(&mut self, image: &DynamicImage, text: &[(String, String)]) -> Result<String, String> {
+        let why = match self.system() {
+            Ok(system) => match system.set_image(image_data(image)) {
+                Ok(()) => return Ok(format!("copied the {}x{} view to the clipboard", image.width(), image.height())),
+                Err(err) => err.to_string(),
+            },
+            Err(err) => err,
+        };
+        let path = env::temp_dir().join(capture::capture_path("clipboard"));
+        metadata::save_png(&path, image, text)?;
+        let path = path.display().to_string();
+        let Some(system) = self.system.as_mut() else {
+            return Err(format!("no clipboard to copy to ({why}); the view is in {path}"));
+        };
+        match system.set_text(path.clone()) {
+            Ok(()) => Ok(format!("the clipboard takes no images here ({why}), so it has the path of {path}")),
+            Err(err) => Err(format!("could not copy the view ({why}) or its path ({err}); it is in {path}")),
+        }
+    }
+
+    fn system(&mut self) -> Result<&mut arboard::Clipboard, String> {
+        if self.system.is_none() {
+            self.system = Some(arboard::Clipboard::new().map_err(|err| err.to_string())?);
+        }
+        Ok(self.system.as_mut().expect("opened just above"))
+    }
+}
+
+/// `image` as the clipboard takes it, 8-bit RGBA.
+fn image_data(image: &DynamicImage) -> ImageData<'static> {
+    let rgba = image.to_rgba8();
+    ImageData { width: rgba.width() as usize, height: rgba.height() as usize, bytes: Cow::Owned(rgba.into_raw()) }
+}
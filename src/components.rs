@@ -0,0 +1,49 @@
+// The main cardioid and the period-2 bulb outlined over the Mandelbrot set
+// (Shift+N), to show its two largest components: the points whose orbit
+// settles on a fixed point, and on a cycle of two. Both have closed forms,
+// the cardioid c = e^it/2 - e^2it/4 and the circle of radius 1/4 around -1.
+// Not drawn over a Julia set, which has no such components.
+
+use std::f64::consts::TAU;
+
+use imgui::Ui;
+
+use crate::viewport::Viewport;
+
+/// Segments of each outline.
+const SEGMENTS: usize = 720;
+const CARDIOID: [f32; 4] = [0.35, 0.9, 1., 0.9];
+const BULB: [f32; 4] = [1., 0.55, 0.3, 0.9];
+
+/// The main cardioid's boundary at `t` radians.
+fn cardioid(t: f64) -> (f64, f64) {
+    (t.cos() / 2. - (2. * t).cos() / 4., t.sin() / 2. - (2. * t).sin() / 4.)
+}
+
+/// The period-2 bulb's boundary at `t` radians.
+fn bulb(t: f64) -> (f64, f64) {
+    (-1. + t.cos() / 4., t.sin() / 4.)
+}
+
+/// Both outlines over the fractal, labeled where they are in view.
+pub fn draw(ui: &Ui, view: &Viewport) {
+    // framebuffer pixels to the UI's screen coordinates
+    let [scale_x, scale_y] = ui.io().display_framebuffer_scale;
+    let (width, height) = (view.width as f64, view.height as f64);
+    let draw = ui.get_background_draw_list();
+    for (curve, color, label) in [(cardioid as fn(f64) -> (f64, f64), CARDIOID, "main cardioid"), (bulb, BULB, "period-2 bulb")] {
+        let points: Vec<(f64, f64)> = (0..=SEGMENTS).map(|i| view.complex_to_screen(curve(TAU * i as f64 / SEGMENTS as f64))).collect();
+        let on_screen = |&(x, y): &(f64, f64)| (0. ..width).contains(&x) && (0. ..height).contains(&y);
+        for pair in points.windows(2) {
+            // zoomed in, most of it is far off screen, past what f32 holds
+            if !pair.iter().any(on_screen) {
+                continue;
+            }
+            let [a, b] = [pair[0], pair[1]].map(|(x, y)| [x as f32 / scale_x, y as f32 / scale_y]);
+            draw.add_line(a, b, color).thickness(2.).build();
+        }
+        if let Some(&(x, y)) = points.iter().filter(|point| on_screen(point)).min_by(|a, b| a.1.total_cmp(&b.1)) {
+            draw.add_text([x as f32 / scale_x + 4., y as f32 / scale_y - 18.], color, label);
+        }
+    }
+}
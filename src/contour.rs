@@ -0,0 +1,290 @@
+// Iso-iteration contours as SVG, for plotters and laser cutters
+// (`render --export-contours`). The smooth escape iteration is sampled on a
+// grid over the view, marching squares traces where it crosses each level,
+// the segments are joined into polylines and simplified with Ramer-Douglas-
+// Peucker. Interior points count as above every level, so the level at
+// `--iter` is the outline of the set. A contour that runs into the edge of
+// the view stays open there; only the ones that come back to where they
+// started are closed paths.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// One line: in grid coordinates (sample `(x, y)` at `(x, y)`) as traced, in
+/// image pixels once in a `Layer`.
+pub struct Contour {
+    pub points: Vec<(f64, f64)>,
+    pub closed: bool,
+}
+
+/// A cell edge between two neighbouring samples: horizontal ones from `(x,
+/// y)` to `(x + 1, y)`, vertical ones from `(x, y)` to `(x, y + 1)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Edge {
+    H(usize, usize),
+    V(usize, usize),
+}
+
+/// Samples of a `width x height` grid, rows from the top.
+pub struct Field<'a> {
+    pub values: &'a [f64],
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Field<'_> {
+    fn at(&self, x: usize, y: usize) -> f64 {
+        self.values[y * self.width + x]
+    }
+
+    /// Where along `edge` the field crosses `level`.
+    fn crossing(&self, edge: Edge, level: f64) -> (f64, f64) {
+        let ((x0, y0), (x1, y1)) = match edge {
+            Edge::H(x, y) => ((x, y), (x + 1, y)),
+            Edge::V(x, y) => ((x, y), (x, y + 1)),
+        };
+        let (a, b) = (self.at(x0, y0), self.at(x1, y1));
+        let t = if a == b { 0.5 } else { ((level - a) / (b - a)).clamp(0., 1.) };
+        (x0 as f64 + t * (x1 as f64 - x0 as f64), y0 as f64 + t * (y1 as f64 - y0 as f64))
+    }
+
+    /// The segments marching squares puts in every cell, as the pairs of
+    /// edges they join.
+    fn segments(&self, level: f64) -> Vec<[Edge; 2]> {
+        let mut segments = Vec::new();
+        for y in 0..self.height.saturating_sub(1) {
+            for x in 0..self.width.saturating_sub(1) {
+                let corners = [self.at(x, y), self.at(x + 1, y), self.at(x + 1, y + 1), self.at(x, y + 1)];
+                let case = corners.iter().fold(0, |case, &value| case << 1 | (value >= level) as usize);
+                let (top, right, bottom, left) = (Edge::H(x, y), Edge::V(x + 1, y), Edge::H(x, y + 1), Edge::V(x, y));
+                // corners in the order top left, top right, bottom right, bottom left
+                let center_inside = || corners.iter().sum::<f64>() / 4. >= level;
+                match case {
+                    0 | 15 => {}
+                    1 | 14 => segments.push([left, bottom]),
+                    2 | 13 => segments.push([bottom, right]),
+                    3 | 12 => segments.push([left, right]),
+                    4 | 11 => segments.push([top, right]),
+                    6 | 9 => segments.push([top, bottom]),
+                    7 | 8 => segments.push([top, left]),
+                    // saddles: the center decides which corners are connected
+                    5 if center_inside() => segments.extend([[top, left], [right, bottom]]),
+                    5 => segments.extend([[top, right], [left, bottom]]),
+                    10 if center_inside() => segments.extend([[top, right], [left, bottom]]),
+                    10 => segments.extend([[top, left], [right, bottom]]),
+                    _ => unreachable!("four corners"),
+                }
+            }
+        }
+        segments
+    }
+
+    /// The contours of `level`: open ones, which end on the edge of the
+    /// grid, then closed ones.
+    pub fn trace(&self, level: f64) -> Vec<Contour> {
+        let segments = self.segments(level);
+        // every edge is shared by at most two cells, each with at most one
+        // segment through it
+        let mut at_edge: HashMap<Edge, Vec<usize>> = HashMap::new();
+        for (index, ends) in segments.iter().enumerate() {
+            for edge in ends {
+                at_edge.entry(*edge).or_default().push(index);
+            }
+        }
+        let mut used = vec![false; segments.len()];
+        let mut contours = Vec::new();
+        let open_starts = segments
+            .iter()
+            .enumerate()
+            .flat_map(|(index, ends)| ends.iter().map(move |edge| (index, *edge)))
+            .filter(|(_, edge)| at_edge[edge].len() == 1);
+        let starts: Vec<(usize, Edge)> = open_starts.chain(segments.iter().enumerate().map(|(index, ends)| (index, ends[0]))).collect();
+        for (first, start) in starts {
+            if used[first] {
+                continue;
+            }
+            let mut points = vec![self.crossing(start, level)];
+            let (mut segment, mut edge) = (first, start);
+            loop {
+                used[segment] = true;
+                let ends = segments[segment];
+                edge = if ends[0] == edge { ends[1] } else { ends[0] };
+                if edge == start {
+                    break;
+                }
+                points.push(self.crossing(edge, level));
+                match at_edge[&edge].iter().find(|&&next| !used[next]) {
+                    Some(&next) => segment = next,
+                    None => break,
+                }
+            }
+            contours.push(Contour { closed: edge == start, points });
+        }
+        contours
+    }
+}
+
+/// Ramer-Douglas-Peucker: drops the points closer than `tolerance` to the
+/// line the simplified path takes instead. The ends are kept, so a closed
+/// path stays closed.
+pub fn simplify(points: &[(f64, f64)], tolerance: f64) -> Vec<(f64, f64)> {
+    if points.len() < 3 || tolerance <= 0. {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut spans = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let (farthest, distance) = (first + 1..last)
+            .map(|index| (index, distance_to_segment(points[index], points[first], points[last])))
+            .fold((first, 0.), |best, candidate| if candidate.1 > best.1 { candidate } else { best });
+        if distance > tolerance {
+            keep[farthest] = true;
+            spans.extend([(first, farthest), (farthest, last)]);
+        }
+    }
+    points.iter().zip(keep).filter(|(_, keep)| *keep).map(|(point, _)| *point).collect()
+}
+
+fn distance_to_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 > 0. { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0., 1.) } else { 0. };
+    (p.0 - a.0 - t * dx).hypot(p.1 - a.1 - t * dy)
+}
+
+/// The contours of one level, in image pixels, drawn with `stroke` wide lines.
+pub struct Layer {
+    pub level: f64,
+    pub stroke: f64,
+    pub contours: Vec<Contour>,
+}
+
+/// A `width x height` SVG with a group of paths per layer; `description`
+/// goes into its `<desc>`.
+pub fn svg(width: u32, height: u32, description: &str, layers: &[Layer]) -> String {
+    let mut svg = String::new();
+    // writing to a String can't fail
+    let _ = writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#);
+    let _ = writeln!(svg, "  <desc>{}</desc>", escape(description));
+    for layer in layers {
+        let _ = writeln!(
+            svg,
+            r#"  <g id="level-{}" fill="none" stroke="black" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round">"#,
+            layer.level, layer.stroke
+        );
+        for contour in &layer.contours {
+            let mut d = String::new();
+            for (index, (x, y)) in contour.points.iter().enumerate() {
+                let _ = write!(d, "{}{x:.2} {y:.2}", if index == 0 { "M" } else { " L" });
+            }
+            if contour.closed {
+                d.push_str(" Z");
+            }
+            let _ = writeln!(svg, r#"    <path d="{d}"/>"#);
+        }
+        let _ = writeln!(svg, "  </g>");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `size x size` grid of each sample's distance from the middle one.
+    fn cone(size: usize) -> Vec<f64> {
+        let middle = (size / 2) as f64;
+        (0..size * size).map(|i| ((i % size) as f64 - middle).hypot((i / size) as f64 - middle)).collect()
+    }
+
+    /// Whether every tag of `xml` is closed, in order.
+    fn well_formed(xml: &str) -> bool {
+        let mut open = Vec::new();
+        for tag in xml.split('<').skip(1) {
+            let Some((tag, _)) = tag.split_once('>') else {
+                return false;
+            };
+            let name = tag.trim_start_matches('/').split_whitespace().next().unwrap_or("");
+            if tag.starts_with('?') || tag.ends_with('/') {
+                continue;
+            } else if tag.starts_with('/') {
+                if open.pop() != Some(name) {
+                    return false;
+                }
+            } else {
+                open.push(name);
+            }
+        }
+        open.is_empty()
+    }
+
+    #[test]
+    fn a_level_around_a_peak_is_one_closed_ring() {
+        let values = cone(21);
+        let field = Field { values: &values, width: 21, height: 21 };
+        let contours = field.trace(5.5);
+        assert_eq!(contours.len(), 1);
+        assert!(contours[0].closed);
+        // interpolated along the cell edges, which cut the corners of the circle a little
+        assert!(contours[0].points.iter().all(|&(x, y)| ((x - 10.).hypot(y - 10.) - 5.5).abs() < 0.3));
+        // each edge crossing once
+        assert_eq!(contours[0].points.len(), field.segments(5.5).len());
+    }
+
+    #[test]
+    fn a_level_through_the_edge_stays_open() {
+        // rising to the right, so the level is a vertical line from the top to the bottom
+        let values: Vec<f64> = (0..8 * 6).map(|i| (i % 8) as f64).collect();
+        let contours = Field { values: &values, width: 8, height: 6 }.trace(3.5);
+        assert_eq!(contours.len(), 1);
+        assert!(!contours[0].closed);
+        assert!(contours[0].points.iter().all(|&(x, _)| x == 3.5));
+        let ends = [contours[0].points[0].1, contours[0].points.last().expect("points").1];
+        assert!(ends == [0., 5.] || ends == [5., 0.], "{ends:?}");
+    }
+
+    #[test]
+    fn a_saddle_gives_two_lines() {
+        // high top left and bottom right, low in the other corners
+        let contours = Field { values: &[1., 0., 0., 1.], width: 2, height: 2 }.trace(0.6);
+        assert_eq!(contours.len(), 2);
+        assert!(contours.iter().all(|contour| !contour.closed && contour.points.len() == 2));
+    }
+
+    #[test]
+    fn simplify_keeps_the_ends_and_the_corners() {
+        let line: Vec<(f64, f64)> = (0..=10).map(|x| (x as f64, 0.)).collect();
+        assert_eq!(simplify(&line, 0.1), [(0., 0.), (10., 0.)]);
+        let mut bent = line.clone();
+        bent[5].1 = 2.;
+        assert_eq!(simplify(&bent, 0.1), [(0., 0.), (4., 0.), (5., 2.), (6., 0.), (10., 0.)]);
+        assert_eq!(simplify(&bent, 0.), bent);
+    }
+
+    #[test]
+    fn svg_is_well_formed_and_closes_rings() {
+        let values = cone(21);
+        let field = Field { values: &values, width: 21, height: 21 };
+        let ramp: Vec<f64> = (0..8 * 6).map(|i| (i % 8) as f64).collect();
+        let layers = [
+            Layer { level: 5.5, stroke: 1., contours: field.trace(5.5) },
+            Layer { level: 3.5, stroke: 0.5, contours: Field { values: &ramp, width: 8, height: 6 }.trace(3.5) },
+        ];
+        let svg = svg(21, 21, "levels <5.5 & 3.5>", &layers);
+        assert!(well_formed(&svg), "{svg}");
+        assert!(svg.contains("<desc>levels &lt;5.5 &amp; 3.5&gt;</desc>"));
+        let paths: Vec<&str> = svg.lines().filter(|line| line.trim_start().starts_with("<path")).collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].starts_with(r#"    <path d="M"#) && paths[0].ends_with(r#" Z"/>"#));
+        assert!(!paths[1].contains('Z'));
+        assert!(svg.contains(r#"<g id="level-3.5" fill="none" stroke="black" stroke-width="0.5""#));
+    }
+}
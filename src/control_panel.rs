@@ -0,0 +1,158 @@
+// Everything the keys tune, in one window (Tab): the view, the iterations,
+// the set, the coloring and the supersampling, and buttons for what is a
+// single key press otherwise. The panel keeps nothing of its own but what is
+// being typed: it shows the explorer's state as it is each frame and hands
+// back what was changed, which goes through the same paths as the keys, so
+// the two never disagree. The center and the scale are typed out like the
+// F4 commands, at the full precision of the view, and set on Enter.
+
+use imgui::{Condition, Ui};
+
+use crate::cli::Quality;
+use crate::navigate;
+use crate::palette::{ColorMode, Tone};
+use crate::viewport::Viewport;
+
+const QUALITIES: [Quality; 4] = [Quality::Low, Quality::Medium, Quality::High, Quality::Ultra];
+const COLOR_MODES: [ColorMode; 3] = [ColorMode::EscapeTime, ColorMode::Angle, ColorMode::Blend];
+
+/// What the panel shows besides the view.
+pub struct Controls {
+    pub iterations: i32,
+    pub julia: bool,
+    pub color_mode: ColorMode,
+    pub quality: Quality,
+    pub ssaa: i32,
+    pub tone: Tone,
+}
+
+/// A change made in the panel.
+pub enum Change {
+    /// A line of F4 commands.
+    Navigate(String),
+    Iterations(i32),
+    /// The Julia set of the center, or back to the Mandelbrot set.
+    Julia(bool),
+    ColorMode(ColorMode),
+    Quality(Quality),
+    Ssaa(i32),
+    Tone(Tone),
+    Screenshot,
+    Reset,
+    Bookmarks,
+}
+
+pub struct ControlPanel {
+    pub open: bool,
+    re: String,
+    im: String,
+    scale: String,
+    /// The center and the scale the fields were filled from.
+    filled: Option<((f64, f64), f64)>,
+    /// Whether one of them is being typed into, and keeps what is typed.
+    editing: bool,
+    error: Option<String>,
+}
+
+impl ControlPanel {
+    pub fn new() -> ControlPanel {
+        ControlPanel { open: false, re: String::new(), im: String::new(), scale: String::new(), filled: None, editing: false, error: None }
+    }
+
+    /// The window; returns what was changed in it.
+    pub fn draw(&mut self, ui: &Ui, view: &Viewport, controls: &Controls) -> Vec<Change> {
+        let mut changes = Vec::new();
+        if !self.open {
+            return changes;
+        }
+        if !self.editing && self.filled != Some((view.center, view.scale)) {
+            // `{}` prints the shortest text that parses back to the same f64
+            self.re = view.center.0.to_string();
+            self.im = view.center.1.to_string();
+            self.scale = format!("{:e}", view.scale);
+            self.filled = Some((view.center, view.scale));
+        }
+        let mut open = true;
+        ui.window("Controls")
+            .opened(&mut open)
+            .size([380., 330.], Condition::FirstUseEver)
+            .build(|| {
+                let mut line = None;
+                let mut editing = false;
+                if ui.input_text("re", &mut self.re).enter_returns_true(true).build() {
+                    line = Some(format!("center {} {}", self.re, self.im));
+                }
+                editing |= ui.is_item_active();
+                if ui.input_text("im", &mut self.im).enter_returns_true(true).build() {
+                    line = Some(format!("center {} {}", self.re, self.im));
+                }
+                editing |= ui.is_item_active();
+                if ui.input_text("scale", &mut self.scale).enter_returns_true(true).build() {
+                    line = Some(format!("scale {}", self.scale));
+                }
+                editing |= ui.is_item_active();
+                self.editing = editing;
+                if let Some(line) = line {
+                    match navigate::parse(&line) {
+                        Ok(_) => {
+                            self.error = None;
+                            // filled again from wherever that goes
+                            self.filled = None;
+                            changes.push(Change::Navigate(line));
+                        }
+                        Err(err) => self.error = Some(err),
+                    }
+                }
+                if let Some(err) = &self.error {
+                    ui.text_colored([1., 0.3, 0.3, 1.], err);
+                }
+                ui.text_disabled(format!("{:.3e}x, Enter sets the center or the scale", view.magnification()));
+
+                let mut iterations = controls.iterations;
+                if ui.input_int("iterations", &mut iterations).build() {
+                    changes.push(Change::Iterations(iterations.max(0)));
+                }
+                let mut julia = controls.julia;
+                let mandelbrot = ui.radio_button("Mandelbrot set", &mut julia, false);
+                ui.same_line();
+                if ui.radio_button("Julia set of the center", &mut julia, true) || mandelbrot {
+                    changes.push(Change::Julia(julia));
+                }
+
+                let mut mode = COLOR_MODES.iter().position(|&mode| mode == controls.color_mode).unwrap_or(0);
+                let names = COLOR_MODES.map(|mode| format!("{mode:?}"));
+                if ui.combo_simple_string("color mode", &mut mode, &names) {
+                    changes.push(Change::ColorMode(COLOR_MODES[mode]));
+                }
+                let mut tone = controls.tone;
+                let toned = ui.slider("brightness", -1., 1., &mut tone.brightness) | ui.slider("contrast", 0., 4., &mut tone.contrast);
+                if toned {
+                    changes.push(Change::Tone(tone));
+                }
+
+                let mut quality = QUALITIES.iter().position(|&quality| quality == controls.quality).unwrap_or(0);
+                let names = QUALITIES.map(|quality| format!("{quality:?}"));
+                if ui.combo_simple_string("quality", &mut quality, &names) {
+                    changes.push(Change::Quality(QUALITIES[quality]));
+                }
+                let mut ssaa = controls.ssaa;
+                if ui.slider("supersampling", 1, 4, &mut ssaa) {
+                    changes.push(Change::Ssaa(ssaa));
+                }
+
+                if ui.button("screenshot") {
+                    changes.push(Change::Screenshot);
+                }
+                ui.same_line();
+                if ui.button("reset") {
+                    changes.push(Change::Reset);
+                }
+                ui.same_line();
+                if ui.button("bookmarks") {
+                    changes.push(Change::Bookmarks);
+                }
+            });
+        self.open = open;
+        changes
+    }
+}
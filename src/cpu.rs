@@ -0,0 +1,344 @@
+// CPU implementation of the fractal kernel, kept in step with the fragment shader.
+
+use image::{ImageBuffer, Rgba, RgbaImage};
+use rayon::prelude::*;
+
+use crate::background::{self, Background};
+use crate::palette::{ColorMode, Lighting, PaletteTable, SlowTint, Tone};
+use crate::rng;
+use crate::viewport::{PixelRect, Viewport};
+
+/// Iterates `z = z^2 + c` and returns the iteration at which `|z|` exceeded the
+/// bailout, or `None` if the point stayed bounded for `substeps` iterations.
+pub fn escape_time(c: (f64, f64), substeps: i32) -> Option<i32> {
+    escape(c, substeps).map(|(i, _)| i)
+}
+
+/// Like `escape_time`, along with `z` at the iteration it escaped.
+pub fn escape(c: (f64, f64), substeps: i32) -> Option<(i32, (f64, f64))> {
+    escape_from((0., 0.), c, substeps)
+}
+
+/// Iterates `z = z^2 + c` from `z`: from 0 for the Mandelbrot set, from the
+/// point itself for a Julia set of `c`.
+pub fn escape_from(z: (f64, f64), c: (f64, f64), substeps: i32) -> Option<(i32, (f64, f64))> {
+    match iterate(z, c, substeps) {
+        (Some(i), z) => Some((i, z)),
+        (None, _) => None,
+    }
+}
+
+/// The escape iteration (if any) and where `z` ended up: at the escape, or
+/// after the last iteration for points that stayed bounded.
+pub fn iterate(z: (f64, f64), c: (f64, f64), substeps: i32) -> (Option<i32>, (f64, f64)) {
+    let (mut x, mut y) = z;
+    for i in 0..=substeps {
+        let xt = x * x - y * y + c.0;
+        y = 2. * x * y + c.1;
+        x = xt;
+        if x * x + y * y > 16. {
+            return (Some(i), (x, y));
+        }
+    }
+    (None, (x, y))
+}
+
+/// Past this the derivative is scaled back down; only its direction is used.
+const DERIVATIVE_RESCALE: f64 = 1e10;
+
+/// `iterate` that also follows the derivative of `z`, for relief shading:
+/// with respect to `c` for the Mandelbrot set, to the starting point for
+/// Julia sets (`julia`). It is rescaled as it grows, so it stays finite but
+/// only its direction means anything. Same as `escape` in the shader.
+pub fn iterate_derivative(z: (f64, f64), c: (f64, f64), substeps: i32, julia: bool) -> (Option<i32>, (f64, f64), (f64, f64)) {
+    let (mut x, mut y) = z;
+    let mut dz = if julia { (1., 0.) } else { (0., 0.) };
+    // d(z^2 + c)/dc adds 1, shrunk along with dz
+    let mut dc = if julia { 0. } else { 1. };
+    for i in 0..=substeps {
+        dz = (2. * (x * dz.0 - y * dz.1) + dc, 2. * (x * dz.1 + y * dz.0));
+        if dz.0.hypot(dz.1) > DERIVATIVE_RESCALE {
+            dz = (dz.0 / DERIVATIVE_RESCALE, dz.1 / DERIVATIVE_RESCALE);
+            dc /= DERIVATIVE_RESCALE;
+        }
+        let xt = x * x - y * y + c.0;
+        y = 2. * x * y + c.1;
+        x = xt;
+        if x * x + y * y > 16. {
+            return (Some(i), (x, y), dz);
+        }
+    }
+    (None, (x, y), dz)
+}
+
+/// Every `z` of the orbit `iterate` follows, starting with `z` itself and
+/// ending with the first one past the bailout if it escapes.
+pub fn orbit(z: (f64, f64), c: (f64, f64), substeps: i32) -> Vec<(f64, f64)> {
+    let (mut x, mut y) = z;
+    let mut orbit = vec![z];
+    for _ in 0..=substeps {
+        let xt = x * x - y * y + c.0;
+        y = 2. * x * y + c.1;
+        x = xt;
+        orbit.push((x, y));
+        if x * x + y * y > 16. {
+            break;
+        }
+    }
+    orbit
+}
+
+/// The escape iteration made continuous (renormalized iteration count): where
+/// between `i` and `i + 1` the orbit crossed the bailout radius of 4, judged
+/// from how far past it `z` landed.
+pub fn smooth_iteration(i: i32, z: (f64, f64)) -> f64 {
+    i as f64 + 1. - (z.0.hypot(z.1).ln() / 4f64.ln()).log2()
+}
+
+/// Pre-palette values of one pixel center, in the order of `data::DataChannel`:
+/// escape iteration and smooth iteration (both -1 inside the set), final `|z|`
+/// and 1 inside the set, 0 outside. The shader's raw output matches this.
+pub fn raw_sample(c: (f64, f64), substeps: i32) -> [f32; 4] {
+    raw_sample_from((0., 0.), c, substeps)
+}
+
+/// `raw_sample` of the orbit of `z` under `c`, for Julia sets.
+pub fn raw_sample_from(z: (f64, f64), c: (f64, f64), substeps: i32) -> [f32; 4] {
+    match iterate(z, c, substeps) {
+        (Some(i), z) => [i as f32, smooth_iteration(i, z) as f32, z.0.hypot(z.1) as f32, 0.],
+        (None, z) => [-1., -1., z.0.hypot(z.1) as f32, 1.],
+    }
+}
+
+/// `raw_sample` of every framebuffer pixel, rows from the top.
+pub fn render_raw(viewport: &Viewport, substeps: i32) -> Vec<[f32; 4]> {
+    render_raw_view(viewport, None, false, substeps)
+}
+
+/// `render_raw` of the Julia set of `julia` if there is one, in the
+/// logarithmic view with `log_polar`, as the shader's raw output has them.
+pub fn render_raw_view(viewport: &Viewport, julia: Option<(f64, f64)>, log_polar: bool, substeps: i32) -> Vec<[f32; 4]> {
+    let width = viewport.width as usize;
+    let mut samples = vec![[0f32; 4]; width * viewport.height as usize];
+    samples.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        for (x, out) in row.iter_mut().enumerate() {
+            let mut point = viewport.screen_to_complex((x as f64 + 0.5, y as f64 + 0.5));
+            if log_polar {
+                point = viewport.log_polar(point);
+            }
+            *out = match julia {
+                Some(c) => raw_sample_from(point, c, substeps),
+                None => raw_sample(point, substeps),
+            };
+        }
+    });
+    samples
+}
+
+/// RGBA with 16 bits per channel.
+pub type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+/// How samples become colors. The explorer starts with the grayscale
+/// palette, `ColorMode::EscapeTime`, an opaque white interior, morph 1 and
+/// everything else off or neutral.
+#[derive(Clone, Copy)]
+pub struct Shading<'a> {
+    /// `Palette::build` of the palette; escaped points sample it from 0 to 1.
+    pub palette: &'a PaletteTable,
+    pub mode: ColorMode,
+    /// Points inside the set come out transparent instead of white.
+    pub transparent_interior: bool,
+    /// Composited under every pixel, after supersampling.
+    pub background: Option<&'a Background>,
+    /// How much of the Julia set shows when one is drawn, as
+    /// `DrawParams::morph`: below 1 the Mandelbrot set is cross-faded in.
+    /// 0 to 1.
+    pub morph: f32,
+    /// Colors the interior points that ended past its threshold instead.
+    pub slow_tint: Option<SlowTint>,
+    pub lighting: Option<Lighting>,
+    /// As `DrawParams::color_span`: iterations the palette spreads over in
+    /// `ColorMode::EscapeTime` and `Blend`, at least 1; `None` for the
+    /// iteration count.
+    pub color_span: Option<i32>,
+    /// As `DrawParams::log_polar`.
+    pub log_polar: bool,
+    /// As `DrawParams::tone`, applied after the background; `Tone::default`
+    /// leaves colors as they are.
+    pub tone: Tone,
+}
+
+/// Grey level of a pixel, matching the shader: escaped points get `i / substeps`,
+/// interior points are white.
+pub fn shade(escape: Option<i32>, substeps: i32) -> f32 {
+    match escape {
+        Some(i) => i as f32 / substeps.max(1) as f32,
+        None => 1.,
+    }
+}
+
+/// Escape time of every framebuffer pixel, rows from the top.
+pub fn render_escapes(viewport: &Viewport, substeps: i32) -> Vec<Option<i32>> {
+    let width = viewport.width as usize;
+    let mut escapes = vec![None; width * viewport.height as usize];
+    escapes.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        for (x, out) in row.iter_mut().enumerate() {
+            // sample the pixel center like the rasterizer does
+            let c = viewport.screen_to_complex((x as f64 + 0.5, y as f64 + 0.5));
+            *out = escape_time(c, substeps);
+        }
+    });
+    escapes
+}
+
+/// Renders the whole viewport, one grey level per framebuffer pixel, rows from the top.
+pub fn render(viewport: &Viewport, substeps: i32) -> Vec<f32> {
+    render_escapes(viewport, substeps)
+        .into_iter()
+        .map(|escape| shade(escape, substeps))
+        .collect()
+}
+
+/// Color of one sample from `iterate`, like the shader: escaped points look
+/// up the palette where the color mode puts them, interior points are white
+/// (and transparent if asked to), or the slow tint if they ended far out.
+pub fn color((escape, z): (Option<i32>, (f64, f64)), substeps: i32, shading: &Shading) -> [f32; 4] {
+    match escape {
+        Some(i) => {
+            let [r, g, b] = shading.palette.sample(shading.mode.position(i, z, shading.color_span.unwrap_or(substeps)));
+            [r, g, b, 1.]
+        }
+        None => match shading.slow_tint {
+            Some(tint) if z.0.hypot(z.1) > tint.threshold as f64 => [tint.color[0], tint.color[1], tint.color[2], 1.],
+            _ if shading.transparent_interior => [1., 1., 1., 0.],
+            _ => [1.; 4],
+        },
+    }
+}
+
+/// `color` of the orbit of `z` under `c`, lit when `shading` has lighting:
+/// `julia` says which of the two the derivative is taken along, `rotation`
+/// is the view's.
+fn sample_color(z: (f64, f64), c: (f64, f64), julia: bool, substeps: i32, shading: &Shading, rotation: f64) -> [f32; 4] {
+    let Some(lighting) = shading.lighting else {
+        return color(iterate(z, c, substeps), substeps, shading);
+    };
+    let (escape, z, dz) = iterate_derivative(z, c, substeps, julia);
+    let mut rgba = color((escape, z), substeps, shading);
+    if escape.is_some() {
+        let shade = lighting.shade(z, dz, rotation);
+        for channel in &mut rgba[..3] {
+            *channel *= shade;
+        }
+    }
+    rgba
+}
+
+/// Renders the viewport as an image, like the shader would, averaging
+/// `ssaa * ssaa` samples per pixel, each jittered inside its cell of a regular
+/// grid as picked by `seed`. With `julia` set, the view shows the Julia set
+/// of that constant instead of the Mandelbrot set.
+pub fn render_image(
+    viewport: &Viewport,
+    substeps: i32,
+    ssaa: i32,
+    seed: u32,
+    julia: Option<(f64, f64)>,
+    shading: &Shading,
+) -> RgbaImage {
+    let whole = PixelRect { x: 0, y: 0, width: viewport.width, height: viewport.height };
+    render_tile(viewport, whole, substeps, ssaa, seed, julia, shading)
+}
+
+/// Renders just `rect` of the viewport, exactly as those pixels come out of
+/// `render_image`.
+pub fn render_tile(
+    viewport: &Viewport,
+    rect: PixelRect,
+    substeps: i32,
+    ssaa: i32,
+    seed: u32,
+    julia: Option<(f64, f64)>,
+    shading: &Shading,
+) -> RgbaImage {
+    let colors = shade_tile(viewport, rect, substeps, ssaa, seed, julia, shading);
+    let pixels = colors.iter().flatten().map(|v| (v * 255.).round() as u8).collect();
+    RgbaImage::from_raw(rect.width, rect.height, pixels).expect("one pixel per sample")
+}
+
+/// `render_tile` with 16 bits per channel, for exports that get graded
+/// afterwards without banding.
+pub fn render_tile_16(
+    viewport: &Viewport,
+    rect: PixelRect,
+    substeps: i32,
+    ssaa: i32,
+    seed: u32,
+    julia: Option<(f64, f64)>,
+    shading: &Shading,
+) -> Rgba16Image {
+    let colors = shade_tile(viewport, rect, substeps, ssaa, seed, julia, shading);
+    let pixels = colors.iter().flatten().map(|v| (v * 65535.).round() as u16).collect();
+    Rgba16Image::from_raw(rect.width, rect.height, pixels).expect("one pixel per sample")
+}
+
+/// The averaged color of every pixel of `rect`, rows from the top, kept in
+/// floats until the caller picks a bit depth.
+fn shade_tile(
+    viewport: &Viewport,
+    rect: PixelRect,
+    substeps: i32,
+    ssaa: i32,
+    seed: u32,
+    julia: Option<(f64, f64)>,
+    shading: &Shading,
+) -> Vec<[f32; 4]> {
+    let width = rect.width as usize;
+    let n = ssaa.max(1);
+    let mut pixels = vec![[0f32; 4]; width * rect.height as usize];
+    pixels.par_chunks_mut(width).enumerate().for_each(|(row_index, row)| {
+        let y = rect.y as usize + row_index;
+        for (column, out) in row.iter_mut().enumerate() {
+            let x = rect.x as usize + column;
+            let mut sum = [0f32; 4];
+            for sx in 0..n {
+                for sy in 0..n {
+                    let cell = if n > 1 {
+                        rng::jitter(x as u32, y as u32, (sx * n + sy) as u32, seed)
+                    } else {
+                        (0.5, 0.5)
+                    };
+                    let sub = ((sx as f64 + cell.0) / n as f64, (sy as f64 + cell.1) / n as f64);
+                    let mut point = viewport.screen_to_complex((x as f64 + sub.0, y as f64 + sub.1));
+                    if shading.log_polar {
+                        point = viewport.log_polar(point);
+                    }
+                    // same as `fractal` in the shader
+                    let rotation = viewport.rotation;
+                    let sample = match julia {
+                        Some(c) if shading.morph >= 1. => sample_color(point, c, true, substeps, shading, rotation),
+                        Some(c) if shading.morph > 0. => {
+                            let from = sample_color((0., 0.), point, false, substeps, shading, rotation);
+                            let to = sample_color(point, c, true, substeps, shading, rotation);
+                            [0, 1, 2, 3].map(|k| from[k] + (to[k] - from[k]) * shading.morph)
+                        }
+                        _ => sample_color((0., 0.), point, false, substeps, shading, rotation),
+                    };
+                    for k in 0..4 {
+                        sum[k] += sample[k];
+                    }
+                }
+            }
+            let samples = (n * n) as f32;
+            *out = sum.map(|v| v / samples);
+            if let Some(background) = shading.background {
+                let uv = ((x as f64 + 0.5) / viewport.width as f64, (y as f64 + 0.5) / viewport.height as f64);
+                let under = background.sample(uv, (viewport.width, viewport.height));
+                *out = background::composite(background.style.blend, *out, under);
+            }
+            let [r, g, b] = shading.tone.apply([out[0], out[1], out[2]]);
+            *out = [r, g, b, out[3]];
+        }
+    });
+    pixels
+}
@@ -0,0 +1,283 @@
+// Numeric export of a render: pre-palette values of every pixel center, for
+// people who want the data rather than a picture, to color or analyze
+// offline. The channels are picked from `DataChannel`; the escape iteration
+// alone by default. Interior points are -1 in the iteration channels.
+//
+// `.npy` files hold a float32 array of shape (height, width), or (height,
+// width, channels) with several channels, rows from the top, loadable with
+// `numpy.load`. `.f32` files are the same floats with no header at all,
+// little-endian and channels interleaved. `.exr` files are 32-bit float
+// OpenEXR images with the channels in R, G, B and A (unused ones are 0).
+// `.csv` files hold one channel, one line per row after `#` comment lines
+// describing the view (skipped by `numpy.loadtxt`). All of them get a
+// `<file>.json` sidecar with the view parameters and the channel order.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, Rgb32FImage, Rgba32FImage};
+use serde::Serialize;
+
+use crate::viewport::Viewport;
+
+/// Value stored for points that never escaped.
+pub const INTERIOR: f32 = -1.;
+
+/// A per-pixel value; the order is that of `cpu::raw_sample`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum DataChannel {
+    /// The iteration at which the point escaped.
+    Escape,
+    /// The escape iteration made continuous, without the bands.
+    Smooth,
+    /// `|z|` at the escape, or after the last iteration inside the set.
+    Modulus,
+    /// 1 inside the set, 0 outside.
+    Interior,
+}
+
+impl DataChannel {
+    /// This channel of a `cpu::raw_sample`.
+    pub fn of(self, sample: [f32; 4]) -> f32 {
+        sample[self as usize]
+    }
+}
+
+#[derive(Serialize)]
+pub struct DataHeader {
+    pub center: (f64, f64),
+    /// Half extents of the view along the image axes, in complex units.
+    pub half_extents: (f64, f64),
+    pub rotation: f64,
+    pub width: u32,
+    pub height: u32,
+    pub iterations: i32,
+    pub interior: f32,
+    pub renderer: &'static str,
+    /// What each value is, interleaved per pixel in this order.
+    pub channels: Vec<DataChannel>,
+}
+
+impl DataHeader {
+    pub fn new(viewport: &Viewport, iterations: i32, renderer: &'static str, channels: &[DataChannel]) -> DataHeader {
+        DataHeader {
+            center: viewport.center,
+            half_extents: viewport.half_extents(),
+            rotation: viewport.rotation,
+            width: viewport.width,
+            height: viewport.height,
+            iterations,
+            interior: INTERIOR,
+            renderer,
+            channels: channels.to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataFormat {
+    Npy,
+    Csv,
+    F32,
+    Exr,
+}
+
+impl DataFormat {
+    pub fn from_path(path: &Path) -> Option<DataFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "npy" => Some(DataFormat::Npy),
+            "csv" => Some(DataFormat::Csv),
+            "f32" => Some(DataFormat::F32),
+            "exr" => Some(DataFormat::Exr),
+            _ => None,
+        }
+    }
+}
+
+impl DataFormat {
+    /// Most channels a file of this format holds.
+    pub fn max_channels(self) -> usize {
+        match self {
+            DataFormat::Csv => 1,
+            DataFormat::Exr => 4,
+            DataFormat::Npy | DataFormat::F32 => usize::MAX,
+        }
+    }
+}
+
+/// Writes `values` (row-major, rows from the top, the header's channels
+/// interleaved) and the JSON sidecar.
+pub fn write(path: &Path, format: DataFormat, header: &DataHeader, values: &[f32]) -> io::Result<()> {
+    if format == DataFormat::Exr {
+        write_exr(path, header, values)?;
+    } else {
+        let mut out = BufWriter::new(File::create(path)?);
+        match format {
+            DataFormat::Npy => write_npy(&mut out, header, values)?,
+            DataFormat::Csv => write_csv(&mut out, header, values)?,
+            DataFormat::F32 => write_floats(&mut out, values)?,
+            DataFormat::Exr => unreachable!("written above"),
+        }
+        out.flush()?;
+    }
+
+    let sidecar = File::create(sidecar_path(path))?;
+    serde_json::to_writer_pretty(sidecar, header)?;
+    Ok(())
+}
+
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".json");
+    PathBuf::from(name)
+}
+
+/// NPY format version 1.0: magic, header length, a Python dict literal padded
+/// so the data starts on a 64-byte boundary, then little-endian float32s.
+fn write_npy(out: &mut impl Write, header: &DataHeader, values: &[f32]) -> io::Result<()> {
+    let shape = match header.channels.len() {
+        1 => format!("({}, {})", header.height, header.width),
+        n => format!("({}, {}, {n})", header.height, header.width),
+    };
+    let mut dict = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {shape}, }}");
+    let unpadded = 6 + 2 + 2 + dict.len() + 1;
+    dict.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    dict.push('\n');
+
+    out.write_all(b"\x93NUMPY\x01\x00")?;
+    out.write_all(&(dict.len() as u16).to_le_bytes())?;
+    out.write_all(dict.as_bytes())?;
+    write_floats(out, values)
+}
+
+fn write_floats(out: &mut impl Write, values: &[f32]) -> io::Result<()> {
+    for value in values {
+        out.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_exr(path: &Path, header: &DataHeader, values: &[f32]) -> io::Result<()> {
+    let channels = header.channels.len();
+    let mut rgba = Vec::with_capacity(values.len() / channels * 4);
+    for pixel in values.chunks(channels) {
+        rgba.extend((0..4).map(|k| pixel.get(k).copied().unwrap_or(0.)));
+    }
+    let image = Rgba32FImage::from_raw(header.width, header.height, rgba).expect("one value per channel and pixel");
+    // without a fourth channel there is no alpha to write either
+    let image = match channels {
+        4 => DynamicImage::ImageRgba32F(image),
+        _ => DynamicImage::ImageRgb32F(Rgb32FImage::from_fn(header.width, header.height, |x, y| {
+            let [r, g, b, _] = image.get_pixel(x, y).0;
+            image::Rgb([r, g, b])
+        })),
+    };
+    image.save(path).map_err(io::Error::other)
+}
+
+fn write_csv(out: &mut impl Write, header: &DataHeader, values: &[f32]) -> io::Result<()> {
+    writeln!(out, "# {:?} per pixel center, rows from the top ({} inside the set for iterations)", header.channels[0], INTERIOR)?;
+    writeln!(out, "# center {} {}", header.center.0, header.center.1)?;
+    writeln!(out, "# half_extents {} {}", header.half_extents.0, header.half_extents.1)?;
+    writeln!(out, "# rotation {}", header.rotation)?;
+    writeln!(out, "# size {}x{}", header.width, header.height)?;
+    writeln!(out, "# iterations {}", header.iterations)?;
+    for row in values.chunks(header.width as usize) {
+        let line: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+        writeln!(out, "{}", line.join(","))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x2 export of `channels`, every value different.
+    fn export(channels: &[DataChannel]) -> (DataHeader, Vec<f32>) {
+        let header = DataHeader::new(&Viewport { center: (-0.75, 0.1), ..Viewport::new(3, 2) }, 500, "cpu", channels);
+        let values = (0..6 * channels.len()).map(|i| if i == 4 { INTERIOR } else { i as f32 * 1.25 + 0.1 }).collect();
+        (header, values)
+    }
+
+    /// Writes `values` in `format` to a temporary file and returns its bytes
+    /// and the sidecar, removing both.
+    fn written(format: DataFormat, extension: &str, header: &DataHeader, values: &[f32]) -> (Vec<u8>, serde_json::Value) {
+        let path = std::env::temp_dir().join(format!("mandelplotter-data-{}-{:?}.{extension}", std::process::id(), header.channels));
+        write(&path, format, header, values).expect("writable");
+        let bytes = std::fs::read(&path).expect("written");
+        let sidecar = serde_json::from_slice(&std::fs::read(sidecar_path(&path)).expect("sidecar")).expect("JSON");
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(sidecar_path(&path)).ok();
+        (bytes, sidecar)
+    }
+
+    fn floats(bytes: &[u8]) -> Vec<f32> {
+        bytes.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().expect("four bytes"))).collect()
+    }
+
+    #[test]
+    fn npy_reads_back() {
+        for channels in [&[DataChannel::Escape][..], &[DataChannel::Smooth, DataChannel::Modulus, DataChannel::Interior]] {
+            let (header, values) = export(channels);
+            let (bytes, sidecar) = written(DataFormat::Npy, "npy", &header, &values);
+            assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+            let len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+            assert_eq!((10 + len) % 64, 0, "the data is aligned");
+            let dict = std::str::from_utf8(&bytes[10..10 + len]).expect("ASCII");
+            let shape = if channels.len() == 1 { "(2, 3)".to_string() } else { format!("(2, 3, {})", channels.len()) };
+            assert!(dict.contains(&format!("'shape': {shape}")) && dict.ends_with('\n'), "{dict}");
+            assert_eq!(floats(&bytes[10 + len..]), values);
+            assert_eq!(sidecar["channels"].as_array().map(Vec::len), Some(channels.len()));
+        }
+    }
+
+    #[test]
+    fn raw_floats_read_back() {
+        let (header, values) = export(&[DataChannel::Escape, DataChannel::Modulus]);
+        let (bytes, sidecar) = written(DataFormat::F32, "f32", &header, &values);
+        assert_eq!(floats(&bytes), values);
+        assert_eq!((sidecar["width"].as_u64(), sidecar["height"].as_u64(), sidecar["iterations"].as_i64()), (Some(3), Some(2), Some(500)));
+        assert_eq!(sidecar["channels"], serde_json::json!(["escape", "modulus"]));
+    }
+
+    #[test]
+    fn exr_reads_back() {
+        for channels in [&[DataChannel::Escape, DataChannel::Smooth][..], &[DataChannel::Escape, DataChannel::Smooth, DataChannel::Modulus, DataChannel::Interior]] {
+            let (header, values) = export(channels);
+            let (bytes, _) = written(DataFormat::Exr, "exr", &header, &values);
+            let image = image::load_from_memory_with_format(&bytes, image::ImageFormat::OpenExr).expect("an EXR").into_rgba32f();
+            assert_eq!(image.dimensions(), (3, 2));
+            for (pixel, expected) in image.pixels().zip(values.chunks(channels.len())) {
+                assert_eq!(&pixel.0[..channels.len()], expected);
+                if channels.len() < 3 {
+                    assert_eq!(pixel.0[2], 0.);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn csv_reads_back() {
+        let (header, values) = export(&[DataChannel::Smooth]);
+        let (bytes, _) = written(DataFormat::Csv, "csv", &header, &values);
+        let text = String::from_utf8(bytes).expect("text");
+        assert!(text.lines().any(|line| line == "# size 3x2"));
+        let read: Vec<f32> = text
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .flat_map(|line| line.split(',').map(|v| v.parse::<f32>().expect("a number")))
+            .collect();
+        assert_eq!(read, values);
+    }
+
+    #[test]
+    fn formats_come_from_the_extension() {
+        assert_eq!(DataFormat::from_path(Path::new("a.NPY")), Some(DataFormat::Npy));
+        assert_eq!(DataFormat::from_path(Path::new("dir.v2/a.exr")), Some(DataFormat::Exr));
+        assert_eq!(DataFormat::from_path(Path::new("a.png")), None);
+        assert_eq!(DataFormat::from_path(Path::new("f32")), None);
+    }
+}
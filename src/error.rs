@@ -0,0 +1,102 @@
+// Why the explorer, or one of the modes run before it opens a window, could
+// not start. Each says what failed and, where it's not obvious, what to look
+// at; `main` prints it and exits with `exit_code`.
+
+use std::io;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::headless::{EXIT_BAD_ARGS, EXIT_RENDER_FAILED};
+
+#[derive(Debug, Error)]
+pub enum StartError {
+    #[error("--threads {threads}: {source}")]
+    Threads { threads: u32, source: rayon::ThreadPoolBuildError },
+    /// A `--shader-dir` that isn't there.
+    #[error("{0}")]
+    ShaderDir(String),
+    #[error("failed to load the shaders: {0}")]
+    Shaders(String),
+    /// An option for what this build left out.
+    #[cfg(not(all(feature = "serve", feature = "backend-glfw")))]
+    #[error("{option}: not compiled in (build with `--features {feature}`)")]
+    NotCompiledIn { option: String, feature: &'static str },
+    #[error("failed to write {}: {reason}", path.display())]
+    Write { path: PathBuf, reason: String },
+    #[error("terminal mode failed: {0}")]
+    Terminal(#[source] io::Error),
+    #[error("--load {what}: {reason}")]
+    Load { what: String, reason: String },
+    #[cfg(feature = "backend-glfw")]
+    #[error("could not initialize GLFW: {0} (is there a display to open a window on? `render` works without one)")]
+    Glfw(#[source] glfw::InitError),
+    #[cfg(feature = "backend-winit")]
+    #[error("could not open a window: {0} (is there a display to open one on? `render` works without one)")]
+    Winit(String),
+    #[cfg(not(feature = "backend-winit"))]
+    #[error("could not create a window with any OpenGL context (see the GLFW errors above)")]
+    Window,
+    #[error("no way to put pixels on screen, the blit shader did not build either:\n{0}")]
+    Blit(String),
+    #[error("could not set up the screenshot buffers: {0}")]
+    Capture(String),
+    #[error("could not hand the window's context to the render thread: {0}")]
+    RenderThread(String),
+}
+
+impl StartError {
+    /// The status to exit with: 2 for what was asked being wrong, as the
+    /// other modes do, 1 for the rest.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StartError::Threads { .. } | StartError::ShaderDir(_) | StartError::Load { .. } => EXIT_BAD_ARGS,
+            #[cfg(not(all(feature = "serve", feature = "backend-glfw")))]
+            StartError::NotCompiledIn { .. } => EXIT_BAD_ARGS,
+            _ => EXIT_RENDER_FAILED,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn what_was_asked_being_wrong_exits_with_2() {
+        let bad_args = [
+            StartError::ShaderDir("--shader-dir shaders: not a directory".into()),
+            StartError::Load { what: "mb2:x".into(), reason: "not a location".into() },
+        ];
+        assert!(bad_args.iter().all(|err| err.exit_code() == EXIT_BAD_ARGS));
+        let failed = [
+            StartError::Shaders("fractal.frag: no such file".into()),
+            StartError::Write { path: "out.png".into(), reason: "permission denied".into() },
+            StartError::Terminal(io::Error::other("not a terminal")),
+            StartError::Blit("0:1: syntax error".into()),
+        ];
+        assert!(failed.iter().all(|err| err.exit_code() == EXIT_RENDER_FAILED));
+    }
+
+    #[test]
+    fn messages_say_what_failed() {
+        let err = StartError::Write { path: PathBuf::from("dir").join("out.png"), reason: "permission denied".into() };
+        assert_eq!(err.to_string(), format!("failed to write {}: permission denied", Path::new("dir").join("out.png").display()));
+        let err = StartError::Load { what: "view.kfr".into(), reason: "no Re: line".into() };
+        assert_eq!(err.to_string(), "--load view.kfr: no Re: line");
+        // the I/O error is both in the message and the source, for whoever walks the chain
+        let err = StartError::Terminal(io::Error::other("not a terminal"));
+        assert_eq!(err.to_string(), "terminal mode failed: not a terminal");
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    #[cfg(not(all(feature = "serve", feature = "backend-glfw")))]
+    fn what_isnt_compiled_in_says_how_to_get_it() {
+        let err = StartError::NotCompiledIn { option: "--serve 8080".into(), feature: "serve" };
+        assert_eq!(err.to_string(), "--serve 8080: not compiled in (build with `--features serve`)");
+        assert_eq!(err.exit_code(), EXIT_BAD_ARGS);
+    }
+}
@@ -0,0 +1,315 @@
+// Renders of a view at a resolution unrelated to the window, from wallpapers
+// to posters far beyond what fits in a texture. The image is cut into tiles
+// rendered one at a time (one per frame in the window, so it stays responsive
+// and no single draw call runs long enough to trip a GPU watchdog). Finished
+// rows of tiles can go straight into a streaming PNG encoder so the whole
+// image never has to be held in memory.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use image::{DynamicImage, RgbaImage};
+
+use crate::cpu::Rgba16Image;
+
+use crate::viewport::{PixelRect, Viewport};
+
+/// Largest tile edge in pixels.
+const TILE: u32 = 1024;
+/// Rows of tiles that may wait for the PNG encoder.
+const BANDS_IN_FLIGHT: usize = 2;
+
+/// Bits per channel of exported images. Videos are always 8-bit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum BitDepth {
+    #[default]
+    #[value(name = "8")]
+    Eight,
+    /// For grading afterwards: no banding when the contrast is pushed.
+    #[value(name = "16")]
+    Sixteen,
+}
+
+impl BitDepth {
+    /// A transparent `width x height` image of this depth.
+    pub fn blank(self, width: u32, height: u32) -> DynamicImage {
+        match self {
+            BitDepth::Eight => DynamicImage::ImageRgba8(RgbaImage::new(width, height)),
+            BitDepth::Sixteen => DynamicImage::ImageRgba16(Rgba16Image::new(width, height)),
+        }
+    }
+}
+
+/// Copies `piece` into `onto` with its top-left corner at `(x, y)`. Both must
+/// have the same depth; `imageops::replace` on `DynamicImage`s would go
+/// through 8 bits.
+pub fn paste(onto: &mut DynamicImage, piece: &DynamicImage, x: u32, y: u32) {
+    match (onto, piece) {
+        (DynamicImage::ImageRgba8(onto), DynamicImage::ImageRgba8(piece)) => {
+            image::imageops::replace(onto, piece, x as i64, y as i64)
+        }
+        (DynamicImage::ImageRgba16(onto), DynamicImage::ImageRgba16(piece)) => {
+            image::imageops::replace(onto, piece, x as i64, y as i64)
+        }
+        _ => panic!("pasting between images of different depths"),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    pub rect: PixelRect,
+    pub index: usize,
+}
+
+pub struct Export {
+    /// The whole export; tiles are pixel rectangles of it.
+    pub viewport: Viewport,
+    pub ssaa: i32,
+    pub depth: BitDepth,
+    tiles: Vec<Tile>,
+    done: usize,
+    /// The row of tiles being assembled.
+    band: DynamicImage,
+}
+
+impl Export {
+    /// Keeps the center, rotation and vertical extent of `view`; the horizontal
+    /// extent follows the aspect ratio of `width x height`.
+    pub fn new(view: &Viewport, width: u32, height: u32, ssaa: i32, depth: BitDepth) -> Export {
+        let mut viewport = *view;
+        viewport.resize(width, height);
+        let mut tiles = Vec::new();
+        for y in (0..viewport.height).step_by(TILE as usize) {
+            for x in (0..viewport.width).step_by(TILE as usize) {
+                let rect = PixelRect { x, y, width: TILE.min(viewport.width - x), height: TILE.min(viewport.height - y) };
+                tiles.push(Tile { rect, index: tiles.len() });
+            }
+        }
+        let band = depth.blank(viewport.width, TILE.min(viewport.height));
+        Export { viewport, ssaa, depth, tiles, done: 0, band }
+    }
+
+    pub fn next_tile(&self) -> Option<Tile> {
+        self.tiles.get(self.done).copied()
+    }
+
+    /// Takes the rendered pixels (rows from the top, of the export's depth) of
+    /// the tile `next_tile` returned. When that completes a row of tiles,
+    /// returns the row as an image spanning the full width, starting at pixel
+    /// row `y`.
+    pub fn store(&mut self, tile: Tile, pixels: &DynamicImage) -> Option<(u32, DynamicImage)> {
+        debug_assert_eq!(tile.index, self.done, "tiles must be stored in order");
+        paste(&mut self.band, pixels, tile.rect.x, 0);
+        self.done += 1;
+
+        let row_done = self.tiles.get(self.done).is_none_or(|next| next.rect.y != tile.rect.y);
+        if !row_done {
+            return None;
+        }
+        let next_height = self.tiles.get(self.done).map_or(0, |next| next.rect.height);
+        let mut band = self.depth.blank(self.viewport.width, next_height);
+        std::mem::swap(&mut band, &mut self.band);
+        Some((tile.rect.y, band))
+    }
+
+    /// `(rendered, total)` tiles.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.done, self.tiles.len())
+    }
+
+    pub fn finished(&self) -> bool {
+        self.done == self.tiles.len()
+    }
+}
+
+/// Rows of blank padding queued at a time by `PngStream::finish_partial`.
+const PARTIAL_BAND_ROWS: u32 = 256;
+
+/// An RGBA PNG, 8 or 16 bits per channel, written row by row on a worker thread.
+pub struct PngStream {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    depth: BitDepth,
+    /// Queued so far.
+    rows: u32,
+    bands: Option<SyncSender<Vec<u8>>>,
+    worker: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl PngStream {
+    /// Creates the file and writes the header, with `text` as iTXt chunks.
+    pub fn create(path: &Path, width: u32, height: u32, depth: BitDepth, text: &[(String, String)]) -> Result<PngStream, String> {
+        let file = File::create(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(match depth {
+            BitDepth::Eight => png::BitDepth::Eight,
+            BitDepth::Sixteen => png::BitDepth::Sixteen,
+        });
+        for (keyword, value) in text {
+            encoder.add_itxt_chunk(keyword.clone(), value.clone()).map_err(|err| err.to_string())?;
+        }
+        let mut rows = encoder
+            .write_header()
+            .and_then(|writer| writer.into_stream_writer())
+            .map_err(|err| err.to_string())?;
+
+        let (sender, bands) = mpsc::sync_channel::<Vec<u8>>(BANDS_IN_FLIGHT);
+        let worker = thread::spawn(move || {
+            for band in bands {
+                rows.write_all(&band).map_err(|err| err.to_string())?;
+            }
+            rows.finish().map_err(|err| err.to_string())
+        });
+        Ok(PngStream { path: path.to_path_buf(), width, height, depth, rows: 0, bands: Some(sender), worker: Some(worker) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Queues full-width rows, top to bottom, of the depth the stream was
+    /// created with. Blocks while the encoder is behind.
+    pub fn write(&mut self, band: DynamicImage) -> Result<(), String> {
+        let sender = self.bands.as_ref().expect("only taken by finish");
+        self.rows += band.height();
+        let bytes = match band {
+            DynamicImage::ImageRgba16(band) => band.into_raw().iter().flat_map(|v| v.to_be_bytes()).collect(),
+            band => band.into_rgba8().into_raw(),
+        };
+        if sender.send(bytes).is_err() {
+            // the worker stopped early, finish() reports why
+            return self.finish_inner();
+        }
+        Ok(())
+    }
+
+    /// Waits for the encoder and closes the file. Fails, leaving no file
+    /// behind, if fewer rows than the image height were written.
+    pub fn finish(mut self) -> Result<(), String> {
+        let result = self.finish_inner();
+        if result.is_err() {
+            let _ = fs::remove_file(&self.path);
+        }
+        result
+    }
+
+    /// Stops encoding and removes the partial file, which is also what
+    /// dropping an unfinished stream does.
+    pub fn cancel(self) {}
+
+    /// Finishes the file with the rows not written yet left transparent, to
+    /// keep what a cancelled export got done.
+    pub fn finish_partial(mut self) -> Result<(), String> {
+        while self.rows < self.height {
+            let rows = (self.height - self.rows).min(PARTIAL_BAND_ROWS);
+            self.write(self.depth.blank(self.width, rows))?;
+        }
+        self.finish()
+    }
+
+    fn finish_inner(&mut self) -> Result<(), String> {
+        drop(self.bands.take());
+        match self.worker.take() {
+            Some(worker) => worker.join().unwrap_or_else(|_| Err("the PNG encoder panicked".to_string())),
+            None => Err("the PNG encoder already stopped".to_string()),
+        }
+    }
+}
+
+impl Drop for PngStream {
+    fn drop(&mut self) {
+        if self.worker.is_some() {
+            let _ = self.finish_inner();
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_the_image_once() {
+        let view = Viewport { center: (-0.75, 0.1), scale: 0.01, rotation: 0.5, ..Viewport::new(800, 600) };
+        let export = Export::new(&view, 2500, 1100, 2, BitDepth::Eight);
+        // the camera is the view's, at the export's aspect ratio
+        assert_eq!((export.viewport.center, export.viewport.scale, export.viewport.rotation), (view.center, view.scale, view.rotation));
+        assert_eq!((export.viewport.width, export.viewport.height), (2500, 1100));
+
+        let mut covered = vec![0u8; 2500 * 1100];
+        for (index, tile) in export.tiles.iter().enumerate() {
+            assert_eq!(tile.index, index);
+            assert!(tile.rect.width <= TILE && tile.rect.height <= TILE);
+            for y in tile.rect.y..tile.rect.y + tile.rect.height {
+                for x in tile.rect.x..tile.rect.x + tile.rect.width {
+                    covered[(y * 2500 + x) as usize] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|&n| n == 1));
+        assert_eq!(export.progress(), (0, 6));
+    }
+
+    #[test]
+    fn rows_of_tiles_come_back_whole() {
+        let view = Viewport::new(100, 100);
+        let mut export = Export::new(&view, TILE + 10, TILE + 5, 1, BitDepth::Eight);
+        let mut bands = Vec::new();
+        while let Some(tile) = export.next_tile() {
+            // each tile filled with its own index
+            let shade = tile.index as u8 + 1;
+            let pixels = RgbaImage::from_pixel(tile.rect.width, tile.rect.height, image::Rgba([shade; 4]));
+            bands.extend(export.store(tile, &pixels.into()));
+        }
+        assert!(export.finished());
+        let bands: Vec<(u32, RgbaImage)> = bands.into_iter().map(|(y, band)| (y, band.into_rgba8())).collect();
+        assert_eq!(bands.iter().map(|(y, band)| (*y, band.dimensions())).collect::<Vec<_>>(), [(0, (TILE + 10, TILE)), (TILE, (TILE + 10, 5))]);
+        let (_, second) = &bands[1];
+        assert_eq!((second.get_pixel(0, 0).0[0], second.get_pixel(TILE + 9, 4).0[0]), (3, 4));
+    }
+
+    #[test]
+    fn streamed_png_reads_back() {
+        let path = std::env::temp_dir().join(format!("mandelplotter-export-{}.png", std::process::id()));
+        let mut stream = PngStream::create(&path, 3, 4, BitDepth::Eight, &[]).expect("writable");
+        for level in [10, 200] {
+            stream.write(RgbaImage::from_pixel(3, 2, image::Rgba([level, 0, 0, 255])).into()).expect("queued");
+        }
+        stream.finish().expect("complete");
+        let image = image::open(&path).expect("a PNG").into_rgba8();
+        fs::remove_file(&path).ok();
+        assert_eq!(image.dimensions(), (3, 4));
+        assert_eq!((image.get_pixel(2, 1).0, image.get_pixel(0, 3).0), ([10, 0, 0, 255], [200, 0, 0, 255]));
+    }
+
+    #[test]
+    fn sixteen_bit_levels_survive() {
+        let path = std::env::temp_dir().join(format!("mandelplotter-export-16-{}.png", std::process::id()));
+        let levels = [0, 1, 255, 256, 257, 32768, 65534, 65535];
+        let band = Rgba16Image::from_fn(2, 1, |x, _| image::Rgba([levels[x as usize * 4], levels[x as usize * 4 + 1], levels[x as usize * 4 + 2], levels[x as usize * 4 + 3]]));
+        let mut stream = PngStream::create(&path, 2, 1, BitDepth::Sixteen, &[]).expect("writable");
+        stream.write(band.into()).expect("queued");
+        stream.finish().expect("complete");
+        let image = image::open(&path).expect("a PNG");
+        fs::remove_file(&path).ok();
+        let DynamicImage::ImageRgba16(image) = image else {
+            panic!("not a 16-bit RGBA PNG");
+        };
+        assert_eq!(image.into_raw(), levels);
+    }
+
+    #[test]
+    fn short_streams_leave_no_file() {
+        let path = std::env::temp_dir().join(format!("mandelplotter-export-short-{}.png", std::process::id()));
+        let mut stream = PngStream::create(&path, 3, 4, BitDepth::Eight, &[]).expect("writable");
+        stream.write(BitDepth::Eight.blank(3, 2)).expect("queued");
+        assert!(stream.finish().is_err());
+        assert!(!path.exists());
+    }
+}
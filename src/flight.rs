@@ -0,0 +1,59 @@
+// Animated transitions between two views. The zoom is interpolated on ln(scale)
+// so that a flight out of a 1e-12 deep zoom takes the same time and looks as
+// steady as one out of 1e-2.
+
+use crate::viewport::Viewport;
+
+pub struct Flight {
+    from: Viewport,
+    to: Viewport,
+    elapsed: f64,
+    duration: f64,
+}
+
+impl Flight {
+    pub fn new(from: Viewport, to: Viewport, duration: f64) -> Flight {
+        Flight { from, to, elapsed: 0., duration }
+    }
+
+    /// Advances the animation by `dt` seconds and returns the view to show.
+    /// The framebuffer size is always taken from `current`, since the window
+    /// may be resized mid-flight.
+    pub fn step(&mut self, current: &Viewport, dt: f64) -> Viewport {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+        let t = if self.duration > 0. { self.elapsed / self.duration } else { 1. };
+        // smoothstep, so the flight neither starts nor stops abruptly
+        let t = t * t * (3. - 2. * t);
+
+        let (from, to) = (&self.from, &self.to);
+        let scale = (from.scale.ln() + (to.scale.ln() - from.scale.ln()) * t).exp();
+        // Move the center in proportion to how far the scale has travelled and
+        // not to time: the start point stays on screen until the view has grown
+        // large enough to show where it is going.
+        let w = if (to.scale - from.scale).abs() > f64::EPSILON * from.scale.max(to.scale) {
+            (scale - from.scale) / (to.scale - from.scale)
+        } else {
+            t
+        };
+        let mut turn = (to.rotation - from.rotation).rem_euclid(std::f64::consts::TAU);
+        if turn > std::f64::consts::PI {
+            turn -= std::f64::consts::TAU;
+        }
+
+        Viewport {
+            center: (
+                from.center.0 + (to.center.0 - from.center.0) * w,
+                from.center.1 + (to.center.1 - from.center.1) * w,
+            ),
+            scale,
+            rotation: (from.rotation + turn * t).rem_euclid(std::f64::consts::TAU),
+            anisotropy: from.anisotropy + (to.anisotropy - from.anisotropy) * t,
+            width: current.width,
+            height: current.height,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
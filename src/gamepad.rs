@@ -0,0 +1,149 @@
+// Navigating with a gamepad (`--gamepad`), through GLFW's gamepad mappings so
+// any controller it knows has the same layout: the left stick pans, the right
+// trigger zooms in and the left one out, the bumpers add and take away
+// iterations while held, A takes a screenshot and Start resets the view. The
+// stick and triggers are read every frame and scale the same per-second
+// speeds the keys use. The first gamepad found is used; it can be plugged in
+// or pulled out at any time. GLFW reads the gamepad whichever backend has the
+// window, so builds without it have none.
+
+#[cfg(feature = "backend-glfw")]
+use glfw::{Action, GamepadAxis, GamepadButton, GamepadState, Glfw, JoystickId};
+#[cfg(feature = "backend-glfw")]
+use log::{info, warn};
+
+use crate::error::StartError;
+
+/// How far a stick or trigger has to be pushed before it counts, as resting
+/// ones rarely read exactly 0.
+#[cfg(feature = "backend-glfw")]
+const DEAD_ZONE: f64 = 0.15;
+
+#[cfg(feature = "backend-glfw")]
+const JOYSTICKS: [JoystickId; 16] = [
+    JoystickId::Joystick1,
+    JoystickId::Joystick2,
+    JoystickId::Joystick3,
+    JoystickId::Joystick4,
+    JoystickId::Joystick5,
+    JoystickId::Joystick6,
+    JoystickId::Joystick7,
+    JoystickId::Joystick8,
+    JoystickId::Joystick9,
+    JoystickId::Joystick10,
+    JoystickId::Joystick11,
+    JoystickId::Joystick12,
+    JoystickId::Joystick13,
+    JoystickId::Joystick14,
+    JoystickId::Joystick15,
+    JoystickId::Joystick16,
+];
+
+/// What the gamepad asks for this frame; all zero without one.
+#[derive(Clone, Default, PartialEq)]
+pub struct Input {
+    /// How far to pan right and up, -1 to 1 each.
+    pub pan: (f64, f64),
+    /// Zoom direction like the I and K keys: -1 all the way in, 1 out.
+    pub zoom: f64,
+    pub more_iterations: bool,
+    pub fewer_iterations: bool,
+    /// Pressed this frame, not only held.
+    pub screenshot: bool,
+    pub reset: bool,
+}
+
+impl Input {
+    /// Adds a later reading: the stick, triggers and bumpers as they are
+    /// now, and the presses of both.
+    pub fn merge(&mut self, later: Input) {
+        let (screenshot, reset) = (self.screenshot, self.reset);
+        *self = later;
+        self.screenshot |= screenshot;
+        self.reset |= reset;
+    }
+}
+
+#[cfg(feature = "backend-glfw")]
+pub struct Gamepad {
+    glfw: Glfw,
+    /// The gamepad in use and its state last frame, so a press only counts once.
+    current: Option<(JoystickId, GamepadState)>,
+}
+
+#[cfg(feature = "backend-glfw")]
+impl Gamepad {
+    pub fn new() -> Result<Gamepad, StartError> {
+        let glfw = glfw::init(crate::offscreen::report_glfw_error).map_err(StartError::Glfw)?;
+        let mut gamepad = Gamepad { glfw, current: None };
+        if !gamepad.connect() {
+            info!("no gamepad connected, plug one in any time");
+        }
+        Ok(gamepad)
+    }
+
+    /// Picks the first gamepad there is, saying whether there was one.
+    fn connect(&mut self) -> bool {
+        self.current = JOYSTICKS.iter().find_map(|&id| {
+            let joystick = self.glfw.get_joystick(id);
+            let state = joystick.get_gamepad_state().filter(|_| joystick.is_gamepad())?;
+            info!("using gamepad {}", joystick.get_gamepad_name().unwrap_or_else(|| format!("{id:?}")));
+            Some((id, state))
+        });
+        self.current.is_some()
+    }
+
+    /// Reads the gamepad, after the events for this frame are polled.
+    pub fn poll(&mut self) -> Input {
+        if self.current.is_none() && !self.connect() {
+            return Input::default();
+        }
+        let Some((id, previous)) = self.current else {
+            return Input::default();
+        };
+        let Some(state) = self.glfw.get_joystick(id).get_gamepad_state() else {
+            warn!("gamepad disconnected");
+            self.current = None;
+            return Input::default();
+        };
+        self.current = Some((id, state));
+
+        let held = |button| state.get_button_state(button) == Action::Press;
+        let pressed = |button| held(button) && previous.get_button_state(button) != Action::Press;
+        // triggers rest at -1
+        let trigger = |axis| dead_zone((state.get_axis(axis) as f64 + 1.) / 2.);
+        Input {
+            // the stick's y grows downwards
+            pan: (dead_zone(state.get_axis(GamepadAxis::AxisLeftX) as f64), -dead_zone(state.get_axis(GamepadAxis::AxisLeftY) as f64)),
+            zoom: trigger(GamepadAxis::AxisLeftTrigger) - trigger(GamepadAxis::AxisRightTrigger),
+            more_iterations: held(GamepadButton::ButtonRightBumper),
+            fewer_iterations: held(GamepadButton::ButtonLeftBumper),
+            screenshot: pressed(GamepadButton::ButtonA),
+            reset: pressed(GamepadButton::ButtonStart),
+        }
+    }
+}
+
+#[cfg(not(feature = "backend-glfw"))]
+pub struct Gamepad;
+
+#[cfg(not(feature = "backend-glfw"))]
+impl Gamepad {
+    pub fn new() -> Result<Gamepad, StartError> {
+        Err(StartError::NotCompiledIn { option: "--gamepad".to_string(), feature: "backend-glfw" })
+    }
+
+    pub fn poll(&mut self) -> Input {
+        Input::default()
+    }
+}
+
+/// `value` with the dead zone cut out, still reaching ±1 at the ends.
+#[cfg(feature = "backend-glfw")]
+fn dead_zone(value: f64) -> f64 {
+    if value.abs() < DEAD_ZONE {
+        0.
+    } else {
+        value.signum() * (value.abs() - DEAD_ZONE) / (1. - DEAD_ZONE)
+    }
+}
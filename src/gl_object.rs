@@ -0,0 +1,129 @@
+// Owned GL object names: each wrapper makes one name, hands it out with `id`
+// and deletes it when dropped. Like every GL call, dropping one has to happen
+// with the context it was made in still current; debug builds check that GL
+// still knows the name before deleting it, which catches a context that was
+// destroyed (or isn't current) first. Errors GL reports while deleting are
+// logged, as there's no one to return them to.
+//
+// The constructors bind what they make, to the target given where there is
+// one, so GL has created the object by the time it is checked; queries only
+// exist once begun.
+
+use gl::types::*;
+
+macro_rules! gl_object {
+    ($(#[$doc:meta])* $name:ident, $what:literal, $gen:ident, $delete:ident, $is:ident) => {
+        $(#[$doc])*
+        pub struct $name(GLuint);
+
+        impl $name {
+            pub fn id(&self) -> GLuint {
+                self.0
+            }
+
+            /// A new name, not yet bound.
+            fn generate() -> Result<$name, String> {
+                // OpenGL 2.1 may have no framebuffers or vertex arrays at all
+                if !gl::$gen::is_loaded() {
+                    return Err(format!("this OpenGL has no {}s", $what));
+                }
+                let mut id = 0;
+                unsafe {
+                    gl::$gen(1, &mut id);
+                }
+                if id == 0 {
+                    return Err(format!("could not create a {}, is a GL context current?", $what));
+                }
+                Ok($name(id))
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                unsafe {
+                    debug_assert!(
+                        gl::$is(self.0) == gl::TRUE,
+                        "{} {} deleted without its context current",
+                        $what,
+                        self.0
+                    );
+                    gl::$delete(1, &self.0);
+                    let error = gl::GetError();
+                    if error != gl::NO_ERROR {
+                        log::warn!("GL error {error:#x} while deleting {} {}", $what, self.0);
+                    }
+                }
+            }
+        }
+    };
+}
+
+gl_object!(
+    /// A buffer object: vertices, or pixels on their way back from the GPU.
+    Buffer, "buffer", GenBuffers, DeleteBuffers, IsBuffer
+);
+gl_object!(
+    /// The vertex attribute setup of a mesh, which core contexts draw from.
+    VertexArray, "vertex array", GenVertexArrays, DeleteVertexArrays, IsVertexArray
+);
+gl_object!(Texture, "texture", GenTextures, DeleteTextures, IsTexture);
+gl_object!(
+    /// A framebuffer object, without the textures attached to it; see
+    /// `renderer::Framebuffer` for a whole offscreen target.
+    Framebuffer, "framebuffer", GenFramebuffers, DeleteFramebuffers, IsFramebuffer
+);
+gl_object!(
+    /// A timer or occlusion query. It has to have been begun at least once by
+    /// the time it drops.
+    Query, "query", GenQueries, DeleteQueries, IsQuery
+);
+
+impl Buffer {
+    /// Bound to `target`.
+    pub fn new(target: GLenum) -> Result<Buffer, String> {
+        let buffer = Buffer::generate()?;
+        unsafe {
+            gl::BindBuffer(target, buffer.0);
+        }
+        Ok(buffer)
+    }
+}
+
+impl VertexArray {
+    /// Bound, so the attribute setup that follows goes into it.
+    pub fn new() -> Result<VertexArray, String> {
+        let vao = VertexArray::generate()?;
+        unsafe {
+            gl::BindVertexArray(vao.0);
+        }
+        Ok(vao)
+    }
+}
+
+impl Texture {
+    /// Bound to `target` on the active texture unit.
+    pub fn new(target: GLenum) -> Result<Texture, String> {
+        let texture = Texture::generate()?;
+        unsafe {
+            gl::BindTexture(target, texture.0);
+        }
+        Ok(texture)
+    }
+}
+
+impl Framebuffer {
+    /// Bound as both the draw and the read framebuffer.
+    pub fn new() -> Result<Framebuffer, String> {
+        let fbo = Framebuffer::generate()?;
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo.0);
+        }
+        Ok(fbo)
+    }
+}
+
+impl Query {
+    pub fn new() -> Result<Query, String> {
+        Query::generate()
+    }
+}
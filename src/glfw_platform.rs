@@ -0,0 +1,325 @@
+// The explorer's window through GLFW, the default backend. GLFW queues the
+// window's events and keeps the state of every key, so this mostly
+// translates its names into `platform`'s.
+
+use std::ffi::c_void;
+use std::time::Duration;
+
+use glfw::Context;
+use log::warn;
+
+use crate::error::StartError;
+use crate::offscreen;
+use crate::platform::{Action, Detached, Event, Key, Modifiers, MouseButton, Surface, Window, WindowOptions};
+
+pub struct GlfwWindow {
+    // the window goes before the library it was made with
+    window: glfw::PWindow,
+    events: glfw::GlfwReceiver<(f64, glfw::WindowEvent)>,
+    glfw: glfw::Glfw,
+}
+
+/// The window's context for another thread; GLFW holds off destroying the
+/// window until it is dropped.
+struct GlfwSurface {
+    context: glfw::PRenderContext,
+    version: (u32, u32),
+}
+
+pub fn open(options: &WindowOptions) -> Result<GlfwWindow, StartError> {
+    let mut glfw = glfw::init(offscreen::report_glfw_error).map_err(StartError::Glfw)?;
+
+    let mut requests: [fn(&mut glfw::Glfw); 2] = [offscreen::request_core_context, request_es_context];
+    if options.gles {
+        requests.reverse();
+    }
+    requests[0](&mut glfw);
+    if options.msaa > 1 {
+        glfw.window_hint(glfw::WindowHint::Samples(Some(options.msaa)));
+    }
+    let (width, height) = options.size;
+    // fullscreen at the primary monitor's current mode, if there is a monitor
+    let create_window = |glfw: &mut glfw::Glfw| {
+        // again after the fallbacks reset the hints
+        glfw.window_hint(glfw::WindowHint::TransparentFramebuffer(options.transparent));
+        glfw.window_hint(glfw::WindowHint::Floating(options.always_on_top));
+        glfw.with_primary_monitor(|glfw, monitor| match monitor.filter(|_| options.fullscreen) {
+            Some(monitor) => {
+                let (width, height) = monitor.get_video_mode().map_or((width, height), |mode| (mode.width, mode.height));
+                glfw.create_window(width, height, &options.title, glfw::WindowMode::FullScreen(monitor))
+            }
+            None => glfw.create_window(width, height, &options.title, glfw::WindowMode::Windowed),
+        })
+    };
+    let mut created = create_window(&mut glfw);
+    if created.is_none() && options.msaa > 1 {
+        warn!("{}x MSAA is not supported here, continuing without it", options.msaa);
+        glfw.window_hint(glfw::WindowHint::Samples(None));
+        created = create_window(&mut glfw);
+    }
+    if created.is_none() {
+        // OpenGL ES 3.0 runs the shaders as well as 3.3 core does
+        glfw.default_window_hints();
+        requests[1](&mut glfw);
+        created = create_window(&mut glfw);
+    }
+    if created.is_none() {
+        // neither, take whatever the driver offers and render on the CPU
+        glfw.default_window_hints();
+        created = create_window(&mut glfw);
+    }
+    let (mut window, events) = created.ok_or(StartError::Window)?;
+    if options.transparent && !window.is_framebuffer_transparent() {
+        warn!("the desktop can't show through the window here, it stays opaque");
+    }
+
+    window.set_key_polling(true);
+    window.set_framebuffer_size_polling(true);
+    window.set_scroll_polling(true);
+    window.set_cursor_pos_polling(true);
+    window.set_mouse_button_polling(true);
+    window.set_char_polling(true);
+    window.set_drag_and_drop_polling(true);
+    window.make_current();
+    glfw.set_swap_interval(if options.vsync { glfw::SwapInterval::Sync(1) } else { glfw::SwapInterval::None });
+    if let Some((x, y)) = options.position.filter(|_| !options.fullscreen) {
+        window.set_pos(x, y);
+    }
+    Ok(GlfwWindow { window, events, glfw })
+}
+
+/// Asks for an OpenGL ES 3.0 context, which the shaders are translated for.
+fn request_es_context(glfw: &mut glfw::Glfw) {
+    glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::OpenGlEs));
+    glfw.window_hint(glfw::WindowHint::ContextVersion(3, 0));
+}
+
+/// The primary monitor's current video mode.
+pub fn monitor_size() -> Result<(u32, u32), String> {
+    let mut glfw = glfw::init(offscreen::report_glfw_error).map_err(|err| format!("could not initialize GLFW: {err}"))?;
+    let mode = glfw.with_primary_monitor(|_, monitor| monitor.and_then(|monitor| monitor.get_video_mode()));
+    let mode = mode.ok_or("no monitor to size the wallpaper for")?;
+    Ok((mode.width, mode.height))
+}
+
+impl Window for GlfwWindow {
+    fn poll_events(&mut self) -> Vec<Event> {
+        self.glfw.poll_events();
+        glfw::flush_messages(&self.events).filter_map(|(_, event)| event_of(event)).collect()
+    }
+
+    fn wait_events(&mut self, timeout: Duration) -> Vec<Event> {
+        self.glfw.wait_events_timeout(timeout.as_secs_f64());
+        glfw::flush_messages(&self.events).filter_map(|(_, event)| event_of(event)).collect()
+    }
+
+    fn is_held(&self, key: Key) -> bool {
+        self.window.get_key(glfw_key(key)) == glfw::Action::Press
+    }
+
+    fn held_keys(&self) -> Vec<Key> {
+        KEYS.iter().filter(|&&(_, glfw)| self.window.get_key(glfw) == glfw::Action::Press).map(|&(key, _)| key).collect()
+    }
+
+    fn cursor_pos(&self) -> (f64, f64) {
+        self.window.get_cursor_pos()
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.window.get_size()
+    }
+
+    fn position(&self) -> (i32, i32) {
+        self.window.get_pos()
+    }
+
+    fn framebuffer_size(&self) -> (i32, i32) {
+        self.window.get_framebuffer_size()
+    }
+
+    fn is_transparent(&self) -> bool {
+        self.window.is_framebuffer_transparent()
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    fn should_close(&self) -> bool {
+        self.window.should_close()
+    }
+
+    fn set_should_close(&mut self, close: bool) {
+        self.window.set_should_close(close);
+    }
+
+    fn swap_buffers(&mut self) {
+        self.window.swap_buffers();
+    }
+
+    fn clipboard_text(&mut self) -> Option<String> {
+        self.window.get_clipboard_string()
+    }
+
+    fn set_clipboard_text(&mut self, text: &str) {
+        self.window.set_clipboard_string(text);
+    }
+
+    fn proc_address(&mut self, name: &str) -> *const c_void {
+        self.window.get_proc_address(name)
+    }
+
+    fn context_version(&self) -> (u32, u32) {
+        let version = self.window.get_context_version();
+        (version.major as u32, version.minor as u32)
+    }
+
+    fn detach(&mut self) -> Result<Box<dyn Detached>, String> {
+        let version = self.context_version();
+        let context = self.window.render_context();
+        glfw::make_context_current(None);
+        Ok(Box::new(GlfwSurface { context, version }))
+    }
+}
+
+impl Detached for GlfwSurface {
+    fn make_current(mut self: Box<Self>) -> Result<Box<dyn Surface>, String> {
+        self.context.make_current();
+        Ok(self)
+    }
+}
+
+impl Surface for GlfwSurface {
+    fn swap_buffers(&mut self) {
+        self.context.swap_buffers();
+    }
+
+    fn proc_address(&mut self, name: &str) -> *const c_void {
+        self.context.get_proc_address(name)
+    }
+
+    fn context_version(&self) -> (u32, u32) {
+        self.version
+    }
+
+    // GLFW's framebuffer follows the window
+    fn resize(&mut self, _: i32, _: i32) {}
+}
+
+fn event_of(event: glfw::WindowEvent) -> Option<Event> {
+    Some(match event {
+        glfw::WindowEvent::Key(key, _, action, modifiers) => Event::Key(key_of(key)?, action_of(action), modifiers_of(modifiers)),
+        glfw::WindowEvent::Char(c) => Event::Char(c),
+        glfw::WindowEvent::CursorPos(x, y) => Event::CursorPos(x, y),
+        glfw::WindowEvent::MouseButton(button, action, modifiers) => {
+            let button = match button {
+                glfw::MouseButton::Button1 => MouseButton::Left,
+                glfw::MouseButton::Button2 => MouseButton::Right,
+                glfw::MouseButton::Button3 => MouseButton::Middle,
+                _ => return None,
+            };
+            Event::MouseButton(button, action_of(action), modifiers_of(modifiers))
+        }
+        glfw::WindowEvent::Scroll(x, y) => Event::Scroll(x, y),
+        glfw::WindowEvent::FramebufferSize(width, height) => Event::FramebufferSize(width, height),
+        glfw::WindowEvent::FileDrop(paths) => Event::FileDrop(paths),
+        _ => return None,
+    })
+}
+
+fn action_of(action: glfw::Action) -> Action {
+    match action {
+        glfw::Action::Press => Action::Press,
+        glfw::Action::Repeat => Action::Repeat,
+        glfw::Action::Release => Action::Release,
+    }
+}
+
+fn modifiers_of(modifiers: glfw::Modifiers) -> Modifiers {
+    [
+        (glfw::Modifiers::Shift, Modifiers::SHIFT),
+        (glfw::Modifiers::Control, Modifiers::CONTROL),
+        (glfw::Modifiers::Alt, Modifiers::ALT),
+        (glfw::Modifiers::Super, Modifiers::SUPER),
+    ]
+    .into_iter()
+    .filter(|&(glfw, _)| modifiers.contains(glfw))
+    .fold(Modifiers::default(), |held, (_, ours)| held | ours)
+}
+
+/// Every `Key` with GLFW's name for it, which `key_of` and `glfw_key` look
+/// up both ways.
+const KEYS: [(Key, glfw::Key); 64] = [
+    (Key::A, glfw::Key::A),
+    (Key::B, glfw::Key::B),
+    (Key::C, glfw::Key::C),
+    (Key::D, glfw::Key::D),
+    (Key::E, glfw::Key::E),
+    (Key::F, glfw::Key::F),
+    (Key::G, glfw::Key::G),
+    (Key::H, glfw::Key::H),
+    (Key::I, glfw::Key::I),
+    (Key::J, glfw::Key::J),
+    (Key::K, glfw::Key::K),
+    (Key::L, glfw::Key::L),
+    (Key::M, glfw::Key::M),
+    (Key::N, glfw::Key::N),
+    (Key::O, glfw::Key::O),
+    (Key::P, glfw::Key::P),
+    (Key::Q, glfw::Key::Q),
+    (Key::R, glfw::Key::R),
+    (Key::S, glfw::Key::S),
+    (Key::T, glfw::Key::T),
+    (Key::U, glfw::Key::U),
+    (Key::V, glfw::Key::V),
+    (Key::W, glfw::Key::W),
+    (Key::X, glfw::Key::X),
+    (Key::Y, glfw::Key::Y),
+    (Key::Z, glfw::Key::Z),
+    (Key::F1, glfw::Key::F1),
+    (Key::F2, glfw::Key::F2),
+    (Key::F3, glfw::Key::F3),
+    (Key::F4, glfw::Key::F4),
+    (Key::F5, glfw::Key::F5),
+    (Key::F6, glfw::Key::F6),
+    (Key::F7, glfw::Key::F7),
+    (Key::F8, glfw::Key::F8),
+    (Key::F9, glfw::Key::F9),
+    (Key::F10, glfw::Key::F10),
+    (Key::F11, glfw::Key::F11),
+    (Key::F12, glfw::Key::F12),
+    (Key::Space, glfw::Key::Space),
+    (Key::Comma, glfw::Key::Comma),
+    (Key::Period, glfw::Key::Period),
+    (Key::LeftBracket, glfw::Key::LeftBracket),
+    (Key::RightBracket, glfw::Key::RightBracket),
+    (Key::Backslash, glfw::Key::Backslash),
+    (Key::Escape, glfw::Key::Escape),
+    (Key::Enter, glfw::Key::Enter),
+    (Key::KpEnter, glfw::Key::KpEnter),
+    (Key::Tab, glfw::Key::Tab),
+    (Key::Backspace, glfw::Key::Backspace),
+    (Key::Delete, glfw::Key::Delete),
+    (Key::Home, glfw::Key::Home),
+    (Key::End, glfw::Key::End),
+    (Key::Left, glfw::Key::Left),
+    (Key::Right, glfw::Key::Right),
+    (Key::Up, glfw::Key::Up),
+    (Key::Down, glfw::Key::Down),
+    (Key::LeftShift, glfw::Key::LeftShift),
+    (Key::RightShift, glfw::Key::RightShift),
+    (Key::LeftControl, glfw::Key::LeftControl),
+    (Key::RightControl, glfw::Key::RightControl),
+    (Key::LeftAlt, glfw::Key::LeftAlt),
+    (Key::RightAlt, glfw::Key::RightAlt),
+    (Key::LeftSuper, glfw::Key::LeftSuper),
+    (Key::RightSuper, glfw::Key::RightSuper),
+];
+
+fn key_of(key: glfw::Key) -> Option<Key> {
+    KEYS.iter().find(|&&(_, glfw)| glfw == key).map(|&(key, _)| key)
+}
+
+fn glfw_key(key: Key) -> glfw::Key {
+    KEYS.iter().find(|&&(ours, _)| ours == key).map(|&(_, glfw)| glfw).expect("every key is in KEYS")
+}
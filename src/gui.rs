@@ -0,0 +1,106 @@
+// Dear ImGui overlay drawn over the fractal, fed from the window's events.
+
+use std::time::Duration;
+
+use crate::platform::{Action, Event, Key, MouseButton, Window};
+
+pub struct Gui {
+    imgui: imgui::Context,
+    renderer: imgui_opengl_renderer::Renderer,
+}
+
+impl Gui {
+    /// Needs the window's GL context to be current.
+    pub fn new(window: &mut dyn Window) -> Gui {
+        let mut imgui = imgui::Context::create();
+        // no imgui.ini next to wherever the explorer was started
+        imgui.set_ini_filename(None);
+        let renderer = imgui_opengl_renderer::Renderer::new(&mut imgui, |s| window.proc_address(s));
+        Gui { imgui, renderer }
+    }
+
+    /// Whether the pointer is over a UI window, so the view shouldn't react to it.
+    pub fn wants_mouse(&self) -> bool {
+        self.imgui.io().want_capture_mouse
+    }
+
+    /// Whether a text field has focus, so keys shouldn't move the view.
+    pub fn wants_keyboard(&self) -> bool {
+        self.imgui.io().want_capture_keyboard
+    }
+
+    /// Whether the UI consumed `event`, decided by what it wanted last frame.
+    pub fn captures(&self, event: &Event) -> bool {
+        match event {
+            Event::CursorPos(..) | Event::MouseButton(..) | Event::Scroll(..) => self.wants_mouse(),
+            Event::Key(..) | Event::Char(..) => self.wants_keyboard(),
+            _ => false,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        let io = self.imgui.io_mut();
+        match *event {
+            Event::CursorPos(x, y) => io.add_mouse_pos_event([x as f32, y as f32]),
+            Event::MouseButton(button, action, _) => {
+                let button = match button {
+                    MouseButton::Left => imgui::MouseButton::Left,
+                    MouseButton::Right => imgui::MouseButton::Right,
+                    MouseButton::Middle => imgui::MouseButton::Middle,
+                };
+                io.add_mouse_button_event(button, action != Action::Release);
+            }
+            Event::Scroll(x, y) => io.add_mouse_wheel_event([x as f32, y as f32]),
+            Event::Char(c) => io.add_input_character(c),
+            Event::Key(key, action, _) => {
+                if let Some(key) = imgui_key(key) {
+                    io.add_key_event(key, action != Action::Release);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Builds the UI with `build` and draws it over whatever is in the back buffer.
+    pub fn draw(&mut self, window: &dyn Window, dt: Duration, build: impl FnOnce(&imgui::Ui)) {
+        let io = self.imgui.io_mut();
+        let (width, height) = window.size();
+        let (fb_width, fb_height) = window.framebuffer_size();
+        io.display_size = [width as f32, height as f32];
+        if width > 0 && height > 0 {
+            io.display_framebuffer_scale = [fb_width as f32 / width as f32, fb_height as f32 / height as f32];
+        }
+        // imgui rejects a zero delta
+        io.update_delta_time(dt.max(Duration::from_micros(100)));
+
+        build(self.imgui.new_frame());
+        self.renderer.render(&mut self.imgui);
+    }
+}
+
+fn imgui_key(key: Key) -> Option<imgui::Key> {
+    Some(match key {
+        Key::Tab => imgui::Key::Tab,
+        Key::Left => imgui::Key::LeftArrow,
+        Key::Right => imgui::Key::RightArrow,
+        Key::Up => imgui::Key::UpArrow,
+        Key::Down => imgui::Key::DownArrow,
+        Key::Home => imgui::Key::Home,
+        Key::End => imgui::Key::End,
+        Key::Delete => imgui::Key::Delete,
+        Key::Backspace => imgui::Key::Backspace,
+        Key::Enter => imgui::Key::Enter,
+        Key::KpEnter => imgui::Key::KeypadEnter,
+        Key::Escape => imgui::Key::Escape,
+        Key::A => imgui::Key::A,
+        Key::C => imgui::Key::C,
+        Key::V => imgui::Key::V,
+        Key::X => imgui::Key::X,
+        Key::Z => imgui::Key::Z,
+        Key::LeftControl | Key::RightControl => imgui::Key::ModCtrl,
+        Key::LeftShift | Key::RightShift => imgui::Key::ModShift,
+        Key::LeftAlt | Key::RightAlt => imgui::Key::ModAlt,
+        Key::LeftSuper | Key::RightSuper => imgui::Key::ModSuper,
+        _ => return None,
+    })
+}
@@ -0,0 +1,819 @@
+// `render` subcommand: one frame (or a zoom video or Julia morph) straight to
+// a file, no visible window.
+
+use std::fs;
+use std::path::Path;
+
+use image::{DynamicImage, ImageFormat};
+
+use crate::animation::{self, KeyframePath};
+use crate::background::{Background, BackgroundStyle};
+use crate::cli::{Options, RenderArgs};
+use crate::contour;
+use crate::cpu;
+use crate::data::{self, DataFormat, DataHeader};
+use crate::export::{self, BitDepth, Export, PngStream};
+use crate::job::{self, Job};
+use crate::julia::{self, FrameInfo};
+use crate::keyframes::{self, Keyframe};
+use crate::kfr::Kfr;
+use crate::metadata::{self, Fractal, ViewMetadata};
+use crate::offscreen::{self, OffscreenContext};
+use crate::palette::{ColorMode, Palette, PaletteTable, Tone};
+use crate::renderer::{self, CoordinateMapping, DrawParams, Framebuffer, Renderer};
+use crate::rng;
+use crate::tileset::{self, Level, Pyramid};
+use crate::video::VideoEncoder;
+use crate::viewport::{self, Viewport};
+
+/// Exit status when the render itself failed (bad arguments exit with 2, like clap does).
+pub const EXIT_RENDER_FAILED: i32 = 1;
+pub const EXIT_BAD_ARGS: i32 = 2;
+/// Exit status after Ctrl+C cancelled a render, as shells report SIGINT.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// Rough bytes per pixel of a frame in a video as ffmpeg encodes it by
+/// default, and of one saved as a PNG, for the guess at the size before a
+/// `--decades-per-second` export. The fractal's fine detail compresses worse
+/// than most footage.
+const VIDEO_BYTES_PER_PIXEL: f64 = 0.02;
+const PNG_BYTES_PER_PIXEL: f64 = 1.5;
+
+/// Renders the requested frame and returns the process exit status.
+pub fn run(args: &RenderArgs, options: &Options) -> i32 {
+    job::catch_interrupt();
+    let (width, height) = args.size;
+    let Some(path) = &args.kfr else {
+        let mut viewport = Viewport::new(width, height);
+        viewport.center = (args.center[0], args.center[1]);
+        viewport.scale = args.zoom;
+        return render(viewport, &Palette::grayscale(), args, options);
+    };
+    // the location takes the place of --center, --zoom and --iter
+    let located = Kfr::load(path).and_then(|kfr| {
+        let view = kfr.camera()?.view(width, height).map_err(|err| format!("{}: {err}", path.display()))?;
+        Ok((view, kfr.iterations()))
+    });
+    match located {
+        Ok((viewport, iterations)) => {
+            let args = RenderArgs { iter: iterations.unwrap_or(args.iter), ..args.clone() };
+            render(viewport, &Palette::grayscale(), &args, options)
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            EXIT_BAD_ARGS
+        }
+    }
+}
+
+/// Renders `args` at `viewport`, a still colored with `palette` (videos and
+/// data exports keep their own), and returns the exit status.
+pub fn render(mut viewport: Viewport, palette: &Palette, args: &RenderArgs, options: &Options) -> i32 {
+    let (width, height) = args.size;
+    viewport.set_extents(options.scale_x, options.scale_y);
+
+    // offline renders default to a fixed seed so they come out identical every time
+    let seed = rng::seed32(options.seed.unwrap_or(0));
+
+    // clap makes sure there is exactly one of --out, --export-data,
+    // --export-contours, --export-video, --export-frames and --export-tiles
+    if let Some(path) = &args.export_contours {
+        return export_contours(&viewport, args, path);
+    }
+    if let Some(dir) = &args.export_tiles {
+        return export_tiles(&viewport, palette, args, options, dir, seed);
+    }
+    let out = match (&args.out, &args.export_data, &args.julia_path) {
+        (Some(out), _, _) => out,
+        (None, Some(path), _) => return export_data(&viewport, args, options, path),
+        (None, None, Some(path)) => return export_julia_morph(&viewport, args, options, path, seed),
+        (None, None, None) => return export_zoom(&viewport, args, options, seed),
+    };
+    let format = match ImageFormat::from_path(out) {
+        Ok(format) if format.writing_enabled() => format,
+        _ => {
+            eprintln!("can't tell which image format to write from `{}`", out.display());
+            return EXIT_BAD_ARGS;
+        }
+    };
+
+    let background = match load_background(options) {
+        Ok(background) => background,
+        Err(err) => {
+            eprintln!("{err}");
+            return EXIT_BAD_ARGS;
+        }
+    };
+    let gpu = match Gpu::create(args, options, background.as_ref()) {
+        Ok(gpu) => gpu,
+        Err(err) => {
+            eprintln!("GPU render failed: {err}");
+            return EXIT_RENDER_FAILED;
+        }
+    };
+    let renderer = gpu.as_ref().map(|gpu| &gpu.renderer);
+    let table = palette.build();
+    let shading = shading(args, options, &table, background.as_ref());
+    let depth = options.bit_depth;
+    let still = Frame { view: viewport, iterations: args.iter, julia: args.julia.as_ref().map(|c| (c[0], c[1])) };
+
+    // PNGs are encoded as the rows come in, so even posters that wouldn't fit
+    // in memory uncompressed can be rendered
+    let mut job = Job::new("render", "tiles", 0);
+    if format == ImageFormat::Png {
+        let metadata = ViewMetadata::new(&viewport, args.iter, Fractal::from_julia(still.julia), args.color_mode, palette).text_chunk();
+        let mut stream = match PngStream::create(out, width, height, depth, &[metadata]) {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("failed to render {}: {err}", out.display());
+                return EXIT_RENDER_FAILED;
+            }
+        };
+        job.wrote(out);
+        let rendered = render_bands(renderer, &still, &shading, (args.ssaa, depth), seed, Some(&mut job), |_, band| stream.write(band));
+        if job::interrupted() {
+            if options.keep_partial {
+                if let Err(err) = stream.finish_partial() {
+                    eprintln!("failed to write {}: {err}", out.display());
+                }
+            }
+            eprintln!("{}", job.cancel(options.keep_partial));
+            return EXIT_INTERRUPTED;
+        }
+        return match rendered.and_then(|()| stream.finish()) {
+            Ok(()) => {
+                report_done(out, renderer.is_some(), &job);
+                0
+            }
+            Err(err) => {
+                eprintln!("failed to render {}: {err}", out.display());
+                EXIT_RENDER_FAILED
+            }
+        };
+    }
+
+    if depth == BitDepth::Sixteen && format != ImageFormat::Tiff {
+        eprintln!("16-bit output needs a .png or .tif file");
+        return EXIT_BAD_ARGS;
+    }
+    let mut image = depth.blank(width, height);
+    let rendered = render_bands(renderer, &still, &shading, (args.ssaa, depth), seed, Some(&mut job), |y, band| {
+        export::paste(&mut image, &band, 0, y);
+        Ok(())
+    });
+    // nothing is written before the end, so a partial image is saved only if asked for
+    if job::interrupted() && !options.keep_partial {
+        eprintln!("{}", job.cancel(false));
+        return EXIT_INTERRUPTED;
+    }
+    if let Some(err) = rendered.err().filter(|_| !job::interrupted()) {
+        eprintln!("render failed: {err}");
+        return EXIT_RENDER_FAILED;
+    }
+
+    // formats without an alpha channel refuse RGBA buffers
+    let image = match format {
+        ImageFormat::Jpeg | ImageFormat::Pnm | ImageFormat::Farbfeld => DynamicImage::ImageRgb8(image.to_rgb8()),
+        _ => image,
+    };
+    match image.save_with_format(out, format) {
+        Ok(()) if job::interrupted() => {
+            eprintln!("{}", job.cancel(true));
+            EXIT_INTERRUPTED
+        }
+        Ok(()) => {
+            report_done(out, renderer.is_some(), &job);
+            0
+        }
+        Err(err) => {
+            eprintln!("failed to write {}: {err}", out.display());
+            EXIT_RENDER_FAILED
+        }
+    }
+}
+
+/// `rendered frame.png on 8 threads in 1s 234ms`, once a still is written.
+fn report_done(out: &Path, gpu: bool, job: &Job) {
+    let on = match rayon::current_num_threads() {
+        _ if gpu => "the GPU".to_string(),
+        1 => "1 thread".to_string(),
+        threads => format!("{threads} threads"),
+    };
+    eprintln!("rendered {} on {on} in {}", out.display(), job.elapsed_precise());
+}
+
+/// One 8-bit still of `viewport` in memory, for `--serve`, with the GPU
+/// when `gpu` is given and the CPU otherwise.
+#[cfg(feature = "serve")]
+pub fn render_image(
+    gpu: Option<&Renderer>,
+    viewport: &Viewport,
+    iterations: i32,
+    palette: &PaletteTable,
+    ssaa: i32,
+    seed: u32,
+) -> Result<DynamicImage, String> {
+    let shading = cpu::Shading {
+        palette,
+        mode: ColorMode::EscapeTime,
+        transparent_interior: false,
+        background: None,
+        morph: 1.,
+        slow_tint: None,
+        lighting: None,
+        color_span: None,
+        log_polar: false,
+        tone: Tone::default(),
+    };
+    let still = Frame { view: *viewport, iterations, julia: None };
+    let mut image = BitDepth::Eight.blank(viewport.width, viewport.height);
+    render_bands(gpu, &still, &shading, (ssaa, BitDepth::Eight), seed, None, |y, band| {
+        export::paste(&mut image, &band, 0, y);
+        Ok(())
+    })?;
+    Ok(image)
+}
+
+fn export_data(viewport: &Viewport, args: &RenderArgs, options: &Options, path: &Path) -> i32 {
+    let Some(format) = DataFormat::from_path(path) else {
+        eprintln!("`{}` should end in .npy, .f32, .exr or .csv", path.display());
+        return EXIT_BAD_ARGS;
+    };
+    let channels = &args.data_channels;
+    if channels.len() > format.max_channels() {
+        eprintln!("`{}` holds at most {} of the {} channels asked for", path.display(), format.max_channels(), channels.len());
+        return EXIT_BAD_ARGS;
+    }
+
+    let (samples, renderer) = if args.gpu {
+        match raw_samples_gpu(viewport, args, options) {
+            Ok(samples) => (samples, "gpu"),
+            Err(err) => {
+                eprintln!("GPU render failed: {err}");
+                return EXIT_RENDER_FAILED;
+            }
+        }
+    } else {
+        (cpu::render_raw(viewport, args.iter), "cpu")
+    };
+    let values: Vec<f32> = samples
+        .iter()
+        .flat_map(|&sample| channels.iter().map(move |channel| channel.of(sample)))
+        .collect();
+
+    match data::write(path, format, &DataHeader::new(viewport, args.iter, renderer, channels), &values) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("failed to write {}: {err}", path.display());
+            EXIT_RENDER_FAILED
+        }
+    }
+}
+
+fn export_contours(viewport: &Viewport, args: &RenderArgs, path: &Path) -> i32 {
+    let levels = if args.contour_levels.is_empty() { vec![args.iter as f64] } else { args.contour_levels.clone() };
+    let strokes = &args.contour_stroke;
+    if strokes.len() != 1 && strokes.len() != levels.len() {
+        eprintln!("--contour-stroke needs one width or {} (one per level), not {}", levels.len(), strokes.len());
+        return EXIT_BAD_ARGS;
+    }
+
+    let (width, height) = args.size;
+    let grid_size = |edge: u32| ((edge as f64 * args.contour_resolution).round() as u32).clamp(2, viewport::MAX_EDGE);
+    let (grid_width, grid_height) = (grid_size(width), grid_size(height));
+    let grid = Viewport { width: grid_width, height: grid_height, ..*viewport };
+    // interior points lie above every level, whatever the iteration count
+    let above = levels.iter().fold(args.iter as f64, |top, &level| top.max(level)) + 1.;
+    let values: Vec<f64> = cpu::render_raw(&grid, args.iter)
+        .iter()
+        .map(|&[escape, smooth, _, _]| if escape < 0. { above } else { smooth as f64 })
+        .collect();
+    let field = contour::Field { values: &values, width: grid_width as usize, height: grid_height as usize };
+
+    // grid samples sit at pixel centers of the grid, scaled to the image
+    let (sx, sy) = (width as f64 / grid_width as f64, height as f64 / grid_height as f64);
+    let layers: Vec<contour::Layer> = levels
+        .iter()
+        .enumerate()
+        .map(|(index, &level)| {
+            let contours = field
+                .trace(level)
+                .into_iter()
+                .map(|traced| {
+                    let points: Vec<_> = traced.points.iter().map(|&(x, y)| ((x + 0.5) * sx, (y + 0.5) * sy)).collect();
+                    let points = if traced.closed {
+                        // simplified with the start repeated at the end, so it stays put
+                        let mut ring = points.clone();
+                        ring.push(points[0]);
+                        let mut simplified = contour::simplify(&ring, args.contour_tolerance);
+                        simplified.pop();
+                        simplified
+                    } else {
+                        contour::simplify(&points, args.contour_tolerance)
+                    };
+                    contour::Contour { points, closed: traced.closed }
+                })
+                // loops around a sample or two simplify down to nothing
+                .filter(|simplified| simplified.points.len() >= if simplified.closed { 3 } else { 2 })
+                .collect();
+            contour::Layer { level, stroke: strokes[index.min(strokes.len() - 1)], contours }
+        })
+        .collect();
+
+    let description = format!(
+        "Mandelbrot contours at iterations {:?}, center {} {}, zoom {}, {} iterations",
+        levels, viewport.center.0, viewport.center.1, viewport.scale, args.iter
+    );
+    let svg = contour::svg(width, height, &description, &layers);
+    if let Err(err) = fs::write(path, svg) {
+        eprintln!("failed to write {}: {err}", path.display());
+        return EXIT_RENDER_FAILED;
+    }
+    let count: usize = layers.iter().map(|layer| layer.contours.len()).sum();
+    eprintln!("traced {count} contours over a {grid_width}x{grid_height} grid into {}", path.display());
+    0
+}
+
+fn raw_samples_gpu(viewport: &Viewport, args: &RenderArgs, options: &Options) -> Result<Vec<[f32; 4]>, String> {
+    let _context = offscreen::create()?;
+
+    let renderer = Renderer::new()?;
+    renderer.set_iterations_per_draw(Some(options.iterations_per_draw));
+    let target = Framebuffer::new_float(viewport.width as i32, viewport.height as i32)?;
+    target.bind();
+    renderer.draw_raw(&DrawParams {
+        view: viewport.uniforms(),
+        substeps: args.iter,
+        ssaa: 1,
+        seed: 0,
+        time: 0.,
+        julia: None,
+        morph: 1.,
+        transparent_interior: false,
+        color_mode: ColorMode::EscapeTime,
+        color_span: None,
+        checkerboard: false,
+        backdrop: None,
+        premultiplied: false,
+        slow_tint: None,
+        lighting: None,
+        tone: Tone::default(),
+        mapping: CoordinateMapping::FragCoord,
+        log_polar: false,
+        background: None,
+    });
+    Ok(renderer::read_values(target.width, target.height))
+}
+
+/// One image to render: the camera, and the Julia constant when it isn't the
+/// Mandelbrot set.
+struct Frame {
+    view: Viewport,
+    iterations: i32,
+    julia: Option<(f64, f64)>,
+}
+
+/// The renderer for `--gpu` with the offscreen context it lives in, dropped
+/// in that order.
+struct Gpu {
+    renderer: Renderer,
+    _context: OffscreenContext,
+}
+
+impl Gpu {
+    fn create(args: &RenderArgs, options: &Options, background: Option<&Background>) -> Result<Option<Gpu>, String> {
+        if !args.gpu {
+            return Ok(None);
+        }
+        let context = offscreen::create()?;
+        let renderer = Renderer::new()?;
+        renderer.set_iterations_per_draw(Some(options.iterations_per_draw));
+        if let Some(background) = background {
+            renderer.set_background(&background.image);
+        }
+        Ok(Some(Gpu { renderer, _context: context }))
+    }
+}
+
+/// The image of `--background`, if one was given.
+fn load_background(options: &Options) -> Result<Option<Background>, String> {
+    let style = BackgroundStyle { fit: options.background_fit, blend: options.blend };
+    options.background.as_deref().map(|path| Background::load(path, style)).transpose()
+}
+
+/// How `args` color the fractal, lit as a saved session was or else if
+/// `--relief` asks; a background always shows through the interior.
+fn shading<'a>(args: &RenderArgs, options: &Options, palette: &'a PaletteTable, background: Option<&'a Background>) -> cpu::Shading<'a> {
+    cpu::Shading {
+        palette,
+        mode: args.color_mode,
+        transparent_interior: args.transparent || background.is_some(),
+        background,
+        morph: 1.,
+        slow_tint: None,
+        lighting: args.lighting.or_else(|| options.relief.then(|| options.lighting())),
+        color_span: args.color_span,
+        log_polar: options.log_polar,
+        tone: args.tone,
+    }
+}
+
+/// The path of `--keyframes`, or else the zoom from the view to
+/// `--to-center`/`--to-zoom`, with iterations growing from `--iter` at the end.
+fn video_path(viewport: &Viewport, args: &RenderArgs, options: &Options) -> Result<KeyframePath, String> {
+    if let Some(path) = &options.keyframes {
+        if args.decades_per_second.is_some() {
+            return Err("--decades-per-second paces a --to-zoom, not --keyframes".to_string());
+        }
+        return KeyframePath::new(keyframes::load(path)?, options.animation_seconds);
+    }
+    let Some(to_zoom) = args.to_zoom else {
+        return Err("--export-video needs --to-zoom or --keyframes".to_string());
+    };
+    let mut to = Keyframe::from_view(viewport, args.iter);
+    to.scale = to_zoom;
+    if let Some(center) = &args.to_center {
+        to.center = (center[0], center[1]);
+    }
+    let from = Keyframe::from_view(viewport, animation::auto_iterations(args.iter, to_zoom, viewport.scale));
+    let seconds = match args.decades_per_second {
+        Some(rate) => zoom_decades(viewport.scale, to_zoom)? / rate,
+        None => options.animation_seconds,
+    };
+    KeyframePath::new(vec![from, to], seconds)
+}
+
+/// Factors of ten between two zooms, for `--decades-per-second`.
+fn zoom_decades(from: f64, to: f64) -> Result<f64, String> {
+    let decades = (from / to).log10().abs();
+    if decades < 1e-6 {
+        return Err("--decades-per-second needs a --to-zoom other than --zoom".to_string());
+    }
+    Ok(decades)
+}
+
+/// What a `--decades-per-second` zoom comes to, said before rendering it.
+fn describe_pace(viewport: &Viewport, args: &RenderArgs, options: &Options, rate: f64, frame_count: usize) -> String {
+    let to_zoom = args.to_zoom.expect("required by --decades-per-second");
+    let decades = (viewport.scale / to_zoom).log10().abs();
+    let fps = options.animation_fps;
+    let factor = (viewport.scale / to_zoom).max(to_zoom / viewport.scale).powf(1. / (frame_count - 1) as f64);
+    let way = if to_zoom < viewport.scale { "in" } else { "out" };
+    let pixels = (args.size.0 as u64 * args.size.1 as u64 * frame_count as u64) as f64;
+    let bytes = match (&args.export_video, options.bit_depth) {
+        (Some(_), _) => pixels * VIDEO_BYTES_PER_PIXEL,
+        (None, BitDepth::Eight) => pixels * PNG_BYTES_PER_PIXEL,
+        (None, BitDepth::Sixteen) => pixels * PNG_BYTES_PER_PIXEL * 2.,
+    };
+    format!(
+        "{decades:.2} decades at {rate} a second: {frame_count} frames, {:.1}s at {fps} fps, each zoomed {way} {factor:.4}x \
+         from the last; roughly {:.1} MB to write",
+        (frame_count - 1) as f64 / fps,
+        bytes / 1e6
+    )
+}
+
+/// Renders the zoom animation into `--export-video` or `--export-frames`.
+/// Returns ffmpeg's exit status when it fails.
+fn export_zoom(viewport: &Viewport, args: &RenderArgs, options: &Options, seed: u32) -> i32 {
+    let zoom = match video_path(viewport, args, options) {
+        Ok(zoom) => zoom,
+        Err(err) => {
+            eprintln!("{err}");
+            return EXIT_BAD_ARGS;
+        }
+    };
+    let frame_count = match args.decades_per_second {
+        // a frame for every 1/fps of the zoom, ends included, so the pace holds between frames
+        Some(_) => ((zoom.duration() * options.animation_fps).round() as usize + 1).max(2),
+        None => zoom.frame_count(options.animation_fps),
+    };
+    if let Some(rate) = args.decades_per_second {
+        eprintln!("{}", describe_pace(viewport, args, options, rate, frame_count));
+    }
+    let (width, height) = (viewport.width, viewport.height);
+    export_frames(args, options, seed, frame_count, |index| {
+        let keyframe = zoom.at(zoom.frame_time(index, frame_count));
+        Frame { view: keyframe.view(width, height), iterations: keyframe.iterations, julia: None }
+    })
+}
+
+/// Renders the Julia constant moving along `--julia-path` with the camera
+/// fixed, then writes the constant of every frame to a sidecar: `frames.json`
+/// with the frames, `<video>.json` next to a video.
+fn export_julia_morph(viewport: &Viewport, args: &RenderArgs, options: &Options, path: &Path, seed: u32) -> i32 {
+    let julia_path = match julia::load(path) {
+        Ok(julia_path) => julia_path,
+        Err(err) => {
+            eprintln!("{err}");
+            return EXIT_BAD_ARGS;
+        }
+    };
+    let frame_count = ((options.animation_seconds * options.animation_fps).round() as usize).max(2);
+    let progress = |index: usize| index as f64 / (frame_count - 1) as f64;
+
+    let frames: Vec<FrameInfo> = (0..frame_count)
+        .map(|index| {
+            let c = julia_path.c_at(progress(index));
+            FrameInfo {
+                file: args.export_frames.as_ref().map(|_| frame_file(index)),
+                time: progress(index) * options.animation_seconds,
+                c: [c.0, c.1],
+            }
+        })
+        .collect();
+    let sidecar_path = match (&args.export_frames, &args.export_video) {
+        (Some(dir), _) => dir.join("frames.json"),
+        (None, Some(video)) => data::sidecar_path(video),
+        (None, None) => unreachable!("checked by run"),
+    };
+    let sidecar = julia::Sidecar {
+        width: viewport.width,
+        height: viewport.height,
+        fps: options.animation_fps,
+        ssaa: args.ssaa,
+        center: [viewport.center.0, viewport.center.1],
+        scale: viewport.scale,
+        rotation: viewport.rotation,
+        iterations: args.iter,
+        path: &julia_path,
+        frames: &frames,
+    };
+    let status = export_frames(args, options, seed, frame_count, |index| {
+        Frame { view: *viewport, iterations: args.iter, julia: Some(julia_path.c_at(progress(index))) }
+    });
+    if status == 0 {
+        if let Err(err) = julia::write_sidecar(&sidecar_path, &sidecar) {
+            eprintln!("{err}");
+            return EXIT_RENDER_FAILED;
+        }
+    }
+    status
+}
+
+/// Name of frame `index` (from 0) in an `--export-frames` directory.
+fn frame_file(index: usize) -> String {
+    format!("frame_{:06}.png", index + 1)
+}
+
+/// Renders `frame_count` frames, described by `frame_at`, and pipes them into
+/// ffmpeg for `--export-video` or saves them as numbered PNGs in
+/// `--export-frames`. Returns ffmpeg's exit status when it fails.
+fn export_frames(args: &RenderArgs, options: &Options, seed: u32, frame_count: usize, frame_at: impl Fn(usize) -> Frame) -> i32 {
+    let background = match load_background(options) {
+        Ok(background) => background,
+        Err(err) => {
+            eprintln!("{err}");
+            return EXIT_BAD_ARGS;
+        }
+    };
+    let gpu = match Gpu::create(args, options, background.as_ref()) {
+        Ok(gpu) => gpu,
+        Err(err) => {
+            eprintln!("GPU render failed: {err}");
+            return EXIT_RENDER_FAILED;
+        }
+    };
+    let renderer = gpu.as_ref().map(|gpu| &gpu.renderer);
+    let palette = Palette::grayscale();
+    let table = palette.build();
+    let shading = shading(args, options, &table, background.as_ref());
+    let (width, height) = args.size;
+    let mut job = Job::new("export", "frames", frame_count);
+    let mut encoder = match &args.export_video {
+        Some(path) => match VideoEncoder::spawn(path, width, height, options.animation_fps, &args.ffmpeg_args) {
+            Ok(encoder) => {
+                job.wrote(path);
+                Some(encoder)
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                return EXIT_RENDER_FAILED;
+            }
+        },
+        None => None,
+    };
+    if let Some(dir) = &args.export_frames {
+        if let Err(err) = job.create_dir(dir) {
+            eprintln!("{err}");
+            return EXIT_RENDER_FAILED;
+        }
+    }
+
+    // ffmpeg is fed 8-bit RGBA whatever the depth, so only frames keep 16 bits
+    let depth = if encoder.is_some() { BitDepth::Eight } else { options.bit_depth };
+    let mut image = depth.blank(width, height);
+    for index in 0..frame_count {
+        let frame = frame_at(index);
+        let rendered = render_bands(renderer, &frame, &shading, (args.ssaa, depth), seed, None, |y, band| {
+            export::paste(&mut image, &band, 0, y);
+            Ok(())
+        });
+        if job::interrupted() {
+            eprintln!();
+            // ffmpeg is let finish what it has, so a kept video still plays
+            if let Some(encoder) = encoder {
+                let _ = encoder.finish();
+            }
+            eprintln!("{}", job.cancel(options.keep_partial));
+            return EXIT_INTERRUPTED;
+        }
+        let written = rendered.and_then(|()| match (&mut encoder, &args.export_frames) {
+            (Some(encoder), _) => encoder.write_frame(image.as_rgba8().expect("8-bit for video")),
+            (None, Some(dir)) => {
+                let saved = ViewMetadata::new(&frame.view, frame.iterations, Fractal::from_julia(frame.julia), args.color_mode, &palette);
+                let path = dir.join(frame_file(index));
+                job.wrote(&path);
+                metadata::save_png(&path, &image, &[saved.text_chunk()])
+            }
+            (None, None) => unreachable!("checked by run"),
+        });
+        if let Err(err) = written {
+            eprintln!("\nexport failed: {err}");
+            // if ffmpeg is what failed, its status is the more useful one
+            return match encoder.map(VideoEncoder::finish) {
+                Some(Ok(status)) if !status.success() => status.code().unwrap_or(EXIT_RENDER_FAILED),
+                _ => EXIT_RENDER_FAILED,
+            };
+        }
+        job.set_progress(index + 1, frame_count);
+        if let Some(status) = job.report() {
+            eprint!("\r{status:<60}");
+        }
+    }
+    eprintln!();
+
+    match encoder.map(VideoEncoder::finish) {
+        None => 0,
+        Some(Ok(status)) if status.success() => 0,
+        Some(Ok(status)) => {
+            eprintln!("ffmpeg failed ({status})");
+            status.code().unwrap_or(EXIT_RENDER_FAILED)
+        }
+        Some(Err(err)) => {
+            eprintln!("{err}");
+            EXIT_RENDER_FAILED
+        }
+    }
+}
+
+/// `--export-tiles`: every tile of every level asked for, shallowest first,
+/// then the files that describe them.
+fn export_tiles(viewport: &Viewport, palette: &Palette, args: &RenderArgs, options: &Options, dir: &Path, seed: u32) -> i32 {
+    if args.min_level > args.max_level {
+        eprintln!("--min-level {} is deeper than --max-level {}", args.min_level, args.max_level);
+        return EXIT_BAD_ARGS;
+    }
+    let background = match load_background(options) {
+        Ok(background) => background,
+        Err(err) => {
+            eprintln!("{err}");
+            return EXIT_BAD_ARGS;
+        }
+    };
+    let gpu = match Gpu::create(args, options, background.as_ref()) {
+        Ok(gpu) => gpu,
+        Err(err) => {
+            eprintln!("GPU render failed: {err}");
+            return EXIT_RENDER_FAILED;
+        }
+    };
+    let renderer = gpu.as_ref().map(|gpu| &gpu.renderer);
+    let table = palette.build();
+    let shading = shading(args, options, &table, background.as_ref());
+    let julia = args.julia.as_ref().map(|c| (c[0], c[1]));
+
+    let pyramid = Pyramid { center: viewport.center, zoom: viewport.scale, tile: args.tile_size, window: args.size };
+    // deeper levels get more iterations, as zoom videos do
+    let levels: Vec<Level> = (args.min_level..=args.max_level)
+        .map(|level| pyramid.level(level, animation::auto_iterations(args.iter, pyramid.zoom, pyramid.scale(level))))
+        .collect();
+    let total = levels.iter().map(Level::count).sum();
+    let mut job = Job::new("export", "tiles", total);
+    if let Err(err) = job.create_dir(dir) {
+        eprintln!("{err}");
+        return EXIT_RENDER_FAILED;
+    }
+    let mut image = BitDepth::Eight.blank(args.tile_size, args.tile_size);
+    let mut done = 0;
+    for level in &levels {
+        for tile in level.tiles() {
+            let frame = Frame { view: pyramid.view(level.level, tile), iterations: level.iterations, julia };
+            let path = tileset::tile_path(dir, level.level, tile);
+            let column = path.parent().expect("tiles are in a column directory");
+            let written = job
+                .create_dir(column.parent().expect("columns are in a level directory"))
+                .and_then(|()| job.create_dir(column))
+                .and_then(|()| {
+                    render_bands(renderer, &frame, &shading, (args.ssaa, BitDepth::Eight), seed, None, |y, band| {
+                        export::paste(&mut image, &band, 0, y);
+                        Ok(())
+                    })
+                })
+                .and_then(|()| {
+                    let saved = ViewMetadata::new(&frame.view, frame.iterations, Fractal::from_julia(julia), args.color_mode, palette);
+                    job.wrote(&path);
+                    metadata::save_png(&path, &image, &[saved.text_chunk()])
+                });
+            if job::interrupted() {
+                eprintln!();
+                eprintln!("{}", job.cancel(options.keep_partial));
+                return EXIT_INTERRUPTED;
+            }
+            if let Err(err) = written {
+                eprintln!("\nexport failed: {err}");
+                return EXIT_RENDER_FAILED;
+            }
+            done += 1;
+            job.set_progress(done, total);
+            if let Some(status) = job.report() {
+                eprint!("\r{status:<60}");
+            }
+        }
+    }
+    eprintln!();
+
+    job.wrote(dir.join("tiles.json"));
+    job.wrote(dir.join("index.html"));
+    if let Err(err) = tileset::write_viewer(dir, &pyramid, &levels) {
+        eprintln!("export failed: {err}");
+        return EXIT_RENDER_FAILED;
+    }
+    eprintln!("rendered {total} tiles of levels {} to {} into {} in {}", args.min_level, args.max_level, dir.display(), job.elapsed());
+    0
+}
+
+/// Renders the view tile by tile, on the GPU when given a renderer, handing
+/// each finished row of tiles to `band` along with its first pixel row.
+/// Tiling keeps the size from being capped by the GPU's texture limit.
+fn render_bands(
+    gpu: Option<&Renderer>,
+    frame: &Frame,
+    shading: &cpu::Shading,
+    (ssaa, depth): (i32, BitDepth),
+    seed: u32,
+    mut job: Option<&mut Job>,
+    mut band: impl FnMut(u32, DynamicImage) -> Result<(), String>,
+) -> Result<(), String> {
+    let (viewport, iterations) = (&frame.view, frame.iterations);
+    let mut export = Export::new(viewport, viewport.width, viewport.height, ssaa, depth);
+    while let Some(tile) = export.next_tile() {
+        if job::interrupted() {
+            if job.is_some() {
+                eprintln!();
+            }
+            return Err("interrupted".to_string());
+        }
+        let pixels = match gpu {
+            Some(renderer) => {
+                let params = DrawParams {
+                    view: viewport.tile_uniforms(tile.rect),
+                    substeps: iterations,
+                    ssaa,
+                    seed,
+                    time: 0.,
+                    julia: frame.julia,
+                    morph: shading.morph,
+                    transparent_interior: shading.transparent_interior,
+                    color_mode: shading.mode,
+                    color_span: shading.color_span,
+                    checkerboard: false,
+                    backdrop: None,
+                    premultiplied: false,
+                    slow_tint: None,
+                    lighting: shading.lighting,
+                    tone: shading.tone,
+                    mapping: CoordinateMapping::FragCoord,
+                    log_polar: shading.log_polar,
+                    background: shading.background.map(|background| background.style),
+                };
+                let (width, height) = (tile.rect.width as i32, tile.rect.height as i32);
+                match depth {
+                    BitDepth::Eight => renderer::render_offscreen(renderer, &params, width, height)?.into(),
+                    BitDepth::Sixteen => renderer::render_offscreen_16(renderer, &params, width, height)?.into(),
+                }
+            }
+            None => match depth {
+                BitDepth::Eight => cpu::render_tile(viewport, tile.rect, iterations, ssaa, seed, frame.julia, shading).into(),
+                BitDepth::Sixteen => {
+                    cpu::render_tile_16(viewport, tile.rect, iterations, ssaa, seed, frame.julia, shading).into()
+                }
+            },
+        };
+        if let Some((y, pixels)) = export.store(tile, &pixels) {
+            band(y, pixels)?;
+        }
+        let (done, total) = export.progress();
+        if let Some(job) = job.as_deref_mut().filter(|_| total > 1) {
+            job.set_progress(done, total);
+            if let Some(status) = job.report() {
+                // padded over the longer line an estimate can leave behind
+                eprint!("\r{status:<60}");
+            }
+            if done == total {
+                eprintln!();
+            }
+        }
+    }
+    Ok(())
+}
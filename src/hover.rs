@@ -0,0 +1,69 @@
+// The escape iteration under the cursor (F8), in a tooltip that follows it.
+// The pixel is read back from the renderer's raw-iteration output, one at a
+// time: again when the cursor has moved a few pixels or the view changed, and
+// at most every `MIN_INTERVAL`, so hovering doesn't cost a readback per frame.
+
+use std::time::{Duration, Instant};
+
+use imgui::Ui;
+
+use crate::viewport::Viewport;
+
+/// Cursor movement, in pixels, that calls for a new readback.
+const MIN_MOVE: f64 = 3.;
+const MIN_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What a readback depends on besides the cursor.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Probed {
+    pub viewport: Viewport,
+    pub iterations: i32,
+    pub julia: Option<(f64, f64)>,
+}
+
+/// The last readback: cursor pixel, what it was read with and when.
+struct Reading {
+    cursor: (f64, f64),
+    probed: Probed,
+    when: Instant,
+    /// Escape iteration, smooth iteration, |z| and interior mask, as `cpu::raw_sample`.
+    sample: [f32; 4],
+}
+
+pub struct Hover {
+    last: Option<Reading>,
+}
+
+impl Hover {
+    pub fn new() -> Hover {
+        Hover { last: None }
+    }
+
+    /// Whether the value at `cursor` should be read back again.
+    pub fn stale(&self, cursor: (f64, f64), probed: &Probed) -> bool {
+        let Some(last) = &self.last else {
+            return true;
+        };
+        let moved = (cursor.0 - last.cursor.0).hypot(cursor.1 - last.cursor.1) > MIN_MOVE;
+        (moved || last.probed != *probed) && last.when.elapsed() >= MIN_INTERVAL
+    }
+
+    pub fn store(&mut self, cursor: (f64, f64), probed: Probed, sample: [f32; 4]) {
+        self.last = Some(Reading { cursor, probed, when: Instant::now(), sample });
+    }
+
+    /// The tooltip, next to the mouse wherever it is.
+    pub fn draw(&self, ui: &Ui) {
+        let Some(Reading { probed, sample: [escape, smooth, modulus, _], .. }) = self.last else {
+            return;
+        };
+        ui.tooltip(|| {
+            if escape < 0. {
+                ui.text(format!("inside: no escape in {} iterations", probed.iterations));
+            } else {
+                ui.text(format!("escapes at iteration {escape} (smooth {smooth:.2})"));
+            }
+            ui.text(format!("|z| = {modulus:.3}"));
+        });
+    }
+}
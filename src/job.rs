@@ -0,0 +1,169 @@
+// Progress and cancellation of the long exports: posters (P and `render
+// --out`), frame sequences (L and `--export-frames`) and videos. Each runs as
+// a `Job` that knows how far along it is, can estimate the time left from
+// the work done so far, and remembers the files it wrote so a cancelled
+// export doesn't leave half of itself behind (unless `--keep-partial`).
+//
+// In the window, Escape cancels and the progress shows in the title bar;
+// headless, Ctrl+C cancels at the next tile. A second Ctrl+C kills the
+// process as usual.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Least time between two progress lines.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Job {
+    /// What is running, e.g. "export" or "frames".
+    name: String,
+    /// What `done` and `total` count, e.g. "tiles".
+    unit: &'static str,
+    done: usize,
+    total: usize,
+    /// Of the whole job, which can be ahead of `done / total` when the units
+    /// are split into smaller steps (frames into tiles).
+    fraction: f64,
+    started: Instant,
+    reported: Option<Instant>,
+    /// What it wrote, removed again on cancel.
+    files: Vec<PathBuf>,
+    /// Directories it created, removed on cancel if they are empty by then.
+    dirs: Vec<PathBuf>,
+}
+
+impl Job {
+    pub fn new(name: impl Into<String>, unit: &'static str, total: usize) -> Job {
+        Job {
+            name: name.into(),
+            unit,
+            done: 0,
+            total,
+            fraction: 0.,
+            started: Instant::now(),
+            reported: None,
+            files: Vec::new(),
+            dirs: Vec::new(),
+        }
+    }
+
+    /// Records a file the job wrote, or is writing.
+    pub fn wrote(&mut self, path: impl Into<PathBuf>) {
+        self.files.push(path.into());
+    }
+
+    /// Creates `dir` if it doesn't exist yet, recording it if it didn't.
+    pub fn create_dir(&mut self, dir: impl Into<PathBuf>) -> Result<(), String> {
+        let dir = dir.into();
+        if dir.is_dir() {
+            return Ok(());
+        }
+        fs::create_dir_all(&dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+        self.dirs.push(dir);
+        Ok(())
+    }
+
+    /// `done` of the `total` units, and `fraction` of the whole job.
+    pub fn update(&mut self, done: usize, fraction: f64) {
+        self.done = done;
+        self.fraction = fraction.clamp(0., 1.);
+    }
+
+    /// `done` of `total` units, all the same size.
+    pub fn set_progress(&mut self, done: usize, total: usize) {
+        self.total = total;
+        self.update(done, done as f64 / total.max(1) as f64);
+    }
+
+    /// Estimated from how long the work done so far took.
+    pub fn remaining(&self) -> Option<Duration> {
+        if self.fraction <= 0. {
+            return None;
+        }
+        let elapsed = self.started.elapsed().as_secs_f64();
+        Some(Duration::from_secs_f64(elapsed * (1. - self.fraction) / self.fraction))
+    }
+
+    /// `export: 12/64 tiles (18%), about 1m 20s left`
+    pub fn status(&self) -> String {
+        let mut status = format!("{}: {}/{} {} ({:.0}%)", self.name, self.done, self.total, self.unit, self.fraction * 100.);
+        if let Some(remaining) = self.remaining().filter(|_| self.fraction < 1.) {
+            status += &format!(", about {} left", seconds(remaining));
+        }
+        status
+    }
+
+    /// The status, at most once every `REPORT_INTERVAL` and always at the end.
+    pub fn report(&mut self) -> Option<String> {
+        let due = self.reported.is_none_or(|at| at.elapsed() >= REPORT_INTERVAL);
+        if !due && self.fraction < 1. {
+            return None;
+        }
+        self.reported = Some(Instant::now());
+        Some(self.status())
+    }
+
+    /// How long it ran, for the line announcing that it is done.
+    pub fn elapsed(&self) -> String {
+        seconds(self.started.elapsed())
+    }
+
+    /// Like `elapsed`, to the millisecond, for jobs that can take under a second.
+    pub fn elapsed_precise(&self) -> String {
+        humantime::format_duration(Duration::from_millis(self.started.elapsed().as_millis() as u64)).to_string()
+    }
+
+    /// Stops the job: removes what it wrote unless `keep_partial`, and says
+    /// what happened.
+    pub fn cancel(self, keep_partial: bool) -> String {
+        let progress = format!("{} cancelled after {}/{} {}", self.name, self.done, self.total, self.unit);
+        if keep_partial {
+            return format!("{progress}, kept what was written");
+        }
+        for file in self.files.iter().rev() {
+            let _ = fs::remove_file(file);
+        }
+        for dir in self.dirs.iter().rev() {
+            // only if nothing else ended up in it
+            let _ = fs::remove_dir(dir);
+        }
+        format!("{progress}, removed its files")
+    }
+}
+
+/// Whole seconds, as `humantime` writes them (`1m 20s`).
+fn seconds(duration: Duration) -> String {
+    humantime::format_duration(Duration::from_secs(duration.as_secs_f64().round() as u64)).to_string()
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl+C was pressed since `catch_interrupt`.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Makes Ctrl+C set `interrupted` instead of killing the process, once: the
+/// handler puts the default back, so pressing it again still does.
+pub fn catch_interrupt() {
+    unsafe {
+        signal(SIGINT, on_interrupt as extern "C" fn(i32) as usize);
+    }
+}
+
+// the C runtime's, on Unix and Windows alike
+const SIGINT: i32 = 2;
+const SIG_DFL: usize = 0;
+
+extern "C" {
+    fn signal(signum: i32, handler: usize) -> usize;
+}
+
+extern "C" fn on_interrupt(_: i32) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+    unsafe {
+        signal(SIGINT, SIG_DFL);
+    }
+}
@@ -0,0 +1,110 @@
+// Julia morphs: the camera stays put while the Julia constant moves along a
+// path, which can be a circle, a line between two constants or a trace of the
+// cursor recorded over the Mandelbrot set in the window (F6). Paths are JSON:
+//
+//     {"kind": "circle", "center": [-0.75, 0.0], "radius": 0.1}
+//     {"kind": "line", "from": [-0.8, 0.156], "to": [0.285, 0.01]}
+//     {"kind": "trace", "points": [[-0.75, 0.1], [-0.74, 0.12], ...]}
+//
+// `render --julia-path` walks one, and writes the constant of every frame to a
+// sidecar so any frame can be rendered again as a still with `--julia`.
+
+use std::f64::consts::TAU;
+use std::fs::File;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JuliaPath {
+    /// Around `center`, starting on its right, `turns` times counterclockwise.
+    Circle {
+        center: (f64, f64),
+        radius: f64,
+        #[serde(default = "one_turn")]
+        turns: f64,
+    },
+    Line { from: (f64, f64), to: (f64, f64) },
+    /// Through every point, at constant speed along the polyline.
+    Trace { points: Vec<(f64, f64)> },
+}
+
+fn one_turn() -> f64 {
+    1.
+}
+
+#[derive(Serialize)]
+pub struct FrameInfo {
+    /// The frame's PNG, when written as frames.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    pub time: f64,
+    pub c: [f64; 2],
+}
+
+/// What `--julia-path` renders, next to the frames or the video.
+#[derive(Serialize)]
+pub struct Sidecar<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f64,
+    pub ssaa: i32,
+    pub center: [f64; 2],
+    pub scale: f64,
+    pub rotation: f64,
+    pub iterations: i32,
+    pub path: &'a JuliaPath,
+    pub frames: &'a [FrameInfo],
+}
+
+impl JuliaPath {
+    /// The constant `u` of the way along (0 to 1).
+    pub fn c_at(&self, u: f64) -> (f64, f64) {
+        let u = u.clamp(0., 1.);
+        let lerp = |a: (f64, f64), b: (f64, f64), t: f64| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+        match self {
+            JuliaPath::Circle { center, radius, turns } => {
+                let (sin, cos) = (u * turns * TAU).sin_cos();
+                (center.0 + radius * cos, center.1 + radius * sin)
+            }
+            JuliaPath::Line { from, to } => lerp(*from, *to, u),
+            JuliaPath::Trace { points } => {
+                let length = |a: (f64, f64), b: (f64, f64)| (b.0 - a.0).hypot(b.1 - a.1);
+                let total: f64 = points.windows(2).map(|w| length(w[0], w[1])).sum();
+                let mut left = u * total;
+                for w in points.windows(2) {
+                    let segment = length(w[0], w[1]);
+                    if left <= segment && segment > 0. {
+                        return lerp(w[0], w[1], left / segment);
+                    }
+                    left -= segment;
+                }
+                *points.last().expect("validated by load")
+            }
+        }
+    }
+}
+
+pub fn load(path: &Path) -> Result<JuliaPath, String> {
+    let file = File::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let loaded: JuliaPath = serde_json::from_reader(file).map_err(|err| format!("{}: {err}", path.display()))?;
+    if let JuliaPath::Trace { points } = &loaded {
+        if points.is_empty() {
+            return Err(format!("{}: a trace needs at least one point", path.display()));
+        }
+    }
+    Ok(loaded)
+}
+
+pub fn save(path: &Path, julia_path: &JuliaPath) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    serde_json::to_writer_pretty(file, julia_path).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+pub fn write_sidecar(path: &Path, sidecar: &Sidecar) -> Result<(), String> {
+    File::create(path)
+        .map_err(|err| err.to_string())
+        .and_then(|file| serde_json::to_writer_pretty(file, sidecar).map_err(|err| err.to_string()))
+        .map_err(|err| format!("{}: {err}", path.display()))
+}
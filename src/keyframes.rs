@@ -0,0 +1,282 @@
+// Keyframes for animations: what the camera looks like at a moment of the
+// video and how to get to the next one. They are marked in the window (J),
+// saved and loaded as JSON and edited with the `keyframes` subcommand:
+//
+//     {"keyframes": [{"center": [-0.5, 0.0], "scale": 1.0, "rotation": 0.0,
+//                     "iterations": 1000, "duration": 4.0, "easing": "smooth"}, ...]}
+//
+// `duration` and `easing` describe the segment to the next keyframe, so the
+// last keyframe's are ignored; without a duration the segment gets the
+// `--animation-seconds` default. Only what the explorer can change is
+// captured: the camera and the iteration count.
+
+use std::f64::consts::{PI, TAU};
+use std::fs::File;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::{KeyframesAction, KeyframesArgs};
+use crate::headless::{EXIT_BAD_ARGS, EXIT_RENDER_FAILED};
+use crate::viewport::Viewport;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    /// Constant speed, so consecutive segments join without slowing down.
+    #[default]
+    Linear,
+    /// Starts and stops gently.
+    Smooth,
+    EaseIn,
+    EaseOut,
+}
+
+impl Easing {
+    /// Maps linear progress through a segment (0 to 1) to eased progress,
+    /// keeping both ends fixed.
+    pub fn apply(self, u: f64) -> f64 {
+        let u = u.clamp(0., 1.);
+        match self {
+            Easing::Linear => u,
+            Easing::Smooth => u * u * (3. - 2. * u),
+            Easing::EaseIn => u * u,
+            Easing::EaseOut => u * (2. - u),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub center: (f64, f64),
+    /// Vertical half extent, as in `Viewport`.
+    pub scale: f64,
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default = "default_anisotropy")]
+    pub anisotropy: f64,
+    pub iterations: i32,
+    /// Seconds from this keyframe to the next.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+fn default_anisotropy() -> f64 {
+    1.
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyframeFile {
+    keyframes: Vec<Keyframe>,
+}
+
+impl Keyframe {
+    pub fn from_view(view: &Viewport, iterations: i32) -> Keyframe {
+        Keyframe {
+            center: view.center,
+            scale: view.scale,
+            rotation: view.rotation,
+            anisotropy: view.anisotropy,
+            iterations,
+            duration: None,
+            easing: Easing::default(),
+        }
+    }
+
+    /// The camera of this keyframe on a `width x height` target.
+    pub fn view(&self, width: u32, height: u32) -> Viewport {
+        Viewport {
+            center: self.center,
+            scale: self.scale,
+            rotation: self.rotation,
+            anisotropy: self.anisotropy,
+            ..Viewport::new(width, height)
+        }
+    }
+}
+
+/// The camera `u` of the way from `from` to `to` (0 and 1 give the keyframes
+/// exactly). The zoom moves on ln(scale), so every factor of two takes equal
+/// time; the center moves linearly; rotation takes the short way around; and
+/// iterations follow the zoom's progress, growing steadily per decade.
+/// Easing is up to the caller.
+pub fn interpolate(from: &Keyframe, to: &Keyframe, u: f64) -> Keyframe {
+    if u <= 0. {
+        return from.clone();
+    }
+    if u >= 1. {
+        return Keyframe { duration: from.duration, easing: from.easing, ..to.clone() };
+    }
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+
+    let (ln_from, ln_to) = (from.scale.ln(), to.scale.ln());
+    let ln_scale = lerp(ln_from, ln_to, u);
+    let zoom_progress = if (ln_to - ln_from).abs() > 1e-12 { (ln_scale - ln_from) / (ln_to - ln_from) } else { u };
+
+    let mut turn = (to.rotation - from.rotation).rem_euclid(TAU);
+    if turn > PI {
+        turn -= TAU;
+    }
+    Keyframe {
+        center: (lerp(from.center.0, to.center.0, u), lerp(from.center.1, to.center.1, u)),
+        scale: ln_scale.exp(),
+        rotation: (from.rotation + turn * u).rem_euclid(TAU),
+        anisotropy: lerp(from.anisotropy, to.anisotropy, u),
+        iterations: lerp(from.iterations as f64, to.iterations as f64, zoom_progress).round() as i32,
+        duration: from.duration,
+        easing: from.easing,
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<Keyframe>, String> {
+    let file = File::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let loaded: KeyframeFile = serde_json::from_reader(file).map_err(|err| format!("{}: {err}", path.display()))?;
+    for (i, keyframe) in loaded.keyframes.iter().enumerate() {
+        let valid_duration = keyframe.duration.is_none_or(|d| d.is_finite() && d > 0.);
+        if !(keyframe.scale.is_finite() && keyframe.scale > 0.) || keyframe.iterations < 1 || !valid_duration {
+            return Err(format!("{}: keyframe {} needs a positive scale, iterations and duration", path.display(), i + 1));
+        }
+    }
+    Ok(loaded.keyframes)
+}
+
+pub fn save(path: &Path, keyframes: &[Keyframe]) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    serde_json::to_writer_pretty(file, &KeyframeFile { keyframes: keyframes.to_vec() })
+        .map_err(|err| format!("{}: {err}", path.display()))
+}
+
+/// `keyframes` subcommand: lists or edits a keyframe file and returns the
+/// process exit status. Keyframes are numbered from 1, like the window does.
+pub fn run(args: &KeyframesArgs) -> i32 {
+    let mut keyframes = match load(&args.file) {
+        Ok(keyframes) => keyframes,
+        Err(err) => {
+            eprintln!("{err}");
+            return EXIT_RENDER_FAILED;
+        }
+    };
+    let count = keyframes.len();
+    let index = |n: usize| -> Result<usize, String> {
+        match n {
+            1.. if n <= count => Ok(n - 1),
+            _ => Err(format!("there is no keyframe {n}, the file has {count}")),
+        }
+    };
+
+    let edited = match &args.action {
+        None | Some(KeyframesAction::List) => {
+            for (i, k) in keyframes.iter().enumerate() {
+                let segment = match (i + 1 < count, k.duration) {
+                    (false, _) => String::new(),
+                    (true, Some(duration)) => format!("  then {duration}s {:?}", k.easing),
+                    (true, None) => format!("  then default length {:?}", k.easing),
+                };
+                println!(
+                    "{:>3}  center {:+.15} {:+.15}  scale {:.3e}  rotation {:.3}  iterations {}{segment}",
+                    i + 1, k.center.0, k.center.1, k.scale, k.rotation, k.iterations
+                );
+            }
+            return 0;
+        }
+        Some(KeyframesAction::Delete { index: n }) => index(*n).map(|i| {
+            keyframes.remove(i);
+        }),
+        Some(KeyframesAction::Move { from, to }) => index(*from).and_then(|from| {
+            let to = index(*to)?;
+            let keyframe = keyframes.remove(from);
+            keyframes.insert(to, keyframe);
+            Ok(())
+        }),
+        Some(KeyframesAction::Set { index: n, duration, easing }) => index(*n).map(|i| {
+            if let Some(duration) = duration {
+                keyframes[i].duration = Some(*duration);
+            }
+            if let Some(easing) = easing {
+                keyframes[i].easing = *easing;
+            }
+        }),
+    };
+    if let Err(err) = edited {
+        eprintln!("{err}");
+        return EXIT_BAD_ARGS;
+    }
+    match save(&args.file, &keyframes) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{err}");
+            EXIT_RENDER_FAILED
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(center: (f64, f64), scale: f64, rotation: f64, iterations: i32) -> Keyframe {
+        Keyframe { center, scale, rotation, anisotropy: 1., iterations, duration: Some(2.), easing: Easing::Smooth }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-12 * a.abs().max(b.abs()).max(1.)
+    }
+
+    #[test]
+    fn ends_are_the_keyframes() {
+        let from = keyframe((-0.5, 0.), 1., 0.2, 1000);
+        let to = Keyframe { duration: None, easing: Easing::Linear, ..keyframe((-0.75, 0.1), 1e-6, 1.4, 4000) };
+        assert_eq!(interpolate(&from, &to, 0.), from);
+        // the segment's own duration and easing stay with it
+        assert_eq!(interpolate(&from, &to, 1.), Keyframe { duration: Some(2.), easing: Easing::Smooth, ..to.clone() });
+    }
+
+    #[test]
+    fn zoom_moves_by_equal_factors() {
+        let (from, to) = (keyframe((0., 0.), 1., 0., 1000), keyframe((1., -1.), 1e-8, 0., 3000));
+        let quarter = interpolate(&from, &to, 0.25);
+        assert!(close(quarter.scale, 1e-2));
+        assert_eq!(quarter.center, (0.25, -0.25));
+        // iterations follow the zoom's progress
+        assert_eq!(quarter.iterations, 1500);
+        let half = interpolate(&from, &to, 0.5);
+        assert!(close(half.scale, 1e-4));
+        assert_eq!(half.iterations, 2000);
+    }
+
+    #[test]
+    fn rotation_takes_the_short_way() {
+        let (from, to) = (keyframe((0., 0.), 1., TAU - 0.25, 100), keyframe((0., 0.), 1., 0.25, 100));
+        let half = interpolate(&from, &to, 0.5);
+        assert!(close(half.rotation, 0.) || close(half.rotation, TAU), "{}", half.rotation);
+        let back = interpolate(&to, &from, 0.5);
+        assert!(close(back.rotation, 0.) || close(back.rotation, TAU), "{}", back.rotation);
+    }
+
+    #[test]
+    fn easings_keep_their_ends() {
+        for easing in [Easing::Linear, Easing::Smooth, Easing::EaseIn, Easing::EaseOut] {
+            assert_eq!((easing.apply(0.), easing.apply(1.)), (0., 1.));
+            assert_eq!((easing.apply(-1.), easing.apply(2.)), (0., 1.));
+            let samples: Vec<f64> = (0..=20).map(|i| easing.apply(i as f64 / 20.)).collect();
+            assert!(samples.windows(2).all(|pair| pair[1] >= pair[0]), "{easing:?}");
+        }
+        assert_eq!(Easing::Smooth.apply(0.5), 0.5);
+        assert!(Easing::EaseIn.apply(0.5) < 0.5 && Easing::EaseOut.apply(0.5) > 0.5);
+    }
+
+    #[test]
+    fn files_round_trip() {
+        let path = std::env::temp_dir().join(format!("mandelplotter-keyframes-{}.json", std::process::id()));
+        let keyframes = vec![keyframe((-0.5, 0.), 1., 0., 1000), Keyframe { duration: None, ..keyframe((-0.74, 0.13), 1e-5, 0.5, 3000) }];
+        save(&path, &keyframes).expect("writable");
+        let loaded = load(&path);
+        std::fs::write(&path, r#"{"keyframes": [{"center": [0, 0], "scale": 0, "iterations": 10}]}"#).expect("writable");
+        let invalid = load(&path);
+        std::fs::remove_file(&path).ok();
+        assert_eq!(loaded, Ok(keyframes));
+        assert!(invalid.is_err());
+    }
+}
@@ -0,0 +1,213 @@
+// Kalles Fraktaler locations (`.kfr`), the format most deep-zoom spots are
+// shared in: one `Key: value` per line, with the center as decimal strings of
+// any length.
+//
+//     Re: -1.76877883336508144975009196794552665138157891623...
+//     Im: -0.00173889736590380573166694709836572024508673513...
+//     Zoom: 3.1218e123
+//     Iterations: 15000
+//     RotateAngle: 0
+//
+// `Zoom` is the magnification from a view 2 high in each direction, i.e. our
+// `Viewport::scale` is `2 / Zoom`. The center strings go into the camera
+// untouched. Everything else (KF's coloring, its stretch, ...) this program has
+// no use for, but it is kept in order and written back, so a file exported
+// after opening one still opens in Kalles Fraktaler the way it was.
+
+use std::fs;
+use std::path::Path;
+
+use crate::session::Camera;
+
+#[derive(Clone)]
+pub struct Kfr {
+    /// Every line in file order, unknown keys included.
+    entries: Vec<(String, String)>,
+}
+
+impl Kfr {
+    pub fn parse(text: &str) -> Result<Kfr, String> {
+        let mut entries = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("line {} is not `Key: value`", number + 1))?;
+            entries.push((key.trim().to_string(), value.trim().to_string()));
+        }
+        let kfr = Kfr { entries };
+        kfr.camera()?;
+        Ok(kfr)
+    }
+
+    pub fn load(path: &Path) -> Result<Kfr, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        Kfr::parse(&text).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    /// The file as Kalles Fraktaler writes it, CRLF line ends included.
+    pub fn to_text(&self) -> String {
+        self.entries.iter().map(|(key, value)| format!("{key}: {value}\r\n")).collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the value of `key` where it is, or adds it at the end.
+    fn set(&mut self, key: &str, value: String) {
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+
+    /// The location as a camera, with all the digits of the center.
+    pub fn camera(&self) -> Result<Camera, String> {
+        let field = |key: &str| self.get(key).ok_or_else(|| format!("no {key}"));
+        let number = |key: &str, text: &str| {
+            text.parse::<f64>().map(|_| text.to_string()).map_err(|_| format!("{key} `{text}` is not a number"))
+        };
+        let (re, im, zoom) = (field("Re")?, field("Im")?, field("Zoom")?);
+        let scale = scale_from_zoom(zoom).ok_or_else(|| format!("Zoom `{zoom}` is not a positive number"))?;
+        // the renderers work in f64, which ends around 1e-308
+        if scale.parse::<f64>().map_or(true, |scale| scale == 0.) {
+            return Err(format!("Zoom {zoom} is deeper than this program can render"));
+        }
+        let rotation = match self.get("RotateAngle") {
+            Some(degrees) => degrees
+                .parse::<f64>()
+                .map_err(|_| format!("RotateAngle `{degrees}` is not a number"))?
+                .to_radians(),
+            None => 0.,
+        };
+        Ok(Camera {
+            center: [number("Re", re)?, number("Im", im)?],
+            scale,
+            rotation,
+            anisotropy: 1.,
+        })
+    }
+
+    /// `Iterations`, if it is there and makes sense.
+    pub fn iterations(&self) -> Option<i32> {
+        self.get("Iterations")?.parse::<i32>().ok().filter(|&n| n > 0)
+    }
+
+    /// This location moved to `camera` with `iterations`, everything else
+    /// kept as it was.
+    pub fn with_view(&self, camera: &Camera, iterations: i32) -> Kfr {
+        let mut kfr = Kfr { entries: self.entries.clone() };
+        kfr.set("Re", camera.center[0].clone());
+        kfr.set("Im", camera.center[1].clone());
+        kfr.set("Zoom", zoom_from_scale(&camera.scale));
+        kfr.set("Iterations", iterations.to_string());
+        kfr.set("RotateAngle", camera.rotation.to_degrees().to_string());
+        kfr
+    }
+
+    /// A new file for `camera`, for views that weren't opened from one.
+    pub fn from_view(camera: &Camera, iterations: i32) -> Kfr {
+        Kfr { entries: Vec::new() }.with_view(camera, iterations)
+    }
+}
+
+/// Splits a decimal like `3.1218e123` into a mantissa and a power of ten,
+/// so exponents past what an f64 holds survive the conversion.
+fn split_exponent(text: &str) -> Option<(f64, i64)> {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(at) => (&text[..at], text[at + 1..].parse::<i64>().ok()?),
+        None => (text, 0),
+    };
+    // only the leading digits of the mantissa matter for the zoom
+    let mantissa = mantissa.parse::<f64>().ok().filter(|m| m.is_finite())?;
+    Some((mantissa, exponent))
+}
+
+/// `2 / zoom`, as a decimal string.
+fn scale_from_zoom(zoom: &str) -> Option<String> {
+    let (mantissa, exponent) = split_exponent(zoom)?;
+    if mantissa <= 0. {
+        return None;
+    }
+    let scaled = 2. / mantissa;
+    let shift = scaled.log10().floor();
+    Some(format!("{}e{}", scaled / 10f64.powf(shift), shift as i64 - exponent))
+}
+
+/// `2 / scale` the way Kalles Fraktaler writes zooms.
+fn zoom_from_scale(scale: &str) -> String {
+    match split_exponent(scale) {
+        Some((mantissa, exponent)) if mantissa > 0. => {
+            let zoom = 2. / mantissa;
+            let shift = zoom.log10().floor();
+            format!("{}E{}", zoom / 10f64.powf(shift), shift as i64 - exponent)
+        }
+        _ => "1".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `digits` digits after the point, never repeating for long.
+    fn long_decimal(sign: &str, digits: usize) -> String {
+        let tail: String = (0..digits).map(|i| char::from(b'0' + ((i * 7 + i / 10) % 10) as u8)).collect();
+        format!("{sign}1.{tail}")
+    }
+
+    fn sample(re: &str, im: &str) -> String {
+        format!("Re: {re}\r\nIm: {im}\r\nZoom: 3.1218e123\r\nIterations: 15000\r\nIterDiv: 0.010000\r\nColorMethod: 7\r\nRotateAngle: 30\r\n")
+    }
+
+    #[test]
+    fn long_centers_stay_exact() {
+        let (re, im) = (long_decimal("-", 400), long_decimal("", 350).replacen("1.", "0.", 1));
+        let kfr = Kfr::parse(&sample(&re, &im)).expect("a location");
+        let camera = kfr.camera().expect("a camera");
+        assert_eq!(camera.center, [re.clone(), im.clone()]);
+        assert!((camera.rotation - 30f64.to_radians()).abs() < 1e-12);
+        assert_eq!(kfr.iterations(), Some(15000));
+        // 2 / 3.1218e123
+        let (mantissa, exponent) = split_exponent(&camera.scale).expect("a decimal");
+        assert!((mantissa - 20. / 3.1218).abs() < 1e-12 && exponent == -124, "{}", camera.scale);
+    }
+
+    #[test]
+    fn unknown_keys_are_written_back() {
+        let text = sample(&long_decimal("-", 300), &long_decimal("", 300));
+        let kfr = Kfr::parse(&text).expect("a location");
+        assert_eq!(kfr.to_text(), text);
+        let camera = Camera { center: ["-0.5".to_string(), "0.25".to_string()], scale: "2e-10".to_string(), rotation: 0., anisotropy: 1. };
+        let moved = Kfr::parse(&kfr.with_view(&camera, 4000).to_text()).expect("a location");
+        assert_eq!((moved.get("Re"), moved.get("Im"), moved.get("Iterations")), (Some("-0.5"), Some("0.25"), Some("4000")));
+        assert_eq!((moved.get("IterDiv"), moved.get("ColorMethod")), (Some("0.010000"), Some("7")));
+        assert_eq!(moved.camera().expect("a camera").center, camera.center);
+    }
+
+    #[test]
+    fn zooms_round_trip_past_f64() {
+        for zoom in ["1", "3.1218e123", "7.5E2000", "2.0e-3"] {
+            let back = zoom_from_scale(&scale_from_zoom(zoom).expect("positive"));
+            let (a, b) = (split_exponent(zoom).expect("a decimal"), split_exponent(&back).expect("a decimal"));
+            assert!((a.0 * 10f64.powi((a.1 - b.1) as i32) - b.0).abs() < 1e-9 * b.0, "{zoom} came back as {back}");
+        }
+    }
+
+    #[test]
+    fn bad_files_are_refused() {
+        assert!(Kfr::parse("Re: 0\nIm: 0\n").is_err());
+        assert!(Kfr::parse("Re: zero\nIm: 0\nZoom: 1\n").is_err());
+        assert!(Kfr::parse("Re: 0\nIm: 0\nZoom: -5\n").is_err());
+        assert!(Kfr::parse("Re: 0\nIm: 0\nZoom: 1e400\n").err().is_some_and(|err| err.contains("deeper")));
+        assert!(Kfr::parse("Re: 0\nno colon here\n").is_err());
+    }
+}
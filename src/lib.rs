@@ -0,0 +1,94 @@
+//! The fractal core without any windowing: the view math, palettes, the CPU
+//! renderer and the GL renderer. The GL side draws into whatever context is
+//! current, so it can be embedded in another application's window or
+//! framebuffer:
+//!
+//! ```ignore
+//! mandelplotter::load_gl(|name| my_context.get_proc_address(name));
+//! let renderer = mandelplotter::renderer::Renderer::new()?;
+//! // every frame, with the host's framebuffer bound
+//! renderer.draw_to(&params, width, height);
+//! ```
+//!
+//! Without a context, `MandelbrotView` renders a view on the CPU into a
+//! buffer, with every coloring option of the explorer and a few more:
+//!
+//! ```
+//! use mandelplotter::{palette::{ColorMode, Palette}, MandelbrotView};
+//!
+//! let mut view = MandelbrotView::new(160, 120);
+//! view.set_center(-0.743, 0.1318);
+//! view.set_scale(1e-2);
+//! view.set_iterations(500);
+//! view.set_palette(Palette::curated(1)); // "ultra"
+//! view.set_color_mode(ColorMode::Blend);
+//! view.set_frequency(4.); // the palette goes round four times
+//! view.set_phase(0.25);
+//! view.set_gamma(1.8);
+//! view.set_interior_color([20, 0, 40, 255]);
+//! let mut pixels = vec![0; 4 * 160 * 120]; // RGBA, rows from the top
+//! view.render_to_buffer(&mut pixels).unwrap();
+//! # assert!(pixels.chunks(4).any(|pixel| pixel == [20, 0, 40, 255]));
+//! ```
+//!
+//! `MandelbrotView` wraps `cpu::render_image`, which takes the same options
+//! the explorer colors with as a `cpu::Shading`:
+//!
+//! ```
+//! use mandelplotter::{cpu, palette::{ColorMode, Palette, Tone}, viewport::Viewport};
+//!
+//! let mut view = Viewport::new(160, 120);
+//! view.center = (-0.743, 0.1318);
+//! view.scale = 1e-2;
+//! let palette = Palette::curated(1).build(); // "ultra"
+//! let shading = cpu::Shading {
+//!     palette: &palette,
+//!     mode: ColorMode::Blend,
+//!     transparent_interior: false,
+//!     background: None,
+//!     morph: 1.,
+//!     slow_tint: None,
+//!     lighting: None,
+//!     // the palette repeats every 100 iterations instead of once over all 500
+//!     color_span: Some(100),
+//!     log_polar: false,
+//!     tone: Tone { brightness: 0.05, contrast: 1.2 },
+//! };
+//! // 500 iterations, 2x2 jittered samples per pixel, seed 0, no Julia set
+//! let image = cpu::render_image(&view, 500, 2, 0, None, &shading);
+//! let pixels: Vec<u8> = image.into_raw(); // RGBA, rows from the top
+//! # assert_eq!(pixels.len(), 4 * 160 * 120);
+//! ```
+//!
+//! The context can be OpenGL 3.3 core, OpenGL ES 3.0, or OpenGL 2.1 with
+//! GLSL 1.20: the shaders are written for the first and translated for the
+//! others (see `shader::Dialect`), which go without some features (see
+//! `caps`). Drawing leaves the fractal's program, vertex array (on 2.1, its
+//! vertex buffer), palette texture (on unit 0) and background texture (on
+//! unit 1) bound; draws split up with `Renderer::set_iterations_per_draw`
+//! also leave their orbit states on units 2 and 3.
+
+use std::os::raw::c_void;
+
+pub mod background;
+pub mod caps;
+pub mod cpu;
+pub mod gl_object;
+pub mod mesh;
+pub mod palette;
+pub mod renderer;
+pub mod rng;
+pub mod shader;
+pub mod view;
+pub mod viewport;
+
+pub use view::MandelbrotView;
+
+/// Loads the GL function pointers the renderer calls through, from the
+/// context that is current. Call once the host's context exists, before
+/// creating a `Renderer`; `caps::GpuCapabilities::current` then probes that
+/// context.
+pub fn load_gl<F: FnMut(&'static str) -> *const c_void>(loader: F) {
+    gl::load_with(loader);
+    caps::GpuCapabilities::forget();
+}
@@ -0,0 +1,107 @@
+// Every way a view gets saved, read back by one door: a saved state (`.json`),
+// a Kalles Fraktaler location (`.kfr`), a PNG the explorer wrote, or a `mb2:`
+// location string. Files are told apart by their extension, or by what they
+// start with when it isn't one of those. `--load`, `--batch`, dropped files
+// and Ctrl+O all come through here.
+
+use std::fs;
+use std::path::Path;
+
+use crate::kfr::Kfr;
+use crate::location;
+use crate::metadata::{self, Fractal, ViewMetadata};
+use crate::palette::{ColorMode, Palette, Tone};
+use crate::session::{self, Camera, Session};
+
+/// What was read, in its own terms, as each keeps different things: a state
+/// has everything, a PNG the view and its coloring, a `.kfr` only the view
+/// (and the rest of the file, which saving it again keeps).
+pub enum Loaded {
+    State(Session),
+    Kfr(Kfr),
+    Png(ViewMetadata),
+}
+
+impl Loaded {
+    /// As a state, with the defaults for whatever it doesn't say.
+    pub fn session(self) -> Result<Session, String> {
+        match self {
+            Loaded::State(session) => Ok(session),
+            Loaded::Kfr(kfr) => {
+                let mut session = defaults(kfr.camera()?);
+                session.iterations = kfr.iterations().unwrap_or(session.iterations);
+                Ok(session)
+            }
+            Loaded::Png(saved) => Ok(Session {
+                iterations: saved.iterations,
+                fractal: saved.fractal.clone(),
+                color_mode: saved.color_mode,
+                palette: saved.palette.clone(),
+                ..defaults(Camera::from_view(&saved.view(1, 1)))
+            }),
+        }
+    }
+
+    /// The camera, with all the digits it was saved with.
+    pub fn camera(&self) -> Result<Camera, String> {
+        match self {
+            Loaded::State(session) => Ok(session.camera.clone()),
+            Loaded::Kfr(kfr) => kfr.camera(),
+            Loaded::Png(saved) => Ok(Camera::from_view(&saved.view(1, 1))),
+        }
+    }
+}
+
+/// A location string, or a file holding a view.
+pub fn load(what: &str) -> Result<Loaded, String> {
+    let path = Path::new(what);
+    if !path.exists() && what.starts_with("mb") && what.contains(':') {
+        return location::decode(what).map(Loaded::State);
+    }
+    file(path)
+}
+
+/// A file holding a view, of whichever kind it is.
+pub fn file(path: &Path) -> Result<Loaded, String> {
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_ascii_lowercase();
+    let loaded = match extension.as_str() {
+        "json" => Loaded::State(session::load(path)?),
+        "kfr" => Loaded::Kfr(Kfr::load(path)?),
+        "png" => Loaded::Png(metadata::read(path)?),
+        _ => {
+            let bytes = fs::read(path).map_err(|err| format!("{}: {err}", path.display()))?;
+            let text = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]).into_owned();
+            if bytes.starts_with(b"\x89PNG") {
+                Loaded::Png(metadata::read(path)?)
+            } else if text.trim_start().starts_with('{') {
+                Loaded::State(session::load(path)?)
+            } else if text.lines().any(|line| line.starts_with("Re:")) {
+                Loaded::Kfr(Kfr::load(path)?)
+            } else if text.trim().starts_with("mb") && text.contains(':') {
+                Loaded::State(location::decode(&text).map_err(|err| format!("{}: {err}", path.display()))?)
+            } else {
+                return Err(format!("{}: not a saved state, .kfr, location string or PNG written by the explorer", path.display()));
+            }
+        }
+    };
+    loaded.camera()?.view(1, 1).map_err(|err| format!("{}: {err}", path.display()))?;
+    Ok(loaded)
+}
+
+/// What a view starts with when its file says nothing else.
+pub fn defaults(camera: Camera) -> Session {
+    Session {
+        version: session::VERSION,
+        camera,
+        iterations: 1000,
+        fractal: Fractal::Mandelbrot,
+        color_mode: ColorMode::EscapeTime,
+        palette: Palette::grayscale(),
+        ssaa: 1,
+        transparent_interior: false,
+        checkerboard: true,
+        lighting: None,
+        color_span: None,
+        tone: Tone::default(),
+    }
+}
@@ -0,0 +1,340 @@
+// Locations: a saved state (see session.rs) as one line short enough to paste
+// in a chat message, `mb2:` followed by base64url without padding. The bytes
+// are a compact binary form of the state and end with a CRC-32 of the rest,
+// so a string that lost characters on the way is caught instead of opening
+// somewhere else. Ctrl+Shift+C copies the current one, Ctrl+Shift+V opens one
+// from the clipboard and `--location` starts there.
+//
+// Format 2, little-endian:
+//
+//     center re, center im, scale   strings, u8 length + ASCII
+//     rotation, anisotropy          f64
+//     iterations                    i32
+//     fractal                       u8 (0 Mandelbrot, 1 Julia + c as 2 f64)
+//     color mode                    u8 (palette::ColorMode order)
+//     ssaa                          u8
+//     flags                         u8 (1 transparent interior, 2 checkerboard)
+//     palette                       u8 name length + UTF-8, u8 stop count,
+//                                   per stop position and color as 4 f32
+//     extras                        per setting that is on: u8 tag, u8
+//                                   length, that many bytes
+//     checksum                      u32, CRC-32 of everything before it
+//
+// The extras are the settings added after format 1, which has none:
+//
+//     1 lighting                    angle, intensity as 2 f32
+//     2 color span                  i32
+//     3 tone                        brightness, contrast as 2 f32
+//
+// A tag this build doesn't know is skipped, so new settings don't need a new
+// format. Decoding gives back exactly the state that was encoded; a state
+// with text over 255 bytes or over 255 palette stops isn't encoded at all.
+
+use crate::metadata::Fractal;
+use crate::palette::{ColorMode, Lighting, Palette, Stop, Tone};
+use crate::session::{self, Camera, Session};
+
+const PREFIX: &str = "mb";
+const FORMAT: u32 = 2;
+const LIGHTING: u8 = 1;
+const COLOR_SPAN: u8 = 2;
+const TONE: u8 = 3;
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+pub fn encode(session: &Session) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    for text in [&session.camera.center[0], &session.camera.center[1], &session.camera.scale] {
+        put_str(&mut bytes, text)?;
+    }
+    bytes.extend(session.camera.rotation.to_le_bytes());
+    bytes.extend(session.camera.anisotropy.to_le_bytes());
+    bytes.extend(session.iterations.to_le_bytes());
+    match &session.fractal {
+        Fractal::Mandelbrot => bytes.push(0),
+        Fractal::Julia { c } => {
+            bytes.push(1);
+            bytes.extend(c[0].to_le_bytes());
+            bytes.extend(c[1].to_le_bytes());
+        }
+    }
+    bytes.push(match session.color_mode {
+        ColorMode::EscapeTime => 0,
+        ColorMode::Angle => 1,
+        ColorMode::Blend => 2,
+    });
+    bytes.push(session.ssaa.clamp(0, u8::MAX as i32) as u8);
+    bytes.push(session.transparent_interior as u8 | (session.checkerboard as u8) << 1);
+    put_str(&mut bytes, &session.palette.name)?;
+    let stops = &session.palette.stops;
+    bytes.push(u8::try_from(stops.len()).map_err(|_| format!("{} palette stops don't fit in a location", stops.len()))?);
+    for stop in stops {
+        for value in [stop.position, stop.color[0], stop.color[1], stop.color[2]] {
+            bytes.extend(value.to_le_bytes());
+        }
+    }
+    if let Some(lighting) = session.lighting {
+        put_extra(&mut bytes, LIGHTING, &[lighting.angle.to_le_bytes(), lighting.intensity.to_le_bytes()].concat());
+    }
+    if let Some(span) = session.color_span {
+        put_extra(&mut bytes, COLOR_SPAN, &span.to_le_bytes());
+    }
+    if !session.tone.is_neutral() {
+        put_extra(&mut bytes, TONE, &[session.tone.brightness.to_le_bytes(), session.tone.contrast.to_le_bytes()].concat());
+    }
+    bytes.extend(crc32(&bytes).to_le_bytes());
+    Ok(format!("{PREFIX}{FORMAT}:{}", base64url(&bytes)))
+}
+
+pub fn decode(location: &str) -> Result<Session, String> {
+    let location = location.trim();
+    let (head, body) = location
+        .split_once(':')
+        .filter(|(head, _)| head.starts_with(PREFIX))
+        .ok_or("not a location string (they start with `mb2:`)")?;
+    match head[PREFIX.len()..].parse::<u32>() {
+        Ok(1..=FORMAT) => {}
+        Ok(format) => return Err(format!("location format {format} is not supported (this build reads up to {FORMAT})")),
+        Err(_) => return Err("not a location string (they start with `mb2:`)".to_string()),
+    }
+    let damaged = || "the location string is damaged or cut off".to_string();
+    let bytes = unbase64url(body).ok_or_else(damaged)?;
+    if bytes.len() < 4 {
+        return Err(damaged());
+    }
+    let (data, checksum) = bytes.split_at(bytes.len() - 4);
+    if crc32(data).to_le_bytes() != checksum {
+        return Err(damaged());
+    }
+
+    // the checksum matched, so running out of bytes means a bad encoder
+    let mut reader = Reader { bytes: data };
+    let camera = Camera {
+        center: [reader.string()?, reader.string()?],
+        scale: reader.string()?,
+        rotation: reader.f64()?,
+        anisotropy: reader.f64()?,
+    };
+    let iterations = i32::from_le_bytes(reader.take()?);
+    let fractal = match reader.u8()? {
+        0 => Fractal::Mandelbrot,
+        1 => Fractal::Julia { c: [reader.f64()?, reader.f64()?] },
+        other => return Err(format!("unknown fractal {other} in the location")),
+    };
+    let color_mode = match reader.u8()? {
+        0 => ColorMode::EscapeTime,
+        1 => ColorMode::Angle,
+        2 => ColorMode::Blend,
+        other => return Err(format!("unknown color mode {other} in the location")),
+    };
+    let ssaa = reader.u8()? as i32;
+    let flags = reader.u8()?;
+    let name = reader.string()?;
+    let mut stops = Vec::new();
+    for _ in 0..reader.u8()? {
+        let position = reader.f32()?;
+        stops.push(Stop { position, color: [reader.f32()?, reader.f32()?, reader.f32()?] });
+    }
+    let (mut lighting, mut color_span, mut tone) = (None, None, Tone::default());
+    // format 1 ends here, so it reads as having none of them
+    while !reader.bytes.is_empty() {
+        let tag = reader.u8()?;
+        let len = reader.u8()? as usize;
+        let payload = reader.bytes.get(..len).ok_or("the location ends early")?;
+        reader.bytes = &reader.bytes[len..];
+        let mut extra = Reader { bytes: payload };
+        match tag {
+            LIGHTING => lighting = Some(Lighting { angle: extra.f32()?, intensity: extra.f32()? }),
+            COLOR_SPAN => color_span = Some(i32::from_le_bytes(extra.take()?)),
+            TONE => tone = Tone { brightness: extra.f32()?, contrast: extra.f32()? },
+            // from a newer build
+            _ => {}
+        }
+    }
+    let session = Session {
+        version: session::VERSION,
+        camera,
+        iterations,
+        fractal,
+        color_mode,
+        palette: Palette { name, stops },
+        ssaa,
+        transparent_interior: flags & 1 != 0,
+        checkerboard: flags & 2 != 0,
+        lighting,
+        color_span,
+        tone,
+    };
+    session.camera.view(1, 1)?;
+    Ok(session)
+}
+
+fn put_str(bytes: &mut Vec<u8>, text: &str) -> Result<(), String> {
+    let len = u8::try_from(text.len()).map_err(|_| format!("`{text}` is too long for a location, {} bytes of at most 255", text.len()))?;
+    bytes.push(len);
+    bytes.extend(text.as_bytes());
+    Ok(())
+}
+
+fn put_extra(bytes: &mut Vec<u8>, tag: u8, payload: &[u8]) {
+    bytes.extend([tag, payload.len() as u8]);
+    bytes.extend(payload);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self.bytes.get(..N).ok_or("the location ends early")?;
+        self.bytes = &self.bytes[N..];
+        Ok(bytes.try_into().expect("N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take()?))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u8()? as usize;
+        let bytes = self.bytes.get(..len).ok_or("the location ends early")?;
+        self.bytes = &self.bytes[len..];
+        String::from_utf8(bytes.to_vec()).map_err(|_| "text in the location is not UTF-8".to_string())
+    }
+}
+
+/// CRC-32 (IEEE, as in zip and PNG), bit by bit: locations are tiny.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn base64url(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    out
+}
+
+fn unbase64url(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text.bytes() {
+        let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+        bits = bits << 6 | value;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    // a lone leftover character can't come from whole bytes
+    (count < 6).then_some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(fractal: Fractal, color_mode: ColorMode) -> Session {
+        Session {
+            version: session::VERSION,
+            camera: Camera {
+                center: ["-0.743643887037158704752191506114774".to_string(), "0.131825904205311970493132056385139".to_string()],
+                scale: "1.5e-30".to_string(),
+                rotation: 0.25,
+                anisotropy: 1.,
+            },
+            iterations: 20000,
+            fractal,
+            color_mode,
+            palette: Palette::curated(1),
+            ssaa: 3,
+            transparent_interior: true,
+            checkerboard: false,
+            lighting: None,
+            color_span: None,
+            tone: Tone::default(),
+        }
+    }
+
+    fn sessions() -> Vec<Session> {
+        let mut sessions = Vec::new();
+        for fractal in [Fractal::Mandelbrot, Fractal::Julia { c: [-0.8, 0.156] }] {
+            for mode in [ColorMode::EscapeTime, ColorMode::Angle, ColorMode::Blend] {
+                sessions.push(session(fractal.clone(), mode));
+            }
+        }
+        sessions[1].lighting = Some(Lighting { angle: 45., intensity: 0.6 });
+        sessions[2].color_span = Some(256);
+        sessions[4].lighting = Some(Lighting { angle: 300., intensity: 1. });
+        sessions[4].color_span = Some(64);
+        sessions[3].tone = Tone { brightness: 0.2, contrast: 1.5 };
+        sessions[5].tone = Tone { brightness: -0.1, contrast: 0.8 };
+        sessions[5].lighting = Some(Lighting { angle: 90., intensity: 0.3 });
+        sessions
+    }
+
+    #[test]
+    fn round_trips_across_fractals_and_color_modes() {
+        for session in sessions() {
+            let text = encode(&session).expect("fits");
+            assert!(text.starts_with("mb2:"));
+            assert_eq!(decode(&text), Ok(session));
+        }
+    }
+
+    #[test]
+    fn format_1_still_opens() {
+        // one without extras is format 1 byte for byte
+        let session = session(Fractal::Mandelbrot, ColorMode::Blend);
+        let text = encode(&session).expect("fits").replacen("mb2:", "mb1:", 1);
+        assert_eq!(decode(&text), Ok(session));
+    }
+
+    #[test]
+    fn unknown_extras_are_skipped() {
+        let session = session(Fractal::Mandelbrot, ColorMode::EscapeTime);
+        let text = encode(&session).expect("fits");
+        let mut bytes = unbase64url(&text[4..]).expect("base64url");
+        bytes.truncate(bytes.len() - 4);
+        put_extra(&mut bytes, 200, &[1, 2, 3]);
+        bytes.extend(crc32(&bytes).to_le_bytes());
+        assert_eq!(decode(&format!("mb2:{}", base64url(&bytes))), Ok(session));
+    }
+
+    #[test]
+    fn long_text_is_refused() {
+        let mut session = session(Fractal::Mandelbrot, ColorMode::EscapeTime);
+        session.camera.center[0] = format!("-0.{}", "7".repeat(300));
+        assert!(encode(&session).is_err());
+    }
+
+    #[test]
+    fn damage_is_caught() {
+        let text = encode(&session(Fractal::Mandelbrot, ColorMode::EscapeTime)).expect("fits");
+        assert!(decode(&text[..text.len() - 3]).is_err());
+        let flipped = if text.ends_with('A') { 'B' } else { 'A' };
+        assert!(decode(&format!("{}{flipped}", &text[..text.len() - 1])).is_err());
+        assert!(decode(&text.replacen("mb2:", "mb9:", 1)).is_err());
+    }
+}
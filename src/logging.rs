@@ -0,0 +1,31 @@
+// What the explorer says while it runs goes through the `log` macros to
+// stderr: what changed (modes, saves, screenshots) at info, things that
+// didn't work or are degraded at warn and error, and per-frame diagnostics
+// at trace, which release builds leave out entirely so the render loop pays
+// nothing for them. Info and up is shown by default; `--verbose` and
+// `--quiet` move the level and `RUST_LOG` (`warn`, `Mandelplotter1=debug`,
+// ...) overrides both. What the quick modes and `render` print as their
+// result stays on stdout.
+
+use std::io::Write;
+
+use log::{Level, LevelFilter};
+
+pub fn init(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format(|out, record| match record.level() {
+            Level::Info => writeln!(out, "{}", record.args()),
+            Level::Warn => writeln!(out, "warning: {}", record.args()),
+            Level::Error => writeln!(out, "error: {}", record.args()),
+            level => writeln!(out, "[{} {}] {}", level.as_str().to_lowercase(), record.target(), record.args()),
+        })
+        .init();
+}
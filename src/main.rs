@@ -1,248 +1,2056 @@
 use gl::*;
-use glfw::*;
-use std::sync::mpsc::Receiver;
-use std::ffi::{CString, CStr};
-use std::ptr;
-use std::str;
-use std::mem;
-use std::time::{Instant, Duration};
-use std::os::raw::c_void;
-use gl::types::*;
-use humantime::format_duration;
+use std::time::Instant;
+use log::{error, info, trace, warn};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::process::ExitCode;
 
-const vertexShaderSource: &str = r#"
-    #version 330 core
+mod animation;
+mod area;
+mod auto_iterations;
+mod batch;
+mod bench;
+mod bookmarks;
+mod capture;
+mod clipboard;
+mod cli;
+mod components;
+mod control_panel;
+mod contour;
+mod data;
+mod error;
+mod export;
+mod flight;
+mod gamepad;
+#[cfg(all(feature = "backend-glfw", not(feature = "backend-winit")))]
+mod glfw_platform;
+mod gui;
+mod headless;
+mod hover;
+mod job;
+mod julia;
+mod keyframes;
+mod kfr;
+mod load;
+mod logging;
+mod location;
+mod metadata;
+mod navigate;
+mod offscreen;
+mod orbit;
+mod palette_editor;
+mod photo;
+mod platform;
+mod playback;
+mod precision;
+mod progressive;
+mod recording;
+mod recovery;
+mod relief;
+mod render_thread;
+mod sensitivity;
+mod selection;
+mod session;
+mod settings;
+#[cfg(feature = "serve")]
+mod serve;
+mod shader_dir;
+mod software;
+mod terminal;
+mod tileset;
+mod timelapse;
+mod timestep;
+mod trail;
+mod video;
+mod wallpaper;
+#[cfg(feature = "backend-winit")]
+mod winit_platform;
 
-    layout(location = 0) in vec2 in_position; // Define input position attribute
+// the windowed front end; the fractal itself lives in the library
+use mandelplotter::{background, caps, cpu, gl_object, mesh, palette, renderer, rng, shader, viewport};
 
-    out vec2 position; // Define output position varying variable
+use animation::{Animation, KeyframePath};
+use keyframes::Keyframe;
+use kfr::Kfr;
+use load::Loaded;
+use metadata::{Fractal, ViewMetadata};
+use navigate::Console;
+use area::AreaJob;
+use auto_iterations::AutoIterations;
+use bookmarks::{Bookmark, Bookmarks, Picked};
+use background::{Background, BackgroundStyle};
+use caps::{Feature, GpuCapabilities};
+use capture::{Capturer, Target};
+use cli::Quality;
+use control_panel::{Change, ControlPanel, Controls};
+use export::{BitDepth, Export, PngStream};
+use flight::Flight;
+use gamepad::Gamepad;
+use gui::Gui;
+use hover::{Hover, Probed};
+use job::Job;
+use palette::{ColorMode, Lighting, Palette, SlowTint, Tone};
+use palette_editor::PaletteEditor;
+use photo::Photo;
+use platform::{Action, Event, Key, Modifiers, MouseButton, Window, WindowOptions};
+use precision::Precision;
+use selection::Selection;
+use progressive::Progressive;
+use relief::Relief;
+use render_thread::RemoteWindow;
+use playback::Playback;
+use recording::Recorder;
+use error::StartError;
+use session::Session;
+use timelapse::{Step, Timelapse};
+use timestep::{Clock, Tick};
+use trail::OrbitTrail;
+use renderer::{CoordinateMapping, DrawParams, Renderer};
+use shader::Dialect;
+use sensitivity::Sensitivity;
+use settings::Settings;
+use shader_dir::ShaderWatch;
+use software::SoftwareRenderer;
+use viewport::{PixelRect, Viewport};
 
-    void main() {
-        gl_Position = vec4(in_position.xy, 0.0, 1.0);
-        position = in_position; // Pass input position to the fragment shader
-    }
-"#;
-
-const fragmentShaderSource: &str = r#"
-    #version 330 core
-    in vec2 position;
-    out vec4 FragColor;
-
-    uniform float time;
-    uniform float zoom;
-
-    uniform int substeps;
-
-    uniform vec2 offset;
-
-    vec4 mandelbrot(){
-        vec2 z = vec2(0.);
-        vec2 c = position;
-        c *= zoom;
-        c += offset;
-
-        for (int i = 0; i <= substeps; i++){
-            z = vec2(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
-            if (length(z) > 4.){
-                return vec4(float(i/substeps), float(i/substeps), float(i/substeps), 1.);
-            }
-        }
-        return vec4(1.);
-    }
-
-    void main() {
-        vec4 color = mandelbrot();
-
-        FragColor = color;
-    }
-"#;
-
-fn main() {
-    let mut glfw = glfw::init(glfw::fail_on_errors).unwrap();
-
-    let (mut window, events) = glfw.create_window(600, 600, "🤓", glfw::WindowMode::Windowed)
-        .expect("Failed to create GLFW window.");
-
-    window.set_key_polling(true);
-    window.make_current();
-
-    load_with(|s| window.get_proc_address(s) as * const _);
-
-    let (shaderProgram, VAO) = unsafe {
-        // build and compile our shader program
-        // ------------------------------------
-        // vertex shader
-        let vertexShader = CreateShader(gl::VERTEX_SHADER);
-        let c_str_vert = CString::new(vertexShaderSource.as_bytes()).unwrap();
-        ShaderSource(vertexShader, 1, &c_str_vert.as_ptr(), ptr::null());
-        CompileShader(vertexShader);
-
-        // check for shader compile errors
-        let mut success = FALSE as GLint;
-        let mut infoLog = Vec::with_capacity(512);
-        infoLog.set_len(512 - 1); // subtract 1 to skip the trailing null character
-        GetShaderiv(vertexShader, COMPILE_STATUS, &mut success);
-        if success != TRUE as GLint {
-            GetShaderInfoLog(vertexShader, 512, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-            println!("ERROR::SHADER::VERTEX::COMPILATION_FAILED\n{}", str::from_utf8(&infoLog).unwrap());
-        }
-
-        // fragment shader
-        let fragmentShader = gl::CreateShader(gl::FRAGMENT_SHADER);
-        let c_str_frag = CString::new(fragmentShaderSource.as_bytes()).unwrap();
-        ShaderSource(fragmentShader, 1, &c_str_frag.as_ptr(), ptr::null());
-        CompileShader(fragmentShader);
-        // check for shader compile errors
-        GetShaderiv(fragmentShader, gl::COMPILE_STATUS, &mut success);
-        if success != gl::TRUE as GLint {
-            GetShaderInfoLog(fragmentShader, 512, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-            println!("ERROR::SHADER::FRAGMENT::COMPILATION_FAILED\n{}", str::from_utf8(&infoLog).unwrap());
-        }
-
-        // link shaders
-        let shaderProgram = gl::CreateProgram();
-        AttachShader(shaderProgram, vertexShader);
-        AttachShader(shaderProgram, fragmentShader);
-        LinkProgram(shaderProgram);
-        // check for linking errors
-        GetProgramiv(shaderProgram, gl::LINK_STATUS, &mut success);
-        if success != gl::TRUE as GLint {
-            GetProgramInfoLog(shaderProgram, 512, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-            println!("ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}", str::from_utf8(&infoLog).unwrap());
+const PALETTE_STRIP_HEIGHT: u32 = 32;
+
+// Holding a zoom key changes ln(scale) at this rate, matching the old 1% per frame at 60 fps.
+const ZOOM_RATE: f64 = 0.597;
+// How fast the zoom velocity decays once the key is released, per second.
+const ZOOM_FRICTION: f64 = 6.;
+// Holding a pan key moves the view by this many heights per second, the old 1/150 per frame at 60 fps.
+const PAN_RATE: f64 = 0.4;
+// Holding a rotate key turns the view by this many radians per second, the old 0.01 per frame at 60 fps.
+const ROTATE_RATE: f64 = 0.6;
+// Length of the O key flight back to the home view, whatever the current depth.
+const OVERVIEW_SECONDS: f64 = 1.5;
+// Length of the glide to a snapped power-of-ten (or two) magnification.
+const SNAP_SECONDS: f64 = 0.4;
+// How much one press of [ or ] changes the contrast, or with Shift the brightness.
+const TONE_STEP: f32 = 0.05;
+
+/// Where a view to open comes from.
+enum OpenRequest {
+    /// A PNG with view metadata, a saved state if it ends in `.json` or a
+    /// Kalles Fraktaler location if it ends in `.kfr`.
+    File(std::path::PathBuf),
+    /// A `mb2:` location string.
+    Location(String),
+    /// Where the last session left off.
+    Restored(Box<Session>),
+    /// What was on screen when the explorer last crashed.
+    Recovered(Box<Session>),
+    /// What `--load` named, read before the window opened.
+    Loaded(String, Box<Loaded>),
+    /// One picked in the F1 window.
+    Bookmark(Box<Bookmark>),
+}
+
+struct ViewState {
+    viewport: Viewport,
+    /// The framing the explorer starts with, where O flies back to.
+    home: Viewport,
+    flight: Option<Flight>,
+    substeps: i32,
+    /// Whether `substeps` follows the zoom.
+    auto_iterations: AutoIterations,
+    ssaa: i32,
+    /// The preset U last applied; the finer controls change things after it.
+    quality: Quality,
+    /// Rate of change of ln(scale), per second.
+    zoom_velocity: f64,
+    zoom_inertia: bool,
+    sensitivity: Sensitivity,
+    /// The Shift+drag rectangle P exports instead of the whole view.
+    selection: Selection,
+    region_edge: u32,
+    /// The F4 line for navigation commands.
+    console: Console,
+    /// Everything the keys tune in one window (Tab).
+    control_panel: ControlPanel,
+    /// The same commands from stdin, with `--stdin-commands`.
+    commands: Option<std::sync::mpsc::Receiver<String>>,
+    transparent_interior: bool,
+    color_mode: ColorMode,
+    /// The iteration count the palette stays spread over while Ctrl+H has
+    /// the colors locked, whatever the iterations are changed to.
+    color_lock: Option<i32>,
+    /// Brightness and contrast over the finished colors, set with [ and ].
+    tone: Tone,
+    checkerboard: bool,
+    /// What a transparent interior shows without the checkerboard.
+    clear_color: [f32; 3],
+    /// The desktop shows through the window (`--transparent-window`), so the
+    /// interior is left see-through instead of drawn over `clear_color`.
+    see_through: bool,
+    /// Marks where the iterations run out (F), in the color of `--slow-color`.
+    show_slow: bool,
+    slow_tint: SlowTint,
+    relief: Relief,
+    /// The point sweeping over the view with its orbit, on with Shift+O.
+    trail: OrbitTrail,
+    /// The main cardioid and period-2 bulb outlined (Shift+N).
+    show_components: bool,
+    photo: Photo,
+    /// Set when `--background` loaded an image into the backend.
+    background: Option<BackgroundStyle>,
+    screenshot_requested: bool,
+    /// Shift+F, a readback of the view to center on its deepest pixel.
+    deepest_requested: bool,
+    /// Ctrl+Shift+S, the frame for the clipboard.
+    copy_requested: bool,
+    /// Screenshots saved on a schedule while Shift+F12 has it on.
+    timelapse: Option<Timelapse>,
+    timelapse_every: timelapse::Every,
+    timelapse_limits: timelapse::Limits,
+    palette_export_requested: bool,
+    /// The palette changed and has to be uploaded again.
+    palette_changed: bool,
+    /// What to open before the next frame, from `--open`, `--state`,
+    /// `--location`, Ctrl+O, Ctrl+Shift+V or a file dropped on the window.
+    open_requested: Option<OpenRequest>,
+    /// Where Ctrl+S saves the state and Ctrl+O loads it.
+    state_path: std::path::PathBuf,
+    /// The F1 window and the views kept in it.
+    bookmarks: Bookmarks,
+    /// The last `.kfr` opened and the view it gave, so F7 writes back its
+    /// other keys, and its digits while the view hasn't moved.
+    kfr: Option<(Kfr, Viewport)>,
+    palette: Palette,
+    mapping: CoordinateMapping,
+    /// The logarithmic view Shift+C toggles.
+    log_polar: bool,
+    palette_editor: PaletteEditor,
+    area: Option<AreaJob>,
+    seed: u64,
+    /// A running poster export, the file its rows stream into and its progress.
+    export: Option<(Export, PngStream, Job)>,
+    export_size: (u32, u32),
+    export_ssaa: i32,
+    /// Of screenshots, exports and animation frames.
+    bit_depth: BitDepth,
+    /// Marked with J (or loaded with `--keyframes`) for the next animation.
+    keyframes: Vec<Keyframe>,
+    /// Where F5 saves the keyframes.
+    keyframes_path: std::path::PathBuf,
+    animation: Option<Animation>,
+    /// Whether a cancelled export or animation keeps what it wrote.
+    keep_partial: bool,
+    /// Samples of the camera while recording a path (R).
+    recording: Option<Recorder>,
+    record_tolerance: f64,
+    /// Cursor positions in the plane while F6 traces a Julia path.
+    julia_trace: Option<Vec<(f64, f64)>>,
+    /// The Julia set Y switched to, kept while fading back out of it.
+    julia: Option<(f64, f64)>,
+    show_julia: bool,
+    /// How far the cross-fade to the Julia set is, from 0 to 1.
+    julia_weight: f64,
+    /// The same one update step earlier, for the frames in between.
+    julia_weight_before: f64,
+    /// Length of that cross-fade, 0 to switch at once.
+    morph_seconds: f64,
+    /// The iteration tooltip under the cursor, while F8 has it on.
+    hover: Option<Hover>,
+    precision: Precision,
+    /// Refinement of deep views over several frames.
+    progressive: Progressive,
+    /// A preview of the animation playing in the window (V).
+    playback: Option<Playback>,
+    /// The view one update step earlier while a flight or the playback
+    /// moves it, for the frames in between.
+    viewport_before: Option<Viewport>,
+    animation_size: (u32, u32),
+    animation_fps: f64,
+    animation_seconds: f64,
+}
+
+impl ViewState {
+    fn draw_params(&self, time: f32) -> DrawParams {
+        DrawParams {
+            view: self.viewport.uniforms(),
+            substeps: self.substeps,
+            ssaa: self.ssaa,
+            seed: rng::seed32(self.seed),
+            time,
+            julia: self.julia,
+            morph: eased(self.julia_weight),
+            transparent_interior: self.transparent_interior,
+            color_mode: self.color_mode,
+            color_span: self.color_lock,
+            tone: self.tone,
+            checkerboard: self.transparent_interior && self.checkerboard && !self.see_through,
+            backdrop: Some(self.clear_color).filter(|_| !self.see_through),
+            premultiplied: self.see_through,
+            slow_tint: Some(self.slow_tint).filter(|_| self.show_slow),
+            lighting: self.relief.lighting(),
+            mapping: self.mapping,
+            log_polar: self.log_polar,
+            background: self.background,
         }
-        DeleteShader(vertexShader);
-        DeleteShader(fragmentShader);
+    }
 
-        // set up vertex data (and buffer(s)) and configure vertex attributes
-        // ------------------------------------------------------------------
-        // HINT: type annotation is crucial since default for float literals is f64
-        let vertices: [f32; 18] = [
-            -1., -1., 0.0, // left1
-             1., -1., 0.0, // right1
-             -1.0,  1., 0.0,  // top1
-             -1., 1., 0.0, // left2
-             1., -1., 0.0, // right2
-             1.0,  1., 0.0  // top2
-        ];
-        let (mut VBO, mut VAO) = (0, 0);
-        gl::GenVertexArrays(1, &mut VAO);
-        gl::GenBuffers(1, &mut VBO);
-        // bind the Vertex Array Object first, then bind and set vertex buffer(s), and then configure vertex attributes(s).
-        gl::BindVertexArray(VAO);
-
-        gl::BindBuffer(gl::ARRAY_BUFFER, VBO);
-        gl::BufferData(gl::ARRAY_BUFFER,
-                       (vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
-                       &vertices[0] as *const f32 as *const c_void,
-                       gl::STATIC_DRAW);
-
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 3 * mem::size_of::<GLfloat>() as GLsizei, ptr::null());
-        gl::EnableVertexAttribArray(0);
-
-        // note that this is allowed, the call to gl::VertexAttribPointer registered VBO as the vertex attribute's bound vertex buffer object so afterwards we can safely unbind
-        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-
-        // You can unbind the VAO afterwards so other VAO calls won't accidentally modify this VAO, but this rarely happens. Modifying other
-        // VAOs requires a call to glBindVertexArray anyways so we generally don't unbind VAOs (nor VBOs) when it's not directly necessary.
-        gl::BindVertexArray(0);
-
-        // uncomment this call to draw in wireframe polygons.
-        //PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
-
-        (shaderProgram, VAO)
-    };
+    /// What saved images of `viewport` record about themselves.
+    fn metadata(&self, viewport: &Viewport) -> ViewMetadata {
+        ViewMetadata::new(viewport, self.substeps, self.fractal(), self.color_mode, &self.palette)
+    }
 
-    unsafe{UseProgram(shaderProgram);}
+    /// The set on screen, or being faded to.
+    fn fractal(&self) -> Fractal {
+        Fractal::from_julia(self.julia.filter(|_| self.show_julia))
+    }
 
-    let mut last_frame = Instant::now();
-    let mut elapsed_time = Duration::new(0, 0);
+    /// Shows the Julia set of `c` (Y), or goes back to the Mandelbrot set
+    /// if one is showing, through a cross-fade of `morph_seconds`.
+    fn toggle_julia(&mut self, c: (f64, f64)) {
+        if self.show_julia {
+            self.show_julia = false;
+            info!("back to the Mandelbrot set");
+        } else {
+            self.julia = Some(c);
+            self.show_julia = true;
+            info!("the Julia set of {} {}", c.0, c.1);
+        }
+        if self.morph_seconds <= 0. {
+            self.update_morph(0.);
+        }
+    }
+
+    /// Moves the cross-fade towards the set that should show.
+    fn update_morph(&mut self, dt: f64) {
+        self.julia_weight_before = self.julia_weight;
+        let target = if self.show_julia { 1. } else { 0. };
+        let step = if self.morph_seconds > 0. { dt / self.morph_seconds } else { 1. };
+        self.julia_weight = if target > self.julia_weight {
+            (self.julia_weight + step).min(target)
+        } else {
+            (self.julia_weight - step).max(target)
+        };
+        if self.julia_weight == 0. {
+            self.julia = None;
+        }
+    }
 
-    let mut zoom:f32 = 1.;
-    let mut substeps:i32 = 1000;
-    let mut offsetx:f32 = 0.;
-    let mut offsety:f32 = 0.;
+    /// Shows `fractal` straight away, without a fade.
+    fn set_fractal(&mut self, fractal: &Fractal) {
+        self.julia = match *fractal {
+            Fractal::Julia { c: [re, im] } => Some((re, im)),
+            Fractal::Mandelbrot => None,
+        };
+        self.show_julia = self.julia.is_some();
+        self.julia_weight = if self.show_julia { 1. } else { 0. };
+        self.julia_weight_before = self.julia_weight;
+    }
 
-    while !window.should_close() {
-        let now = Instant::now();
-        let delta_time = now - last_frame;
-        last_frame = now;
+    /// Goes to the view, iterations and coloring an image was saved with.
+    fn open(&mut self, saved: &ViewMetadata) {
+        self.viewport = saved.view(self.viewport.width, self.viewport.height);
+        self.flight = None;
+        self.zoom_velocity = 0.;
+        self.substeps = saved.iterations;
+        self.color_mode = saved.color_mode;
+        self.palette = saved.palette.clone();
+        self.set_fractal(&saved.fractal);
+    }
+
+    /// Whatever `load` read, the way each kind opens.
+    fn open_loaded(&mut self, loaded: &Loaded) -> Result<(), String> {
+        match loaded {
+            Loaded::State(saved) => self.restore(saved),
+            Loaded::Kfr(kfr) => return self.open_kfr(kfr.clone()),
+            Loaded::Png(saved) => self.open(saved),
+        }
+        Ok(())
+    }
+
+    fn session(&self) -> Session {
+        Session {
+            version: session::VERSION,
+            camera: session::Camera::from_view(&self.viewport),
+            iterations: self.substeps,
+            fractal: self.fractal(),
+            color_mode: self.color_mode,
+            palette: self.palette.clone(),
+            ssaa: self.ssaa,
+            transparent_interior: self.transparent_interior,
+            checkerboard: self.checkerboard,
+            lighting: self.relief.lighting(),
+            color_span: self.color_lock,
+            tone: self.tone,
+        }
+    }
+
+    /// What the next run starts from.
+    fn settings(&self, window: &dyn Window, msaa: u32, iterations_per_draw: i32) -> Settings {
+        Settings {
+            version: settings::VERSION,
+            session: Some(self.session()),
+            window: settings::Window { size: window.size(), position: window.position() },
+            msaa,
+            quality: self.quality,
+            export_size: self.export_size,
+            animation_size: self.animation_size,
+            zoom_inertia: self.zoom_inertia,
+            sensitivity: self.sensitivity.curve,
+            clear_color: self.clear_color,
+            iterations_per_draw,
+        }
+    }
+
+    /// Back to the starting view at once (Backspace).
+    fn reset_view(&mut self) {
+        self.viewport.reset();
+        self.zoom_velocity = 0.;
+        self.flight = None;
+    }
+
+    /// Switches to a quality preset's iterations and supersampling (U).
+    fn set_quality(&mut self, quality: Quality) {
+        self.quality = quality;
+        self.substeps = quality.iterations();
+        self.ssaa = quality.ssaa();
+        self.export_ssaa = quality.export_ssaa();
+        info!(
+            "quality {:?}: {} iterations, {2}x{2} supersampling, {3}x{3} for exports",
+            self.quality, self.substeps, self.ssaa, self.export_ssaa
+        );
+    }
+
+    /// Makes a change from the control panel the way the keys would.
+    fn apply(&mut self, change: Change) {
+        match change {
+            Change::Navigate(line) => self.run_commands(&line),
+            Change::Iterations(iterations) => self.substeps = iterations,
+            Change::Julia(julia) => {
+                if julia != self.show_julia {
+                    self.toggle_julia(self.viewport.center);
+                }
+            }
+            Change::ColorMode(mode) => {
+                self.color_mode = mode;
+                info!("color mode: {:?}", self.color_mode);
+            }
+            Change::Quality(quality) => self.set_quality(quality),
+            Change::Ssaa(ssaa) => self.ssaa = ssaa.clamp(1, 4),
+            Change::Tone(tone) => self.tone = tone,
+            Change::Screenshot => self.screenshot_requested = true,
+            Change::Reset => self.reset_view(),
+            Change::Bookmarks => self.bookmarks.open = !self.bookmarks.open,
+        }
+    }
+
+    /// Runs a line of navigation commands, saying what it did or why it didn't.
+    fn run_commands(&mut self, line: &str) {
+        let result = navigate::parse(line).and_then(|commands| navigate::apply(&self.viewport, &commands)).map(|view| {
+            self.viewport = view;
+            self.flight = None;
+            self.zoom_velocity = 0.;
+            format!("center {} {}, scale {:e}", view.center.0, view.center.1, view.scale)
+        });
+        match &result {
+            Ok(done) => info!("{done}"),
+            Err(err) => warn!("{err}"),
+        }
+        self.console.set_result(result);
+    }
+
+    /// Puts everything a saved state has back; `session::load` checked the camera.
+    fn restore(&mut self, saved: &Session) {
+        if let Ok(view) = saved.camera.view(self.viewport.width, self.viewport.height) {
+            self.viewport = view;
+        }
+        self.flight = None;
+        self.zoom_velocity = 0.;
+        self.substeps = saved.iterations;
+        self.color_mode = saved.color_mode;
+        self.palette = saved.palette.clone();
+        self.ssaa = saved.ssaa.clamp(1, 4);
+        self.transparent_interior = saved.transparent_interior;
+        self.checkerboard = saved.checkerboard;
+        self.relief.set(saved.lighting);
+        self.color_lock = saved.color_span;
+        self.tone = saved.tone;
+        self.set_fractal(&saved.fractal);
+    }
+
+    /// Goes to a Kalles Fraktaler location, keeping the coloring.
+    fn open_kfr(&mut self, kfr: Kfr) -> Result<(), String> {
+        let view = kfr.camera()?.view(self.viewport.width, self.viewport.height)?;
+        self.viewport = view;
+        self.flight = None;
+        self.zoom_velocity = 0.;
+        if let Some(iterations) = kfr.iterations() {
+            self.substeps = iterations;
+        }
+        self.kfr = Some((kfr, view));
+        Ok(())
+    }
 
-        // Update elapsed time
-        elapsed_time += delta_time;
+    /// The current view as a Kalles Fraktaler location.
+    fn kfr(&self) -> Kfr {
+        match &self.kfr {
+            Some((kfr, view)) if *view == self.viewport => {
+                kfr.with_view(&kfr.camera().expect("checked when opened"), self.substeps)
+            }
+            Some((kfr, _)) => kfr.with_view(&session::Camera::from_view(&self.viewport), self.substeps),
+            None => Kfr::from_view(&session::Camera::from_view(&self.viewport), self.substeps),
+        }
+    }
 
-        glfw.poll_events();
+    /// Flies back to the home view (O), in the same time however deep the view is.
+    fn fly_home(&mut self) {
+        self.zoom_velocity = 0.;
+        self.flight = Some(Flight::new(self.viewport, self.home, OVERVIEW_SECONDS));
+    }
+
+    /// Glides to the nearest magnification that is a power of `base` and
+    /// reports it.
+    fn snap_zoom(&mut self, base: f64) {
+        let (scale, exponent) = self.viewport.snapped_scale(base);
+        info!("magnification {base}^{exponent} = {:e}x", scale.recip());
+        self.zoom_velocity = 0.;
+        self.flight = Some(Flight::new(self.viewport, Viewport { scale, ..self.viewport }, SNAP_SECONDS));
+    }
 
-        if (window.get_key(Key::I) == Action::Press){
-            zoom /= 1.01;
+    /// Glides to the pixel of `samples` (the raw output of the whole view)
+    /// that escapes last, smooth iteration breaking ties of the escape
+    /// iteration. Says why not and stays put when nothing in view escapes, or
+    /// when more than one pixel is the deepest, as one of them would be a guess.
+    fn center_on_deepest(&mut self, samples: &[[f32; 4]]) {
+        // channel 1 is the smooth iteration, 3 set inside the set
+        let escaped = || samples.iter().enumerate().filter(|(_, sample)| sample[3] == 0.);
+        let Some(deepest) = escaped().map(|(_, sample)| sample[1]).reduce(f32::max) else {
+            info!("nothing in view escapes within {} iterations, no deepest point to center on", self.substeps);
+            return;
+        };
+        let at: Vec<usize> = escaped().filter(|(_, sample)| sample[1] == deepest).map(|(index, _)| index).collect();
+        if let [index] = at[..] {
+            let width = self.viewport.width as usize;
+            let mut center = self.viewport.screen_to_complex(((index % width) as f64 + 0.5, (index / width) as f64 + 0.5));
+            if self.log_polar {
+                center = self.viewport.log_polar(center);
+            }
+            info!("centering on {} {}, which escapes after {deepest:.2} iterations", center.0, center.1);
+            self.zoom_velocity = 0.;
+            self.flight = Some(Flight::new(self.viewport, Viewport { center, ..self.viewport }, SNAP_SECONDS));
+        } else {
+            info!("{} pixels tie for the deepest at {deepest:.2} iterations, not picking one", at.len());
         }
-        if (window.get_key(Key::K) == Action::Press){
-            zoom *= 1.01;
+    }
+
+    /// The path the next animation takes: the keyframes, or with fewer than
+    /// two, from the one keyframe (or the home view) to the current view.
+    fn planned_path(&self) -> KeyframePath {
+        let mut keyframes = self.keyframes.clone();
+        if keyframes.len() < 2 {
+            if keyframes.is_empty() {
+                let iterations = animation::auto_iterations(self.substeps, self.viewport.scale, self.home.scale);
+                keyframes.push(Keyframe::from_view(&self.home, iterations));
+            }
+            keyframes.push(Keyframe::from_view(&self.viewport, self.substeps));
         }
-        if (window.get_key(Key::W) == Action::Press){
-            offsety += zoom/150.;
+        KeyframePath::new(keyframes, self.animation_seconds).expect("at least two keyframes")
+    }
+
+    /// Plays the planned animation in the window at its export length.
+    fn start_playback(&mut self) {
+        let path = self.planned_path();
+        self.zoom_velocity = 0.;
+        self.flight = None;
+        self.playback = Some(Playback::new(path, (self.viewport, self.substeps)));
+    }
+
+    /// Ends a preview and goes back to the view it started from.
+    fn stop_playback(&mut self) {
+        if let Some(playback) = self.playback.take() {
+            let (viewport, substeps) = playback.restore;
+            self.viewport = Viewport { width: self.viewport.width, height: self.viewport.height, ..viewport };
+            self.substeps = substeps;
         }
-        if (window.get_key(Key::S) == Action::Press){
-            offsety -= zoom/150.;
+    }
+
+    /// Advances a running preview; like a flight, it owns the view meanwhile.
+    fn update_playback(&mut self, dt: f64) -> bool {
+        let Some(playback) = &mut self.playback else {
+            return false;
+        };
+        playback.step(dt);
+        self.viewport_before = Some(self.viewport);
+        self.viewport = playback.view(self.viewport.width, self.viewport.height);
+        self.substeps = playback.iterations();
+        true
+    }
+
+    /// Advances a running flight; returns whether one is running, in which
+    /// case it owns the view and navigation keys are ignored.
+    fn update_flight(&mut self, dt: f64) -> bool {
+        let Some(flight) = &mut self.flight else {
+            return false;
+        };
+        self.viewport_before = Some(self.viewport);
+        self.viewport = flight.step(&self.viewport, dt);
+        if flight.finished() {
+            self.flight = None;
         }
-        if (window.get_key(Key::D) == Action::Press){
-            offsetx += zoom/150.;
+        true
+    }
+
+    /// The view to present, `alpha` of the way into the next update step.
+    fn shown_viewport(&self, alpha: f64) -> Viewport {
+        match &self.viewport_before {
+            Some(before) => timestep::lerp_view(before, &self.viewport, alpha),
+            None => self.viewport,
+        }
+    }
+
+    /// `direction` is negative while zooming in, positive while zooming out and 0
+    /// when no zoom key is held; its magnitude scales the zoom speed.
+    fn update_zoom(&mut self, direction: f64, dt: f64) {
+        if direction != 0. {
+            self.zoom_velocity = direction * ZOOM_RATE;
+        } else if self.zoom_inertia {
+            self.zoom_velocity *= (-ZOOM_FRICTION * dt).exp();
+            if self.zoom_velocity.abs() < 1e-3 {
+                self.zoom_velocity = 0.;
+            }
+        } else {
+            self.zoom_velocity = 0.;
         }
-        if (window.get_key(Key::A) == Action::Press){
-            offsetx -= zoom/150.;
+        self.viewport.zoom_by((self.zoom_velocity * dt).exp());
+    }
+}
+
+/// The cross-fade's morph for the shader at `weight`: eased, so the fade
+/// starts and ends gently.
+fn eased(weight: f64) -> f32 {
+    (weight * weight * (3. - 2. * weight)) as f32
+}
+
+/// Has the renderer compile the shaders in `dir`, or in a `shaders` directory
+/// next to the executable without one, and returns the directory used.
+fn load_shaders(dir: Option<&std::path::Path>) -> Result<Option<std::path::PathBuf>, StartError> {
+    let Some(dir) = shader_dir::find(dir).map_err(StartError::ShaderDir)? else {
+        return Ok(None);
+    };
+    match shader_dir::read(&dir).map_err(StartError::Shaders)? {
+        (None, None) => Ok(None),
+        (vertex, fragment) => {
+            info!("using the shaders in {}", dir.display());
+            renderer::set_shader_sources(vertex, fragment);
+            Ok(Some(dir))
         }
-        if (window.get_key(Key::Backspace) == Action::Press){
-            offsetx = 0.;
-            offsety = 0.;
-            zoom = 1.;
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            error!("{err}");
+            ExitCode::from(err.exit_code() as u8)
         }
-        if (window.get_key(Key::Up) == Action::Press){
-            substeps += 1;
+    }
+}
+
+/// The explorer, unless the options ask for one of the other modes; those
+/// exit with their own status when done.
+fn run() -> Result<(), StartError> {
+    let options = cli::parse();
+    if let Some(threads) = options.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads as usize)
+            .build_global()
+            .map_err(|source| StartError::Threads { threads, source })?;
+    }
+    let shader_dir = load_shaders(options.shader_dir.as_deref())?;
+
+    match &options.command {
+        Some(cli::Command::Render(args)) => std::process::exit(headless::run(args, &options)),
+        Some(cli::Command::Keyframes(args)) => std::process::exit(keyframes::run(args)),
+        None => {}
+    }
+
+    if let Some(path) = &options.batch {
+        std::process::exit(batch::run(path, &options));
+    }
+
+    if let Some(port) = options.serve {
+        #[cfg(feature = "serve")]
+        std::process::exit(serve::run(port, &options));
+        #[cfg(not(feature = "serve"))]
+        return Err(StartError::NotCompiledIn { option: format!("--serve {port}"), feature: "serve" });
+    }
+
+    if let Some(path) = &options.export_palette {
+        // the palette the window would open with, named as F10 names it
+        let palette = options.palette.clone().unwrap_or_else(Palette::grayscale);
+        let path = path.clone().unwrap_or_else(|| format!("palette_{}.png", palette.name).into());
+        let strip = palette::strip_image(&palette.build().texels, PALETTE_STRIP_HEIGHT);
+        return strip.save(&path).map_err(|err| StartError::Write { path, reason: err.to_string() });
+    }
+
+    if let Some(path) = &options.dump_shader {
+        let source = renderer::fragment_shader_source();
+        match path {
+            Some(path) => {
+                std::fs::write(path, source).map_err(|err| StartError::Write { path: path.clone(), reason: err.to_string() })?;
+            }
+            None => print!("{source}"),
         }
-        if (window.get_key(Key::Down) == Action::Press && substeps > 0){
-            substeps -= 1;
+        return Ok(());
+    }
+
+    if options.set_wallpaper {
+        std::process::exit(wallpaper::run(&options));
+    }
+
+    if options.bench {
+        std::process::exit(bench::run(options.bench_json.as_deref()));
+    }
+
+    if options.print_caps {
+        std::process::exit(print_caps());
+    }
+
+    if options.terminal {
+        let result = terminal::canvas_size().and_then(|(width, height)| {
+            terminal::run(initial_viewport(&options, width, height), options.quality.iterations(), options.ascii)
+        });
+        return result.map_err(StartError::Terminal);
+    }
+
+    let restored_window = options.restored.as_ref().map(|restored| restored.window);
+    let (width, height) = restored_window.map_or(options.window_size, |window| (window.size.0.max(1) as u32, window.size.1.max(1) as u32));
+    // a view that can't be shown is reported before there is a window
+    let loaded = options
+        .load
+        .as_ref()
+        .map(|what| match load::load(what).and_then(|loaded| check_depth(loaded, width, height)) {
+            Ok(loaded) => Ok(OpenRequest::Loaded(what.clone(), Box::new(loaded))),
+            Err(reason) => Err(StartError::Load { what: what.clone(), reason }),
+        })
+        .transpose()?;
+
+    let gamepad = options.gamepad.then(Gamepad::new).transpose()?;
+    let window = platform::open(&WindowOptions {
+        size: (width, height),
+        position: restored_window.map(|restored| restored.position),
+        fullscreen: options.fullscreen,
+        transparent: options.transparent_window,
+        always_on_top: options.always_on_top,
+        msaa: options.msaa,
+        vsync: options.vsync,
+        gles: options.gles,
+        title: "🤓".to_string(),
+    })?;
+    // Ctrl+C in the terminal closes the window like Escape, so the settings get saved
+    job::catch_interrupt();
+    // and a panic saves the view on its own
+    recovery::install();
+
+    // the window stays on this thread to keep taking events while a frame draws
+    render_thread::run(window, gamepad, move |mut window| explore(&mut window, options, shader_dir, loaded))
+}
+
+/// `--print-caps`: the probe of an offscreen context, on stdout.
+fn print_caps() -> i32 {
+    match offscreen::create() {
+        Ok(_context) => {
+            print!("{}", GpuCapabilities::current());
+            0
         }
-        println!("{substeps}");
-        for (_, event) in glfw::flush_messages(&events) {
-            handle_window_event(&mut window, event);
+        Err(err) => {
+            error!("--print-caps: no GL context: {err}");
+            headless::EXIT_RENDER_FAILED
         }
+    }
+}
+
+/// The explorer's window on the render thread, open until it's closed.
+fn explore(window: &mut RemoteWindow, options: cli::Options, shader_dir: Option<std::path::PathBuf>, loaded: Option<OpenRequest>) -> Result<(), StartError> {
+    mandelplotter::load_gl(|s| window.proc_address(s));
+    let gles = renderer::is_gles();
 
+    if options.msaa > 1 {
+        // drivers may hand out fewer samples than asked for
+        let mut samples = 0;
         unsafe {
-            ClearColor(0., 0., 0., 0.);
-            Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT);
+            // always on in OpenGL ES, which has no switch for it
+            if !gles {
+                Enable(MULTISAMPLE);
+            }
+            GetIntegerv(SAMPLES, &mut samples);
+        }
+        if samples < options.msaa as i32 {
+            warn!("asked for {}x MSAA, got {samples}x", options.msaa);
+        }
+    }
+
+    let (major, minor) = window.context_version();
+    let caps = GpuCapabilities::current();
+    info!("{}", caps.summary());
+    if gles {
+        if options.gles != gles {
+            warn!("no desktop OpenGL 3.3 context, using OpenGL ES");
+        }
+    } else if options.gles {
+        warn!("no OpenGL ES 3.0 context, using desktop OpenGL {major}.{minor}");
+    }
+    // OpenGL ES 3.0 and 2.1 run the shaders too, translated
+    let gpu = if gles && major < 3 {
+        Err(format!("OpenGL ES {major}.{minor} is too old, the fractal shader needs 3.0"))
+    } else if !gles && (major, minor) < (2, 1) {
+        Err(format!("OpenGL {major}.{minor} is too old, the fractal shader needs 2.1"))
+    } else {
+        Renderer::new().map_err(|err| format!("the fractal shader did not build:\n{err}"))
+    };
+    let mut backend = match gpu {
+        Ok(renderer) => {
+            if renderer.dialect() == Dialect::Glsl120 {
+                warn!("OpenGL {major}.{minor} has no GLSL 3.30, drawing with the shaders translated to GLSL 1.20");
+                warn!("the supersampling jitter then ignores the seed and differs from the software renderer's");
+            }
+            let gated = [
+                (Feature::FloatTargets, "no F8 readout or Shift+F, and --iterations-per-draw draws in one go"),
+                (Feature::Norm16Targets, "no `--bit-depth 16` screenshots or exports"),
+                (Feature::Framebuffers, "no progressive rendering or exports"),
+            ];
+            for (feature, instead) in gated {
+                if let Err(reason) = caps.require(feature) {
+                    warn!("{reason}: {instead}");
+                }
+            }
+            renderer.set_iterations_per_draw(Some(options.iterations_per_draw));
+            Backend::Gpu(renderer)
+        }
+        Err(reason) => {
+            warn!("{reason}");
+            warn!("falling back to the software renderer: everything works, but expect it to be slow");
+            info!("CPU threads: {} (--threads sets how many)", rayon::current_num_threads());
+            Backend::Software(Box::new(SoftwareRenderer::new().map_err(StartError::Blit)?))
+        }
+    };
+
+    let style = BackgroundStyle { fit: options.background_fit, blend: options.blend };
+    let background = options.background.as_ref().and_then(|path| match Background::load(path, style) {
+        Ok(background) => {
+            backend.set_background(background);
+            Some(style)
+        }
+        Err(err) => {
+            warn!("not loading the background: {err}");
+            None
+        }
+    });
+
+    // only the GPU draws with the shaders
+    let mut shader_watch = shader_dir.filter(|_| matches!(backend, Backend::Gpu(_))).and_then(|dir| match ShaderWatch::new(&dir) {
+        Ok(watch) => {
+            info!("watching {} to reload the shaders when they change", dir.display());
+            Some(watch)
+        }
+        Err(err) => {
+            warn!("not reloading the shaders when they change: {err}");
+            None
+        }
+    });
+
+    let mut capturer = Capturer::new().map_err(StartError::Capture)?;
+    // the overlay renderer needs vertex array objects, and its shaders are desktop GLSL
+    let mut gui = if gles {
+        warn!("the UI overlays are drawn with desktop GLSL, which OpenGL ES can't compile; they are disabled");
+        None
+    } else if major >= 3 {
+        Some(Gui::new(&mut *window))
+    } else {
+        warn!("OpenGL {major}.{minor} can't draw the UI overlays, they are disabled");
+        None
+    };
+
+    // what the title bar shows, changed to the progress while exports run
+    let mut title = "🤓".to_string();
+    let mut clock = Clock::new();
+
+    let seed = options.seed.unwrap_or_else(|| {
+        // a clock before 1970 just makes for a fixed seed
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+    });
+    info!("seed {seed} (pass --seed {seed} to reproduce this session)");
+
+    let keyframes = match &options.keyframes {
+        Some(path) if path.exists() => keyframes::load(path).unwrap_or_else(|err| {
+            warn!("not loading keyframes: {err}");
+            Vec::new()
+        }),
+        _ => Vec::new(),
+    };
+    let (fb_width, fb_height) = window.framebuffer_size();
+    let home = initial_viewport(&options, fb_width as u32, fb_height as u32);
+    let mut state = ViewState {
+        viewport: home,
+        home,
+        flight: None,
+        substeps: options.iter,
+        ssaa: options.quality.ssaa(),
+        quality: options.quality,
+        zoom_velocity: 0.,
+        zoom_inertia: options.restored.as_ref().is_none_or(|restored| restored.zoom_inertia),
+        sensitivity: Sensitivity::new(options.sensitivity),
+        selection: Selection::new(),
+        region_edge: options.region_edge,
+        console: Console::new(),
+        control_panel: ControlPanel::new(),
+        commands: options.stdin_commands.then(navigate::read_stdin),
+        // the background only shows through a transparent interior, as does the desktop
+        transparent_interior: background.is_some() || window.is_transparent(),
+        color_mode: options.color_mode,
+        color_lock: None,
+        tone: Tone::default(),
+        checkerboard: true,
+        clear_color: options.clear_color,
+        see_through: window.is_transparent(),
+        show_slow: false,
+        slow_tint: SlowTint { color: options.slow_color, threshold: options.slow_threshold as f32 },
+        relief: Relief::new(options.relief, options.lighting()),
+        trail: OrbitTrail::new(),
+        show_components: false,
+        photo: Photo::new(options.photo_countdown, options.photo_scale),
+        background,
+        screenshot_requested: false,
+        deepest_requested: false,
+        copy_requested: false,
+        timelapse: None,
+        timelapse_every: match options.timelapse_frames {
+            Some(frames) => timelapse::Every::Frames(frames),
+            None => timelapse::Every::Seconds(options.timelapse_seconds),
+        },
+        timelapse_limits: timelapse::Limits {
+            max_images: options.timelapse_max_images,
+            max_bytes: options.timelapse_max_mb.map(|mb| (mb * 1e6) as u64),
+        },
+        palette_export_requested: false,
+        palette_changed: options.palette.is_some(),
+        open_requested: loaded
+            .or_else(|| options.location.clone().map(OpenRequest::Location))
+            .or_else(|| options.open.clone().or_else(|| options.state.clone().filter(|path| path.exists())).map(OpenRequest::File))
+            .or_else(|| options.recovered.clone().map(|saved| OpenRequest::Recovered(Box::new(saved))))
+            .or_else(|| Some(OpenRequest::Restored(Box::new(options.restored.clone()?.session?)))),
+        state_path: options.state.clone().unwrap_or_else(|| "state.json".into()),
+        bookmarks: Bookmarks::load(options.bookmarks.as_deref().unwrap_or("bookmarks.json".as_ref())),
+        kfr: None,
+        palette: options.palette.clone().unwrap_or_else(Palette::grayscale),
+        mapping: CoordinateMapping::FragCoord,
+        log_polar: options.log_polar,
+        palette_editor: PaletteEditor::new(),
+        area: None,
+        seed,
+        export: None,
+        export_size: options.export_size,
+        export_ssaa: options.export_ssaa,
+        bit_depth: options.bit_depth,
+        keyframes,
+        keyframes_path: options.keyframes.clone().unwrap_or_else(|| "keyframes.json".into()),
+        animation: None,
+        keep_partial: options.keep_partial,
+        playback: None,
+        viewport_before: None,
+        recording: None,
+        record_tolerance: options.record_tolerance,
+        julia_trace: None,
+        julia: None,
+        show_julia: false,
+        julia_weight: 0.,
+        julia_weight_before: 0.,
+        morph_seconds: options.morph_seconds,
+        auto_iterations: AutoIterations::new(options.auto_iterations),
+        hover: None,
+        precision: Precision::new(),
+        // the window's framebuffer can't take the blit when multisampled
+        progressive: Progressive::new(if options.msaa > 1 { 0 } else { options.progressive_above }),
+        animation_size: options.animation_size,
+        animation_fps: options.animation_fps,
+        animation_seconds: options.animation_seconds,
+    };
+    if let Some(c) = &options.julia {
+        state.set_fractal(&Fractal::from_julia(Some((c[0], c[1]))));
+    }
+
+    while !window.should_close() {
+        let Tick { delta: delta_time, steps } = clock.tick(Instant::now());
+        let step = Clock::step().as_secs_f64();
+
+        let events = window.poll_events();
+
+        for _ in 0..steps {
+            state.update_morph(step);
+            state.update_flight(step);
+            state.update_playback(step);
+        }
+        let flying = state.flight.is_some() || state.playback.is_some();
+        if !flying {
+            state.viewport_before = None;
+        }
+        // keys typed into a UI text field are not for the view
+        let typing = gui.as_ref().filter(|_| !state.photo.on).is_some_and(|gui| gui.wants_keyboard());
+        if !typing {
+            let dt = delta_time.as_secs_f64();
+            let speed = speed_modifier(&*window);
+            let pad = window.take_gamepad();
+            let held = |keys: &[Key]| keys.iter().any(|&key| window.is_held(key));
+            let zoom_speed = speed * state.sensitivity.zoom(held(&[Key::I, Key::K]) || pad.zoom != 0., dt);
+            let mut zoom_direction = zoom_speed * pad.zoom;
+            if window.is_held(Key::I) {
+                zoom_direction -= zoom_speed;
+            }
+            if window.is_held(Key::K) {
+                zoom_direction += zoom_speed;
+            }
+            if !flying {
+                for _ in 0..steps {
+                    state.update_zoom(zoom_direction, step);
+                }
+            }
+            // with Ctrl, S and the others are shortcuts (Ctrl+S, Ctrl+Shift+S)
+            let control = held(&[Key::LeftControl, Key::RightControl]);
+            let keys_pan = !control && held(&[Key::W, Key::A, Key::S, Key::D]);
+            let pan = speed * state.sensitivity.pan(keys_pan || pad.pan != (0., 0.), dt) * PAN_RATE * dt;
+            if pad.pan != (0., 0.) && !flying {
+                state.viewport.pan(pan * pad.pan.0, pan * pad.pan.1);
+            }
+            if (window.is_held(Key::W)) && !control && !flying {
+                state.viewport.pan(0., pan);
+            }
+            if (window.is_held(Key::S)) && !control && !flying {
+                state.viewport.pan(0., -pan);
+            }
+            if (window.is_held(Key::D)) && !control && !flying {
+                state.viewport.pan(pan, 0.);
+            }
+            if (window.is_held(Key::A)) && !control && !flying {
+                state.viewport.pan(-pan, 0.);
+            }
+            let turn = speed * ROTATE_RATE * dt;
+            if (window.is_held(Key::Q)) && !flying {
+                state.viewport.rotate_by(turn);
+            }
+            if (window.is_held(Key::E)) && !flying {
+                state.viewport.rotate_by(-turn);
+            }
+            if (window.is_held(Key::Backspace)) || pad.reset {
+                state.reset_view();
+            }
+            if (window.is_held(Key::Up)) || pad.more_iterations {
+                state.substeps += 1;
+            }
+            if (window.is_held(Key::Down) || pad.fewer_iterations) && state.substeps > 0 {
+                state.substeps -= 1;
+            }
+            state.screenshot_requested |= pad.screenshot;
+        }
+        trace!("frame {:.1} ms, {} iterations", delta_time.as_secs_f64() * 1e3, state.substeps);
+        for event in events {
+            // hidden in photo mode, the UI doesn't take any
+            if let Some(gui) = gui.as_mut().filter(|_| !state.photo.on) {
+                gui.handle_event(&event);
+                if gui.captures(&event) {
+                    continue;
+                }
+            }
+            handle_window_event(&mut *window, &mut state, event);
+        }
+
+        let lines: Vec<String> = state.commands.as_ref().map_or_else(Vec::new, |commands| commands.try_iter().collect());
+        for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+            state.run_commands(line);
+        }
 
-            Uniform1f(
-                GetUniformLocation(shaderProgram, CString::new("time").expect("aaaaa demonio").as_ptr()),
-                elapsed_time.as_secs_f32()
-            );
+        if let Some(job) = &state.area {
+            if !job.matches(&state.viewport, state.substeps) {
+                info!("area estimate stopped: the view changed");
+                state.area = None;
+            } else if let Some(estimate) = job.poll() {
+                info!(
+                    "area in view ≈ {:.6e} ± {:.1e} (95%, {} samples at {} iterations)",
+                    estimate.area(), estimate.margin(), estimate.samples, state.substeps
+                );
+            }
+        }
 
-            Uniform1f(
-                GetUniformLocation(shaderProgram, CString::new("zoom").expect("aaaaa demonio").as_ptr()),
-                zoom
-            );
+        if let Some(recorder) = &mut state.recording {
+            recorder.step(delta_time.as_secs_f64(), &state.viewport, state.substeps);
+        }
+        if let Some(trace) = &mut state.julia_trace {
+            let c = state.viewport.screen_to_complex(cursor_pixel(&*window, &state.viewport));
+            if trace.last() != Some(&c) {
+                trace.push(c);
+            }
+        }
 
-            Uniform1i(
-                GetUniformLocation(shaderProgram, CString::new("substeps").expect("aaaaa demonio").as_ptr()),
-                substeps
-            );
+        if let Some(request) = state.open_requested.take() {
+            let opened = match &request {
+                OpenRequest::File(path) => load::file(path).and_then(|loaded| state.open_loaded(&loaded)),
+                OpenRequest::Location(text) => location::decode(text).map(|saved| state.restore(&saved)),
+                OpenRequest::Restored(saved) => {
+                    state.restore(saved);
+                    // over the look the last session left
+                    state.relief.on |= options.relief;
+                    Ok(())
+                }
+                OpenRequest::Loaded(_, loaded) => state.open_loaded(loaded),
+                OpenRequest::Recovered(saved) => {
+                    state.restore(saved);
+                    Ok(())
+                }
+                OpenRequest::Bookmark(bookmark) => {
+                    state.restore(&bookmark.session);
+                    Ok(())
+                }
+            };
+            match (opened, &request) {
+                (Ok(()), OpenRequest::File(path)) => {
+                    backend.set_palette(&state.palette.build());
+                    info!("opened {}", path.display());
+                }
+                (Ok(()), OpenRequest::Location(_)) => {
+                    backend.set_palette(&state.palette.build());
+                    info!("opened the location");
+                }
+                (Ok(()), OpenRequest::Loaded(what, _)) => {
+                    backend.set_palette(&state.palette.build());
+                    info!("loaded {what}");
+                }
+                (Ok(()), OpenRequest::Restored(_)) => {
+                    backend.set_palette(&state.palette.build());
+                    info!("picked up where the last session left off (--fresh starts over)");
+                }
+                (Ok(()), OpenRequest::Recovered(_)) => {
+                    backend.set_palette(&state.palette.build());
+                    info!("back at the view from before the crash");
+                }
+                (Ok(()), OpenRequest::Bookmark(bookmark)) => {
+                    backend.set_palette(&state.palette.build());
+                    info!("opened the bookmark {}", bookmark.label());
+                }
+                (Err(err), _) => warn!("not opening: {err}"),
+            }
+        }
 
-            Uniform2f(
-                GetUniformLocation(shaderProgram, CString::new("offset").expect("aaaaa demonio").as_ptr()),
-                offsetx,
-                offsety
-            );
+        step_export(&mut backend, &mut state);
+        step_animation(&mut backend, &mut state);
+        state.precision.update(backend.precision(), &state.viewport);
+        let progress = state.export.as_ref().map(|(_, _, job)| job).or(state.animation.as_ref().map(Animation::job));
+        let progress_title = match progress {
+            Some(job) => format!("🤓 {} — {}", state.palette.name, job.status()),
+            None if state.photo.on => format!("🤓 {} — {}", state.palette.name, state.photo.status()),
+            None => format!("🤓 {}", state.palette.name),
+        };
+        if progress_title != title {
+            window.set_title(&progress_title);
+            title = progress_title;
+        }
 
-            BindVertexArray(VAO);
-            DrawArrays(TRIANGLES, 0, 6);
+        if state.auto_iterations.on && state.playback.is_none() {
+            let base = state.quality.iterations();
+            let dt = delta_time.as_secs_f64();
+            state.substeps = state.auto_iterations.step(&state.viewport, state.home.scale, base, state.substeps, dt, state.progressive.above);
+        }
+        let shown = state.shown_viewport(clock.alpha());
+        let params = DrawParams {
+            view: shown.uniforms(),
+            morph: eased(timestep::lerp(state.julia_weight_before, state.julia_weight, clock.alpha())),
+            ..state.draw_params(clock.time() as f32)
+        };
+        if let Some(mut hover) = state.hover.take() {
+            let cursor = cursor_pixel(&*window, &state.viewport);
+            // the raw output shows whichever set is more than half faded in
+            let julia = params.julia.filter(|_| params.morph >= 0.5);
+            let probed = Probed { viewport: state.viewport, iterations: state.substeps, julia };
+            let inside = (0. ..state.viewport.width as f64).contains(&cursor.0) && (0. ..state.viewport.height as f64).contains(&cursor.1);
+            if inside && hover.stale(cursor, &probed) {
+                match backend.probe(&state, &params, cursor) {
+                    Ok(sample) => hover.store(cursor, probed, sample),
+                    Err(err) => warn!("reading back the pixel under the cursor failed: {err}"),
+                }
+            }
+            state.hover = Some(hover);
+        }
+        if state.deepest_requested {
+            state.deepest_requested = false;
+            match backend.raw_view(&state, &params) {
+                Ok(samples) => state.center_on_deepest(&samples),
+                Err(err) => warn!("reading back the iterations in view failed: {err}"),
+            }
+        }
+        if state.screenshot_requested {
+            state.screenshot_requested = false;
+            backend.screenshot(&state, &params, &mut capturer, Target::File(capture::capture_path("screenshot")));
+        }
+        if state.photo.step(delta_time.as_secs_f64()) {
+            backend.photo(&state, &params, &mut capturer);
+        }
+        if state.copy_requested {
+            state.copy_requested = false;
+            backend.screenshot(&state, &params, &mut capturer, Target::Clipboard);
+        }
+        if let Some(timelapse) = &mut state.timelapse {
+            match timelapse.step(delta_time.as_secs_f64(), &state.viewport, state.substeps) {
+                Step::Wait => {}
+                Step::Capture(path) => backend.screenshot(&state, &params, &mut capturer, Target::File(path)),
+                Step::Stop(reason) => {
+                    info!("timelapse stopped, {reason}: {} frames in {}", timelapse.frame_count(), timelapse.dir().display());
+                    state.timelapse = None;
+                }
+            }
+        }
+
+        if state.palette_export_requested {
+            state.palette_export_requested = false;
+            let path = format!("palette_{}.png", state.palette.name);
+            match palette::strip_image(&backend.palette_texels(&state), PALETTE_STRIP_HEIGHT).save(&path) {
+                Ok(()) => info!("saved {path}"),
+                Err(err) => warn!("failed to save {path}: {err}"),
+            }
+        }
+
+        if state.palette_changed {
+            state.palette_changed = false;
+            backend.set_palette(&state.palette.build());
+        }
+        if let (Some(watch), Backend::Gpu(renderer)) = (&mut shader_watch, &mut backend) {
+            if watch.update(renderer) {
+                // the refined picture is of the old shaders
+                state.progressive.release();
+            }
+        }
+        backend.draw(&mut state, &shown, &params);
+        // photo mode shows the view alone
+        if let Some(gui) = gui.as_mut().filter(|_| !state.photo.on) {
+            let mut palette_changed = false;
+            let mut preview_closed = false;
+            let mut entered = None;
+            let mut picked = None;
+            let mut changes = Vec::new();
+            let mode = backend.precision();
+            gui.draw(&*window, delta_time, |ui| {
+                palette_changed = state.palette_editor.draw(ui, &mut state.palette);
+                state.sensitivity.draw(ui);
+                state.relief.draw(ui);
+                state.trail.draw(ui, &state.viewport, params.time, params.julia.filter(|_| params.morph >= 0.5));
+                if state.show_components && (params.julia.is_none() || params.morph < 0.5) {
+                    components::draw(ui, &state.viewport);
+                }
+                if let Some(watch) = &shader_watch {
+                    watch.draw(ui);
+                }
+                entered = state.console.draw(ui);
+                let controls = Controls {
+                    iterations: state.substeps,
+                    julia: state.show_julia,
+                    color_mode: state.color_mode,
+                    quality: state.quality,
+                    ssaa: state.ssaa,
+                    tone: state.tone,
+                };
+                changes = state.control_panel.draw(ui, &state.viewport, &controls);
+                picked = state.bookmarks.draw(ui);
+                state.precision.draw(ui, mode, &state.viewport);
+                state.selection.draw(ui, &state.viewport);
+                state.progressive.draw_progress(ui);
+                if let Some(playback) = &mut state.playback {
+                    preview_closed = !playback.draw(ui);
+                }
+                if let Some(hover) = &state.hover {
+                    // not over the panels
+                    if !ui.io().want_capture_mouse {
+                        hover.draw(ui);
+                    }
+                }
+            });
+            state.palette_changed |= palette_changed;
+            if let Some(line) = entered {
+                state.run_commands(&line);
+            }
+            for change in changes {
+                state.apply(change);
+            }
+            match picked {
+                Some(Picked::Add(note)) => {
+                    let session = state.session();
+                    state.bookmarks.add(note, session);
+                    info!("bookmarked the view");
+                }
+                Some(Picked::Open(bookmark)) => state.open_requested = Some(OpenRequest::Bookmark(bookmark)),
+                None => {}
+            }
+            if preview_closed {
+                state.stop_playback();
+            }
         }
         window.swap_buffers();
+        recovery::snapshot(state.session());
+        capturer.poll();
+        if job::interrupted() {
+            window.set_should_close(true);
+        }
+    }
+
+    let settings = state.settings(&*window, options.msaa, options.iterations_per_draw);
+    if let Err(err) = settings::save(&settings) {
+        warn!("failed to save the settings: {err}");
+    }
+    // don't lose screenshots taken right before closing
+    capturer.shutdown();
+    // free the GL objects while the window's context is still alive
+    drop(gui);
+    drop(backend);
+    Ok(())
+}
+
+/// Whoever is drawing the fractal this session.
+enum Backend {
+    Gpu(Renderer),
+    Software(Box<SoftwareRenderer>),
+}
+
+impl Backend {
+    /// What the fractal is computed in.
+    fn precision(&self) -> precision::Mode {
+        match self {
+            Backend::Gpu(_) => precision::Mode::F32,
+            Backend::Software(_) => precision::Mode::F64,
+        }
+    }
+
+    fn draw(&mut self, state: &mut ViewState, shown: &Viewport, params: &DrawParams) {
+        match self {
+            Backend::Gpu(renderer) if state.progressive.applies(params) => {
+                if let Err(err) = state.progressive.draw(renderer, params, &state.palette) {
+                    warn!("progressive rendering is off: {err}");
+                    state.progressive = Progressive::new(0);
+                    renderer.draw(params);
+                }
+            }
+            Backend::Gpu(renderer) => {
+                state.progressive.release();
+                renderer.draw(params);
+            }
+            Backend::Software(software) => software.draw(shown, params, &state.palette.build()),
+        }
+    }
+
+    /// Captures the current view to `target` as it would be saved: no
+    /// preview checkerboard, real alpha.
+    fn screenshot(&mut self, state: &ViewState, params: &DrawParams, capturer: &mut Capturer, target: Target) {
+        let clean = DrawParams { checkerboard: false, backdrop: None, premultiplied: false, slow_tint: None, ..*params };
+        let text = vec![state.metadata(&state.viewport).text_chunk()];
+        // the clipboard takes 8 bits per channel anyway
+        if state.bit_depth == BitDepth::Sixteen && matches!(target, Target::File(_)) {
+            // the window only has 8 bits per channel, so render the view again offscreen
+            let (width, height) = (state.viewport.width, state.viewport.height);
+            match self.render_still(state, &clean, width, height, BitDepth::Sixteen) {
+                Ok(image) => capturer.save(image, target, text),
+                Err(err) => warn!("screenshot failed: {err}"),
+            }
+            return;
+        }
+        match self {
+            Backend::Gpu(renderer) => {
+                renderer.draw(&clean);
+                capturer.read_framebuffer(state.viewport.width as i32, state.viewport.height as i32, target, text);
+            }
+            Backend::Software(software) => {
+                software.draw(&state.viewport, &clean, &state.palette.build());
+                capturer.save(software.frame().expect("drawn just above").clone().into(), target, text);
+            }
+        }
+    }
+
+    /// Takes the photo of photo mode: the view at the photo's size with the
+    /// export's supersampling and bit depth, clean as a screenshot.
+    fn photo(&mut self, state: &ViewState, params: &DrawParams, capturer: &mut Capturer) {
+        let clean = DrawParams { checkerboard: false, backdrop: None, premultiplied: false, slow_tint: None, ssaa: state.export_ssaa, ..*params };
+        let (width, height) = state.photo.size(state.viewport.width, state.viewport.height);
+        let started = Instant::now();
+        match self.render_still(state, &clean, width, height, state.bit_depth) {
+            Ok(image) => {
+                let mut view = state.viewport;
+                view.resize(width, height);
+                let text = vec![state.metadata(&view).text_chunk()];
+                info!("took a {width}x{height} photo with {0}x{0} supersampling in {1:.1?}", state.export_ssaa, started.elapsed());
+                capturer.save(image, Target::File(capture::capture_path("photo")), text);
+            }
+            Err(err) => warn!("photo failed: {err}"),
+        }
+    }
+
+    /// The window's view at `width x height` and `depth`, tile by tile like an
+    /// export.
+    fn render_still(&mut self, state: &ViewState, params: &DrawParams, width: u32, height: u32, depth: BitDepth) -> Result<image::DynamicImage, String> {
+        let mut export = Export::new(&state.viewport, width, height, params.ssaa, depth);
+        let mut image = depth.blank(width, height);
+        while let Some(tile) = export.next_tile() {
+            let params = DrawParams {
+                view: export.viewport.tile_uniforms(tile.rect),
+                mapping: CoordinateMapping::FragCoord,
+                ..*params
+            };
+            let pixels = self.render_tile(state, &export, tile.rect, &params)?;
+            if let Some((y, band)) = export.store(tile, &pixels) {
+                export::paste(&mut image, &band, 0, y);
+            }
+        }
+        Ok(image)
+    }
+
+    /// Renders one tile of an export, leaving the window's draw state as it was.
+    fn render_tile(&mut self, state: &ViewState, export: &Export, rect: PixelRect, params: &DrawParams) -> Result<image::DynamicImage, String> {
+        match self {
+            Backend::Gpu(renderer) => {
+                let (width, height) = (rect.width as i32, rect.height as i32);
+                let pixels = match export.depth {
+                    BitDepth::Eight => renderer::render_offscreen(renderer, params, width, height).map(Into::into),
+                    BitDepth::Sixteen => renderer::render_offscreen_16(renderer, params, width, height).map(Into::into),
+                };
+                unsafe { gl::Viewport(0, 0, state.viewport.width as i32, state.viewport.height as i32); }
+                pixels
+            }
+            Backend::Software(software) => {
+                let palette = state.palette.build();
+                let shading = cpu::Shading {
+                    palette: &palette,
+                    mode: params.color_mode,
+                    transparent_interior: params.transparent_interior,
+                    background: software.background(params),
+                    morph: params.morph,
+                    slow_tint: params.slow_tint,
+                    lighting: params.lighting,
+                    color_span: params.color_span,
+                    log_polar: params.log_polar,
+                    tone: params.tone,
+                };
+                let (view, seed) = (&export.viewport, params.seed);
+                Ok(match export.depth {
+                    BitDepth::Eight => cpu::render_tile(view, rect, params.substeps, params.ssaa, seed, params.julia, &shading).into(),
+                    BitDepth::Sixteen => cpu::render_tile_16(view, rect, params.substeps, params.ssaa, seed, params.julia, &shading).into(),
+                })
+            }
+        }
+    }
+
+    /// The raw sample (as `cpu::raw_sample`) of the pixel at `cursor` in the
+    /// window's view, the same one `params` draws.
+    fn probe(&mut self, state: &ViewState, params: &DrawParams, cursor: (f64, f64)) -> Result<[f32; 4], String> {
+        let (x, y) = (cursor.0.floor(), cursor.1.floor());
+        let julia = params.julia.filter(|_| params.morph >= 0.5);
+        match self {
+            Backend::Gpu(renderer) => {
+                let target = renderer::Framebuffer::new_float(1, 1)?;
+                target.bind();
+                let rect = PixelRect { x: x as u32, y: y as u32, width: 1, height: 1 };
+                renderer.draw_raw(&DrawParams {
+                    view: state.viewport.tile_uniforms(rect),
+                    mapping: CoordinateMapping::FragCoord,
+                    ..*params
+                });
+                let sample = renderer::read_values(1, 1)[0];
+                unsafe {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                    gl::Viewport(0, 0, state.viewport.width as i32, state.viewport.height as i32);
+                }
+                Ok(sample)
+            }
+            Backend::Software(_) => {
+                let point = state.viewport.screen_to_complex((x + 0.5, y + 0.5));
+                Ok(match julia {
+                    Some(c) => cpu::raw_sample_from(point, c, params.substeps),
+                    None => cpu::raw_sample(point, params.substeps),
+                })
+            }
+        }
+    }
+
+    /// The raw output of every pixel of the view, rows from the top, of
+    /// whichever set shows more like `probe`.
+    fn raw_view(&mut self, state: &ViewState, params: &DrawParams) -> Result<Vec<[f32; 4]>, String> {
+        let julia = params.julia.filter(|_| params.morph >= 0.5);
+        let (width, height) = (state.viewport.width as i32, state.viewport.height as i32);
+        match self {
+            Backend::Gpu(renderer) => {
+                let target = renderer::Framebuffer::new_float(width, height)?;
+                target.bind();
+                renderer.draw_raw(&DrawParams { view: state.viewport.uniforms(), mapping: CoordinateMapping::FragCoord, ..*params });
+                let samples = renderer::read_values(width, height);
+                unsafe {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                    gl::Viewport(0, 0, width, height);
+                }
+                Ok(samples)
+            }
+            Backend::Software(_) => Ok(cpu::render_raw_view(&state.viewport, julia, params.log_polar, params.substeps)),
+        }
+    }
+
+    fn set_background(&mut self, background: Background) {
+        match self {
+            Backend::Gpu(renderer) => renderer.set_background(&background.image),
+            Backend::Software(software) => software.set_background(background),
+        }
+    }
+
+    fn set_palette(&mut self, table: &palette::PaletteTable) {
+        match self {
+            Backend::Gpu(renderer) => renderer.set_palette(table),
+            // the software renderer takes the palette with every draw
+            Backend::Software(_) => {}
+        }
+    }
+
+    fn palette_texels(&self, state: &ViewState) -> Vec<[u8; 4]> {
+        match self {
+            Backend::Gpu(renderer) => renderer.read_palette(),
+            Backend::Software(_) => state.palette.build().texels,
+        }
+    }
+}
+
+/// Shift moves and zooms 10x faster, Ctrl 10x slower for precise framing.
+fn speed_modifier(window: &dyn Window) -> f64 {
+    let held = |a, b| window.is_held(a) || window.is_held(b);
+    if held(Key::LeftShift, Key::RightShift) {
+        10.
+    } else if held(Key::LeftControl, Key::RightControl) {
+        0.1
+    } else {
+        1.
+    }
+}
+
+/// Renders the next tile of a running export, streaming each finished row of
+/// tiles to its file.
+fn step_export(backend: &mut Backend, state: &mut ViewState) {
+    let Some((mut export, mut stream, mut job)) = state.export.take() else {
+        return;
+    };
+    let Some(tile) = export.next_tile() else {
+        return;
+    };
+    let params = DrawParams {
+        view: export.viewport.tile_uniforms(tile.rect),
+        ssaa: export.ssaa,
+        checkerboard: false,
+        backdrop: None,
+        premultiplied: false,
+        slow_tint: None,
+        // tile offsets only work with pixel coordinates
+        mapping: CoordinateMapping::FragCoord,
+        ..state.draw_params(0.)
+    };
+    let written = backend.render_tile(state, &export, tile.rect, &params).and_then(|pixels| {
+        match export.store(tile, &pixels) {
+            Some((_, band)) => stream.write(band),
+            None => Ok(()),
+        }
+    });
+    if let Err(err) = written {
+        warn!("export failed: {err}");
+        stream.cancel();
+        return;
+    }
+
+    let (done, total) = export.progress();
+    job.set_progress(done, total);
+    if let Some(status) = job.report() {
+        info!("{status}");
+    }
+    if !export.finished() {
+        state.export = Some((export, stream, job));
+        return;
+    }
+    let path = stream.path().display().to_string();
+    match stream.finish() {
+        Ok(()) => info!("saved {path} in {}", job.elapsed()),
+        Err(err) => warn!("export failed: {err}"),
+    }
+}
+
+/// Starts the tiled export of `view` at `width x height` into
+/// `<prefix>_<time>.png`, which `step_export` then renders a tile a frame.
+fn start_export(state: &mut ViewState, view: &Viewport, width: u32, height: u32, prefix: &str) {
+    let export = Export::new(view, width, height, state.export_ssaa, state.bit_depth);
+    let path = capture::capture_path(prefix);
+    let metadata = state.metadata(&export.viewport).text_chunk();
+    match PngStream::create(path.as_ref(), width, height, state.bit_depth, &[metadata]) {
+        Ok(stream) => {
+            info!("exporting {width}x{height} with {0}x{0} supersampling to {path} (Escape cancels)", state.export_ssaa);
+            let mut job = Job::new("export", "tiles", export.progress().1);
+            job.wrote(&path);
+            state.export = Some((export, stream, job));
+        }
+        Err(err) => warn!("export failed: {err}"),
+    }
+}
+
+/// Stops a running export, leaving the rows so far in its file if
+/// `keep_partial`.
+fn cancel_export((_, stream, job): (Export, PngStream, Job), keep_partial: bool) -> String {
+    if keep_partial {
+        if let Err(err) = stream.finish_partial() {
+            warn!("export failed: {err}");
+        }
+    } else {
+        stream.cancel();
     }
+    job.cancel(keep_partial)
 }
 
-fn handle_window_event(window: &mut glfw::Window, event: glfw::WindowEvent) {
+/// Renders the next tile of a running animation, writing each frame as it completes.
+fn step_animation(backend: &mut Backend, state: &mut ViewState) {
+    let Some(mut animation) = state.animation.take() else {
+        return;
+    };
+    let Some((export, tile, iterations)) = animation.next_tile() else {
+        return;
+    };
+    let params = DrawParams {
+        view: export.viewport.tile_uniforms(tile.rect),
+        substeps: iterations,
+        ssaa: export.ssaa,
+        checkerboard: false,
+        backdrop: None,
+        premultiplied: false,
+        slow_tint: None,
+        mapping: CoordinateMapping::FragCoord,
+        ..state.draw_params(0.)
+    };
+    let written = backend.render_tile(state, export, tile.rect, &params).and_then(|pixels| animation.store(tile, &pixels));
+    if let Err(err) = written {
+        warn!("animation failed: {err}");
+        let _ = animation.write_sidecar();
+        return;
+    }
+
+    if let Some(status) = animation.report() {
+        info!("{status}");
+    }
+    if !animation.finished() {
+        state.animation = Some(animation);
+        return;
+    }
+    let (_, total) = animation.progress();
+    match animation.write_sidecar() {
+        Ok(()) => info!("saved {total} frames to {} in {}", animation.dir().display(), animation.job().elapsed()),
+        Err(err) => warn!("animation failed: {err}"),
+    }
+}
+
+/// Refuses a view deeper than f64, the most precise this build computes in,
+/// resolves on a `width x height` window.
+fn check_depth(loaded: Loaded, width: u32, height: u32) -> Result<Loaded, String> {
+    let view = loaded.camera()?.view(width, height)?;
+    let deepest = precision::Mode::F64.deepest_scale(&view);
+    if view.scale < deepest {
+        return Err(format!("zoom {:.1e} is deeper than the {deepest:.1e} this build can compute (in f64)", view.scale));
+    }
+    Ok(loaded)
+}
+
+fn initial_viewport(options: &cli::Options, width: u32, height: u32) -> Viewport {
+    let mut viewport = Viewport::new(width, height);
+    viewport.center = (options.center[0], options.center[1]);
+    viewport.scale = options.zoom;
+    viewport.set_extents(options.scale_x, options.scale_y);
+    viewport
+}
+
+/// The cursor in framebuffer pixels. The window reports it in screen
+/// coordinates, which differ from pixels on HiDPI displays.
+fn cursor_pixel(window: &dyn Window, viewport: &Viewport) -> (f64, f64) {
+    let (cursor_x, cursor_y) = window.cursor_pos();
+    let (win_width, win_height) = window.size();
+    (
+        cursor_x * viewport.width as f64 / win_width as f64,
+        cursor_y * viewport.height as f64 / win_height as f64,
+    )
+}
+
+fn handle_window_event(window: &mut dyn Window, state: &mut ViewState, event: Event) {
     match event {
-        glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-            window.set_should_close(true)
+        Event::Scroll(_, _) if state.flight.is_some() || state.playback.is_some() => {}
+        Event::Scroll(_, y) => {
+            let px = cursor_pixel(window, &state.viewport);
+            state.viewport.zoom_at(px, 0.9f64.powf(state.sensitivity.scroll(y) * speed_modifier(window)));
+        }
+        Event::Key(Key::Escape, Action::Press, _) => {
+            // a running export or animation gets cancelled first, the window closes next time
+            if let Some(export) = state.export.take() {
+                info!("{}", cancel_export(export, state.keep_partial));
+            } else if let Some(animation) = state.animation.take() {
+                info!("{}", animation.cancel(state.keep_partial));
+            } else if state.playback.is_some() {
+                state.stop_playback();
+            } else if state.photo.on {
+                state.photo.toggle();
+                info!("photo mode off");
+            } else if state.selection.clear() {
+                info!("selection cleared");
+            } else {
+                window.set_should_close(true)
+            }
+        }
+        Event::FramebufferSize(width, height) => {
+            state.viewport.resize(width as u32, height as u32);
+            unsafe { gl::Viewport(0, 0, width, height); }
+        }
+        Event::Key(Key::T, Action::Press, _) => {
+            state.transparent_interior = !state.transparent_interior;
+        }
+        Event::Key(Key::H, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            state.relief.on = !state.relief.on;
+            let Lighting { angle, intensity } = state.relief.lighting;
+            if state.relief.on {
+                info!("relief shading, lit from {angle}° at {intensity} intensity");
+            } else {
+                info!("no relief shading");
+            }
+        }
+        Event::Key(Key::H, Action::Press, modifiers) if modifiers.contains(Modifiers::CONTROL) => {
+            state.color_lock = match state.color_lock {
+                Some(_) => None,
+                None => Some(state.substeps),
+            };
+            match state.color_lock {
+                Some(span) => info!("colors locked to {span} iterations, Ctrl+H again follows the iterations"),
+                None => info!("colors follow the iterations"),
+            }
+        }
+        Event::Key(Key::H, Action::Press, _) => {
+            state.color_mode = state.color_mode.next();
+            info!("color mode: {:?}", state.color_mode);
+        }
+        Event::Key(Key::B, Action::Press, _) => {
+            state.checkerboard = !state.checkerboard;
+        }
+        Event::Key(key @ (Key::LeftBracket | Key::RightBracket), action, modifiers) if action != Action::Release => {
+            let step = if key == Key::RightBracket { 1. } else { -1. };
+            let tone = &mut state.tone;
+            if modifiers.contains(Modifiers::SHIFT) {
+                tone.brightness = (tone.brightness + step * TONE_STEP).clamp(-1., 1.);
+            } else {
+                tone.contrast = (tone.contrast + step * TONE_STEP).clamp(0., 4.);
+            }
+            info!("brightness {:+.2}, contrast {:.2}", tone.brightness, tone.contrast);
+        }
+        Event::Key(Key::Backslash, Action::Press, _) => {
+            state.tone = Tone::default();
+            info!("brightness and contrast reset");
+        }
+        Event::Key(Key::O, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            state.trail.on = !state.trail.on;
+        }
+        Event::Key(Key::O, Action::Press, modifiers) => {
+            if modifiers.contains(Modifiers::CONTROL) {
+                state.open_requested = Some(OpenRequest::File(state.state_path.clone()));
+            } else {
+                state.fly_home();
+            }
+        }
+        Event::Key(Key::S, Action::Press, modifiers) if modifiers.contains(Modifiers::CONTROL | Modifiers::SHIFT) => {
+            state.copy_requested = true;
+        }
+        Event::Key(Key::S, Action::Press, modifiers) if modifiers.contains(Modifiers::CONTROL) => {
+            match session::save(&state.state_path, &state.session()) {
+                Ok(()) => info!("saved the state to {}", state.state_path.display()),
+                Err(err) => warn!("failed to save the state: {err}"),
+            }
+        }
+        Event::Key(Key::Z, Action::Press, modifiers) => {
+            state.snap_zoom(if modifiers.contains(Modifiers::SHIFT) { 2. } else { 10. });
+        }
+        Event::Key(Key::M, Action::Press, _) => {
+            state.zoom_inertia = !state.zoom_inertia;
+        }
+        Event::Key(Key::U, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            state.auto_iterations.on = !state.auto_iterations.on;
+            info!("iterations {}", if state.auto_iterations.on { "follow the zoom" } else { "stay where they are" });
+        }
+        Event::Key(Key::U, Action::Press, _) => {
+            state.set_quality(state.quality.next());
+        }
+        Event::Key(Key::X, Action::Press, _) => {
+            state.ssaa = state.ssaa % 4 + 1;
+            info!("supersampling {0}x{0}", state.ssaa);
+        }
+        Event::Key(Key::N, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            state.show_components = !state.show_components;
+            info!("main cardioid and period-2 bulb {}", if state.show_components { "outlined" } else { "hidden" });
+        }
+        Event::Key(Key::N, Action::Press, _) => {
+            if state.area.take().is_some() {
+                info!("area estimate stopped");
+            } else {
+                state.area = Some(AreaJob::start(state.viewport, state.substeps, state.seed));
+            }
+        }
+        Event::Key(Key::C, Action::Press, modifiers) if modifiers.contains(Modifiers::CONTROL | Modifiers::SHIFT) => {
+            match location::encode(&state.session()) {
+                Ok(text) => {
+                    window.set_clipboard_text(&text);
+                    info!("copied the location: {text}");
+                }
+                Err(err) => warn!("can't copy the location: {err}"),
+            }
+        }
+        Event::Key(Key::C, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            state.log_polar = !state.log_polar;
+            if state.log_polar {
+                info!("logarithmic view around the center");
+            } else {
+                info!("plain view");
+            }
+        }
+        Event::Key(Key::C, Action::Press, _) => {
+            state.mapping = match state.mapping {
+                CoordinateMapping::FragCoord => CoordinateMapping::QuadPosition,
+                CoordinateMapping::QuadPosition => CoordinateMapping::FragCoord,
+            };
+            info!("coordinate mapping: {:?}", state.mapping);
+        }
+        Event::Key(Key::G, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            state.palette = state.palette.next_curated();
+            state.palette_changed = true;
+            info!("palette: {}", state.palette.name);
+        }
+        Event::Key(Key::G, Action::Press, _) => {
+            state.palette_editor.open = !state.palette_editor.open;
+        }
+        Event::Key(Key::F2, Action::Press, _) => {
+            state.sensitivity.open = !state.sensitivity.open;
+        }
+        Event::Key(Key::F3, Action::Press, _) => {
+            state.precision.open = !state.precision.open;
+        }
+        Event::Key(Key::F4, Action::Press, _) => {
+            state.console.open = !state.console.open;
+        }
+        Event::Key(Key::Tab, Action::Press, _) => {
+            state.control_panel.open = !state.control_panel.open;
+        }
+        Event::Key(Key::F1, Action::Press, _) => {
+            state.bookmarks.open = !state.bookmarks.open;
+        }
+        Event::Key(Key::P, Action::Press, _) => {
+            if let Some(export) = state.export.take() {
+                info!("{}", cancel_export(export, state.keep_partial));
+            } else if let Some(region) = state.selection.region.take() {
+                let (width, height) = selection::export_size(&region, state.region_edge);
+                start_export(state, &region, width, height, "region");
+            } else {
+                let ((width, height), view) = (state.export_size, state.viewport);
+                start_export(state, &view, width, height, "export");
+            }
+        }
+        Event::MouseButton(MouseButton::Left, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            state.selection.start(cursor_pixel(window, &state.viewport));
+        }
+        Event::CursorPos(..) if state.selection.dragging() => {
+            state.selection.drag_to(cursor_pixel(window, &state.viewport));
+        }
+        Event::MouseButton(MouseButton::Left, Action::Release, _) if state.selection.dragging() => {
+            if let Some(region) = state.selection.finish(&state.viewport) {
+                let (width, height) = selection::export_size(&region, state.region_edge);
+                info!("selected {}x{} pixels: P exports them at {width}x{height}, Escape clears", region.width, region.height);
+            }
+        }
+        Event::Key(Key::J, Action::Press, modifiers) => {
+            if modifiers.contains(Modifiers::SHIFT) {
+                if state.keyframes.pop().is_some() {
+                    info!("keyframe {} removed", state.keyframes.len() + 1);
+                }
+            } else {
+                state.keyframes.push(Keyframe::from_view(&state.viewport, state.substeps));
+                info!("keyframe {} marked", state.keyframes.len());
+            }
+        }
+        Event::Key(Key::R, Action::Press, _) => {
+            if let Some(recorder) = state.recording.take() {
+                let samples = recorder.sample_count();
+                state.keyframes = recorder.finish(&state.viewport, state.substeps, state.record_tolerance);
+                info!("recorded {samples} samples into {} keyframes", state.keyframes.len());
+            } else {
+                state.recording = Some(Recorder::new());
+                info!("recording the camera path, R again stops");
+            }
+        }
+        Event::Key(Key::F6, Action::Press, _) => {
+            if let Some(points) = state.julia_trace.take() {
+                let path = format!("{}.json", capture::capture_name("julia_trace"));
+                let count = points.len();
+                match julia::save(path.as_ref(), &julia::JuliaPath::Trace { points }) {
+                    Ok(()) => info!("saved a Julia path of {count} points to {path}"),
+                    Err(err) => warn!("failed to save the Julia path: {err}"),
+                }
+            } else {
+                state.julia_trace = Some(Vec::new());
+                info!("tracing the cursor as a Julia path, F6 again saves it");
+            }
+        }
+        Event::Key(Key::F5, Action::Press, _) => {
+            match keyframes::save(&state.keyframes_path, &state.keyframes) {
+                Ok(()) => info!("saved {} keyframes to {}", state.keyframes.len(), state.keyframes_path.display()),
+                Err(err) => warn!("failed to save keyframes: {err}"),
+            }
+        }
+        Event::Key(Key::L, Action::Press, _) => {
+            if let Some(animation) = state.animation.take() {
+                info!("{}", animation.cancel(state.keep_partial));
+            } else {
+                // a preview shows the path, not the view it ends at
+                state.stop_playback();
+                let dir = capture::capture_name("animation");
+                let quality = (state.export_ssaa, state.bit_depth);
+                let metadata = state.metadata(&state.viewport);
+                match Animation::new(state.planned_path(), state.animation_size, state.animation_fps, quality, metadata, dir.as_ref()) {
+                    Ok(animation) => {
+                        let (_, total) = animation.progress();
+                        info!("rendering {total} frames into {dir}/ (Escape cancels)");
+                        state.animation = Some(animation);
+                    }
+                    Err(err) => warn!("animation failed: {err}"),
+                }
+            }
+        }
+        Event::Key(Key::V, Action::Press, modifiers) if modifiers.contains(Modifiers::CONTROL | Modifiers::SHIFT) => {
+            match window.clipboard_text() {
+                Some(text) => state.open_requested = Some(OpenRequest::Location(text)),
+                None => warn!("not opening: the clipboard has no text"),
+            }
+        }
+        Event::Key(Key::V, Action::Press, _) => {
+            if state.playback.is_some() {
+                state.stop_playback();
+            } else {
+                state.start_playback();
+                info!("previewing the animation: Space pauses, Left/Right scrub, ,/. change speed, V stops");
+            }
+        }
+        Event::Key(key @ (Key::Space | Key::Left | Key::Right | Key::Comma | Key::Period), action, _)
+            if state.playback.is_some() && action != Action::Release =>
+        {
+            // only scrubbing repeats while the key is held
+            if action == Action::Repeat && !matches!(key, Key::Left | Key::Right) {
+                return;
+            }
+            let playback = state.playback.as_mut().expect("checked by the guard");
+            match key {
+                Key::Space => playback.toggle_pause(),
+                Key::Left => playback.scrub(-speed_modifier(window)),
+                Key::Right => playback.scrub(speed_modifier(window)),
+                Key::Comma => playback.change_speed(0.5),
+                _ => playback.change_speed(2.),
+            }
+            info!("{}", playback.describe());
+        }
+        Event::Key(Key::F10, Action::Press, _) => {
+            state.palette_export_requested = true;
+        }
+        Event::Key(Key::F, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            state.deepest_requested = true;
+        }
+        Event::Key(Key::F, Action::Press, _) => {
+            state.show_slow = !state.show_slow;
+            if state.show_slow {
+                info!("marking points still bounded with |z| > {} (more iterations would resolve them)", state.slow_tint.threshold);
+            } else {
+                info!("not marking slow points");
+            }
+        }
+        Event::Key(Key::F12, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            match state.timelapse.take() {
+                Some(timelapse) => {
+                    info!("timelapse stopped: {} frames in {}", timelapse.frame_count(), timelapse.dir().display());
+                }
+                None => match Timelapse::start(state.timelapse_every, state.timelapse_limits) {
+                    Ok(timelapse) => {
+                        info!("timelapse started into {} (Shift+F12 stops it)", timelapse.dir().display());
+                        state.timelapse = Some(timelapse);
+                    }
+                    Err(err) => warn!("can't start a timelapse: {err}"),
+                },
+            }
+        }
+        Event::Key(Key::F12, Action::Press, _) => {
+            state.screenshot_requested = true;
+        }
+        Event::Key(Key::F11, Action::Press, _) => {
+            state.photo.toggle();
+            info!("photo mode {}", if state.photo.on { "on: the UI is hidden, Enter takes the photo, F11 or Escape leaves" } else { "off" });
+        }
+        Event::Key(Key::Enter | Key::KpEnter, Action::Press, _) if state.photo.on => {
+            if state.photo.trigger() {
+                info!("taking the photo after the countdown (Enter again stops it)");
+            } else {
+                info!("photo countdown stopped");
+            }
+        }
+        Event::Key(Key::Y, Action::Press, _) => {
+            let c = state.viewport.screen_to_complex(cursor_pixel(window, &state.viewport));
+            state.toggle_julia(c);
+        }
+        Event::Key(Key::F8, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            let point = state.viewport.screen_to_complex(cursor_pixel(window, &state.viewport));
+            // like the hover readback, the orbit is of whichever set shows more
+            let params = state.draw_params(0.);
+            let (z, c) = match params.julia.filter(|_| params.morph >= 0.5) {
+                Some(julia) => (point, julia),
+                None => ((0., 0.), point),
+            };
+            let orbit = cpu::orbit(z, c, state.substeps);
+            let path = format!("{}.csv", capture::capture_name("orbit"));
+            let &(re, im) = orbit.last().expect("starts with z");
+            match cpu::iterate(z, c, state.substeps).0 {
+                Some(escape) => info!("escapes at iteration {escape}, at z = {re} {im:+}i (|z| = {})", re.hypot(im)),
+                None => info!("still bounded after {} iterations, at z = {re} {im:+}i (|z| = {})", state.substeps, re.hypot(im)),
+            }
+            match orbit::save(path.as_ref(), &orbit) {
+                Ok(()) => info!("saved the orbit of {} {}i to {path}", point.0, point.1),
+                Err(err) => warn!("failed to save the orbit: {err}"),
+            }
+        }
+        Event::Key(Key::F8, Action::Press, _) => {
+            state.hover = match state.hover {
+                Some(_) => None,
+                None => Some(Hover::new()),
+            };
+        }
+        Event::Key(Key::F7, Action::Press, _) => {
+            let path = format!("{}.kfr", capture::capture_name("location"));
+            match state.kfr().save(std::path::Path::new(&path)) {
+                Ok(()) => info!("saved the location to {path}"),
+                Err(err) => warn!("failed to save the location: {err}"),
+            }
+        }
+        Event::Key(Key::F9, Action::Press, modifiers) if modifiers.contains(Modifiers::SHIFT) => {
+            let source = renderer::shadertoy_source(&DrawParams { premultiplied: false, ..state.draw_params(0.) }, &state.palette.build());
+            if let Err(err) = renderer::check_shadertoy(&source) {
+                warn!("the Shadertoy export does not compile:\n{err}");
+                return;
+            }
+            let path = format!("{}.glsl", capture::capture_name("shadertoy"));
+            match std::fs::write(&path, source) {
+                Ok(()) => info!("saved the view as a Shadertoy shader to {path}"),
+                Err(err) => warn!("failed to save {path}: {err}"),
+            }
+        }
+        Event::Key(Key::F9, Action::Press, _) => {
+            let path = format!("{}.frag", capture::capture_name("shader"));
+            match std::fs::write(&path, renderer::fragment_shader_source()) {
+                Ok(()) => info!("saved the fragment shader to {path}"),
+                Err(err) => warn!("failed to save {path}: {err}"),
+            }
+        }
+        Event::FileDrop(paths) => {
+            state.open_requested = paths.into_iter().next().map(OpenRequest::File);
         }
         _ => {}
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,80 @@
+// The geometry every full-screen pass draws: one triangle big enough to cover
+// clip space, with its 2D positions on attribute 0 for the vertex shaders'
+// `in_position`. One triangle rather than a quad's two, so no pixel along the
+// diagonal is shaded twice.
+
+use gl::types::*;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use crate::gl_object::{Buffer, VertexArray};
+
+/// The attribute location the positions are on; programs linked without a
+/// `layout` qualifier bind `in_position` to it.
+pub const POSITION: GLuint = 0;
+
+/// Clip space positions; the corners past 1 are clipped away, so the part on
+/// screen runs from -1 to 1 on both axes.
+const VERTICES: [[GLfloat; 2]; 3] = [[-1., -1.], [3., -1.], [-1., 3.]];
+
+/// Deleted when dropped, which must happen while the context it was created
+/// in is still current.
+pub struct FullscreenTriangle {
+    /// Only core contexts need (and have) vertex array objects; without one
+    /// the attribute is pointed at the buffer on every draw.
+    vao: Option<VertexArray>,
+    vbo: Buffer,
+}
+
+impl FullscreenTriangle {
+    /// Uploads the triangle. `core` is whether the current context is a core
+    /// one, which needs a vertex array object; a compatibility context may not
+    /// have them at all.
+    pub fn new(core: bool) -> Result<FullscreenTriangle, String> {
+        let vbo = Buffer::new(gl::ARRAY_BUFFER)?;
+        unsafe {
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(&VERTICES) as GLsizeiptr,
+                VERTICES.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            );
+        }
+        let vao = if core {
+            let vao = VertexArray::new()?;
+            unsafe {
+                set_attribute();
+                gl::BindVertexArray(0);
+            }
+            Some(vao)
+        } else {
+            None
+        };
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        Ok(FullscreenTriangle { vao, vbo })
+    }
+
+    /// Draws the triangle with whatever program and textures are bound,
+    /// leaving its vertex array bound.
+    pub fn draw(&self) {
+        unsafe {
+            match &self.vao {
+                Some(vao) => gl::BindVertexArray(vao.id()),
+                None => {
+                    gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
+                    set_attribute();
+                }
+            }
+            gl::DrawArrays(gl::TRIANGLES, 0, VERTICES.len() as GLsizei);
+        }
+    }
+}
+
+/// Points `POSITION` at the bound buffer, two floats per vertex.
+unsafe fn set_attribute() {
+    gl::VertexAttribPointer(POSITION, 2, gl::FLOAT, gl::FALSE, mem::size_of::<[GLfloat; 2]>() as GLsizei, ptr::null());
+    gl::EnableVertexAttribArray(POSITION);
+}
@@ -0,0 +1,201 @@
+// Provenance of saved images. Every PNG the explorer writes (screenshots,
+// posters, animation frames, `render` output) carries one iTXt chunk,
+// `mandelbrot-view`, holding this as JSON:
+//
+//     {"version": "0.1.0", "center": [-0.745, 0.11], "half_extents": [0.0178, 0.01],
+//      "scale": 0.01, "rotation": 0.0, "anisotropy": 1.0, "iterations": 2000,
+//      "fractal": {"kind": "mandelbrot"}, "color_mode": "escape_time",
+//      "palette": {"name": "grayscale", "stops": [...]}}
+//
+// `--open` (or dropping the file on the window) reads it back. Files written
+// before the chunk had more than the view in it still open: everything past
+// center, half extents, rotation and iterations has a default.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+use crate::palette::{ColorMode, Palette};
+use crate::viewport::Viewport;
+
+pub const KEYWORD: &str = "mandelbrot-view";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Fractal {
+    Mandelbrot,
+    Julia { c: [f64; 2] },
+}
+
+impl Fractal {
+    pub fn from_julia(julia: Option<(f64, f64)>) -> Fractal {
+        match julia {
+            Some((re, im)) => Fractal::Julia { c: [re, im] },
+            None => Fractal::Mandelbrot,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ViewMetadata {
+    /// Of the program that wrote the file.
+    #[serde(default)]
+    pub version: String,
+    pub center: [f64; 2],
+    /// Visible half width and height in complex units.
+    pub half_extents: [f64; 2],
+    /// Half height, as `Viewport::scale`; 1 is the starting zoom.
+    #[serde(default)]
+    pub scale: f64,
+    pub rotation: f64,
+    #[serde(default = "square_pixels")]
+    pub anisotropy: f64,
+    pub iterations: i32,
+    #[serde(default = "mandelbrot")]
+    pub fractal: Fractal,
+    #[serde(default)]
+    pub color_mode: ColorMode,
+    #[serde(default = "Palette::grayscale")]
+    pub palette: Palette,
+}
+
+fn square_pixels() -> f64 {
+    1.
+}
+
+fn mandelbrot() -> Fractal {
+    Fractal::Mandelbrot
+}
+
+impl ViewMetadata {
+    pub fn new(viewport: &Viewport, iterations: i32, fractal: Fractal, color_mode: ColorMode, palette: &Palette) -> ViewMetadata {
+        let (half_w, half_h) = viewport.half_extents();
+        ViewMetadata {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            center: [viewport.center.0, viewport.center.1],
+            half_extents: [half_w, half_h],
+            scale: viewport.scale,
+            rotation: viewport.rotation,
+            anisotropy: viewport.anisotropy,
+            iterations,
+            fractal,
+            color_mode,
+            palette: palette.clone(),
+        }
+    }
+
+    /// The same metadata for another frame of an animation.
+    pub fn with_view(&self, viewport: &Viewport, iterations: i32) -> ViewMetadata {
+        ViewMetadata::new(viewport, iterations, self.fractal.clone(), self.color_mode, &self.palette)
+    }
+
+    /// `(keyword, JSON)` for `PngStream::create` and `save_png`.
+    pub fn text_chunk(&self) -> (String, String) {
+        (KEYWORD.to_string(), serde_json::to_string(self).expect("plain data serializes"))
+    }
+
+    /// The view this was rendered with, on a `width x height` target: same
+    /// center, vertical extent and rotation.
+    pub fn view(&self, width: u32, height: u32) -> Viewport {
+        // old files only have the half extents
+        let scale = if self.scale > 0. { self.scale } else { self.half_extents[1] };
+        Viewport {
+            center: (self.center[0], self.center[1]),
+            scale,
+            rotation: self.rotation,
+            anisotropy: self.anisotropy,
+            ..Viewport::new(width, height)
+        }
+    }
+}
+
+/// Reads the metadata chunk of a PNG.
+pub fn read(path: &Path) -> Result<ViewMetadata, String> {
+    let file = File::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let decoder = png::Decoder::new(BufReader::new(file));
+    let reader = decoder.read_info().map_err(|err| format!("{}: {err}", path.display()))?;
+    let info = reader.info();
+    let utf8 = info.utf8_text.iter().filter(|chunk| chunk.keyword == KEYWORD).filter_map(|chunk| chunk.get_text().ok());
+    let latin1 = info.uncompressed_latin1_text.iter().filter(|chunk| chunk.keyword == KEYWORD).map(|chunk| chunk.text.clone());
+    let json = utf8
+        .chain(latin1)
+        .next()
+        .ok_or_else(|| format!("{} has no {KEYWORD} metadata", path.display()))?;
+    serde_json::from_str(&json).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+/// Writes an 8 or 16 bit RGBA PNG with `text` as iTXt chunks.
+pub fn save_png(path: &Path, image: &DynamicImage, text: &[(String, String)]) -> Result<(), String> {
+    let write = || -> Result<(), String> {
+        let file = File::create(path).map_err(|err| err.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), image.width(), image.height());
+        encoder.set_color(png::ColorType::Rgba);
+        for (keyword, value) in text {
+            encoder.add_itxt_chunk(keyword.clone(), value.clone()).map_err(|err| err.to_string())?;
+        }
+        let data = match image {
+            DynamicImage::ImageRgba16(image) => {
+                encoder.set_depth(png::BitDepth::Sixteen);
+                image.as_raw().iter().flat_map(|v| v.to_be_bytes()).collect()
+            }
+            image => {
+                encoder.set_depth(png::BitDepth::Eight);
+                image.to_rgba8().into_raw()
+            }
+        };
+        let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+        writer.write_image_data(&data).map_err(|err| err.to_string())?;
+        writer.finish().map_err(|err| err.to_string())
+    };
+    write().map_err(|err| format!("{}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> ViewMetadata {
+        let view = Viewport { center: (-0.743643887037158, 0.131825904205311), scale: 3.2e-11, rotation: 0.4, anisotropy: 1.25, ..Viewport::new(640, 360) };
+        ViewMetadata::new(&view, 8000, Fractal::Julia { c: [-0.8, 0.156] }, ColorMode::Angle, &Palette::curated(3))
+    }
+
+    #[test]
+    fn saved_pngs_read_back() {
+        let path = std::env::temp_dir().join(format!("mandelplotter-metadata-{}.png", std::process::id()));
+        let metadata = metadata();
+        save_png(&path, &DynamicImage::new_rgba8(4, 3), &[metadata.text_chunk()]).expect("writable");
+        let read = read(&path);
+        std::fs::remove_file(&path).ok();
+        assert_eq!(read, Ok(metadata));
+    }
+
+    #[test]
+    fn the_view_comes_back() {
+        let view = metadata().view(640, 360);
+        assert_eq!((view.center, view.scale, view.rotation, view.anisotropy), ((-0.743643887037158, 0.131825904205311), 3.2e-11, 0.4, 1.25));
+        // on another size the vertical extent is kept
+        assert_eq!(metadata().view(100, 400).half_extents().1, 3.2e-11);
+    }
+
+    #[test]
+    fn old_chunks_get_defaults() {
+        let (_, json) = metadata().text_chunk();
+        assert!(json.contains(r#""fractal":{"kind":"julia","c":[-0.8,0.156]}"#), "{json}");
+        let old: ViewMetadata = serde_json::from_str(r#"{"center": [-0.5, 0], "half_extents": [1.5, 0.25], "rotation": 0, "iterations": 300}"#).expect("old JSON");
+        assert_eq!((&old.fractal, old.color_mode, old.anisotropy), (&Fractal::Mandelbrot, ColorMode::EscapeTime, 1.));
+        // without a scale the half height stands in for it
+        assert_eq!(old.view(4, 4).scale, 0.25);
+    }
+
+    #[test]
+    fn pngs_without_it_are_refused() {
+        let path = std::env::temp_dir().join(format!("mandelplotter-metadata-none-{}.png", std::process::id()));
+        save_png(&path, &DynamicImage::new_rgba8(2, 2), &[]).expect("writable");
+        let read = read(&path);
+        std::fs::remove_file(&path).ok();
+        assert!(read.expect_err("no chunk").contains(KEYWORD));
+    }
+}
@@ -0,0 +1,130 @@
+// Navigation typed out, for adjustments that have to be exact and
+// reproducible: F4 opens a line to type commands into, and with
+// `--stdin-commands` every line on stdin is run the same way, so a script can
+// drive the view. Commands are separated by commas, semicolons or new lines:
+//
+//     pan 0.001 -0.002, zoom 0.5
+//
+// `pan RE IM` moves the center by that complex delta and `zoom FACTOR`
+// multiplies the visible extent (0.5 zooms in 2x), whatever the rotation;
+// `center RE IM` and `scale SCALE` (the half height, as `render --zoom`) set
+// them outright. A line with a mistake in it changes nothing.
+
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use imgui::{Condition, Ui};
+
+use crate::viewport::Viewport;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Pan(f64, f64),
+    Zoom(f64),
+    Center(f64, f64),
+    Scale(f64),
+    /// Panics, to try out what a crash saves; only in debug builds.
+    Crash,
+}
+
+/// Every command in `text`, or what is wrong with the first bad one.
+pub fn parse(text: &str) -> Result<Vec<Command>, String> {
+    text.split([',', ';', '\n']).map(str::trim).filter(|command| !command.is_empty()).map(parse_one).collect()
+}
+
+fn parse_one(command: &str) -> Result<Command, String> {
+    let mut words = command.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let numbers = words
+        .map(|word| word.parse::<f64>().ok().filter(|n| n.is_finite()).ok_or_else(|| format!("`{word}` is not a number")))
+        .collect::<Result<Vec<f64>, String>>()
+        .map_err(|err| format!("{command}: {err}"))?;
+    let positive = |n: f64| if n > 0. { Ok(n) } else { Err(format!("{command}: must be positive")) };
+    match (name, numbers.as_slice()) {
+        ("pan", &[re, im]) => Ok(Command::Pan(re, im)),
+        ("zoom", &[factor]) => Ok(Command::Zoom(positive(factor)?)),
+        ("center", &[re, im]) => Ok(Command::Center(re, im)),
+        ("scale", &[scale]) => Ok(Command::Scale(positive(scale)?)),
+        ("crash", &[]) if cfg!(debug_assertions) => Ok(Command::Crash),
+        ("pan" | "center", _) => Err(format!("{command}: takes a real and an imaginary part")),
+        ("zoom" | "scale", _) => Err(format!("{command}: takes one number")),
+        _ => Err(format!("{command}: not a command (pan, zoom, center or scale)")),
+    }
+}
+
+/// `viewport` after `commands`, if it is still one that can be drawn.
+pub fn apply(viewport: &Viewport, commands: &[Command]) -> Result<Viewport, String> {
+    let mut moved = *viewport;
+    for command in commands {
+        match *command {
+            Command::Pan(re, im) => moved.center = (moved.center.0 + re, moved.center.1 + im),
+            Command::Zoom(factor) => moved.zoom_by(factor),
+            Command::Center(re, im) => moved.center = (re, im),
+            Command::Scale(scale) => moved.scale = scale,
+            Command::Crash => panic!("crash typed as a navigation command"),
+        }
+    }
+    let finite = moved.center.0.is_finite() && moved.center.1.is_finite() && moved.scale.is_finite();
+    if !finite || moved.scale < f64::MIN_POSITIVE {
+        return Err("that leaves the range of f64".to_string());
+    }
+    Ok(moved)
+}
+
+/// The lines of stdin as they come, until it closes.
+pub fn read_stdin() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+pub struct Console {
+    /// Whether the F4 window is showing.
+    pub open: bool,
+    text: String,
+    /// What the last command did, or why it didn't.
+    result: Option<Result<String, String>>,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console { open: false, text: String::new(), result: None }
+    }
+
+    /// Records what running a line did, for the window to show.
+    pub fn set_result(&mut self, result: Result<String, String>) {
+        self.result = Some(result);
+    }
+
+    /// The window; returns the line typed when Enter is pressed.
+    pub fn draw(&mut self, ui: &Ui) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+        let mut open = true;
+        let mut entered = None;
+        ui.window("Navigate")
+            .opened(&mut open)
+            .size([420., 110.], Condition::FirstUseEver)
+            .build(|| {
+                if ui.input_text("##commands", &mut self.text).enter_returns_true(true).build() {
+                    entered = Some(self.text.clone());
+                }
+                ui.text_disabled("pan RE IM, zoom FACTOR, center RE IM, scale SCALE");
+                match &self.result {
+                    Some(Ok(done)) => ui.text(done),
+                    Some(Err(err)) => ui.text_colored([1., 0.3, 0.3, 1.], err),
+                    None => {}
+                }
+            });
+        self.open = open;
+        entered
+    }
+}
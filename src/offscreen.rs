@@ -0,0 +1,237 @@
+// GL contexts without anything on screen, for the `render` subcommand, the
+// benchmark and CI machines without a display.
+//
+// `MANDELPLOTTER_GL_CONTEXT` picks how the context is created:
+// - `glfw`: an invisible GLFW window (needs a display server, even a virtual one)
+// - `winit`: the same through winit (`backend-winit` feature)
+// - `egl`: an EGL surfaceless context, no display server at all (Linux, `egl` feature)
+// - `auto` or unset: each of those that is compiled in, in that order
+
+#[cfg(feature = "backend-glfw")]
+use glfw::Context;
+#[cfg(feature = "backend-glfw")]
+use log::{error, warn};
+
+#[cfg(feature = "backend-winit")]
+use crate::platform::Window;
+
+pub const CONTEXT_ENV: &str = "MANDELPLOTTER_GL_CONTEXT";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Strategy {
+    Auto,
+    Glfw,
+    Winit,
+    Egl,
+}
+
+/// Keeps the context alive; everything is drawn into offscreen framebuffers.
+/// The bigger ones are boxed, so the enum is no bigger than a pointer more.
+pub enum OffscreenContext {
+    #[cfg(feature = "backend-glfw")]
+    Glfw { _glfw: glfw::Glfw, _window: glfw::PWindow },
+    #[cfg(feature = "backend-winit")]
+    Winit { _window: Box<crate::winit_platform::WinitWindow> },
+    #[cfg(all(feature = "egl", target_os = "linux"))]
+    Egl { _context: Box<egl_context::EglContext> },
+}
+
+/// Creates a current GL context with loaded function pointers: OpenGL 3.3
+/// core where the driver has it, or else OpenGL ES 3.0 (winit) or OpenGL 2.1
+/// and GLSL 1.20 (GLFW, winit), which the shaders are translated for.
+pub fn create() -> Result<OffscreenContext, String> {
+    let strategy = match std::env::var(CONTEXT_ENV).as_deref() {
+        Ok("glfw") => Strategy::Glfw,
+        Ok("winit") => Strategy::Winit,
+        Ok("egl") => Strategy::Egl,
+        Ok("auto") | Ok("") | Err(_) => Strategy::Auto,
+        Ok(other) => return Err(format!("{CONTEXT_ENV}={other} is not one of glfw, winit, egl, auto")),
+    };
+
+    match strategy {
+        Strategy::Glfw => hidden_window(),
+        Strategy::Winit => hidden_winit_window(),
+        Strategy::Egl => surfaceless(),
+        Strategy::Auto => {
+            let mut failures = Vec::new();
+            for create in [hidden_window, hidden_winit_window, surfaceless] {
+                match create() {
+                    Ok(context) => return Ok(context),
+                    Err(err) => failures.push(err),
+                }
+            }
+            Err(failures.join("; "))
+        }
+    }
+}
+
+/// GLFW error callback. Most errors come with a failed call that the caller
+/// already handles (no window, no fullscreen on that monitor, an unsupported
+/// context version), so they are only reported; the ones that leave nothing
+/// to recover end the process with a clear message instead of a panic.
+#[cfg(feature = "backend-glfw")]
+pub fn report_glfw_error(error: glfw::Error, description: String) {
+    match error {
+        glfw::Error::NotInitialized | glfw::Error::OutOfMemory => {
+            error!("fatal GLFW error ({error}): {description}");
+            std::process::exit(1);
+        }
+        _ => warn!("GLFW error ({error}): {description}"),
+    }
+}
+
+/// Asks for the OpenGL 3.3 core context the shaders are written for.
+#[cfg(feature = "backend-glfw")]
+pub fn request_core_context(glfw: &mut glfw::Glfw) {
+    glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
+    glfw.window_hint(glfw::WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
+    // macOS only hands out core contexts that are forward compatible
+    glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
+}
+
+#[cfg(feature = "backend-glfw")]
+fn hidden_window() -> Result<OffscreenContext, String> {
+    let mut glfw = glfw::init(report_glfw_error).map_err(|err| format!("GLFW: {err}"))?;
+    request_core_context(&mut glfw);
+    glfw.window_hint(glfw::WindowHint::Visible(false));
+    let mut created = glfw.create_window(1, 1, "", glfw::WindowMode::Windowed);
+    if created.is_none() {
+        // no 3.3 core context; the shaders run on 2.1 as well, translated
+        glfw.default_window_hints();
+        glfw.window_hint(glfw::WindowHint::Visible(false));
+        created = glfw.create_window(1, 1, "", glfw::WindowMode::Windowed);
+    }
+    let (mut window, _events) = created.ok_or("GLFW: could not create an OpenGL context")?;
+    window.make_current();
+    mandelplotter::load_gl(|s| window.get_proc_address(s) as *const _);
+    Ok(OffscreenContext::Glfw { _glfw: glfw, _window: window })
+}
+
+#[cfg(not(feature = "backend-glfw"))]
+fn hidden_window() -> Result<OffscreenContext, String> {
+    Err("GLFW: not compiled in (build with `--features backend-glfw`)".to_string())
+}
+
+#[cfg(feature = "backend-winit")]
+fn hidden_winit_window() -> Result<OffscreenContext, String> {
+    let mut window = crate::winit_platform::hidden().map_err(|err| format!("winit: {err}"))?;
+    mandelplotter::load_gl(|s| window.proc_address(s));
+    Ok(OffscreenContext::Winit { _window: Box::new(window) })
+}
+
+#[cfg(not(feature = "backend-winit"))]
+fn hidden_winit_window() -> Result<OffscreenContext, String> {
+    Err("winit: not compiled in (build with `--features backend-winit`)".to_string())
+}
+
+#[cfg(all(feature = "egl", target_os = "linux"))]
+fn surfaceless() -> Result<OffscreenContext, String> {
+    egl_context::EglContext::new().map(|context| OffscreenContext::Egl { _context: Box::new(context) })
+}
+
+#[cfg(not(all(feature = "egl", target_os = "linux")))]
+fn surfaceless() -> Result<OffscreenContext, String> {
+    Err("EGL: not compiled in (build with `--features egl` on Linux)".to_string())
+}
+
+#[cfg(all(feature = "egl", target_os = "linux"))]
+mod egl_context {
+    use khronos_egl as egl;
+
+    /// EGL_MESA_platform_surfaceless
+    const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+    pub struct EglContext {
+        egl: egl::DynamicInstance<egl::EGL1_5>,
+        display: egl::Display,
+        context: egl::Context,
+    }
+
+    impl EglContext {
+        pub fn new() -> Result<EglContext, String> {
+            // loaded at runtime so builds with the feature still start without libEGL
+            let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+                .map_err(|err| format!("EGL: {err}"))?;
+            let display = unsafe {
+                egl.get_platform_display(PLATFORM_SURFACELESS_MESA, egl::DEFAULT_DISPLAY, &[egl::NONE as egl::Attrib])
+            }
+            .map_err(|err| format!("EGL: no surfaceless display: {err}"))?;
+            egl.initialize(display).map_err(|err| format!("EGL: {err}"))?;
+
+            let result = (|| -> Result<egl::Context, egl::Error> {
+                egl.bind_api(egl::OPENGL_API)?;
+                let config = egl
+                    .choose_first_config(display, &[egl::RENDERABLE_TYPE, egl::OPENGL_BIT, egl::NONE])?
+                    .ok_or(egl::Error::BadConfig)?;
+                let context = egl.create_context(
+                    display,
+                    config,
+                    None,
+                    &[
+                        egl::CONTEXT_MAJOR_VERSION, 3,
+                        egl::CONTEXT_MINOR_VERSION, 3,
+                        egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                        egl::NONE,
+                    ],
+                )?;
+                // no surface at all, EGL_KHR_surfaceless_context
+                egl.make_current(display, None, None, Some(context))?;
+                Ok(context)
+            })();
+            let context = match result {
+                Ok(context) => context,
+                Err(err) => {
+                    let _ = egl.terminate(display);
+                    return Err(format!("EGL: {err}"));
+                }
+            };
+
+            mandelplotter::load_gl(|s| egl.get_proc_address(s).map_or(std::ptr::null(), |f| f as *const _));
+            Ok(EglContext { egl, display, context })
+        }
+    }
+
+    impl Drop for EglContext {
+        fn drop(&mut self) {
+            let _ = self.egl.make_current(self.display, None, None, None);
+            let _ = self.egl.destroy_context(self.display, self.context);
+            let _ = self.egl.terminate(self.display);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mandelplotter::palette::{ColorMode, Lighting, Palette, Tone};
+    use mandelplotter::renderer::{self, CoordinateMapping, DrawParams};
+    use mandelplotter::viewport::Viewport;
+
+    #[test]
+    #[ignore = "needs a GL context; run with --ignored on a machine with one"]
+    fn shadertoy_export_compiles() {
+        let _context = super::create().expect("an offscreen GL context");
+        let params = DrawParams {
+            view: Viewport::new(640, 480).uniforms(),
+            substeps: 500,
+            ssaa: 2,
+            seed: 0,
+            time: 0.,
+            julia: Some((-0.8, 0.156)),
+            morph: 1.,
+            transparent_interior: false,
+            color_mode: ColorMode::Blend,
+            color_span: Some(64),
+            checkerboard: false,
+            backdrop: None,
+            premultiplied: false,
+            slow_tint: None,
+            lighting: Some(Lighting { angle: 45., intensity: 0.5 }),
+            tone: Tone::default(),
+            mapping: CoordinateMapping::FragCoord,
+            log_polar: false,
+            background: None,
+        };
+        let source = renderer::shadertoy_source(&params, &Palette::curated(1).build());
+        renderer::check_shadertoy(&source).unwrap_or_else(|err| panic!("{err}\n{source}"));
+    }
+}
@@ -0,0 +1,85 @@
+// The orbit of the point under the cursor as CSV (Shift+F8), for teaching
+// and debugging: one line per `z`, from the starting point to the escape or
+// the last iteration, computed in f64 like the software renderer.
+//
+//     n,re,im,abs
+//     0,0,0,0
+//     1,-0.7436438870371587,0.1318259042053119,0.7552379093684867
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub fn write_csv(out: &mut impl Write, orbit: &[(f64, f64)]) -> io::Result<()> {
+    writeln!(out, "n,re,im,abs")?;
+    for (n, (re, im)) in orbit.iter().enumerate() {
+        writeln!(out, "{n},{re},{im},{}", re.hypot(*im))?;
+    }
+    Ok(())
+}
+
+pub fn save(path: &Path, orbit: &[(f64, f64)]) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let mut out = BufWriter::new(file);
+    write_csv(&mut out, orbit).and_then(|()| out.flush()).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mandelplotter::cpu;
+
+    fn csv(orbit: &[(f64, f64)]) -> String {
+        let mut out = Vec::new();
+        write_csv(&mut out, orbit).expect("writing to a Vec");
+        String::from_utf8(out).expect("UTF-8")
+    }
+
+    #[test]
+    fn one_line_per_z() {
+        assert_eq!(csv(&[(0., 0.), (-0.75, 1.), (3., -4.)]), "n,re,im,abs\n0,0,0,0\n1,-0.75,1,1.25\n2,3,-4,5\n");
+        assert_eq!(csv(&[]), "n,re,im,abs\n");
+    }
+
+    #[test]
+    fn values_read_back_exactly() {
+        let orbit = cpu::orbit((0., 0.), (-0.7436438870371587, 0.1318259042053119), 200);
+        let text = csv(&orbit);
+        let read: Vec<(f64, f64)> = text
+            .lines()
+            .skip(1)
+            .enumerate()
+            .map(|(n, line)| {
+                let fields: Vec<&str> = line.split(',').collect();
+                assert_eq!(fields[0], n.to_string());
+                (fields[1].parse().expect("re"), fields[2].parse().expect("im"))
+            })
+            .collect();
+        assert_eq!(read, orbit);
+    }
+
+    #[test]
+    fn orbits_end_where_iterate_does() {
+        // escaping, bounded (0, -1, 0, -1, ...) and a Julia set's
+        for (z, c) in [((0., 0.), (0.3, 0.6)), ((0., 0.), (-1., 0.)), ((0.1, 0.2), (-0.8, 0.156))] {
+            let orbit = cpu::orbit(z, c, 100);
+            let (escape, end) = cpu::iterate(z, c, 100);
+            assert_eq!(orbit[0], z);
+            assert_eq!(*orbit.last().expect("starts with z"), end);
+            assert_eq!(orbit.len() as i32, escape.unwrap_or(100) + 2);
+        }
+        assert_eq!(cpu::iterate((0., 0.), (0.3, 0.6), 100).0, Some(15));
+        assert_eq!(cpu::orbit((0., 0.), (-1., 0.), 3), [(0., 0.), (-1., 0.), (0., 0.), (-1., 0.), (0., 0.)]);
+    }
+
+    #[test]
+    fn saves_to_the_path() {
+        let path = std::env::temp_dir().join(format!("mandelplotter-orbit-{}.csv", std::process::id()));
+        save(&path, &[(0., 0.), (0.25, 0.)]).expect("saved");
+        let text = std::fs::read_to_string(&path).expect("read back");
+        std::fs::remove_file(&path).ok();
+        assert_eq!(text, "n,re,im,abs\n0,0,0,0\n1,0.25,0,0.25\n");
+        let missing = std::env::temp_dir().join("mandelplotter-no-such-dir").join("orbit.csv");
+        assert!(save(&missing, &[]).is_err_and(|err| err.contains("mandelplotter-no-such-dir")));
+    }
+}
@@ -0,0 +1,287 @@
+// Color palettes: a list of control points that is baked into a small lookup
+// table, uploaded as a 1D texture for the shader and sampled directly by the
+// CPU renderer. Palettes are saved and loaded as JSON:
+//
+//     {"name": "fire", "stops": [{"position": 0.0, "color": [0.0, 0.0, 0.0]}, ...]}
+
+use std::f64::consts::TAU;
+use std::fs::File;
+use std::path::Path;
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::cpu;
+
+/// Number of texels in the baked palette.
+pub const PALETTE_SIZE: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stop {
+    /// Position along the palette, from 0 to 1.
+    pub position: f32,
+    pub color: [f32; 3],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Palette {
+    pub name: String,
+    /// Control points sorted by position.
+    pub stops: Vec<Stop>,
+}
+
+/// Control points as `(position, [r, g, b])`.
+type Stops = &'static [(f32, [f32; 3])];
+
+/// The palettes that ship with the explorer, cycled with Shift+G. The first,
+/// black to white, is the original look and the default.
+const CURATED: &[(&str, Stops)] = &[
+    ("grayscale", &[(0., [0., 0., 0.]), (1., [1., 1., 1.])]),
+    (
+        "ultra",
+        &[
+            (0., [0., 0.027, 0.392]),
+            (0.16, [0.125, 0.42, 0.796]),
+            (0.42, [0.929, 1., 1.]),
+            (0.6425, [1., 0.667, 0.]),
+            (0.8575, [0., 0.008, 0.]),
+            (1., [0., 0.027, 0.392]),
+        ],
+    ),
+    ("fire", &[(0., [0., 0., 0.]), (0.3, [0.5, 0., 0.]), (0.55, [0.9, 0.2, 0.]), (0.8, [1., 0.7, 0.1]), (1., [1., 1., 0.85])]),
+    ("ice", &[(0., [0., 0., 0.05]), (0.35, [0.05, 0.15, 0.45]), (0.7, [0.3, 0.7, 0.95]), (1., [0.95, 1., 1.])]),
+    (
+        "rainbow",
+        &[
+            (0., [1., 0., 0.]),
+            (0.17, [1., 1., 0.]),
+            (0.33, [0., 1., 0.]),
+            (0.5, [0., 1., 1.]),
+            (0.67, [0., 0., 1.]),
+            (0.83, [1., 0., 1.]),
+            (1., [1., 0., 0.]),
+        ],
+    ),
+    ("sunset", &[(0., [0.1, 0.02, 0.2]), (0.35, [0.55, 0.1, 0.45]), (0.65, [0.95, 0.4, 0.25]), (1., [1., 0.85, 0.4])]),
+    ("ocean", &[(0., [0., 0.02, 0.08]), (0.4, [0., 0.25, 0.4]), (0.75, [0.1, 0.65, 0.6]), (1., [0.85, 0.95, 0.8])]),
+    ("forest", &[(0., [0.05, 0.03, 0.]), (0.3, [0.3, 0.2, 0.05]), (0.65, [0.15, 0.45, 0.1]), (1., [0.8, 0.9, 0.5])]),
+    ("copper", &[(0., [0., 0., 0.]), (0.6, [0.72, 0.4, 0.2]), (0.85, [0.95, 0.7, 0.45]), (1., [1., 0.95, 0.85])]),
+    ("neon", &[(0., [0.02, 0., 0.05]), (0.3, [0.45, 0., 0.7]), (0.6, [1., 0.1, 0.6]), (0.8, [0.1, 0.9, 1.]), (1., [0.02, 0., 0.05])]),
+];
+
+impl Palette {
+    /// Black to white, the original look of the explorer.
+    pub fn grayscale() -> Palette {
+        Palette::curated(0)
+    }
+
+    /// The `index`th of the shipped palettes, wrapping around.
+    pub fn curated(index: usize) -> Palette {
+        let (name, stops) = CURATED[index % CURATED.len()];
+        Palette {
+            name: name.to_string(),
+            stops: stops.iter().map(|&(position, color)| Stop { position, color }).collect(),
+        }
+    }
+
+    /// The shipped palette after this one, by name; the first one if this is
+    /// not one of them.
+    pub fn next_curated(&self) -> Palette {
+        match CURATED.iter().position(|(name, _)| *name == self.name) {
+            Some(index) => Palette::curated(index + 1),
+            None => Palette::curated(0),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Palette, String> {
+        let file = File::open(path).map_err(|err| err.to_string())?;
+        let mut palette: Palette = serde_json::from_reader(file).map_err(|err| err.to_string())?;
+        if palette.stops.is_empty() {
+            return Err("a palette needs at least one stop".to_string());
+        }
+        for stop in &mut palette.stops {
+            stop.position = stop.position.clamp(0., 1.);
+        }
+        palette.sort();
+        Ok(palette)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|err| err.to_string())?;
+        serde_json::to_writer_pretty(file, self).map_err(|err| err.to_string())
+    }
+
+    /// Restores the position order `color_at` relies on.
+    pub fn sort(&mut self) {
+        self.stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+    }
+
+    /// Linear interpolation between the control points around `t`.
+    pub fn color_at(&self, t: f32) -> [f32; 3] {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return [0.; 3],
+        };
+        if t <= first.position {
+            return first.color;
+        }
+        for pair in self.stops.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            if t <= b.position {
+                let span = b.position - a.position;
+                let f = if span > 0. { (t - a.position) / span } else { 1. };
+                return [0, 1, 2].map(|i| a.color[i] + (b.color[i] - a.color[i]) * f);
+            }
+        }
+        last.color
+    }
+
+    /// Bakes the palette into `PALETTE_SIZE` RGBA8 texels.
+    pub fn build(&self) -> PaletteTable {
+        let texels = (0..PALETTE_SIZE)
+            .map(|i| {
+                let [r, g, b] = self.color_at(i as f32 / (PALETTE_SIZE - 1) as f32);
+                [to_u8(r), to_u8(g), to_u8(b), 255]
+            })
+            .collect();
+        PaletteTable { texels }
+    }
+}
+
+fn to_u8(v: f32) -> u8 {
+    (v.clamp(0., 1.) * 255.).round() as u8
+}
+
+/// A diagnostic color for points that stayed bounded but ended the last
+/// iteration far out, with `|z|` over `threshold`: they probably escape with
+/// a few more iterations, past 2 they certainly do.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SlowTint {
+    pub color: [f32; 3],
+    pub threshold: f32,
+}
+
+/// Relief shading of the exterior: the distance estimate's gradient taken as
+/// the slope of a surface, lit from one side so the bands look embossed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Lighting {
+    /// Where the light comes from, in degrees counter-clockwise from the
+    /// right of the screen.
+    pub angle: f32,
+    /// How much the lighting darkens the palette color, 0 to 1.
+    pub intensity: f32,
+}
+
+/// Height of the light over the surface, in units of the slope; lower gives
+/// harsher shadows. Same as `light_height` in the shader.
+const LIGHT_HEIGHT: f64 = 1.5;
+
+impl Lighting {
+    /// The factor the color of an escaped point is multiplied by, from `z`
+    /// and its derivative `dz` at the escape, in a view rotated by
+    /// `rotation` radians. Kept in step with the shader's `relief_shade`.
+    pub fn shade(&self, z: (f64, f64), dz: (f64, f64), rotation: f64) -> f32 {
+        // the normal points along z / dz, the direction of z * conj(dz)
+        let normal = (z.0 * dz.0 + z.1 * dz.1, z.1 * dz.0 - z.0 * dz.1);
+        let length = normal.0.hypot(normal.1);
+        if length == 0. || !length.is_finite() {
+            return 1.;
+        }
+        let angle = (self.angle as f64).to_radians() + rotation;
+        let lit = (normal.0 / length * angle.cos() + normal.1 / length * angle.sin() + LIGHT_HEIGHT) / (1. + LIGHT_HEIGHT);
+        (1. + (lit.max(0.) as f32 - 1.) * self.intensity.clamp(0., 1.)).max(0.)
+    }
+}
+
+/// Brightness and contrast over the finished color, for tuning the exposure
+/// without touching the palette: `(color - 0.5) * contrast + 0.5 + brightness`,
+/// clamped, on each channel. Same as the shader's adjustment.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tone {
+    pub brightness: f32,
+    pub contrast: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Tone {
+        Tone { brightness: 0., contrast: 1. }
+    }
+}
+
+impl Tone {
+    /// Whether it leaves every color as it is.
+    pub fn is_neutral(&self) -> bool {
+        *self == Tone::default()
+    }
+
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        rgb.map(|v| ((v - 0.5) * self.contrast + 0.5 + self.brightness).clamp(0., 1.))
+    }
+}
+
+/// Where along the palette an escaped point lands.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMode {
+    /// The escape iteration over the iteration count, in bands.
+    #[default]
+    EscapeTime,
+    /// The angle of the last `z` around the origin, once around the palette,
+    /// which draws pinwheels in the exterior.
+    Angle,
+    /// The angle shifted by the smooth (unbanded) escape iteration, so the
+    /// pinwheels twist with the depth.
+    Blend,
+}
+
+impl ColorMode {
+    pub fn next(self) -> ColorMode {
+        match self {
+            ColorMode::EscapeTime => ColorMode::Angle,
+            ColorMode::Angle => ColorMode::Blend,
+            ColorMode::Blend => ColorMode::EscapeTime,
+        }
+    }
+
+    /// Palette position of a point that escaped at iteration `i` with `z`
+    /// past the bailout radius of 4, the palette spread over `span`
+    /// iterations (the iteration count, unless the colors are locked). Kept
+    /// in step with the shader's `palette_position`.
+    pub fn position(self, i: i32, z: (f64, f64), span: i32) -> f32 {
+        let substeps = span.max(1) as f64;
+        let angle = || z.1.atan2(z.0) / TAU + 0.5;
+        let t = match self {
+            ColorMode::EscapeTime => i as f64 / substeps,
+            ColorMode::Angle => angle(),
+            ColorMode::Blend => (angle() + cpu::smooth_iteration(i, z) / substeps).rem_euclid(1.),
+        };
+        t as f32
+    }
+}
+
+/// The baked form of a palette, exactly what ends up in the texture.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaletteTable {
+    pub texels: Vec<[u8; 4]>,
+}
+
+impl PaletteTable {
+    /// Samples like the GPU does with linear filtering and clamp-to-edge.
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        let n = self.texels.len();
+        let x = (t.clamp(0., 1.) * n as f32 - 0.5).clamp(0., (n - 1) as f32);
+        let i = x.floor() as usize;
+        let j = (i + 1).min(n - 1);
+        let f = x - i as f32;
+        [0, 1, 2].map(|k| {
+            let a = self.texels[i][k] as f32 / 255.;
+            let b = self.texels[j][k] as f32 / 255.;
+            a + (b - a) * f
+        })
+    }
+}
+
+/// A `width x height` image with the palette running from left to right.
+pub fn strip_image(texels: &[[u8; 4]], height: u32) -> RgbaImage {
+    RgbaImage::from_fn(texels.len() as u32, height, |x, _| image::Rgba(texels[x as usize]))
+}
@@ -0,0 +1,156 @@
+// Gradient editor window: click the bar to add a stop, drag the handles under
+// it to move stops, pick a handle to recolor or delete it.
+
+use std::path::Path;
+
+use imgui::{Condition, MouseButton, Ui};
+use log::{info, warn};
+
+use crate::palette::{Palette, Stop};
+
+const BAR_HEIGHT: f32 = 24.;
+const HANDLE_SIZE: f32 = 7.;
+
+pub struct PaletteEditor {
+    pub open: bool,
+    selected: usize,
+    dragging: Option<usize>,
+    path: String,
+}
+
+impl PaletteEditor {
+    pub fn new() -> PaletteEditor {
+        PaletteEditor { open: false, selected: 0, dragging: None, path: "palette.json".to_string() }
+    }
+
+    /// Draws the editor if it is open. Returns whether `palette` was changed,
+    /// so the texture is only rebuilt when needed.
+    pub fn draw(&mut self, ui: &Ui, palette: &mut Palette) -> bool {
+        if !self.open {
+            return false;
+        }
+        let mut changed = false;
+        let mut open = true;
+        ui.window("Palette")
+            .opened(&mut open)
+            .size([440., 230.], Condition::FirstUseEver)
+            .build(|| {
+                changed |= self.gradient(ui, palette);
+                self.selected = self.selected.min(palette.stops.len() - 1);
+
+                let stop = &mut palette.stops[self.selected];
+                changed |= ui.color_edit3("color", &mut stop.color);
+                if ui.slider("position", 0., 1., &mut stop.position) {
+                    self.selected = reorder(palette, self.selected);
+                    changed = true;
+                }
+                let deletable = palette.stops.len() > 1;
+                ui.disabled(!deletable, || {
+                    if ui.button("delete stop") {
+                        palette.stops.remove(self.selected);
+                        self.selected = self.selected.saturating_sub(1);
+                        changed = true;
+                    }
+                });
+
+                ui.separator();
+                ui.input_text("name", &mut palette.name).build();
+                ui.input_text("file", &mut self.path).build();
+                if ui.button("save") {
+                    match palette.save(Path::new(&self.path)) {
+                        Ok(()) => info!("saved palette to {}", self.path),
+                        Err(err) => warn!("failed to save {}: {err}", self.path),
+                    }
+                }
+                ui.same_line();
+                if ui.button("load") {
+                    match Palette::load(Path::new(&self.path)) {
+                        Ok(loaded) => {
+                            *palette = loaded;
+                            self.selected = 0;
+                            changed = true;
+                        }
+                        Err(err) => warn!("failed to load {}: {err}", self.path),
+                    }
+                }
+            });
+        self.open = open;
+        changed
+    }
+
+    /// The gradient bar with one handle per stop.
+    fn gradient(&mut self, ui: &Ui, palette: &mut Palette) -> bool {
+        let mut changed = false;
+        let origin = ui.cursor_screen_pos();
+        let width = ui.content_region_avail()[0].max(1.);
+        ui.invisible_button("gradient", [width, BAR_HEIGHT + HANDLE_SIZE * 2.]);
+
+        let mouse = ui.io().mouse_pos;
+        let t = ((mouse[0] - origin[0]) / width).clamp(0., 1.);
+        if ui.is_item_activated() {
+            let nearest = palette
+                .stops
+                .iter()
+                .enumerate()
+                .map(|(i, stop)| (i, (stop.position - t).abs() * width))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let index = match nearest {
+                Some((i, distance)) if distance <= HANDLE_SIZE => i,
+                _ => {
+                    palette.stops.push(Stop { position: t, color: palette.color_at(t) });
+                    changed = true;
+                    reorder(palette, palette.stops.len() - 1)
+                }
+            };
+            self.selected = index;
+            self.dragging = Some(index);
+        }
+        if let Some(index) = self.dragging {
+            if !ui.is_mouse_down(MouseButton::Left) {
+                self.dragging = None;
+            } else if palette.stops[index].position != t {
+                palette.stops[index].position = t;
+                let index = reorder(palette, index);
+                self.selected = index;
+                self.dragging = Some(index);
+                changed = true;
+            }
+        }
+
+        let draw = ui.get_window_draw_list();
+        let x = |position: f32| origin[0] + position * width;
+        let bottom = origin[1] + BAR_HEIGHT;
+        let rgba = |c: [f32; 3]| [c[0], c[1], c[2], 1.];
+        let (first, last) = (&palette.stops[0], &palette.stops[palette.stops.len() - 1]);
+        draw.add_rect(origin, [x(first.position), bottom], rgba(first.color)).filled(true).build();
+        draw.add_rect([x(last.position), origin[1]], [x(1.), bottom], rgba(last.color)).filled(true).build();
+        for pair in palette.stops.windows(2) {
+            let (a, b) = (rgba(pair[0].color), rgba(pair[1].color));
+            draw.add_rect_filled_multicolor([x(pair[0].position), origin[1]], [x(pair[1].position), bottom], a, b, b, a);
+        }
+        for (i, stop) in palette.stops.iter().enumerate() {
+            let cx = x(stop.position);
+            let tip = [cx, bottom];
+            let left = [cx - HANDLE_SIZE, bottom + HANDLE_SIZE * 1.7];
+            let right = [cx + HANDLE_SIZE, bottom + HANDLE_SIZE * 1.7];
+            draw.add_triangle(tip, left, right, rgba(stop.color)).filled(true).build();
+            let outline = if i == self.selected { [1., 1., 1., 1.] } else { [0.3, 0.3, 0.3, 1.] };
+            draw.add_triangle(tip, left, right, outline).thickness(1.5).build();
+        }
+        changed
+    }
+}
+
+/// Moves the stop at `index` to where its position belongs and returns its new index.
+fn reorder(palette: &mut Palette, mut index: usize) -> usize {
+    let stops = &mut palette.stops;
+    while index > 0 && stops[index - 1].position > stops[index].position {
+        stops.swap(index - 1, index);
+        index -= 1;
+    }
+    while index + 1 < stops.len() && stops[index + 1].position < stops[index].position {
+        stops.swap(index, index + 1);
+        index += 1;
+    }
+    index
+}
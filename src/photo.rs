@@ -0,0 +1,66 @@
+// Photo mode (F11): the UI overlays go away so the view can be framed as it
+// will come out, while navigating works as usual. Enter counts down
+// `--photo-countdown` seconds in the title bar, to let go of the mouse or
+// let a morph settle, then takes the photo: the view rendered again at
+// `--photo-scale` times the window's size with the export's jittered
+// supersampling and bit depth, clean like a screenshot, into
+// `photo_<time>.png`. F11 or Escape brings the UI back.
+
+use crate::viewport;
+
+pub struct Photo {
+    pub on: bool,
+    countdown: f64,
+    scale: u32,
+    /// Seconds left until the photo is taken, while counting down.
+    remaining: Option<f64>,
+}
+
+impl Photo {
+    pub fn new(countdown: f64, scale: u32) -> Photo {
+        Photo { on: false, countdown, scale, remaining: None }
+    }
+
+    pub fn toggle(&mut self) {
+        self.on = !self.on;
+        self.remaining = None;
+    }
+
+    /// Starts the countdown, or stops it if it runs; says whether it started.
+    pub fn trigger(&mut self) -> bool {
+        self.remaining = match self.remaining {
+            Some(_) => None,
+            None => Some(self.countdown),
+        };
+        self.remaining.is_some()
+    }
+
+    /// Advances the countdown by `dt` seconds; true when the photo is due.
+    pub fn step(&mut self, dt: f64) -> bool {
+        let Some(remaining) = self.remaining.as_mut() else {
+            return false;
+        };
+        *remaining -= dt;
+        if *remaining > 0. {
+            return false;
+        }
+        self.remaining = None;
+        true
+    }
+
+    /// For the title bar.
+    pub fn status(&self) -> String {
+        match self.remaining {
+            Some(remaining) => format!("photo in {}…", remaining.ceil().max(1.)),
+            None => "photo mode, Enter takes the photo".to_string(),
+        }
+    }
+
+    /// The photo's size for a `width x height` window: `--photo-scale` times
+    /// it, less where that would pass the largest image there can be.
+    pub fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        let longest = width.max(height).max(1);
+        let scale = self.scale.min(viewport::MAX_EDGE / longest).max(1);
+        (width * scale, height * scale)
+    }
+}
@@ -0,0 +1,211 @@
+// The little the explorer needs from a windowing library: a window with a GL
+// context, its input as `Event`s, the keys held down, sizes, the title, the
+// clipboard and swapping buffers. Everything past `open` goes through the
+// `Window` trait, so the event loop in `main` is the same whichever library
+// is underneath, and drawing through `Surface` once the context is handed to
+// the render thread (see render_thread.rs):
+// - GLFW (`backend-glfw`, the default), which needs a C toolchain and CMake
+//   to build
+// - winit and glutin (`backend-winit`), pure Rust; used for the window
+//   whenever it is compiled in
+//
+// Keys are named after their place on a US layout, as GLFW does, so WASD and
+// the rest stay where they are on other layouts. Window sizes and the cursor
+// are in screen coordinates, framebuffer sizes in pixels; the two differ on
+// HiDPI displays.
+
+use std::ffi::c_void;
+use std::ops::BitOr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::StartError;
+
+#[cfg(not(any(feature = "backend-glfw", feature = "backend-winit")))]
+compile_error!("no windowing backend: build with `--features backend-glfw` or `--features backend-winit`");
+
+/// The keys the explorer and its UI react to; others are left out of the
+/// events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Space,
+    Comma,
+    Period,
+    LeftBracket,
+    RightBracket,
+    Backslash,
+    Escape,
+    Enter,
+    KpEnter,
+    Tab,
+    Backspace,
+    Delete,
+    Home,
+    End,
+    Left,
+    Right,
+    Up,
+    Down,
+    LeftShift,
+    RightShift,
+    LeftControl,
+    RightControl,
+    LeftAlt,
+    RightAlt,
+    LeftSuper,
+    RightSuper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Press,
+    /// The key is still held and the system repeats it.
+    Repeat,
+    Release,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+/// The modifier keys held with a key or a click.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const SHIFT: Modifiers = Modifiers(1);
+    pub const CONTROL: Modifiers = Modifiers(2);
+    pub const ALT: Modifiers = Modifiers(4);
+    pub const SUPER: Modifiers = Modifiers(8);
+
+    /// Whether all of `other` are held.
+    pub fn contains(self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 | other.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Key(Key, Action, Modifiers),
+    /// Text typed, for the UI's text fields.
+    Char(char),
+    /// Where the cursor moved to, in screen coordinates.
+    CursorPos(f64, f64),
+    MouseButton(MouseButton, Action, Modifiers),
+    /// In lines of a mouse wheel; up and right are positive.
+    Scroll(f64, f64),
+    FramebufferSize(i32, i32),
+    FileDrop(Vec<PathBuf>),
+}
+
+/// How the explorer's window opens.
+pub struct WindowOptions {
+    pub size: (u32, u32),
+    /// Where it was last time, if it goes back there.
+    pub position: Option<(i32, i32)>,
+    /// On the primary monitor, at its current resolution.
+    pub fullscreen: bool,
+    /// Samples per pixel of the window's framebuffer, 0 or 1 for none.
+    pub msaa: u32,
+    pub vsync: bool,
+    /// An OpenGL ES 3.0 context instead of desktop GL, which is also tried
+    /// when the driver has no 3.3 core context.
+    pub gles: bool,
+    /// A framebuffer the desktop shows through where its alpha is below 1,
+    /// if the platform has one; otherwise the window says so and is opaque.
+    pub transparent: bool,
+    /// Kept above the other windows.
+    pub always_on_top: bool,
+    pub title: String,
+}
+
+/// A window with a current GL context. Dropping it destroys both, so
+/// everything made in the context has to be dropped first.
+pub trait Window {
+    /// The events since the last call, without waiting for any.
+    fn poll_events(&mut self) -> Vec<Event>;
+    /// The events since the last call, waiting up to `timeout` for one if
+    /// there are none yet.
+    fn wait_events(&mut self, timeout: Duration) -> Vec<Event>;
+    /// Whether `key` is down, as of the last `poll_events`.
+    fn is_held(&self, key: Key) -> bool;
+    /// Every key that `is_held`.
+    fn held_keys(&self) -> Vec<Key>;
+    /// In screen coordinates from the top left of the window's inside.
+    fn cursor_pos(&self) -> (f64, f64);
+    /// Of the window's inside, in screen coordinates.
+    fn size(&self) -> (i32, i32);
+    fn position(&self) -> (i32, i32);
+    fn framebuffer_size(&self) -> (i32, i32);
+    /// Whether the desktop shows through where the framebuffer's alpha is
+    /// below 1, as `WindowOptions::transparent` asked for.
+    fn is_transparent(&self) -> bool;
+    fn set_title(&mut self, title: &str);
+    fn should_close(&self) -> bool;
+    fn set_should_close(&mut self, close: bool);
+    fn swap_buffers(&mut self);
+    /// The text on the system clipboard, if there is any.
+    fn clipboard_text(&mut self) -> Option<String>;
+    fn set_clipboard_text(&mut self, text: &str);
+    /// The address of a GL function in the window's context, null if it has
+    /// none by that name.
+    fn proc_address(&mut self, name: &str) -> *const c_void;
+    /// The version of the context, which may be older than the 3.3 asked for
+    /// when the driver has nothing newer. Needs the GL functions loaded.
+    fn context_version(&self) -> (u32, u32);
+    /// Releases the context from this thread and hands it out, for another
+    /// thread to draw with. The window keeps its events and everything else;
+    /// `swap_buffers`, `proc_address` and `context_version` are the
+    /// surface's from then on. The surface has to go before the window.
+    fn detach(&mut self) -> Result<Box<dyn Detached>, String>;
+}
+
+/// The window's context on its way to the thread that draws.
+pub trait Detached: Send {
+    /// Makes the context current on the calling thread, which it stays on.
+    fn make_current(self: Box<Self>) -> Result<Box<dyn Surface>, String>;
+}
+
+/// The window's context, current on the thread that draws.
+pub trait Surface {
+    fn swap_buffers(&mut self);
+    fn proc_address(&mut self, name: &str) -> *const c_void;
+    /// As `Window::context_version`.
+    fn context_version(&self) -> (u32, u32);
+    /// Follows the window's framebuffer to a new size in pixels, for window
+    /// systems that don't on their own.
+    fn resize(&mut self, width: i32, height: i32);
+}
+
+/// Opens the explorer's window with a current context, 3.3 core if the
+/// driver has one, then OpenGL ES 3.0 (first with `gles`), and whatever it
+/// has otherwise; the caller falls back to the software renderer for those. Fewer MSAA samples than asked for, or none,
+/// are not an error either.
+pub fn open(options: &WindowOptions) -> Result<Box<dyn Window>, StartError> {
+    #[cfg(feature = "backend-winit")]
+    return crate::winit_platform::open(options).map(|window| Box::new(window) as Box<dyn Window>);
+    #[cfg(not(feature = "backend-winit"))]
+    return crate::glfw_platform::open(options).map(|window| Box::new(window) as Box<dyn Window>);
+}
+
+/// The resolution of the primary monitor's current video mode, for a
+/// wallpaper of the same size.
+pub fn monitor_size() -> Result<(u32, u32), String> {
+    #[cfg(feature = "backend-winit")]
+    return crate::winit_platform::monitor_size();
+    #[cfg(not(feature = "backend-winit"))]
+    return crate::glfw_platform::monitor_size();
+}
@@ -0,0 +1,109 @@
+// Live preview of a planned animation: plays the keyframe path in the window
+// at the length the export would have, with pause, scrubbing and slow or fast
+// motion, so the path can be checked before spending minutes on the frames.
+
+use imgui::{Condition, ProgressBar, Ui};
+
+use crate::animation::KeyframePath;
+use crate::viewport::Viewport;
+
+const MIN_SPEED: f64 = 0.125;
+const MAX_SPEED: f64 = 8.;
+
+pub struct Playback {
+    path: KeyframePath,
+    /// Length of the path at normal speed, in seconds.
+    duration: f64,
+    /// From 0 (first keyframe) to 1 (last).
+    position: f64,
+    speed: f64,
+    paused: bool,
+    /// The view and iterations to go back to when the preview ends.
+    pub restore: (Viewport, i32),
+}
+
+impl Playback {
+    pub fn new(path: KeyframePath, restore: (Viewport, i32)) -> Playback {
+        let duration = path.duration().max(f64::EPSILON);
+        Playback { path, duration, position: 0., speed: 1., paused: false, restore }
+    }
+
+    /// Advances the position by `dt` seconds of wall time; stops at the end.
+    pub fn step(&mut self, dt: f64) {
+        if !self.paused {
+            self.seek(self.position + dt * self.speed / self.duration);
+            if self.position >= 1. {
+                self.paused = true;
+            }
+        }
+    }
+
+    pub fn seek(&mut self, position: f64) {
+        self.position = position.clamp(0., 1.);
+    }
+
+    /// Moves by `seconds` of animation time (negative to go back).
+    pub fn scrub(&mut self, seconds: f64) {
+        self.seek(self.position + seconds / self.duration);
+    }
+
+    /// Pauses, or resumes (restarting if the end was reached).
+    pub fn toggle_pause(&mut self) {
+        if self.paused && self.position >= 1. {
+            self.position = 0.;
+        }
+        self.paused = !self.paused;
+    }
+
+    /// Multiplies the playback speed by `factor`, within 1/8x and 8x.
+    pub fn change_speed(&mut self, factor: f64) {
+        self.speed = (self.speed * factor).clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    /// The view at the current position, on a `width x height` framebuffer.
+    pub fn view(&self, width: u32, height: u32) -> Viewport {
+        self.path.view_at(self.position * self.duration, width, height)
+    }
+
+    pub fn iterations(&self) -> i32 {
+        self.path.at(self.position * self.duration).iterations
+    }
+
+    /// `time / duration` at normal speed, for the console.
+    pub fn describe(&self) -> String {
+        let state = if self.paused { "paused" } else { "playing" };
+        format!("preview {:.2}s / {:.2}s at {}x, {state}", self.position * self.duration, self.duration, self.speed)
+    }
+
+    /// The progress window; its slider scrubs and its buttons mirror the keys.
+    /// Returns false once the window is closed.
+    pub fn draw(&mut self, ui: &Ui) -> bool {
+        let mut open = true;
+        ui.window("Preview")
+            .opened(&mut open)
+            .size([360., 120.], Condition::FirstUseEver)
+            .build(|| {
+                ProgressBar::new(self.position as f32)
+                    .overlay_text(format!("{:.2}s / {:.2}s", self.position * self.duration, self.duration))
+                    .build(ui);
+                let mut time = (self.position * self.duration) as f32;
+                if ui.slider("time", 0., self.duration as f32, &mut time) {
+                    self.seek(time as f64 / self.duration);
+                }
+                if ui.button(if self.paused { "play" } else { "pause" }) {
+                    self.toggle_pause();
+                }
+                ui.same_line();
+                if ui.button("slower") {
+                    self.change_speed(0.5);
+                }
+                ui.same_line();
+                if ui.button("faster") {
+                    self.change_speed(2.);
+                }
+                ui.same_line();
+                ui.text(format!("{}x", self.speed));
+            });
+        open
+    }
+}
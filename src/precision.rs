@@ -0,0 +1,114 @@
+// How deep the renderer in use can zoom before it runs out of digits, in a
+// corner overlay F3 toggles. The GPU shader computes in f32, the software
+// renderer (and `render` without `--gpu`) in f64. Once a pixel is smaller
+// than the spacing of the numbers around the view, machine epsilon times the
+// size of the coordinates, neighbouring pixels compute the same point and the
+// image breaks up into blocks; the limit shown is the zoom where that spacing
+// reaches a pixel. Getting close to it prints a warning, overlay or not.
+
+use imgui::{Condition, Ui};
+use log::info;
+
+use crate::viewport::Viewport;
+
+/// Pixels this many number spacings wide or fewer count as close to the limit.
+const CLOSE_HEADROOM: f64 = 16.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    F32,
+    F64,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::F32 => "f32 (GPU shader)",
+            Mode::F64 => "f64 (software renderer)",
+        }
+    }
+
+    fn epsilon(self) -> f64 {
+        match self {
+            Mode::F32 => f32::EPSILON as f64,
+            Mode::F64 => f64::EPSILON,
+        }
+    }
+
+    /// Smallest `scale` (the zoom of `render --zoom`) at which the pixels of
+    /// `viewport` still land on different numbers. |z| wanders up to about 1
+    /// whatever the center, so coordinates count as at least that large.
+    pub fn deepest_scale(self, viewport: &Viewport) -> f64 {
+        let magnitude = viewport.center.0.abs().max(viewport.center.1.abs()).max(1.);
+        self.epsilon() * magnitude * viewport.height as f64 / 2.
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum Level {
+    Fine,
+    Close,
+    Past,
+}
+
+fn level(headroom: f64) -> Level {
+    if headroom < 1. {
+        Level::Past
+    } else if headroom < CLOSE_HEADROOM {
+        Level::Close
+    } else {
+        Level::Fine
+    }
+}
+
+pub struct Precision {
+    /// Whether the overlay is showing.
+    pub open: bool,
+    /// Where the view was last frame, to warn once on the way in.
+    level: Level,
+}
+
+impl Precision {
+    pub fn new() -> Precision {
+        Precision { open: false, level: Level::Fine }
+    }
+
+    /// Prints a warning when the view gets close to or past the limit of `mode`.
+    pub fn update(&mut self, mode: Mode, viewport: &Viewport) {
+        let deepest = mode.deepest_scale(viewport);
+        let level = level(viewport.scale / deepest);
+        if level > self.level {
+            let fix = if mode == Mode::F32 { "; `render` without `--gpu` computes in f64" } else { "" };
+            match level {
+                Level::Close => info!("zoom {:.1e} is getting close to the {deepest:.1e} {} resolves{fix}", viewport.scale, mode.name()),
+                _ => info!("zoom {:.1e} is past the {deepest:.1e} {} resolves, expect blocky pixels{fix}", viewport.scale, mode.name()),
+            }
+        }
+        self.level = level;
+    }
+
+    /// The overlay, in the top left corner.
+    pub fn draw(&self, ui: &Ui, mode: Mode, viewport: &Viewport) {
+        if !self.open {
+            return;
+        }
+        let deepest = mode.deepest_scale(viewport);
+        let headroom = viewport.scale / deepest;
+        ui.window("Precision")
+            .position([10., 10.], Condition::FirstUseEver)
+            .always_auto_resize(true)
+            .bg_alpha(0.7)
+            .build(|| {
+                ui.text(format!("computing in {}", mode.name()));
+                ui.text(format!("zoom {:.2e}, deepest about {deepest:.1e}", viewport.scale));
+                match level(headroom) {
+                    Level::Fine => ui.text(format!("{headroom:.0}x of zoom left")),
+                    Level::Close => ui.text_colored([1., 0.8, 0.2, 1.], format!("close to the limit: {headroom:.1}x left")),
+                    Level::Past => ui.text_colored([1., 0.3, 0.3, 1.], format!("past the limit by {:.1}x", 1. / headroom)),
+                }
+                if mode == Mode::F32 {
+                    ui.text_disabled(format!("f64 (`render` without `--gpu`) reaches about {:.1e}", Mode::F64.deepest_scale(viewport)));
+                }
+            });
+    }
+}
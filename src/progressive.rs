@@ -0,0 +1,150 @@
+// Deep views at high iteration counts on the GPU, drawn over several frames
+// so the window keeps responding: once `--progressive-above` iterations are
+// reached, a change of view first draws it at an eighth of the resolution,
+// stretched over the window, then refines it in tiles from the center out,
+// as many each frame as fit in `FRAME_BUDGET`. Whatever changes the picture
+// starts over from the coarse pass. A thin bar along the bottom of the window
+// shows how far the refinement has got.
+
+use std::time::{Duration, Instant};
+
+use imgui::Ui;
+
+use crate::palette::Palette;
+use crate::renderer::{CoordinateMapping, DrawParams, Framebuffer, Renderer};
+
+/// Edge of a refinement tile, in pixels.
+const TILE_SIZE: i32 = 128;
+/// The coarse pass is this many times smaller along each axis.
+const COARSE_FACTOR: i32 = 8;
+/// GPU time spent refining per frame, past the first tile.
+const FRAME_BUDGET: Duration = Duration::from_millis(12);
+
+/// A tile of the window, in GL pixel coordinates (y up).
+#[derive(Clone, Copy)]
+struct Tile {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+pub struct Progressive {
+    /// Iteration count from which views are refined progressively, 0 never.
+    pub above: i32,
+    /// The full-resolution picture being refined.
+    target: Option<Framebuffer>,
+    coarse: Option<Framebuffer>,
+    /// What `target` is a picture of, with the time zeroed.
+    drawn: Option<(DrawParams, Palette)>,
+    /// Tiles in the order they are refined, and how many are.
+    tiles: Vec<Tile>,
+    done: usize,
+}
+
+impl Progressive {
+    pub fn new(above: i32) -> Progressive {
+        Progressive { above, target: None, coarse: None, drawn: None, tiles: Vec::new(), done: 0 }
+    }
+
+    pub fn applies(&self, params: &DrawParams) -> bool {
+        self.above > 0 && params.substeps >= self.above
+    }
+
+    /// Frees the framebuffers once views are drawn in one go again.
+    pub fn release(&mut self) {
+        *self = Progressive::new(self.above);
+    }
+
+    /// Draws another step of the view `params` and `palette` describe into
+    /// the window's framebuffer, starting from the coarse pass if it's not
+    /// the view drawn last frame.
+    pub fn draw(&mut self, renderer: &Renderer, params: &DrawParams, palette: &Palette) -> Result<(), String> {
+        let (width, height) = (params.view.resolution[0] as i32, params.view.resolution[1] as i32);
+        // the custom shaders' animation would restart the refinement every frame
+        let params = DrawParams { time: 0., mapping: CoordinateMapping::FragCoord, ..*params };
+        if self.target.as_ref().is_none_or(|target| (target.width, target.height) != (width, height)) {
+            self.target = Some(Framebuffer::new(width, height)?);
+            self.drawn = None;
+        }
+        let target = self.target.as_ref().expect("created just above");
+        if self.drawn.as_ref().is_none_or(|(drawn, drawn_palette)| *drawn != params || drawn_palette != palette) {
+            let (coarse_width, coarse_height) = ((width / COARSE_FACTOR).max(1), (height / COARSE_FACTOR).max(1));
+            if self.coarse.as_ref().is_none_or(|coarse| (coarse.width, coarse.height) != (coarse_width, coarse_height)) {
+                self.coarse = Some(Framebuffer::new(coarse_width, coarse_height)?);
+            }
+            let coarse = self.coarse.as_ref().expect("created just above");
+            coarse.bind();
+            let mut view = params.view;
+            view.resolution = [coarse_width as f32, coarse_height as f32];
+            renderer.draw(&DrawParams { view, ssaa: 1, ..params });
+            coarse.blit(Some(target), width, height, true);
+            self.tiles = tiles(width, height);
+            self.done = 0;
+            self.drawn = Some((params, palette.clone()));
+        }
+
+        let started = Instant::now();
+        target.bind();
+        unsafe {
+            gl::Enable(gl::SCISSOR_TEST);
+        }
+        while self.done < self.tiles.len() {
+            let tile = self.tiles[self.done];
+            unsafe {
+                // the renderer clears what it draws to, the scissor keeps that to the tile
+                gl::Viewport(tile.x, tile.y, tile.width, tile.height);
+                gl::Scissor(tile.x, tile.y, tile.width, tile.height);
+            }
+            renderer.draw(&params);
+            self.done += 1;
+            unsafe {
+                // wait for the GPU, or the budget only measures queueing the draws
+                gl::Finish();
+            }
+            if started.elapsed() >= FRAME_BUDGET {
+                break;
+            }
+        }
+        unsafe {
+            gl::Disable(gl::SCISSOR_TEST);
+        }
+        target.blit(None, width, height, false);
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+        }
+        Ok(())
+    }
+
+    /// How much of the view is refined, while it isn't all.
+    pub fn progress(&self) -> Option<f64> {
+        (self.drawn.is_some() && self.done < self.tiles.len()).then(|| self.done as f64 / self.tiles.len() as f64)
+    }
+
+    /// The bar along the bottom of the window.
+    pub fn draw_progress(&self, ui: &Ui) {
+        let Some(progress) = self.progress() else {
+            return;
+        };
+        let [width, height] = ui.io().display_size;
+        let draw = ui.get_background_draw_list();
+        draw.add_rect([0., height - 3.], [width, height], [0., 0., 0., 0.4]).filled(true).build();
+        draw.add_rect([0., height - 3.], [width * progress as f32, height], [1., 1., 1., 0.6]).filled(true).build();
+    }
+}
+
+/// Tiles covering `width x height`, the ones nearest the center first.
+fn tiles(width: i32, height: i32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(TILE_SIZE as usize) {
+        for x in (0..width).step_by(TILE_SIZE as usize) {
+            tiles.push(Tile { x, y, width: TILE_SIZE.min(width - x), height: TILE_SIZE.min(height - y) });
+        }
+    }
+    let from_center = |tile: &Tile| {
+        let (dx, dy) = (tile.x * 2 + tile.width - width, tile.y * 2 + tile.height - height);
+        dx as i64 * dx as i64 + dy as i64 * dy as i64
+    };
+    tiles.sort_by_key(from_center);
+    tiles
+}
@@ -0,0 +1,147 @@
+// Camera paths flown by hand. While recording, the view is sampled a few times
+// per second; when the recording stops the samples are thinned out with a
+// Ramer–Douglas–Peucker pass into keyframes that replay the flight within a
+// tolerance, measured relative to the view: center offsets in view heights,
+// zoom in ln(scale) and rotation in radians.
+
+use std::f64::consts::{PI, TAU};
+
+use crate::keyframes::{self, Easing, Keyframe};
+use crate::viewport::Viewport;
+
+const SAMPLES_PER_SECOND: f64 = 5.;
+
+pub struct Recorder {
+    /// Seconds since the recording started, and the camera then.
+    samples: Vec<(f64, Keyframe)>,
+    elapsed: f64,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder { samples: Vec::new(), elapsed: 0. }
+    }
+
+    /// Advances the clock by `dt` and samples the view when a sample is due.
+    pub fn step(&mut self, dt: f64, view: &Viewport, iterations: i32) {
+        let due = self.samples.last().is_none_or(|(time, _)| self.elapsed + dt - time >= 1. / SAMPLES_PER_SECOND);
+        self.elapsed += dt;
+        if due {
+            self.samples.push((self.elapsed, Keyframe::from_view(view, iterations)));
+        }
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Ends the recording with the view it stopped at and returns the
+    /// keyframes simplified to `tolerance`.
+    pub fn finish(mut self, view: &Viewport, iterations: i32, tolerance: f64) -> Vec<Keyframe> {
+        if self.samples.last().is_some_and(|(time, _)| *time >= self.elapsed) {
+            self.samples.pop();
+        }
+        self.samples.push((self.elapsed, Keyframe::from_view(view, iterations)));
+        simplify(&self.samples, tolerance)
+    }
+}
+
+/// How far `actual` is from `expected`, in the units of the module comment;
+/// the largest of the three.
+pub fn camera_error(expected: &Keyframe, actual: &Keyframe) -> f64 {
+    let dx = expected.center.0 - actual.center.0;
+    let dy = expected.center.1 - actual.center.1;
+    let center = (dx * dx + dy * dy).sqrt() / expected.scale;
+    let zoom = (expected.scale.ln() - actual.scale.ln()).abs();
+    let mut rotation = (expected.rotation - actual.rotation).rem_euclid(TAU);
+    if rotation > PI {
+        rotation = TAU - rotation;
+    }
+    center.max(zoom).max(rotation)
+}
+
+/// The fewest keyframes (first and last always kept) whose linear replay
+/// stays within `tolerance` of every sample at the sample's time.
+pub fn simplify(samples: &[(f64, Keyframe)], tolerance: f64) -> Vec<Keyframe> {
+    if samples.len() < 2 {
+        return samples.iter().map(|(_, keyframe)| keyframe.clone()).collect();
+    }
+    let mut keep = vec![false; samples.len()];
+    keep[0] = true;
+    keep[samples.len() - 1] = true;
+    // an explicit stack, since a long recording could recurse deeply
+    let mut spans = vec![(0, samples.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let (t0, from) = &samples[first];
+        let (t1, to) = &samples[last];
+        let worst = (first + 1..last)
+            .map(|i| {
+                let (t, sample) = &samples[i];
+                (i, camera_error(sample, &keyframes::interpolate(from, to, (t - t0) / (t1 - t0))))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((split, error)) = worst {
+            if error > tolerance {
+                keep[split] = true;
+                spans.push((first, split));
+                spans.push((split, last));
+            }
+        }
+    }
+
+    let kept: Vec<&(f64, Keyframe)> = samples.iter().zip(&keep).filter(|(_, &k)| k).map(|(s, _)| s).collect();
+    kept.iter()
+        .enumerate()
+        .map(|(i, (time, keyframe))| Keyframe {
+            duration: kept.get(i + 1).map(|(next, _)| next - time),
+            easing: Easing::Linear,
+            ..keyframe.clone()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::KeyframePath;
+
+    /// A flight that circles half a view height around a point while it
+    /// zooms in on it and turns, at `time` seconds.
+    fn flown(time: f64) -> Viewport {
+        let scale = 0.5 * (-0.4 * time).exp();
+        let center = (-0.75 + 0.5 * scale * (0.7 * time).cos(), 0.1 + 0.5 * scale * (0.7 * time).sin());
+        Viewport { center, scale, rotation: (0.3 * time).rem_euclid(TAU), ..Viewport::new(320, 240) }
+    }
+
+    #[test]
+    fn replay_stays_within_the_tolerance() {
+        let mut recorder = Recorder::new();
+        let dt = 1. / 60.;
+        for frame in 1..=1200 {
+            recorder.step(dt, &flown(frame as f64 * dt), 1000);
+        }
+        let samples = recorder.samples.clone();
+        let tolerance = 0.02;
+        let keyframes = recorder.finish(&flown(20.), 1000, tolerance);
+        assert!(keyframes.len() >= 3 && keyframes.len() < samples.len() / 2, "{} keyframes", keyframes.len());
+
+        // the replay starts at the first sample
+        let path = KeyframePath::new(keyframes, 1.).expect("keyframes");
+        let start = samples[0].0;
+        for (time, sample) in &samples {
+            let error = camera_error(sample, &path.at(time - start));
+            assert!(error <= tolerance + 1e-9, "off by {error} at {time}s");
+        }
+    }
+
+    #[test]
+    fn a_still_camera_keeps_its_ends() {
+        let mut recorder = Recorder::new();
+        for _ in 0..100 {
+            recorder.step(0.05, &flown(0.), 500);
+        }
+        let keyframes = recorder.finish(&flown(0.), 500, 0.01);
+        assert_eq!(keyframes.len(), 2);
+        assert!((keyframes[0].duration.expect("a segment") - 4.95).abs() < 1e-9);
+    }
+}
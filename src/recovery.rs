@@ -0,0 +1,172 @@
+// A crash doesn't lose the view: the explorer keeps a copy of its state in
+// memory, refreshed every frame, and a panic writes it to `recovery.json`
+// next to `settings.json` before the process goes. The next start opens it
+// in place of where the last session left off, says so and removes the file.
+// Ctrl+C needs none of this, as it closes the window and the settings get
+// saved like after Escape.
+//
+// The hook only ever reads the copy, which is plain data, and skips it if
+// the panic happened while it was being replaced.
+
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, TryLockError};
+
+use log::{error, warn};
+
+use crate::session::{self, Session};
+use crate::settings;
+
+/// The state as of the last frame drawn.
+static SNAPSHOT: Mutex<Option<Session>> = Mutex::new(None);
+
+/// `recovery.json` in the directory `settings.json` is in.
+pub fn path() -> Option<PathBuf> {
+    Some(settings::path()?.with_file_name("recovery.json"))
+}
+
+/// Makes a panic save the last snapshot before the usual message.
+pub fn install() {
+    let report = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        save();
+        report(info);
+    }));
+}
+
+/// Replaces the state a panic would save.
+pub fn snapshot(session: Session) {
+    *SNAPSHOT.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(session);
+}
+
+fn save() {
+    let snapshot = match SNAPSHOT.try_lock() {
+        Ok(snapshot) => snapshot,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        // the panic came from inside `snapshot`, on this or another thread
+        Err(TryLockError::WouldBlock) => return,
+    };
+    let (Some(session), Some(path)) = (snapshot.as_ref(), path()) else {
+        return;
+    };
+    let saved = path
+        .parent()
+        .map_or(Ok(()), |dir| fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display())))
+        .and_then(|()| session::save(&path, session));
+    match saved {
+        Ok(()) => error!("saved the view to {}, the next start opens it", path.display()),
+        Err(err) => error!("could not save the view to {}: {err}", path.display()),
+    }
+}
+
+/// The view a crash saved, if one did, removing the file so it is only
+/// opened once.
+pub fn take() -> Option<Session> {
+    take_from(&path()?)
+}
+
+fn take_from(path: &Path) -> Option<Session> {
+    if !path.exists() {
+        return None;
+    }
+    let loaded = session::load(path);
+    if let Err(err) = fs::remove_file(path) {
+        warn!("could not remove {}: {err}", path.display());
+    }
+    match loaded {
+        Ok(session) => Some(session),
+        Err(err) => {
+            warn!("ignoring the view saved at a crash: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::metadata::Fractal;
+    use crate::navigate;
+    use crate::palette::{ColorMode, Palette, Tone};
+    use crate::session::Camera;
+    use crate::viewport::Viewport;
+
+    /// Set in the copy of the test binary that crashes.
+    const CRASHING: &str = "MANDELPLOTTER_TEST_CRASH";
+
+    fn session() -> Session {
+        let view = Viewport { center: (-0.743643887037151, 0.13182590420533), scale: 1.5e-9, ..Viewport::new(600, 600) };
+        Session {
+            version: session::VERSION,
+            camera: Camera::from_view(&view),
+            iterations: 5000,
+            fractal: Fractal::Mandelbrot,
+            color_mode: ColorMode::EscapeTime,
+            palette: Palette::curated(1),
+            ssaa: 2,
+            transparent_interior: false,
+            checkerboard: false,
+            lighting: None,
+            color_span: None,
+            tone: Tone::default(),
+        }
+    }
+
+    /// A configuration directory of its own for each test.
+    fn config_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mandelplotter-recovery-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    // the crash command is only there in debug builds
+    #[cfg(debug_assertions)]
+    fn a_panicking_command_saves_the_view() {
+        if std::env::var_os(CRASHING).is_some() {
+            install();
+            snapshot(session());
+            let crash = navigate::parse("crash").expect("a debug build");
+            let _ = navigate::apply(&Viewport::new(600, 600), &crash);
+            unreachable!("crash panics");
+        }
+        // the hook is for the whole process, so it panics in a process of its own
+        let dir = config_dir("crash");
+        let crashed = Command::new(std::env::current_exe().expect("the test binary"))
+            .args(["--exact", "recovery::tests::a_panicking_command_saves_the_view", "--test-threads", "1"])
+            .env(CRASHING, "1")
+            .env("XDG_CONFIG_HOME", &dir)
+            .env("HOME", &dir)
+            .env("APPDATA", &dir)
+            .output()
+            .expect("the test binary runs");
+        assert!(!crashed.status.success());
+        let config = if cfg!(target_os = "macos") { dir.join("Library/Application Support") } else { dir.clone() };
+        let saved = config.join("mandelplotter").join("recovery.json");
+        let recovered = take_from(&saved);
+        let left = saved.exists();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(recovered, Some(session()));
+        assert!(!left, "opened only once");
+    }
+
+    #[test]
+    fn nothing_saved_is_nothing_to_open() {
+        let dir = config_dir("none");
+        assert_eq!(take_from(&dir.join("recovery.json")), None);
+    }
+
+    #[test]
+    fn a_broken_file_is_removed_and_ignored() {
+        let dir = config_dir("broken");
+        fs::create_dir_all(&dir).expect("a temporary directory");
+        let path = dir.join("recovery.json");
+        fs::write(&path, "{ not json").expect("written");
+        let recovered = take_from(&path);
+        let left = path.exists();
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(recovered, None);
+        assert!(!left);
+    }
+}
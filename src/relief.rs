@@ -0,0 +1,48 @@
+// Relief shading (Shift+H, or `--relief`): the exterior lit from one side by
+// the slope of the distance estimate, so the escape bands look embossed.
+// While it is on, a window holds the direction of the light and how strong it
+// is; closing the window turns it off again.
+
+use imgui::{Condition, Ui};
+
+use crate::palette::Lighting;
+
+pub struct Relief {
+    pub on: bool,
+    /// Kept while off, to come back the same.
+    pub lighting: Lighting,
+}
+
+impl Relief {
+    pub fn new(on: bool, lighting: Lighting) -> Relief {
+        Relief { on, lighting }
+    }
+
+    /// What to draw with, or `None` while off.
+    pub fn lighting(&self) -> Option<Lighting> {
+        self.on.then_some(self.lighting)
+    }
+
+    /// Takes on what a saved state had.
+    pub fn set(&mut self, lighting: Option<Lighting>) {
+        self.on = lighting.is_some();
+        if let Some(lighting) = lighting {
+            self.lighting = lighting;
+        }
+    }
+
+    pub fn draw(&mut self, ui: &Ui) {
+        if !self.on {
+            return;
+        }
+        let mut open = true;
+        ui.window("Relief")
+            .opened(&mut open)
+            .size([300., 80.], Condition::FirstUseEver)
+            .build(|| {
+                ui.slider("light angle", 0., 360., &mut self.lighting.angle);
+                ui.slider("intensity", 0., 1., &mut self.lighting.intensity);
+            });
+        self.on = open;
+    }
+}
@@ -0,0 +1,486 @@
+// Drawing on a thread of its own, so a slow frame doesn't hold up the
+// window: the main thread keeps the window, waits on its events and reads
+// the gamepad, while the render thread makes the context current and runs
+// the explorer against a `RemoteWindow`, a `Window` made of what the main
+// thread last sent it. Between the two:
+// - input: the main thread sends a `Frame` with the events since the last
+//   one, the window's state after them and the gamepad's, whenever any of
+//   that changed; the render thread takes everything queued at the start of
+//   each of its frames, however many piled up during a slow one
+// - resizing: a `FramebufferSize` event; the render thread resizes the
+//   surface itself when it takes the event, before drawing into it
+// - screenshots and exports are read back in the render thread's context
+//   and written by their own threads, so they need nothing from this one
+// - the title, the clipboard and closing go back as `Request`s, served
+//   between waits for events; reading the clipboard waits for the answer
+// - shutdown: closing the window shows up in the next `Frame`; the explorer
+//   saves the settings, frees its GL objects and the surface and returns,
+//   which hangs up the channels. The main thread joins it and only then
+//   drops the window; a panic on the render thread is raised again here.
+
+use std::ffi::c_void;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::StartError;
+use crate::gamepad::{self, Gamepad};
+use crate::platform::{Detached, Event, Key, Surface, Window};
+
+/// The longest the main thread waits for events before serving requests
+/// and reading the gamepad again.
+const WAIT: Duration = Duration::from_millis(10);
+
+/// What the window looks like after the events sent with it.
+#[derive(Clone, PartialEq)]
+struct Snapshot {
+    held: Vec<Key>,
+    cursor: (f64, f64),
+    size: (i32, i32),
+    position: (i32, i32),
+    framebuffer_size: (i32, i32),
+    transparent: bool,
+    should_close: bool,
+}
+
+impl Snapshot {
+    fn of(window: &dyn Window) -> Snapshot {
+        Snapshot {
+            held: window.held_keys(),
+            cursor: window.cursor_pos(),
+            size: window.size(),
+            position: window.position(),
+            framebuffer_size: window.framebuffer_size(),
+            transparent: window.is_transparent(),
+            should_close: window.should_close(),
+        }
+    }
+}
+
+/// From the main thread to the render thread.
+enum Update {
+    Frame { events: Vec<Event>, snapshot: Snapshot, gamepad: gamepad::Input },
+    /// The answer to `Request::GetClipboard`.
+    Clipboard(Option<String>),
+}
+
+/// From the render thread to the main thread.
+enum Request {
+    SetTitle(String),
+    SetClipboard(String),
+    GetClipboard,
+    SetShouldClose(bool),
+}
+
+/// The window as the render thread sees it.
+pub struct RemoteWindow {
+    surface: Box<dyn Surface>,
+    updates: Receiver<Update>,
+    requests: Sender<Request>,
+    snapshot: Snapshot,
+    /// Taken but not yet handed out by `poll_events`.
+    events: Vec<Event>,
+    gamepad: gamepad::Input,
+    /// Set here or by the main thread; never unset by a stale snapshot.
+    should_close: bool,
+    title: String,
+}
+
+impl RemoteWindow {
+    /// What the gamepad asks for since the last call.
+    pub fn take_gamepad(&mut self) -> gamepad::Input {
+        let input = self.gamepad.clone();
+        self.gamepad.screenshot = false;
+        self.gamepad.reset = false;
+        input
+    }
+
+    fn take(&mut self, update: Update) -> Option<Option<String>> {
+        match update {
+            Update::Frame { events, snapshot, gamepad } => {
+                for event in &events {
+                    if let &Event::FramebufferSize(width, height) = event {
+                        self.surface.resize(width, height);
+                    }
+                }
+                self.events.extend(events);
+                self.should_close |= snapshot.should_close;
+                self.snapshot = snapshot;
+                self.gamepad.merge(gamepad);
+                None
+            }
+            Update::Clipboard(text) => Some(text),
+        }
+    }
+
+    /// Sends `request`; the main thread only goes away after this one does.
+    fn request(&self, request: Request) {
+        let _ = self.requests.send(request);
+    }
+}
+
+impl Window for RemoteWindow {
+    fn poll_events(&mut self) -> Vec<Event> {
+        while let Ok(update) = self.updates.try_recv() {
+            self.take(update);
+        }
+        std::mem::take(&mut self.events)
+    }
+
+    fn wait_events(&mut self, timeout: Duration) -> Vec<Event> {
+        if self.events.is_empty() {
+            if let Ok(update) = self.updates.recv_timeout(timeout) {
+                self.take(update);
+            }
+        }
+        self.poll_events()
+    }
+
+    fn is_held(&self, key: Key) -> bool {
+        self.snapshot.held.contains(&key)
+    }
+
+    fn held_keys(&self) -> Vec<Key> {
+        self.snapshot.held.clone()
+    }
+
+    fn cursor_pos(&self) -> (f64, f64) {
+        self.snapshot.cursor
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.snapshot.size
+    }
+
+    fn position(&self) -> (i32, i32) {
+        self.snapshot.position
+    }
+
+    fn framebuffer_size(&self) -> (i32, i32) {
+        self.snapshot.framebuffer_size
+    }
+
+    fn is_transparent(&self) -> bool {
+        self.snapshot.transparent
+    }
+
+    fn set_title(&mut self, title: &str) {
+        // the title is set every frame while exports run
+        if self.title != title {
+            self.title = title.to_string();
+            self.request(Request::SetTitle(self.title.clone()));
+        }
+    }
+
+    fn should_close(&self) -> bool {
+        self.should_close
+    }
+
+    fn set_should_close(&mut self, close: bool) {
+        self.should_close = close;
+        self.request(Request::SetShouldClose(close));
+    }
+
+    fn swap_buffers(&mut self) {
+        self.surface.swap_buffers();
+    }
+
+    fn clipboard_text(&mut self) -> Option<String> {
+        self.request(Request::GetClipboard);
+        // frames sent meanwhile wait for the next `poll_events`
+        while let Ok(update) = self.updates.recv() {
+            if let Some(text) = self.take(update) {
+                return text;
+            }
+        }
+        None
+    }
+
+    fn set_clipboard_text(&mut self, text: &str) {
+        self.request(Request::SetClipboard(text.to_string()));
+    }
+
+    fn proc_address(&mut self, name: &str) -> *const c_void {
+        self.surface.proc_address(name)
+    }
+
+    fn context_version(&self) -> (u32, u32) {
+        self.surface.context_version()
+    }
+
+    fn detach(&mut self) -> Result<Box<dyn Detached>, String> {
+        Err("the context is already on the render thread".to_string())
+    }
+}
+
+/// Hands `window`'s context to a new thread and runs `explore` there, while
+/// this thread pumps the window's events and reads `gamepad` until
+/// `explore` returns.
+pub fn run<F>(mut window: Box<dyn Window>, mut gamepad: Option<Gamepad>, explore: F) -> Result<(), StartError>
+where
+    F: FnOnce(RemoteWindow) -> Result<(), StartError> + Send + 'static,
+{
+    let detached = window.detach().map_err(StartError::RenderThread)?;
+    let (update_sender, updates) = mpsc::channel();
+    let (request_sender, requests) = mpsc::channel();
+    let mut snapshot = Snapshot::of(&*window);
+    let first = snapshot.clone();
+    let render = thread::Builder::new()
+        .name("render".to_string())
+        .spawn(move || {
+            let surface = detached.make_current().map_err(StartError::RenderThread)?;
+            explore(RemoteWindow {
+                surface,
+                updates,
+                requests: request_sender,
+                snapshot: first,
+                events: Vec::new(),
+                gamepad: gamepad::Input::default(),
+                should_close: false,
+                title: String::new(),
+            })
+        })
+        .map_err(|err| StartError::RenderThread(err.to_string()))?;
+
+    let mut pad = gamepad::Input::default();
+    'pump: loop {
+        let events = window.wait_events(WAIT);
+        let next_pad = gamepad.as_mut().map_or_else(gamepad::Input::default, Gamepad::poll);
+        let next = Snapshot::of(&*window);
+        if !events.is_empty() || next != snapshot || next_pad != pad {
+            snapshot = next;
+            pad = next_pad;
+            let frame = Update::Frame { events, snapshot: snapshot.clone(), gamepad: pad.clone() };
+            // gone means the render thread is done, which the requests show
+            let _ = update_sender.send(frame);
+        }
+        loop {
+            match requests.try_recv() {
+                Ok(Request::SetTitle(title)) => window.set_title(&title),
+                Ok(Request::SetClipboard(text)) => window.set_clipboard_text(&text),
+                Ok(Request::GetClipboard) => {
+                    let _ = update_sender.send(Update::Clipboard(window.clipboard_text()));
+                }
+                Ok(Request::SetShouldClose(close)) => window.set_should_close(close),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => break 'pump,
+            }
+        }
+    }
+    // the window goes only once the surface on the render thread is gone
+    let result = render.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+    drop(window);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use super::*;
+    use crate::platform::{Action, Modifiers};
+
+    /// What the fake window and surface did, as seen from the test.
+    #[derive(Default)]
+    struct Log {
+        title: String,
+        closed: bool,
+        surface_size: (i32, i32),
+        dropped: Vec<&'static str>,
+    }
+
+    /// A window whose user never stops scrolling: every wait for events
+    /// comes back with another scroll, tagged with its number, and every
+    /// tenth also resizes the framebuffer.
+    struct Spamming {
+        log: Arc<Mutex<Log>>,
+        sent: u32,
+        should_close: bool,
+    }
+
+    impl Window for Spamming {
+        fn poll_events(&mut self) -> Vec<Event> {
+            self.sent += 1;
+            let mut events = vec![Event::Key(Key::W, Action::Repeat, Modifiers::default()), Event::Scroll(0., self.sent as f64)];
+            if self.sent.is_multiple_of(10) {
+                events.push(Event::FramebufferSize(600 + self.sent as i32, 400));
+            }
+            events
+        }
+
+        fn wait_events(&mut self, _: Duration) -> Vec<Event> {
+            thread::sleep(Duration::from_micros(200));
+            self.poll_events()
+        }
+
+        fn is_held(&self, key: Key) -> bool {
+            key == Key::W
+        }
+
+        fn held_keys(&self) -> Vec<Key> {
+            vec![Key::W]
+        }
+
+        fn cursor_pos(&self) -> (f64, f64) {
+            (self.sent as f64, 0.)
+        }
+
+        fn size(&self) -> (i32, i32) {
+            (600, 400)
+        }
+
+        fn position(&self) -> (i32, i32) {
+            (0, 0)
+        }
+
+        fn framebuffer_size(&self) -> (i32, i32) {
+            (600, 400)
+        }
+
+        fn is_transparent(&self) -> bool {
+            false
+        }
+
+        fn set_title(&mut self, title: &str) {
+            self.log.lock().unwrap().title = title.to_string();
+        }
+
+        fn should_close(&self) -> bool {
+            self.should_close
+        }
+
+        fn set_should_close(&mut self, close: bool) {
+            self.should_close = close;
+            self.log.lock().unwrap().closed = close;
+        }
+
+        fn swap_buffers(&mut self) {}
+
+        fn clipboard_text(&mut self) -> Option<String> {
+            Some(format!("clipboard after {} waits", self.sent))
+        }
+
+        fn set_clipboard_text(&mut self, _: &str) {}
+
+        fn proc_address(&mut self, _: &str) -> *const c_void {
+            std::ptr::null()
+        }
+
+        fn context_version(&self) -> (u32, u32) {
+            (3, 3)
+        }
+
+        fn detach(&mut self) -> Result<Box<dyn Detached>, String> {
+            Ok(Box::new(Handed(self.log.clone())))
+        }
+    }
+
+    impl Drop for Spamming {
+        fn drop(&mut self) {
+            self.log.lock().unwrap().dropped.push("window");
+        }
+    }
+
+    struct Handed(Arc<Mutex<Log>>);
+
+    impl Detached for Handed {
+        fn make_current(self: Box<Self>) -> Result<Box<dyn Surface>, String> {
+            Ok(Box::new(FakeSurface(self.0)))
+        }
+    }
+
+    struct FakeSurface(Arc<Mutex<Log>>);
+
+    impl Surface for FakeSurface {
+        fn swap_buffers(&mut self) {}
+
+        fn proc_address(&mut self, _: &str) -> *const c_void {
+            std::ptr::null()
+        }
+
+        fn context_version(&self) -> (u32, u32) {
+            (3, 3)
+        }
+
+        fn resize(&mut self, width: i32, height: i32) {
+            self.0.lock().unwrap().surface_size = (width, height);
+        }
+    }
+
+    impl Drop for FakeSurface {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().dropped.push("surface");
+        }
+    }
+
+    fn spamming(log: &Arc<Mutex<Log>>) -> Box<dyn Window> {
+        Box::new(Spamming { log: log.clone(), sent: 0, should_close: false })
+    }
+
+    #[test]
+    fn slow_frames_take_every_event_in_order() {
+        let log = Arc::new(Mutex::new(Log::default()));
+        let seen = Arc::new(Mutex::new((Vec::new(), 0)));
+        let frames = seen.clone();
+        let surface_log = log.clone();
+        run(spamming(&log), None, move |mut window| {
+            let started = Instant::now();
+            let mut clipboard = None;
+            while !window.should_close() {
+                let events = window.poll_events();
+                let mut frames = frames.lock().unwrap();
+                frames.1 += 1;
+                frames.0.extend(events.iter().filter_map(|event| match *event {
+                    Event::Scroll(_, n) => Some(n as u32),
+                    _ => None,
+                }));
+                // the surface was resized before the frame that draws at the new size
+                if let Some(&Event::FramebufferSize(width, height)) = events.iter().rev().find(|event| matches!(event, Event::FramebufferSize(..))) {
+                    assert_eq!(surface_log.lock().unwrap().surface_size, (width, height));
+                }
+                assert!(window.is_held(Key::W));
+                drop(frames);
+                // a deliberately slow render
+                thread::sleep(Duration::from_millis(20));
+                if clipboard.is_none() && started.elapsed() > Duration::from_millis(100) {
+                    clipboard = window.clipboard_text();
+                }
+                if started.elapsed() > Duration::from_millis(300) {
+                    window.set_title(clipboard.as_deref().unwrap_or("no clipboard"));
+                    window.set_should_close(true);
+                }
+            }
+            Ok(())
+        })
+        .expect("no start errors");
+
+        let (scrolls, frames) = &*seen.lock().unwrap();
+        // the events are all there, in the order they came in, up to the last frame
+        assert!(scrolls.len() > 50, "only {} events in 300 ms", scrolls.len());
+        assert!(scrolls.iter().zip(1..).all(|(&n, expected)| n == expected), "{scrolls:?}");
+        // piled up during each slow frame, not one frame each
+        assert!(*frames < scrolls.len() / 4, "{frames} frames for {} events", scrolls.len());
+        let log = log.lock().unwrap();
+        assert!(log.title.starts_with("clipboard after "), "{}", log.title);
+        assert!(log.closed);
+        assert_eq!(log.dropped, ["surface", "window"]);
+    }
+
+    #[test]
+    fn a_panic_on_the_render_thread_comes_back() {
+        let log = Arc::new(Mutex::new(Log::default()));
+        let window = spamming(&log);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            run(window, None, |mut window| {
+                thread::sleep(Duration::from_millis(20));
+                window.poll_events();
+                panic!("a bug while drawing");
+            })
+        }));
+        let payload = result.expect_err("the panic is raised again");
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"a bug while drawing"));
+        // the window only goes with the unwinding, still after the surface
+        assert_eq!(log.lock().unwrap().dropped, ["surface", "window"]);
+    }
+}
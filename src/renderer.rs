@@ -0,0 +1,942 @@
+// The GL side of the fractal: the shader program, the triangle it is drawn on and
+// the offscreen targets used when rendering without a visible window.
+
+use gl::*;
+use gl::types::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::c_void;
+use std::ptr;
+use std::str;
+use std::sync::{PoisonError, RwLock};
+
+use crate::background::{BackgroundFit, BackgroundStyle, BlendMode};
+use crate::caps::{Feature, GpuCapabilities};
+use crate::cpu::Rgba16Image;
+use crate::gl_object::{self, Texture};
+use crate::mesh::{self, FullscreenTriangle};
+use crate::palette::{ColorMode, Lighting, Palette, PaletteTable, SlowTint, Tone};
+use crate::shader::{Dialect, Program, Shader, ShaderError, Stage};
+use crate::viewport::ViewUniforms;
+
+// Embedded so the binary stands alone; `set_shader_sources` and
+// `Renderer::reload` swap in edited copies loaded from disk.
+const VERTEX_SOURCE: &str = include_str!("../shaders/fractal.vert");
+const FRAGMENT_SOURCE: &str = include_str!("../shaders/fractal.frag");
+
+/// What replaces the embedded vertex and fragment shader, if anything.
+static LOADED_SOURCES: RwLock<(Option<String>, Option<String>)> = RwLock::new((None, None));
+
+/// Has every `Renderer` created from now on compile `vertex` and `fragment`
+/// instead of the embedded shaders, each stage that is `None` the embedded
+/// one. Running renderers keep theirs until `Renderer::reload`.
+pub fn set_shader_sources(vertex: Option<String>, fragment: Option<String>) {
+    *LOADED_SOURCES.write().unwrap_or_else(PoisonError::into_inner) = (vertex, fragment);
+}
+
+/// The vertex shader `Renderer::new` compiles.
+pub fn vertex_shader_source() -> String {
+    let loaded = LOADED_SOURCES.read().unwrap_or_else(PoisonError::into_inner);
+    loaded.0.clone().unwrap_or_else(|| VERTEX_SOURCE.to_string())
+}
+
+/// The fragment shader exactly as `Renderer::new` compiles it, uniform
+/// declarations included, for inspecting what the driver was given; contexts
+/// without GLSL 3.30 get it through `Dialect::translate` first.
+pub fn fragment_shader_source() -> String {
+    let loaded = LOADED_SOURCES.read().unwrap_or_else(PoisonError::into_inner);
+    loaded.1.clone().unwrap_or_else(|| FRAGMENT_SOURCE.to_string())
+}
+
+/// The fragment shader for one view and palette as a single Shadertoy image
+/// pass: `mainImage` instead of `main`, the uniforms baked in as constants
+/// and the palette as an array, so it runs wherever GLSL ES 3 does. The view
+/// follows `iResolution` at the same height and pixel aspect; `iTime` stands
+/// in for the animation time and `iChannel0` for the background image, which
+/// isn't exported.
+pub fn shadertoy_source(params: &DrawParams, palette: &PaletteTable) -> String {
+    let mut values: HashMap<&str, Uniform> = uniform_values(params).into_iter().collect();
+    values.insert("raw_iterations", Uniform::Int(0));
+    values.insert("background", Uniform::Int(0));
+    values.insert("frag_coord_mapping", Uniform::Int(1));
+    values.insert("pixel_offset", Uniform::Vec2([0., 0.]));
+    values.insert("write_state", Uniform::Int(0));
+    values.insert("resume", Uniform::Int(0));
+    values.insert("iteration_first", Uniform::Int(0));
+    values.insert("state_size", Uniform::Vec2([1., 1.]));
+    let view = &params.view;
+    let anisotropy = (view.scale[0] / view.scale[1]) / (view.resolution[0] / view.resolution[1]);
+
+    let mut out = format!(
+        "// Mandelplotter view baked for Shadertoy: offset ({:?}, {:?}), scale {:?}, \
+         {} iterations.\n\n",
+        view.offset[0], view.offset[1], view.scale[1], params.substeps
+    );
+    out.push_str("#define time iTime\n#define resolution iResolution.xy\n#define background_image iChannel0\n");
+    // never read, with `resume` off
+    out.push_str("#define iteration_state iChannel0\n#define derivative_state iChannel0\n");
+    out.push_str(&format!(
+        "#define scale vec2({:?} * {:?} * iResolution.x / iResolution.y, {:?})\n\n",
+        view.scale[1], anisotropy, view.scale[1]
+    ));
+
+    let colors: Vec<String> = palette
+        .texels
+        .iter()
+        .map(|[r, g, b, _]| format!("vec3({:?}, {:?}, {:?})", *r as f32 / 255., *g as f32 / 255., *b as f32 / 255.))
+        .collect();
+    let n = colors.len();
+    out.push_str(&format!("const vec3 palette_colors[{n}] = vec3[](\n    {}\n);\n\n", colors.join(",\n    ")));
+    // same as PaletteTable::sample
+    out.push_str(&format!(
+        "vec4 palette_texel(float t){{\n    \
+         float x = clamp(clamp(t, 0., 1.) * {n}. - 0.5, 0., {:?});\n    \
+         int i = int(floor(x));\n    \
+         return vec4(mix(palette_colors[i], palette_colors[min(i + 1, {})], x - floor(x)), 1.);\n}}\n\n\
+         vec2 position;\nvec4 frag_coord;\n",
+        (n - 1) as f32,
+        n - 1
+    ));
+
+    for line in fragment_shader_source().lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#version") || trimmed.starts_with("in vec2 position;") || trimmed.starts_with("layout(location = 0) out vec4 FragColor;") {
+            continue;
+        }
+        if let Some(output) = trimmed.strip_prefix("layout(location = 1) out ") {
+            // the other outputs are plain globals, there being one in an image pass
+            out.push_str(output);
+            out.push('\n');
+            continue;
+        }
+        if let Some(declaration) = trimmed.strip_prefix("uniform ") {
+            // `uniform TYPE NAME; // comment`
+            let (declaration, comment) = declaration.split_once("//").unwrap_or((declaration, ""));
+            let Some((ty, name)) = declaration.trim().trim_end_matches(';').split_once(' ') else {
+                continue;
+            };
+            let Some(&value) = values.get(name) else {
+                continue;
+            };
+            if matches!(name, "time" | "scale" | "resolution" | "background_image" | "palette" | "iteration_state" | "derivative_state") {
+                continue;
+            }
+            let literal = match (ty, value) {
+                ("bool", Uniform::Int(v)) => (v != 0).to_string(),
+                (_, Uniform::Int(v)) => v.to_string(),
+                (_, Uniform::Uint(v)) => format!("{v}u"),
+                (_, Uniform::Float(v)) => format!("{v:?}"),
+                (_, Uniform::Vec2([x, y])) => format!("vec2({x:?}, {y:?})"),
+                (_, Uniform::Vec3([x, y, z])) => format!("vec3({x:?}, {y:?}, {z:?})"),
+            };
+            let comment = if comment.is_empty() { String::new() } else { format!(" //{comment}") };
+            out.push_str(&format!("const {ty} {name} = {literal};{comment}\n"));
+            continue;
+        }
+        let line = line.replace("texture(palette, ", "palette_texel(").replace("gl_FragCoord", "frag_coord");
+        if line.trim() == "void main() {" {
+            out.push_str("void mainImage(out vec4 FragColor, in vec2 fragCoord) {\n");
+            out.push_str("    frag_coord = vec4(fragCoord, 0., 1.);\n");
+            out.push_str("    position = fragCoord / iResolution.xy * 2. - 1.;\n");
+            continue;
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// Compiles a `shadertoy_source` shader the way Shadertoy wraps an image
+/// pass, with desktop GLSL standing in for GLSL ES unless the context is an
+/// ES one. Needs a current context.
+pub fn check_shadertoy(source: &str) -> Result<(), String> {
+    let version = if is_gles() { "#version 300 es\nprecision highp float;\nprecision highp int;" } else { "#version 330 core" };
+    let wrapped = format!(
+        "{version}\nuniform vec3 iResolution;\nuniform float iTime;\nuniform sampler2D iChannel0;\n\
+         out vec4 shadertoy_color;\n{source}\nvoid main() {{ mainImage(shadertoy_color, gl_FragCoord.xy); }}\n"
+    );
+    Shader::compile(Stage::Fragment, &wrapped).map(drop).map_err(|err| err.to_string())
+}
+
+/// Whether the current context is OpenGL ES rather than desktop GL. Needs
+/// the GL functions loaded.
+pub fn is_gles() -> bool {
+    Dialect::current() == Dialect::GlslEs300
+}
+
+/// Whether the current context has the extension `name`, such as
+/// "GL_EXT_color_buffer_float".
+pub fn has_extension(name: &str) -> bool {
+    GpuCapabilities::current().has_extension(name)
+}
+
+/// How the fragment shader finds where in the view a pixel is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoordinateMapping {
+    /// From `gl_FragCoord` and the target resolution, independent of the geometry.
+    FragCoord,
+    /// From the position interpolated across the full-screen triangle, `[-1, 1]` on screen.
+    QuadPosition,
+}
+
+/// Everything the fragment shader reads for one frame.
+#[derive(Clone, Copy, PartialEq)]
+pub struct DrawParams {
+    pub view: ViewUniforms,
+    pub substeps: i32,
+    /// Supersamples per pixel axis, 1 disables supersampling.
+    pub ssaa: i32,
+    /// Seed of the supersample jitter; a fixed seed gives identical frames.
+    pub seed: u32,
+    pub time: f32,
+    /// Draw the Julia set of this constant instead of the Mandelbrot set.
+    pub julia: Option<(f64, f64)>,
+    /// How much of the Julia set shows, with `julia` set: 1 draws only it,
+    /// lower values also draw the Mandelbrot set and cross-fade the two,
+    /// which costs twice as much while switching between them.
+    pub morph: f32,
+    pub transparent_interior: bool,
+    pub color_mode: ColorMode,
+    /// Spread the palette over this many iterations instead of `substeps`,
+    /// so the colors hold still while the iteration count changes.
+    pub color_span: Option<i32>,
+    pub checkerboard: bool,
+    /// Composite over this color, so no pixel is left see-through; the
+    /// window needs it, as compositing desktops show its alpha. The
+    /// checkerboard goes over it.
+    pub backdrop: Option<[f32; 3]>,
+    /// Leave the alpha in, with the color premultiplied by it as compositors
+    /// take it, for a window the desktop shows through; without `backdrop`.
+    pub premultiplied: bool,
+    /// Mark points the iteration count is too low for.
+    pub slow_tint: Option<SlowTint>,
+    /// Relief shading of the exterior.
+    pub lighting: Option<Lighting>,
+    /// Brightness and contrast over the finished color.
+    pub tone: Tone,
+    pub mapping: CoordinateMapping,
+    /// Remap the plane logarithmically around the view center, see
+    /// `Viewport::log_polar`.
+    pub log_polar: bool,
+    /// Composite over the image given to `Renderer::set_background`.
+    pub background: Option<BackgroundStyle>,
+}
+
+/// A uniform's value as last uploaded.
+#[derive(Clone, Copy, PartialEq)]
+enum Uniform {
+    Int(i32),
+    Uint(u32),
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+}
+
+/// Whether `value` differs from what was `last` uploaded, recording it as
+/// uploaded if so.
+fn changed(last: &mut Option<Uniform>, value: Uniform) -> bool {
+    if *last == Some(value) {
+        return false;
+    }
+    *last = Some(value);
+    true
+}
+
+/// The value of every uniform but `raw_iterations` for `params`, samplers
+/// as texture units.
+fn uniform_values(params: &DrawParams) -> Vec<(&'static str, Uniform)> {
+    let view = &params.view;
+    let (julia_x, julia_y) = params.julia.unwrap_or_default();
+    let style = params.background.unwrap_or(BackgroundStyle { fit: BackgroundFit::Fill, blend: BlendMode::Over });
+    vec![
+        ("time", Uniform::Float(params.time)),
+        ("scale", Uniform::Vec2(view.scale)),
+        ("rotation", Uniform::Vec2(view.rotation)),
+        ("substeps", Uniform::Int(params.substeps)),
+        ("offset", Uniform::Vec2(view.offset)),
+        ("ssaa", Uniform::Int(params.ssaa)),
+        ("seed", Uniform::Uint(params.seed)),
+        ("transparent_interior", Uniform::Int(params.transparent_interior as i32)),
+        ("julia", Uniform::Int(params.julia.is_some() as i32)),
+        ("julia_c", Uniform::Vec2([julia_x as f32, julia_y as f32])),
+        ("morph", Uniform::Float(params.morph)),
+        (
+            "color_mode",
+            Uniform::Int(match params.color_mode {
+                ColorMode::EscapeTime => 0,
+                ColorMode::Angle => 1,
+                ColorMode::Blend => 2,
+            }),
+        ),
+        ("color_span", Uniform::Int(params.color_span.unwrap_or(0))),
+        ("checkerboard", Uniform::Int(params.checkerboard as i32)),
+        ("backdrop", Uniform::Int(params.backdrop.is_some() as i32)),
+        ("backdrop_color", Uniform::Vec3(params.backdrop.unwrap_or([0.; 3]))),
+        ("premultiplied", Uniform::Int(params.premultiplied as i32)),
+        ("slow_tint", Uniform::Int(params.slow_tint.is_some() as i32)),
+        ("slow_color", Uniform::Vec3(params.slow_tint.map_or([0.; 3], |tint| tint.color))),
+        ("slow_threshold", Uniform::Float(params.slow_tint.map_or(0., |tint| tint.threshold))),
+        ("relief", Uniform::Int(params.lighting.is_some() as i32)),
+        ("light", Uniform::Vec2(params.lighting.map_or([1., 0.], |lighting| {
+            let angle = lighting.angle.to_radians();
+            [angle.cos(), angle.sin()]
+        }))),
+        ("light_intensity", Uniform::Float(params.lighting.map_or(0., |lighting| lighting.intensity))),
+        ("brightness", Uniform::Float(params.tone.brightness)),
+        ("contrast", Uniform::Float(params.tone.contrast)),
+        ("resolution", Uniform::Vec2(view.resolution)),
+        ("pixel_offset", Uniform::Vec2(view.pixel_offset)),
+        ("frag_coord_mapping", Uniform::Int((params.mapping == CoordinateMapping::FragCoord) as i32)),
+        ("log_polar", Uniform::Int(params.log_polar as i32)),
+        ("background", Uniform::Int(params.background.is_some() as i32)),
+        (
+            "background_fit",
+            Uniform::Int(match style.fit {
+                BackgroundFit::Fit => 0,
+                BackgroundFit::Fill => 1,
+                BackgroundFit::Stretch => 2,
+            }),
+        ),
+        (
+            "blend_mode",
+            Uniform::Int(match style.blend {
+                BlendMode::Over => 0,
+                BlendMode::Multiply => 1,
+                BlendMode::Screen => 2,
+            }),
+        ),
+        ("background_image", Uniform::Int(1)),
+        ("palette", Uniform::Int(0)),
+        // units of their own even when unused: samplers of two types on one unit fail every draw
+        ("iteration_state", Uniform::Int(2)),
+        ("derivative_state", Uniform::Int(3)),
+    ]
+}
+
+/// Owns its GL objects and deletes them when dropped, which must happen while
+/// the context it was created in is still current. They go in the order they
+/// are declared in: the program, then the geometry, then the textures.
+pub struct Renderer {
+    program: Program,
+    triangle: FullscreenTriangle,
+    /// `TEXTURE_1D`, or on OpenGL ES, which has no 1D textures, a
+    /// `TEXTURE_2D` one texel high.
+    palette_target: GLenum,
+    palette: Texture,
+    /// Texels in `palette`, which ES 3.0 can't ask the texture for.
+    palette_width: Cell<GLsizei>,
+    background: Texture,
+    /// Of the image in `background`, for GLSL 1.20, which can't ask it.
+    background_size: Cell<[f32; 2]>,
+    dialect: Dialect,
+    /// Location and last uploaded value of every uniform set so far.
+    uniforms: RefCell<HashMap<&'static str, (GLint, Option<Uniform>)>>,
+    /// Iterations one draw runs at most, see `set_iterations_per_draw`.
+    iterations_per_draw: Cell<Option<i32>>,
+    /// Where split draws leave the orbits for the next, made on first use.
+    orbit_states: RefCell<Option<[OrbitState; 2]>>,
+    /// Whether it was said why a draw couldn't be split, which is only worth
+    /// saying once.
+    warned_unsplit: Cell<bool>,
+}
+
+impl Renderer {
+    /// Compiles the shader program and uploads the triangle. Needs a current GL
+    /// context with the function pointers already loaded. Fails with the
+    /// compiler/linker output if the driver rejects the shaders.
+    pub fn new() -> Result<Renderer, ShaderError> {
+        let dialect = Dialect::current();
+        let program = build_program(dialect, &vertex_shader_source(), &fragment_shader_source())?;
+        let triangle = FullscreenTriangle::new(dialect.vertex_arrays()).map_err(ShaderError::Context)?;
+
+        let palette_target = if dialect == Dialect::GlslEs300 { TEXTURE_2D } else { TEXTURE_1D };
+        let palette = Texture::new(palette_target).map_err(ShaderError::Context)?;
+        unsafe {
+            TexParameteri(palette_target, TEXTURE_MIN_FILTER, LINEAR as GLint);
+            TexParameteri(palette_target, TEXTURE_MAG_FILTER, LINEAR as GLint);
+            TexParameteri(palette_target, TEXTURE_WRAP_S, CLAMP_TO_EDGE as GLint);
+            TexParameteri(palette_target, TEXTURE_WRAP_T, CLAMP_TO_EDGE as GLint);
+        }
+
+        let background = Texture::new(TEXTURE_2D).map_err(ShaderError::Context)?;
+        unsafe {
+            TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, LINEAR as GLint);
+            TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR as GLint);
+            TexParameteri(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as GLint);
+            TexParameteri(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as GLint);
+        }
+
+        let renderer = Renderer {
+            program,
+            triangle,
+            palette_target,
+            palette,
+            palette_width: Cell::new(0),
+            background,
+            background_size: Cell::new([1., 1.]),
+            dialect,
+            uniforms: RefCell::default(),
+            iterations_per_draw: Cell::new(None),
+            orbit_states: RefCell::new(None),
+            warned_unsplit: Cell::new(false),
+        };
+        renderer.set_palette(&Palette::grayscale().build());
+        Ok(renderer)
+    }
+
+    /// Swaps in a program built from `vertex` and `fragment`, as in
+    /// `set_shader_sources`, and makes them the sources from now on if it
+    /// builds; otherwise keeps drawing with the one it had. The palette and
+    /// background stay, and uniforms are looked up again in the new program.
+    pub fn reload(&mut self, vertex: Option<String>, fragment: Option<String>) -> Result<(), ShaderError> {
+        self.program = build_program(
+            self.dialect,
+            vertex.as_deref().unwrap_or(VERTEX_SOURCE),
+            fragment.as_deref().unwrap_or(FRAGMENT_SOURCE),
+        )?;
+        self.uniforms.get_mut().clear();
+        set_shader_sources(vertex, fragment);
+        Ok(())
+    }
+
+    pub fn set_palette(&self, table: &PaletteTable) {
+        tight_rows();
+        let width = table.texels.len() as GLsizei;
+        let texels = table.texels.as_ptr() as *const c_void;
+        unsafe {
+            BindTexture(self.palette_target, self.palette.id());
+            if self.palette_target == TEXTURE_1D {
+                TexImage1D(TEXTURE_1D, 0, RGBA8 as GLint, width, 0, RGBA, UNSIGNED_BYTE, texels);
+            } else {
+                TexImage2D(TEXTURE_2D, 0, RGBA8 as GLint, width, 1, 0, RGBA, UNSIGNED_BYTE, texels);
+            }
+        }
+        self.palette_width.set(width);
+    }
+
+    /// Uploads the image that `DrawParams::background` composites over.
+    pub fn set_background(&self, image: &image::RgbaImage) {
+        tight_rows();
+        unsafe {
+            BindTexture(TEXTURE_2D, self.background.id());
+            TexImage2D(
+                TEXTURE_2D, 0, RGBA8 as GLint, image.width() as GLsizei, image.height() as GLsizei, 0,
+                RGBA, UNSIGNED_BYTE, image.as_raw().as_ptr() as *const c_void,
+            );
+        }
+        self.background_size.set([image.width() as f32, image.height() as f32]);
+    }
+
+    /// Has every draw with more than `cap` iterations run them over several
+    /// draws of at most `cap` each, which carry each pixel's orbit forward in
+    /// float targets: a long draw can hang the desktop, and Windows resets a
+    /// GPU that is busy with one for two seconds. `None` draws every frame in
+    /// one go. Supersampled draws, and the cross-fade between the Mandelbrot
+    /// and Julia sets, need more than one orbit per pixel and still draw in
+    /// one go, as does everything on a driver without float targets.
+    pub fn set_iterations_per_draw(&self, cap: Option<i32>) {
+        self.iterations_per_draw.set(cap.filter(|&cap| cap > 0));
+    }
+
+    /// The GLSL the shaders were compiled as.
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// Reads the palette back from the GPU, i.e. exactly what the shader samples.
+    pub fn read_palette(&self) -> Vec<[u8; 4]> {
+        let width = self.palette_width.get();
+        let mut texels = vec![[0u8; 4]; width as usize];
+        tight_rows();
+        if self.palette_target == TEXTURE_1D {
+            unsafe {
+                BindTexture(TEXTURE_1D, self.palette.id());
+                GetTexImage(TEXTURE_1D, 0, RGBA, UNSIGNED_BYTE, texels.as_mut_ptr() as *mut c_void);
+            }
+            return texels;
+        }
+        // ES can't read textures back, only framebuffers
+        let Ok(fbo) = gl_object::Framebuffer::new() else {
+            return texels;
+        };
+        unsafe {
+            FramebufferTexture2D(FRAMEBUFFER, COLOR_ATTACHMENT0, TEXTURE_2D, self.palette.id(), 0);
+            ReadPixels(0, 0, width, 1, RGBA, UNSIGNED_BYTE, texels.as_mut_ptr() as *mut c_void);
+            BindFramebuffer(FRAMEBUFFER, 0);
+        }
+        drop(fbo);
+        texels
+    }
+
+    /// Clears the bound framebuffer and draws the fractal over all of it.
+    pub fn draw(&self, params: &DrawParams) {
+        self.draw_with(params, false);
+    }
+
+    /// Draws the values of `cpu::raw_sample` at each pixel center instead of
+    /// colors, for a float target such as `Framebuffer::new_float`.
+    pub fn draw_raw(&self, params: &DrawParams) {
+        self.draw_with(params, true);
+    }
+
+    /// Clears the bound framebuffer and draws over `width x height` pixels of
+    /// it, for hosts that own the context and the window and only hand over
+    /// the size of the target.
+    pub fn draw_to(&self, params: &DrawParams, width: i32, height: i32) {
+        unsafe {
+            gl::Viewport(0, 0, width, height);
+        }
+        self.draw(params);
+    }
+
+    /// Makes the program current and sets every uniform and the palette
+    /// texture from `params`, leaving the draw call to the caller. Uniforms
+    /// keep their values in the program between draws, so only the ones whose
+    /// value differs from the last upload are sent: a still view sends none.
+    pub fn set_uniforms(&self, params: &DrawParams) {
+        debug_assert!(self.program.is_linked(), "drawing with fractal program {} that isn't linked in the current context", self.program.id());
+        unsafe {
+            UseProgram(self.program.id());
+        }
+        for (name, value) in uniform_values(params) {
+            self.set_uniform(name, value);
+        }
+        self.set_uniform("background_image_size", Uniform::Vec2(self.background_size.get()));
+
+        // the bindings are context state that imgui changes, so they are set every time
+        unsafe {
+            ActiveTexture(TEXTURE1);
+            BindTexture(TEXTURE_2D, self.background.id());
+            ActiveTexture(TEXTURE0);
+            BindTexture(self.palette_target, self.palette.id());
+        }
+    }
+
+    /// Uploads `value` to the uniform `name` of the current program unless
+    /// it already holds it, or the compiler dropped it as unused (as `time`,
+    /// which would otherwise change every frame). Locations are looked up on
+    /// first use.
+    fn set_uniform(&self, name: &'static str, value: Uniform) {
+        let mut uniforms = self.uniforms.borrow_mut();
+        let (location, last) = uniforms.entry(name).or_insert_with(|| {
+            let c_name = CString::new(name).expect("uniform names are literals without NULs");
+            (unsafe { GetUniformLocation(self.program.id(), c_name.as_ptr()) }, None)
+        });
+        if *location < 0 || !changed(last, value) {
+            return;
+        }
+        unsafe {
+            match value {
+                Uniform::Int(v) => Uniform1i(*location, v),
+                Uniform::Uint(v) => Uniform1ui(*location, v),
+                Uniform::Float(v) => Uniform1f(*location, v),
+                Uniform::Vec2([x, y]) => Uniform2f(*location, x, y),
+                Uniform::Vec3([x, y, z]) => Uniform3f(*location, x, y, z),
+            }
+        }
+    }
+
+    fn draw_with(&self, params: &DrawParams, raw_iterations: bool) {
+        self.set_uniforms(params);
+        self.set_uniform("raw_iterations", Uniform::Int(raw_iterations as i32));
+        self.set_uniform("write_state", Uniform::Int(0));
+        self.set_uniform("resume", Uniform::Int(0));
+        self.set_uniform("iteration_first", Uniform::Int(0));
+        if let Some(cap) = self.iterations_per_draw.get().filter(|&cap| params.substeps >= cap) {
+            self.draw_split(params, cap);
+        }
+        // opaque, so whatever the triangle leaves uncovered doesn't show the desktop
+        let [r, g, b] = params.backdrop.unwrap_or([0.; 3]);
+        unsafe {
+            ClearColor(r, g, b, if params.premultiplied { 0. } else { 1. });
+            Clear(COLOR_BUFFER_BIT | DEPTH_BUFFER_BIT);
+        }
+        self.triangle.draw();
+    }
+
+    /// Runs the iterations of `params` but the last `cap` or fewer into the
+    /// orbit states, `cap` per draw, and leaves the uniforms set for the
+    /// draw that finishes them into the bound framebuffer. Does nothing if
+    /// `params` can't be split, and the one draw does it all.
+    fn draw_split(&self, params: &DrawParams, cap: i32) {
+        let morphing = params.julia.is_some() && params.morph > 0. && params.morph < 1.;
+        if params.ssaa > 1 || morphing {
+            if !self.warned_unsplit.replace(true) {
+                let what = if morphing { "the cross-fade between the Mandelbrot and Julia sets" } else { "supersampled views" };
+                log::warn!("{what} draw all their iterations at once, whatever the iterations per draw");
+            }
+            return;
+        }
+        let mut viewport = [0; 4];
+        let (mut draw_framebuffer, mut read_framebuffer) = (0, 0);
+        unsafe {
+            GetIntegerv(VIEWPORT, viewport.as_mut_ptr());
+            GetIntegerv(DRAW_FRAMEBUFFER_BINDING, &mut draw_framebuffer);
+            GetIntegerv(READ_FRAMEBUFFER_BINDING, &mut read_framebuffer);
+        }
+        // as big as gl_FragCoord gets in the target
+        let (width, height) = (viewport[0] + viewport[2], viewport[1] + viewport[3]);
+        let mut states = self.orbit_states.borrow_mut();
+        if states.as_ref().is_none_or(|[state, _]| (state.width, state.height) != (width, height)) {
+            *states = None;
+            match OrbitState::new(width, height).and_then(|first| Ok([first, OrbitState::new(width, height)?])) {
+                Ok(pair) => *states = Some(pair),
+                Err(err) => {
+                    if !self.warned_unsplit.replace(true) {
+                        log::warn!("drawing all iterations at once, there is nowhere to keep the orbits between draws: {err}");
+                    }
+                    return;
+                }
+            }
+        }
+        let states = states.as_ref().expect("made above");
+
+        let blend = unsafe { IsEnabled(BLEND) } == TRUE;
+        unsafe {
+            Disable(BLEND);
+        }
+        self.set_uniform("write_state", Uniform::Int(1));
+        self.set_uniform("state_size", Uniform::Vec2([width as f32, height as f32]));
+        // each draw writes one state and reads the other, the last one's
+        let mut first = 0;
+        let mut pass = 0;
+        while params.substeps - first >= cap {
+            self.set_uniforms(&DrawParams { substeps: first + cap - 1, ..*params });
+            self.set_uniform("resume", Uniform::Int((first > 0) as i32));
+            self.set_uniform("iteration_first", Uniform::Int(first));
+            states[(pass + 1) % 2].bind_textures();
+            unsafe {
+                BindFramebuffer(FRAMEBUFFER, states[pass % 2].fbo.id());
+                gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+            }
+            self.triangle.draw();
+            unsafe {
+                // handed to the GPU a draw at a time, not as one long batch
+                Flush();
+            }
+            first += cap;
+            pass += 1;
+        }
+
+        self.set_uniforms(params);
+        self.set_uniform("write_state", Uniform::Int(0));
+        self.set_uniform("resume", Uniform::Int(1));
+        self.set_uniform("iteration_first", Uniform::Int(first));
+        states[(pass + 1) % 2].bind_textures();
+        unsafe {
+            BindFramebuffer(DRAW_FRAMEBUFFER, draw_framebuffer as GLuint);
+            BindFramebuffer(READ_FRAMEBUFFER, read_framebuffer as GLuint);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+            if blend {
+                Enable(BLEND);
+            }
+        }
+    }
+}
+
+/// Where one draw of a split one left every pixel's orbit: z and the escape
+/// iteration (-1 while it runs) in one float target, the derivative for
+/// relief in another. `c` follows from the pixel again.
+struct OrbitState {
+    fbo: gl_object::Framebuffer,
+    iteration: Texture,
+    derivative: Texture,
+    width: i32,
+    height: i32,
+}
+
+impl OrbitState {
+    fn new(width: i32, height: i32) -> Result<OrbitState, String> {
+        float_targets()?;
+        let target = || {
+            let texture = Texture::new(TEXTURE_2D)?;
+            unsafe {
+                TexImage2D(TEXTURE_2D, 0, RGBA32F as GLint, width, height, 0, RGBA, FLOAT, ptr::null());
+                TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as GLint);
+                TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST as GLint);
+                BindTexture(TEXTURE_2D, 0);
+            }
+            Ok::<_, String>(texture)
+        };
+        let (iteration, derivative) = (target()?, target()?);
+        let fbo = gl_object::Framebuffer::new()?;
+        let status = unsafe {
+            FramebufferTexture2D(FRAMEBUFFER, COLOR_ATTACHMENT0, TEXTURE_2D, iteration.id(), 0);
+            FramebufferTexture2D(FRAMEBUFFER, COLOR_ATTACHMENT1, TEXTURE_2D, derivative.id(), 0);
+            DrawBuffers(2, [COLOR_ATTACHMENT0, COLOR_ATTACHMENT1].as_ptr());
+            let status = CheckFramebufferStatus(FRAMEBUFFER);
+            BindFramebuffer(FRAMEBUFFER, 0);
+            status
+        };
+        if status != FRAMEBUFFER_COMPLETE {
+            return Err(format!("orbit state framebuffer is incomplete (status {status:#x})"));
+        }
+        Ok(OrbitState { fbo, iteration, derivative, width, height })
+    }
+
+    /// On units 2 and 3, where the fractal shader reads them.
+    fn bind_textures(&self) {
+        unsafe {
+            ActiveTexture(TEXTURE2);
+            BindTexture(TEXTURE_2D, self.iteration.id());
+            ActiveTexture(TEXTURE3);
+            BindTexture(TEXTURE_2D, self.derivative.id());
+            ActiveTexture(TEXTURE0);
+        }
+    }
+}
+
+/// Whether float targets can be drawn into: OpenGL ES only can with
+/// GL_EXT_color_buffer_float, OpenGL 2.1 with GL_ARB_texture_float.
+fn float_targets() -> Result<(), String> {
+    GpuCapabilities::current().require(Feature::FloatTargets)
+}
+
+/// `Program::build` in `dialect`, with `in_position` bound for GLSL 1.20,
+/// which has no `layout` qualifiers.
+fn build_program(dialect: Dialect, vertex: &str, fragment: &str) -> Result<Program, ShaderError> {
+    let shaders = [
+        Shader::compile(Stage::Vertex, &dialect.translate(Stage::Vertex, vertex))?,
+        Shader::compile(Stage::Fragment, &dialect.translate(Stage::Fragment, fragment))?,
+    ];
+    Program::link(&shaders, &[(mesh::POSITION, c"in_position")])
+}
+
+/// An offscreen color target, freed when dropped.
+pub struct Framebuffer {
+    fbo: gl_object::Framebuffer,
+    /// What `fbo` draws into, kept for as long as it is.
+    _texture: Texture,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Framebuffer {
+    /// RGBA8, for images.
+    pub fn new(width: i32, height: i32) -> Result<Framebuffer, String> {
+        Framebuffer::with_format(width, height, RGBA8, RGBA, UNSIGNED_BYTE)
+    }
+
+    /// RGBA with 16 bits per channel, for exports meant for grading. OpenGL ES
+    /// only has these with GL_EXT_texture_norm16.
+    pub fn new_rgba16(width: i32, height: i32) -> Result<Framebuffer, String> {
+        GpuCapabilities::current().require(Feature::Norm16Targets)?;
+        Framebuffer::with_format(width, height, RGBA16, RGBA, UNSIGNED_SHORT)
+    }
+
+    /// Four 32-bit float channels, for numeric output such as escape times.
+    /// OpenGL ES can only draw into these with GL_EXT_color_buffer_float,
+    /// OpenGL 2.1 with GL_ARB_texture_float.
+    pub fn new_float(width: i32, height: i32) -> Result<Framebuffer, String> {
+        float_targets()?;
+        Framebuffer::with_format(width, height, RGBA32F, RGBA, FLOAT)
+    }
+
+    fn with_format(width: i32, height: i32, internal: GLenum, format: GLenum, kind: GLenum) -> Result<Framebuffer, String> {
+        GpuCapabilities::current().check_texture_size(width, height)?;
+
+        let texture = Texture::new(TEXTURE_2D)?;
+        unsafe {
+            TexImage2D(TEXTURE_2D, 0, internal as GLint, width, height, 0, format, kind, ptr::null());
+            TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as GLint);
+            TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST as GLint);
+            BindTexture(TEXTURE_2D, 0);
+        }
+
+        let fbo = gl_object::Framebuffer::new()?;
+        let status = unsafe {
+            FramebufferTexture2D(FRAMEBUFFER, COLOR_ATTACHMENT0, TEXTURE_2D, texture.id(), 0);
+            let status = CheckFramebufferStatus(FRAMEBUFFER);
+            BindFramebuffer(FRAMEBUFFER, 0);
+            status
+        };
+        if status != FRAMEBUFFER_COMPLETE {
+            return Err(format!("offscreen framebuffer is incomplete (status {status:#x})"));
+        }
+        Ok(Framebuffer { fbo, _texture: texture, width, height })
+    }
+
+    /// Makes this the draw/read target and sets the viewport to cover it.
+    pub fn bind(&self) {
+        unsafe {
+            BindFramebuffer(FRAMEBUFFER, self.fbo.id());
+            gl::Viewport(0, 0, self.width, self.height);
+        }
+    }
+
+    /// Copies this whole target, stretched, over `width x height` pixels of
+    /// `onto`, or of the window's framebuffer if `None` (which must not be
+    /// multisampled). `linear` filters when scaling, otherwise the nearest
+    /// pixel is taken. Leaves `onto` bound and the viewport to the caller.
+    pub fn blit(&self, onto: Option<&Framebuffer>, width: i32, height: i32, linear: bool) {
+        unsafe {
+            BindFramebuffer(READ_FRAMEBUFFER, self.fbo.id());
+            BindFramebuffer(DRAW_FRAMEBUFFER, onto.map_or(0, |target| target.fbo.id()));
+            let filter = if linear { LINEAR } else { NEAREST };
+            BlitFramebuffer(0, 0, self.width, self.height, 0, 0, width, height, COLOR_BUFFER_BIT, filter);
+            BindFramebuffer(FRAMEBUFFER, onto.map_or(0, |target| target.fbo.id()));
+        }
+    }
+}
+
+/// Draws into a temporary `width x height` framebuffer and reads it back. The
+/// default framebuffer is bound again afterwards, the viewport is left to the caller.
+pub fn render_offscreen(renderer: &Renderer, params: &DrawParams, width: i32, height: i32) -> Result<image::RgbaImage, String> {
+    let target = Framebuffer::new(width, height)?;
+    target.bind();
+    renderer.draw(params);
+    let image = read_pixels(width, height);
+    unsafe {
+        BindFramebuffer(FRAMEBUFFER, 0);
+    }
+    Ok(image)
+}
+
+/// `render_offscreen` into a 16-bit target, read back at full precision.
+pub fn render_offscreen_16(renderer: &Renderer, params: &DrawParams, width: i32, height: i32) -> Result<Rgba16Image, String> {
+    let target = Framebuffer::new_rgba16(width, height)?;
+    target.bind();
+    renderer.draw(params);
+    let image = read_pixels_16(width, height);
+    unsafe {
+        BindFramebuffer(FRAMEBUFFER, 0);
+    }
+    Ok(image)
+}
+
+/// Packs pixel rows back to back, with no padding and no row length or skip
+/// offsets left over from someone else (imgui uploads atlases), in both
+/// directions. The buffers here are sized `width * height * channels`, which
+/// is only true of tight rows: with GL's default 4-byte alignment a row whose
+/// byte length isn't a multiple of 4 is padded, and every row after the first
+/// lands shifted, a skewed image at odd widths. Call before any `ReadPixels`
+/// or `TexImage*` from client memory.
+pub fn tight_rows() {
+    unsafe {
+        for alignment in [PACK_ALIGNMENT, UNPACK_ALIGNMENT] {
+            PixelStorei(alignment, 1);
+        }
+        for offset in [PACK_ROW_LENGTH, PACK_SKIP_ROWS, PACK_SKIP_PIXELS, UNPACK_ROW_LENGTH, UNPACK_SKIP_ROWS, UNPACK_SKIP_PIXELS] {
+            PixelStorei(offset, 0);
+        }
+    }
+}
+
+/// `read_pixels` with 16 bits per channel.
+pub fn read_pixels_16(width: i32, height: i32) -> Rgba16Image {
+    let mut pixels = vec![0u16; (width * height * 4) as usize];
+    tight_rows();
+    unsafe {
+        ReadPixels(0, 0, width, height, RGBA, UNSIGNED_SHORT, pixels.as_mut_ptr() as *mut c_void);
+    }
+    let image = Rgba16Image::from_raw(width as u32, height as u32, pixels).expect("pixel buffer has the wrong size");
+    image::imageops::flip_vertical(&image)
+}
+
+/// Reads the bound framebuffer as RGBA, flipped so the first row is the top one.
+pub fn read_pixels(width: i32, height: i32) -> image::RgbaImage {
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    tight_rows();
+    unsafe {
+        ReadPixels(0, 0, width, height, RGBA, UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut c_void);
+    }
+
+    // GL rows start at the bottom, image rows at the top
+    let image = image::RgbaImage::from_raw(width as u32, height as u32, pixels)
+        .expect("pixel buffer has the wrong size");
+    image::imageops::flip_vertical(&image)
+}
+
+/// Reads a bound float framebuffer, first row at the top.
+pub fn read_values(width: i32, height: i32) -> Vec<[f32; 4]> {
+    let mut values = vec![[0f32; 4]; (width * height) as usize];
+    tight_rows();
+    unsafe {
+        ReadPixels(0, 0, width, height, RGBA, FLOAT, values.as_mut_ptr() as *mut c_void);
+    }
+    values
+        .chunks(width as usize)
+        .rev()
+        .flatten()
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::viewport::Viewport;
+
+    fn params() -> DrawParams {
+        DrawParams {
+            view: Viewport::new(640, 480).uniforms(),
+            substeps: 1000,
+            ssaa: 1,
+            seed: 7,
+            time: 0.,
+            julia: None,
+            morph: 1.,
+            transparent_interior: false,
+            color_mode: ColorMode::EscapeTime,
+            color_span: None,
+            checkerboard: false,
+            backdrop: None,
+            premultiplied: false,
+            slow_tint: None,
+            lighting: None,
+            tone: Tone::default(),
+            mapping: CoordinateMapping::FragCoord,
+            log_polar: false,
+            background: None,
+        }
+    }
+
+    /// Runs `params` through a cache of last uploads, as `set_uniform` does,
+    /// and returns the names it would upload.
+    fn uploads(cache: &mut HashMap<&'static str, Option<Uniform>>, params: &DrawParams) -> Vec<&'static str> {
+        uniform_values(params).into_iter().filter(|&(name, value)| changed(cache.entry(name).or_default(), value)).map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn a_still_view_uploads_nothing() {
+        let mut cache = HashMap::new();
+        assert_eq!(uploads(&mut cache, &params()).len(), uniform_values(&params()).len());
+        assert!(uploads(&mut cache, &params()).is_empty());
+    }
+
+    #[test]
+    fn a_changed_value_propagates() {
+        let zoomed = Viewport { scale: 0.5, ..Viewport::new(640, 480) }.uniforms();
+        let changes: [(DrawParams, &[&str]); 8] = [
+            (DrawParams { view: zoomed, ..params() }, &["scale"]),
+            (DrawParams { substeps: 2000, ..params() }, &["substeps"]),
+            (DrawParams { julia: Some((-0.8, 0.156)), ..params() }, &["julia", "julia_c"]),
+            (DrawParams { color_mode: ColorMode::Blend, ..params() }, &["color_mode"]),
+            (DrawParams { tone: Tone { brightness: 0.25, contrast: 1. }, ..params() }, &["brightness"]),
+            (DrawParams { backdrop: Some([0.1, 0.2, 0.3]), ..params() }, &["backdrop", "backdrop_color"]),
+            (DrawParams { lighting: Some(Lighting { angle: 90., intensity: 0.5 }), ..params() }, &["relief", "light", "light_intensity"]),
+            (DrawParams { slow_tint: Some(SlowTint { color: [1., 0., 0.], threshold: 2. }), ..params() }, &["slow_tint", "slow_color", "slow_threshold"]),
+        ];
+        for (changed, expected) in changes {
+            let mut cache = HashMap::new();
+            uploads(&mut cache, &params());
+            assert_eq!(uploads(&mut cache, &changed), expected);
+            // and back again
+            assert_eq!(uploads(&mut cache, &params()), expected);
+        }
+    }
+
+    #[test]
+    fn shadertoy_source_is_one_image_pass() {
+        let palette = Palette::curated(1).build();
+        let source = shadertoy_source(&DrawParams { julia: Some((-0.8, 0.156)), substeps: 1234, ..params() }, &palette);
+        assert!(source.contains("void mainImage(out vec4 FragColor, in vec2 fragCoord) {"));
+        let code: Vec<&str> = source.lines().map(|line| line.split("//").next().unwrap_or(line)).collect();
+        assert!(!code.iter().any(|line| line.contains("void main()") || line.contains("#version") || line.contains("gl_FragCoord")));
+        // every uniform is baked but what Shadertoy provides
+        assert!(!code.iter().any(|line| line.trim_start().starts_with("uniform ")), "{source}");
+        assert!(source.contains("const int substeps = 1234;") && source.contains("const bool julia = true;"));
+        assert!(source.contains(&format!("const vec3 palette_colors[{}]", palette.texels.len())));
+    }
+}
@@ -0,0 +1,49 @@
+// Small seedable generator (SplitMix64). The sequence depends only on the seed,
+// never on the platform or a dependency version, so seeded runs reproduce exactly.
+
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Folds a 64-bit seed into the 32 bits the shader works with.
+pub fn seed32(seed: u64) -> u32 {
+    (seed ^ (seed >> 32)) as u32
+}
+
+/// Integer hash shared with the fragment shader (lowbias32), bit for bit.
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+/// Position of a supersample inside its cell, each coordinate in `[0, 1)`. A
+/// pure function of the pixel, the sample index and the seed, so the same seed
+/// always jitters the same way.
+pub fn jitter(x: u32, y: u32, sample: u32, seed: u32) -> (f64, f64) {
+    let h = hash(x ^ hash(y ^ hash(sample ^ hash(seed))));
+    ((h >> 16) as f64 / 65536., (h & 0xffff) as f64 / 65536.)
+}
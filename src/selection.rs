@@ -0,0 +1,97 @@
+// A rectangle dragged over the view with Shift held, for exporting just that
+// part big: P with a selection renders it at `--region-edge` pixels on its
+// longer side. The selection is kept as a view of its own, so it stays on the
+// same part of the plane when the view moves under it.
+
+use imgui::Ui;
+
+use crate::viewport::{PixelRect, Viewport};
+
+/// Drags shorter than this on either side, in pixels, select nothing.
+const MIN_PIXELS: f64 = 4.;
+
+pub struct Selection {
+    /// Where the drag started and where the cursor is, in framebuffer pixels.
+    drag: Option<((f64, f64), (f64, f64))>,
+    /// The part of the view selected.
+    pub region: Option<Viewport>,
+}
+
+impl Selection {
+    pub fn new() -> Selection {
+        Selection { drag: None, region: None }
+    }
+
+    pub fn dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    pub fn start(&mut self, cursor: (f64, f64)) {
+        self.drag = Some((cursor, cursor));
+        self.region = None;
+    }
+
+    pub fn drag_to(&mut self, cursor: (f64, f64)) {
+        if let Some((start, _)) = self.drag {
+            self.drag = Some((start, cursor));
+        }
+    }
+
+    /// Ends the drag over `view`, selecting what it covered unless that is
+    /// too small to mean anything.
+    pub fn finish(&mut self, view: &Viewport) -> Option<Viewport> {
+        let ((x0, y0), (x1, y1)) = self.drag.take()?;
+        let clamp = |v: f64, max: u32| v.clamp(0., max as f64);
+        let (left, right) = (clamp(x0.min(x1), view.width), clamp(x0.max(x1), view.width));
+        let (top, bottom) = (clamp(y0.min(y1), view.height), clamp(y0.max(y1), view.height));
+        if right - left < MIN_PIXELS || bottom - top < MIN_PIXELS {
+            return None;
+        }
+        let rect = PixelRect {
+            x: left.round() as u32,
+            y: top.round() as u32,
+            width: (right - left).round() as u32,
+            height: (bottom - top).round() as u32,
+        };
+        self.region = Some(view.region(rect));
+        self.region
+    }
+
+    /// Drops the selection; whether there was one.
+    pub fn clear(&mut self) -> bool {
+        self.drag = None;
+        self.region.take().is_some()
+    }
+
+    /// The rectangle being dragged, or the outline of the selection where it
+    /// is in `view` now.
+    pub fn draw(&self, ui: &Ui, view: &Viewport) {
+        // framebuffer pixels to the UI's screen coordinates
+        let [scale_x, scale_y] = ui.io().display_framebuffer_scale;
+        let point = |(x, y): (f64, f64)| [x as f32 / scale_x, y as f32 / scale_y];
+        let corners = match (self.drag, self.region) {
+            (Some(((x0, y0), (x1, y1))), _) => [(x0, y0), (x1, y0), (x1, y1), (x0, y1)],
+            (None, Some(region)) => {
+                let (width, height) = (region.width as f64, region.height as f64);
+                [(0., 0.), (width, 0.), (width, height), (0., height)].map(|px| view.complex_to_screen(region.screen_to_complex(px)))
+            }
+            (None, None) => return,
+        };
+        let draw = ui.get_background_draw_list();
+        let color = [1., 1., 1., 0.8];
+        for (index, &corner) in corners.iter().enumerate() {
+            draw.add_line(point(corner), point(corners[(index + 1) % 4]), color).thickness(1.5).build();
+        }
+    }
+}
+
+/// The output size for `region` with `edge` pixels on its longer side and
+/// the other following its aspect ratio.
+pub fn export_size(region: &Viewport, edge: u32) -> (u32, u32) {
+    let aspect = region.aspect();
+    if aspect >= 1. {
+        (edge, ((edge as f64 / aspect).round() as u32).max(1))
+    } else {
+        (((edge as f64 * aspect).round() as u32).max(1), edge)
+    }
+}
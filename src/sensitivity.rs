@@ -0,0 +1,69 @@
+// How navigation speeds up the longer a key is held or the harder the wheel
+// is flicked. The gain is `(1 + x)^curve`, with `x` the seconds held (in
+// units of `RAMP_SECONDS`) or the wheel ticks of one event, so it grows like
+// an exponential of ln(1 + x): a curve of 0 keeps the speed constant as it
+// always was, 1 makes it grow linearly, higher values make it snappier. Set
+// with `--sensitivity`, or the slider F2 opens.
+
+use imgui::{Condition, Ui};
+
+/// Holding a key this long makes `x` 1.
+const RAMP_SECONDS: f64 = 0.5;
+/// Highest gain, so a key held for a minute doesn't shoot off the set.
+const MAX_GAIN: f64 = 100.;
+pub const MAX_CURVE: f64 = 3.;
+
+pub struct Sensitivity {
+    pub curve: f64,
+    /// Seconds the pan and the zoom keys have been held.
+    pan_held: f64,
+    zoom_held: f64,
+    /// Whether the F2 window is showing.
+    pub open: bool,
+}
+
+impl Sensitivity {
+    pub fn new(curve: f64) -> Sensitivity {
+        Sensitivity { curve, pan_held: 0., zoom_held: 0., open: false }
+    }
+
+    fn gain(&self, x: f64) -> f64 {
+        (1. + x).powf(self.curve).min(MAX_GAIN)
+    }
+
+    /// Speed multiplier of the pan keys this frame, `panning` if any is held.
+    pub fn pan(&mut self, panning: bool, dt: f64) -> f64 {
+        self.pan_held = if panning { self.pan_held + dt } else { 0. };
+        self.gain(self.pan_held / RAMP_SECONDS)
+    }
+
+    /// Same for the zoom keys.
+    pub fn zoom(&mut self, zooming: bool, dt: f64) -> f64 {
+        self.zoom_held = if zooming { self.zoom_held + dt } else { 0. };
+        self.gain(self.zoom_held / RAMP_SECONDS)
+    }
+
+    /// The wheel ticks of one scroll event, bent by the curve.
+    pub fn scroll(&self, ticks: f64) -> f64 {
+        ticks * self.gain(ticks.abs())
+    }
+
+    /// The settings window.
+    pub fn draw(&mut self, ui: &Ui) {
+        if !self.open {
+            return;
+        }
+        let mut open = true;
+        ui.window("Navigation")
+            .opened(&mut open)
+            .size([360., 90.], Condition::FirstUseEver)
+            .build(|| {
+                let mut curve = self.curve as f32;
+                if ui.slider("sensitivity", 0., MAX_CURVE as f32, &mut curve) {
+                    self.curve = curve as f64;
+                }
+                ui.text_disabled("0 keeps speeds constant, more speeds up held keys and fast scrolls");
+            });
+        self.open = open;
+    }
+}
@@ -0,0 +1,312 @@
+// `--serve PORT`: renders views on request for web front-ends, one PNG per
+// GET, from the same offscreen renderer as `render`:
+//
+//     curl 'http://localhost:8080/?center=-0.743,0.1318&zoom=1e-4&iterations=4000&width=1024&height=768' > view.png
+//
+// Every parameter is optional: the defaults are the whole set at 800x600 with
+// the iterations of `--quality`. `zoom` is half the visible height, as with
+// `render --zoom`. The HTTP is just enough for that, one request per
+// connection, handled one after the other, so a client that stalls is cut
+// off with a 408 after `TIMEOUT`; it is meant for a local front-end or behind
+// a reverse proxy, not for the open internet.
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::cli::Options;
+use crate::headless::{self, EXIT_BAD_ARGS, EXIT_RENDER_FAILED};
+use crate::metadata::{Fractal, ViewMetadata};
+use crate::offscreen;
+use crate::palette::{ColorMode, Palette, PaletteTable};
+use crate::renderer::Renderer;
+use crate::rng;
+use crate::viewport::{self, Viewport};
+
+/// Largest image a request may ask for, 4096x4096 worth of pixels: the image
+/// is held in memory uncompressed while it is encoded.
+const MAX_PIXELS: u64 = 4096 * 4096;
+const MAX_ITERATIONS: i32 = 1_000_000;
+/// Longest request head read before giving up on a client.
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+/// How long a client may take to send its request, and to take each part of
+/// the answer, before the next one gets its turn.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serves until the process is killed; returns the exit status if the port
+/// can't be opened.
+pub fn run(port: u16, options: &Options) -> i32 {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("can't listen on port {port}: {err}");
+            return EXIT_BAD_ARGS;
+        }
+    };
+    // the renderer lives in this thread's context for as long as the server
+    let gpu = match offscreen::create().and_then(|context| Ok((Renderer::new()?, context))) {
+        Ok(gpu) => Some(gpu),
+        Err(err) => {
+            eprintln!("no GPU ({err}), rendering on the CPU");
+            None
+        }
+    };
+    let server = Server {
+        renderer: gpu.as_ref().map(|(renderer, _context)| renderer),
+        palette: Palette::grayscale().build(),
+        iterations: options.quality.iterations(),
+        ssaa: options.quality.ssaa(),
+        seed: rng::seed32(options.seed.unwrap_or(0)),
+    };
+    println!("serving on http://127.0.0.1:{port}/");
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => server.handle(stream),
+            Err(err) => eprintln!("connection failed: {err}"),
+        }
+    }
+    EXIT_RENDER_FAILED
+}
+
+struct Server<'a> {
+    renderer: Option<&'a Renderer>,
+    palette: PaletteTable,
+    iterations: i32,
+    ssaa: i32,
+    seed: u32,
+}
+
+/// What a request asked for, after the defaults.
+struct View {
+    center: (f64, f64),
+    zoom: f64,
+    iterations: i32,
+    width: u32,
+    height: u32,
+}
+
+impl Server<'_> {
+    fn handle(&self, mut stream: TcpStream) {
+        let started = Instant::now();
+        if let Err(err) = stream.set_read_timeout(Some(TIMEOUT)).and_then(|()| stream.set_write_timeout(Some(TIMEOUT))) {
+            eprintln!("connection failed: {err}");
+            return;
+        }
+        let (target, response) = match read_head(&stream) {
+            Ok(line) => {
+                let target = line.split_whitespace().nth(1).unwrap_or_default().to_string();
+                let response = match line.split_whitespace().next() {
+                    Some("GET") => self.respond(&target),
+                    Some(_) => Err((405, "only GET is supported".to_string())),
+                    None => return,
+                };
+                (target, response)
+            }
+            Err(err) if timed_out(&err) => ("-".to_string(), Err((408, format!("no request within {}s", TIMEOUT.as_secs())))),
+            Err(_) => return,
+        };
+        let (status, content_type, body) = match response {
+            Ok(png) => (200, "image/png", png),
+            Err((status, message)) => (status, "text/plain; charset=utf-8", format!("{message}\n").into_bytes()),
+        };
+        println!("{status} {target} in {:.2}s", started.elapsed().as_secs_f64());
+        let head = format!(
+            "HTTP/1.1 {status} {}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+             Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+            reason(status),
+            body.len()
+        );
+        if let Err(err) = stream.write_all(head.as_bytes()).and_then(|()| stream.write_all(&body)) {
+            eprintln!("failed to answer {target}: {err}");
+        }
+    }
+
+    fn respond(&self, target: &str) -> Result<Vec<u8>, (u16, String)> {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        if path != "/" {
+            return Err((404, format!("nothing at {path}; ask for /?center=RE,IM&zoom=..&iterations=..&width=..&height=..")));
+        }
+        let view = self.parse(query).map_err(|err| (400, err))?;
+        if u64::from(view.width) * u64::from(view.height) > MAX_PIXELS {
+            return Err((413, format!("{}x{} is more than the {MAX_PIXELS} pixels a request may render", view.width, view.height)));
+        }
+
+        let mut viewport = Viewport::new(view.width, view.height);
+        viewport.center = view.center;
+        viewport.scale = view.zoom;
+        let image = headless::render_image(self.renderer, &viewport, view.iterations, &self.palette, self.ssaa, self.seed)
+            .map_err(|err| (500, format!("render failed: {err}")))?;
+        let metadata = ViewMetadata::new(&viewport, view.iterations, Fractal::Mandelbrot, ColorMode::EscapeTime, &Palette::grayscale());
+        encode_png(&image.to_rgba8(), metadata.text_chunk()).map_err(|err| (500, format!("encoding failed: {err}")))
+    }
+
+    fn parse(&self, query: &str) -> Result<View, String> {
+        let mut view = View { center: (0., 0.), zoom: 1., iterations: self.iterations, width: 800, height: 600 };
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value).ok_or_else(|| format!("`{key}` is not valid percent-encoded UTF-8"))?;
+            let number = |text: &str| text.trim().parse::<f64>().ok().filter(|n| n.is_finite());
+            match key {
+                "center" => {
+                    view.center = value
+                        .split_once(',')
+                        .and_then(|(re, im)| Some((number(re)?, number(im)?)))
+                        .ok_or_else(|| format!("center should be RE,IM, got `{value}`"))?;
+                }
+                "zoom" => {
+                    view.zoom = number(&value)
+                        .filter(|&zoom| zoom > 0.)
+                        .ok_or_else(|| format!("zoom should be a positive number, got `{value}`"))?;
+                }
+                "iterations" => {
+                    view.iterations = value
+                        .parse::<i32>()
+                        .ok()
+                        .filter(|n| (1..=MAX_ITERATIONS).contains(n))
+                        .ok_or_else(|| format!("iterations should be between 1 and {MAX_ITERATIONS}, got `{value}`"))?;
+                }
+                "width" | "height" => {
+                    let edge = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|n| (1..=viewport::MAX_EDGE).contains(n))
+                        .ok_or_else(|| format!("{key} should be between 1 and {}, got `{value}`", viewport::MAX_EDGE))?;
+                    if key == "width" {
+                        view.width = edge;
+                    } else {
+                        view.height = edge;
+                    }
+                }
+                _ => return Err(format!("unknown parameter `{key}`")),
+            }
+        }
+        Ok(view)
+    }
+}
+
+/// The request line, once the headers after it are read too.
+fn read_head(stream: &TcpStream) -> io::Result<String> {
+    let mut head = BufReader::new(stream).take(MAX_REQUEST_BYTES);
+    let mut line = String::new();
+    head.read_line(&mut line)?;
+    // the headers don't matter, but the client expects them to be read
+    let mut header = String::new();
+    loop {
+        match head.read_line(&mut header) {
+            Ok(read) if read > 2 => header.clear(),
+            Err(err) if timed_out(&err) => return Err(err),
+            _ => return Ok(line),
+        }
+    }
+}
+
+/// Whether a read or write gave up at `TIMEOUT`, which Unix reports as
+/// `WouldBlock` and Windows as `TimedOut`.
+fn timed_out(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+fn encode_png(image: &image::RgbaImage, (keyword, text): (String, String)) -> Result<Vec<u8>, String> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.add_itxt_chunk(keyword, text).map_err(|err| err.to_string())?;
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    writer.write_image_data(image.as_raw()).map_err(|err| err.to_string())?;
+    writer.finish().map_err(|err| err.to_string())?;
+    Ok(png)
+}
+
+/// `%2C` and friends, and `+` as a space.
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.bytes();
+    while let Some(byte) = rest.next() {
+        match byte {
+            b'%' => {
+                let hex = [rest.next()?, rest.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b'+' => bytes.push(b' '),
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> Server<'static> {
+        Server { renderer: None, palette: Palette::grayscale().build(), iterations: 250, ssaa: 1, seed: 0 }
+    }
+
+    #[test]
+    fn defaults_to_the_whole_set() {
+        let view = server().parse("").unwrap();
+        assert_eq!((view.center, view.zoom, view.iterations, view.width, view.height), ((0., 0.), 1., 250, 800, 600));
+    }
+
+    #[test]
+    fn centers_are_two_finite_numbers() {
+        assert_eq!(server().parse("center=-0.743,0.1318").unwrap().center, (-0.743, 0.1318));
+        for bad in ["center=-0.743", "center=a,0", "center=0,inf", "center="] {
+            assert!(server().parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn zooms_are_positive() {
+        assert_eq!(server().parse("zoom=1e-4").unwrap().zoom, 1e-4);
+        for bad in ["zoom=0", "zoom=-1", "zoom=NaN"] {
+            assert!(server().parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn edges_are_in_range() {
+        let view = server().parse("width=1024&height=768").unwrap();
+        assert_eq!((view.width, view.height), (1024, 768));
+        let too_wide = format!("width={}", viewport::MAX_EDGE + 1);
+        for bad in ["width=0", "height=-1", "width=wide", too_wide.as_str()] {
+            assert!(server().parse(bad).is_err(), "{bad}");
+        }
+        assert!(server().parse("iterations=0").is_err());
+        assert!(server().parse("angle=1").is_err());
+    }
+
+    #[test]
+    fn query_values_are_percent_decoded() {
+        assert_eq!(server().parse("center=-0.5%2C0.25").unwrap().center, (-0.5, 0.25));
+        assert_eq!(percent_decode("a%2cb+c").as_deref(), Some("a,b c"));
+        // cut short, not hex, or not UTF-8
+        for bad in ["%2", "%zz", "%ff"] {
+            assert_eq!(percent_decode(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn oversized_images_are_refused_before_rendering() {
+        let Err((status, message)) = server().respond("/?width=8192&height=2049") else {
+            panic!("rendered");
+        };
+        assert_eq!(status, 413, "{message}");
+        assert_eq!(server().respond("/favicon.ico").err().map(|(status, _)| status), Some(404));
+        assert_eq!(server().respond("/?zoom=0").err().map(|(status, _)| status), Some(400));
+    }
+}
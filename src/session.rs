@@ -0,0 +1,213 @@
+// The explorer's state as JSON, saved with Ctrl+S and restored with Ctrl+O or
+// `--state` at startup, to get back to exactly what was on screen:
+//
+//     {"version": 1, "camera": {"center": ["-0.7436438870371587", "0.1318259042053119"],
+//      "scale": "1.4e-11", "rotation": 0.0, "anisotropy": 1.0}, "iterations": 4000,
+//      "fractal": {"kind": "mandelbrot"}, "color_mode": "escape_time", "palette": {...},
+//      "ssaa": 2, "transparent_interior": false, "checkerboard": true}
+//
+// Center and scale are decimal strings, so files stay exact for deep zooms
+// and can later carry more digits than an f64 holds. Fields added after a
+// version default when missing; a file from a newer version is refused.
+
+use std::fs::File;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::Fractal;
+use crate::palette::{ColorMode, Lighting, Palette, Tone};
+use crate::viewport::Viewport;
+
+/// The schema this build writes and the newest it reads.
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Camera {
+    pub center: [String; 2],
+    pub scale: String,
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default = "square_pixels")]
+    pub anisotropy: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    pub camera: Camera,
+    pub iterations: i32,
+    #[serde(default = "mandelbrot")]
+    pub fractal: Fractal,
+    #[serde(default)]
+    pub color_mode: ColorMode,
+    #[serde(default = "Palette::grayscale")]
+    pub palette: Palette,
+    #[serde(default = "one")]
+    pub ssaa: i32,
+    #[serde(default)]
+    pub transparent_interior: bool,
+    #[serde(default = "yes")]
+    pub checkerboard: bool,
+    /// Relief shading, when it is on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lighting: Option<Lighting>,
+    /// The iterations the palette is locked to, when it is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_span: Option<i32>,
+    /// Brightness and contrast, when they are changed.
+    #[serde(default, skip_serializing_if = "Tone::is_neutral")]
+    pub tone: Tone,
+}
+
+fn square_pixels() -> f64 {
+    1.
+}
+
+fn mandelbrot() -> Fractal {
+    Fractal::Mandelbrot
+}
+
+fn one() -> i32 {
+    1
+}
+
+fn yes() -> bool {
+    true
+}
+
+impl Camera {
+    /// Rust prints the shortest decimal that parses back to the same f64.
+    pub fn from_view(view: &Viewport) -> Camera {
+        Camera {
+            center: [view.center.0.to_string(), view.center.1.to_string()],
+            scale: view.scale.to_string(),
+            rotation: view.rotation,
+            anisotropy: view.anisotropy,
+        }
+    }
+
+    /// The camera on a `width x height` target.
+    pub fn view(&self, width: u32, height: u32) -> Result<Viewport, String> {
+        let number = |s: &str| s.trim().parse::<f64>().map_err(|_| format!("`{s}` is not a number"));
+        let scale = number(&self.scale)?;
+        if !(scale.is_finite() && scale > 0.) {
+            return Err("the camera needs a positive scale".to_string());
+        }
+        Ok(Viewport {
+            center: (number(&self.center[0])?, number(&self.center[1])?),
+            scale,
+            rotation: self.rotation,
+            anisotropy: self.anisotropy,
+            ..Viewport::new(width, height)
+        })
+    }
+}
+
+pub fn load(path: &Path) -> Result<Session, String> {
+    let file = File::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let value: serde_json::Value = serde_json::from_reader(file).map_err(|err| format!("{}: {err}", path.display()))?;
+    // check the version before the fields, whose meaning it decides
+    match value.get("version").and_then(|v| v.as_u64()) {
+        Some(version) if (1..=VERSION as u64).contains(&version) => {}
+        Some(version) => {
+            return Err(format!("{}: state version {version} is not supported (this build reads up to {VERSION})", path.display()));
+        }
+        None => return Err(format!("{}: not a saved state (no version)", path.display())),
+    }
+    let session: Session = serde_json::from_value(value).map_err(|err| format!("{}: {err}", path.display()))?;
+    session.camera.view(1, 1).map_err(|err| format!("{}: {err}", path.display()))?;
+    Ok(session)
+}
+
+pub fn save(path: &Path, session: &Session) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    serde_json::to_writer_pretty(file, session).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// A file of its own in the temporary directory, for each test.
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mandelplotter-session-{}-{name}.json", std::process::id()))
+    }
+
+    fn session() -> Session {
+        Session {
+            version: VERSION,
+            camera: Camera {
+                center: ["-1.7497591451303665".to_string(), "-0.0000000000000000000000001".to_string()],
+                scale: "2.5e-14".to_string(),
+                rotation: 1.25,
+                anisotropy: 1.5,
+            },
+            iterations: 12000,
+            fractal: Fractal::Julia { c: [-0.4, 0.6] },
+            color_mode: ColorMode::Blend,
+            palette: Palette::curated(2),
+            ssaa: 4,
+            transparent_interior: true,
+            checkerboard: false,
+            lighting: Some(Lighting { angle: 135., intensity: 0.5 }),
+            color_span: Some(500),
+            tone: Tone { brightness: 0.1, contrast: 1.2 },
+        }
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = temp("round-trip");
+        let session = session();
+        save(&path, &session).expect("writable");
+        let loaded = load(&path);
+        std::fs::remove_file(&path).ok();
+        assert_eq!(loaded, Ok(session));
+    }
+
+    #[test]
+    fn deep_coordinates_stay_exact() {
+        // more digits than an f64 holds are kept as written
+        let mut session = session();
+        session.camera.center[0] = "-1.768610493014677074503175653270226520239677907588665494658".to_string();
+        session.camera.scale = "3.1e-290".to_string();
+        let path = temp("deep");
+        save(&path, &session).expect("writable");
+        let loaded = load(&path).expect("readable");
+        std::fs::remove_file(&path).ok();
+        assert_eq!(loaded.camera, session.camera);
+
+        // and what an f64 holds comes back bit for bit
+        let view = Viewport { center: (-0.7436438870371587, 0.13182590420531197), scale: 7.3e-301, ..Viewport::new(640, 480) };
+        let back = Camera::from_view(&view).view(640, 480).expect("valid camera");
+        assert_eq!(back.center, view.center);
+        assert_eq!(back.scale.to_bits(), view.scale.to_bits());
+    }
+
+    #[test]
+    fn missing_fields_default() {
+        let path = temp("defaults");
+        std::fs::write(&path, r#"{"version": 1, "camera": {"center": ["0", "0"], "scale": "1"}, "iterations": 100}"#).expect("writable");
+        let loaded = load(&path).expect("readable");
+        std::fs::remove_file(&path).ok();
+        assert_eq!(loaded.fractal, Fractal::Mandelbrot);
+        assert_eq!(loaded.camera.anisotropy, 1.);
+        assert_eq!((loaded.ssaa, loaded.checkerboard, loaded.lighting, loaded.color_span), (1, true, None, None));
+        assert!(loaded.tone.is_neutral());
+    }
+
+    #[test]
+    fn newer_versions_and_bad_cameras_are_refused() {
+        let path = temp("refused");
+        std::fs::write(&path, r#"{"version": 99, "camera": {"center": ["0", "0"], "scale": "1"}, "iterations": 100}"#).expect("writable");
+        let newer = load(&path);
+        std::fs::write(&path, r#"{"version": 1, "camera": {"center": ["0", "0"], "scale": "-1"}, "iterations": 100}"#).expect("writable");
+        let negative = load(&path);
+        std::fs::remove_file(&path).ok();
+        assert!(newer.expect_err("too new").contains("version 99"));
+        assert!(negative.is_err());
+    }
+}
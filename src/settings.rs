@@ -0,0 +1,103 @@
+// What the explorer remembers between runs: on exit (closing the window,
+// Escape or Ctrl+C) it writes `settings.json` to the platform's config
+// directory, and the next start picks up from there unless `--fresh`:
+//
+//     {"version": 1, "session": {...}, "window": {"size": [600, 600], "position": [80, 60]},
+//      "msaa": 0, "quality": "medium", "export_size": [7680, 4320], "animation_size": [1920, 1080],
+//      "zoom_inertia": true, "sensitivity": 0.0, "clear_color": [0.0, 0.0, 0.0],
+//      "iterations_per_draw": 0}
+//
+// `session` is the same as a state saved with Ctrl+S. Arguments given on the
+// command line win over what is restored. A file that is broken or from
+// another version is skipped with a warning, and replaced at the next exit.
+
+use std::env;
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::Quality;
+use crate::session::Session;
+
+/// The schema this build writes and the only one it reads.
+pub const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Settings {
+    pub version: u32,
+    /// Camera, iterations, palette and coloring; dropped when the command
+    /// line picks the view.
+    pub session: Option<Session>,
+    pub window: Window,
+    pub msaa: u32,
+    pub quality: Quality,
+    pub export_size: (u32, u32),
+    pub animation_size: (u32, u32),
+    pub zoom_inertia: bool,
+    pub sensitivity: f64,
+    /// Missing from files written before it was remembered.
+    #[serde(default)]
+    pub clear_color: [f32; 3],
+    /// 0 when missing, as `clear_color`.
+    #[serde(default)]
+    pub iterations_per_draw: i32,
+}
+
+/// In screen coordinates, as the window reports them.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Window {
+    pub size: (i32, i32),
+    pub position: (i32, i32),
+}
+
+/// `mandelplotter/settings.json` in `%APPDATA%`, `~/Library/Application
+/// Support` or `$XDG_CONFIG_HOME` (`~/.config`), whichever the platform uses.
+pub fn path() -> Option<PathBuf> {
+    let home = || env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from);
+    let dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from).or_else(|| Some(home()?.join(".config")))
+    };
+    Some(dir?.join("mandelplotter").join("settings.json"))
+}
+
+/// The settings of the last run, if there are any usable ones; says why on
+/// stderr when a file is there but can't be used.
+pub fn load() -> Option<Settings> {
+    let path = path()?;
+    let file = File::open(&path).ok()?;
+    let read = serde_json::from_reader(file).map_err(|err| err.to_string()).and_then(|value: serde_json::Value| {
+        // like a saved state, the version decides what the fields mean
+        match value.get("version").and_then(|v| v.as_u64()) {
+            Some(version) if version == VERSION as u64 => {}
+            Some(version) => return Err(format!("version {version} is not the {VERSION} this build reads")),
+            None => return Err("no version".to_string()),
+        }
+        let settings: Settings = serde_json::from_value(value).map_err(|err| err.to_string())?;
+        if let Some(session) = &settings.session {
+            session.camera.view(1, 1)?;
+        }
+        Ok(settings)
+    });
+    match read {
+        Ok(settings) => Some(settings),
+        Err(err) => {
+            warn!("ignoring the saved settings in {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+pub fn save(settings: &Settings) -> Result<(), String> {
+    let path = path().ok_or("no config directory (HOME is not set)")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+    }
+    let file = File::create(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+    serde_json::to_writer_pretty(file, settings).map_err(|err| format!("{}: {err}", path.display()))
+}
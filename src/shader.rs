@@ -0,0 +1,511 @@
+// Compiling and linking GLSL programs. A failure comes back as a
+// `ShaderError` with the driver's whole info log and, for compile errors, the
+// lines of the source around the ones the log names, since logs only give
+// line numbers. Shader objects are deleted whichever way it goes; a linked
+// `Program` deletes its own when dropped, with its context still current, as
+// the objects in gl_object.rs do.
+//
+// Sources edited on disk go through `preprocess` first, which pastes in the
+// files named by `#include "file"` lines so a shader can be split up.
+//
+// The shaders are written once, in GLSL 3.30. Contexts that don't take it get
+// them through `Dialect::translate`: GLSL ES 3.00 for OpenGL ES 3, GLSL 1.20
+// for OpenGL 2.1. The 1.20 translation defines `LEGACY_GLSL`, for the few
+// places (integer hashing) a shader needs a second version of.
+
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use gl::types::*;
+
+use crate::caps;
+
+/// Lines of source shown before and after each line an error is on.
+const EXCERPT_CONTEXT: usize = 2;
+/// Lines an excerpt picks out at most, so a cascade of errors stays readable.
+const EXCERPT_LINES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stage {
+    Vertex,
+    Fragment,
+}
+
+impl Stage {
+    fn gl_enum(self) -> GLenum {
+        match self {
+            Stage::Vertex => gl::VERTEX_SHADER,
+            Stage::Fragment => gl::FRAGMENT_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Vertex => "vertex",
+            Stage::Fragment => "fragment",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShaderError {
+    /// The source has a NUL byte in it, which GL can't be given.
+    Nul(Stage),
+    Compile {
+        stage: Stage,
+        log: String,
+        /// The source around the lines `log` reports, see `excerpt`.
+        excerpt: String,
+    },
+    Link { log: String },
+    /// GL made no object to build in, as happens without a current context.
+    Context(String),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Nul(stage) => write!(f, "the {stage} shader source contains a NUL byte"),
+            ShaderError::Compile { stage, log, excerpt } => {
+                write!(f, "the {stage} shader failed to compile:\n{}", log.trim_end())?;
+                if !excerpt.is_empty() {
+                    write!(f, "\n\n{excerpt}")?;
+                }
+                Ok(())
+            }
+            ShaderError::Link { log } => write!(f, "the shader program failed to link:\n{}", log.trim_end()),
+            ShaderError::Context(err) => f.write_str(err),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl From<ShaderError> for String {
+    fn from(err: ShaderError) -> String {
+        err.to_string()
+    }
+}
+
+/// A compiled shader object, deleted when dropped.
+pub struct Shader(GLuint);
+
+impl Shader {
+    /// Needs a current GL context.
+    pub fn compile(stage: Stage, source: &str) -> Result<Shader, ShaderError> {
+        let c_source = CString::new(source).map_err(|_| ShaderError::Nul(stage))?;
+        unsafe {
+            let shader = Shader(gl::CreateShader(stage.gl_enum()));
+            if shader.0 == 0 {
+                return Err(ShaderError::Context(format!("could not create a {stage} shader, is a GL context current?")));
+            }
+            gl::ShaderSource(shader.0, 1, &c_source.as_ptr(), ptr::null());
+            gl::CompileShader(shader.0);
+            let mut success = gl::FALSE as GLint;
+            gl::GetShaderiv(shader.0, gl::COMPILE_STATUS, &mut success);
+            if success != gl::TRUE as GLint {
+                let log = info_log(shader.0, gl::GetShaderiv, gl::GetShaderInfoLog);
+                let excerpt = excerpt(source, &log);
+                return Err(ShaderError::Compile { stage, log, excerpt });
+            }
+            Ok(shader)
+        }
+    }
+}
+
+impl Drop for Shader {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteShader(self.0);
+        }
+    }
+}
+
+/// A linked program, deleted when dropped.
+pub struct Program(GLuint);
+
+impl Program {
+    /// Compiles both stages and links them. Needs a current GL context.
+    pub fn build(vertex: &str, fragment: &str) -> Result<Program, ShaderError> {
+        Program::link(&[Shader::compile(Stage::Vertex, vertex)?, Shader::compile(Stage::Fragment, fragment)?], &[])
+    }
+
+    /// Links `shaders`, with vertex attributes bound to the locations given
+    /// in `attributes` first.
+    pub fn link(shaders: &[Shader], attributes: &[(GLuint, &CStr)]) -> Result<Program, ShaderError> {
+        unsafe {
+            let program = Program(gl::CreateProgram());
+            if program.0 == 0 {
+                return Err(ShaderError::Context("could not create a program, is a GL context current?".to_string()));
+            }
+            for shader in shaders {
+                gl::AttachShader(program.0, shader.0);
+            }
+            for (location, name) in attributes {
+                gl::BindAttribLocation(program.0, *location, name.as_ptr());
+            }
+            gl::LinkProgram(program.0);
+            // so the shaders go as soon as they are dropped
+            for shader in shaders {
+                gl::DetachShader(program.0, shader.0);
+            }
+            let mut success = gl::FALSE as GLint;
+            gl::GetProgramiv(program.0, gl::LINK_STATUS, &mut success);
+            if success != gl::TRUE as GLint {
+                return Err(ShaderError::Link { log: info_log(program.0, gl::GetProgramiv, gl::GetProgramInfoLog) });
+            }
+            Ok(program)
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.0
+    }
+
+    /// Whether GL still has this as a linked program. A `Program` is only
+    /// made from a link that succeeded, so this is for `debug_assert!`s that
+    /// catch one being used in another context, or after a reset lost it,
+    /// before it draws nothing.
+    pub fn is_linked(&self) -> bool {
+        let mut linked = gl::FALSE as GLint;
+        unsafe {
+            if gl::IsProgram(self.0) != gl::TRUE {
+                return false;
+            }
+            gl::GetProgramiv(self.0, gl::LINK_STATUS, &mut linked);
+        }
+        linked == gl::TRUE as GLint
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.0);
+        }
+    }
+}
+
+/// The GLSL a context compiles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dialect {
+    /// OpenGL 3.3 and later, what the shaders are written in.
+    Glsl330,
+    /// OpenGL ES 3.
+    GlslEs300,
+    /// OpenGL 2.1 up to 3.2: no vertex array objects needed, no integer
+    /// bit operations, no float render targets to count on.
+    Glsl120,
+}
+
+impl Dialect {
+    /// The dialect of the current context, from its version string ("4.6.0
+    /// NVIDIA 535.54", "OpenGL ES 3.1 Mesa 23.2.1"). Needs the GL functions
+    /// loaded.
+    pub fn current() -> Dialect {
+        let version = unsafe {
+            let version = gl::GetString(gl::VERSION);
+            if version.is_null() {
+                return Dialect::Glsl120;
+            }
+            CStr::from_ptr(version.cast()).to_string_lossy().into_owned()
+        };
+        match caps::parse_version(&version) {
+            (true, _) => Dialect::GlslEs300,
+            (false, version) if version >= (3, 3) => Dialect::Glsl330,
+            (false, _) => Dialect::Glsl120,
+        }
+    }
+
+    /// Whether the context needs (and has) vertex array objects, as
+    /// `FullscreenTriangle::new` asks.
+    pub fn vertex_arrays(self) -> bool {
+        self != Dialect::Glsl120
+    }
+
+    /// `source`, a `stage` shader written in GLSL 3.30, in this dialect.
+    pub fn translate(self, stage: Stage, source: &str) -> String {
+        match self {
+            Dialect::Glsl330 => source.to_string(),
+            Dialect::GlslEs300 => to_gles(source),
+            Dialect::Glsl120 => to_glsl120(stage, source),
+        }
+    }
+}
+
+/// `source` as GLSL ES 3.00: the `#version` line becomes `#version 300 es`
+/// with high precision defaults, which desktop GLSL has and ES leaves to the
+/// shader, and each 1D sampler, which ES has none of, a 2D one a texel high
+/// that `texture` calls on it go through a function for. Everything else has
+/// to be in the subset the two share: no implicit int to float conversions
+/// and no doubles.
+fn to_gles(source: &str) -> String {
+    let samplers_1d: Vec<String> = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("uniform sampler1D "))
+        .filter_map(|rest| rest.split(';').next())
+        .map(|name| name.trim().to_string())
+        .collect();
+    let mut out = String::with_capacity(source.len());
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#version") {
+            out.push_str("#version 300 es\nprecision highp float;\nprecision highp int;\nprecision highp sampler2D;\n");
+            continue;
+        }
+        if let Some(name) = samplers_1d.iter().find(|name| trimmed.starts_with(&format!("uniform sampler1D {name};"))) {
+            out.push_str(&format!(
+                "uniform sampler2D {name};\nvec4 {name}_1d(float x) {{ return texture({name}, vec2(x, 0.5)); }}\n"
+            ));
+            continue;
+        }
+        let mut line = line.to_string();
+        for name in &samplers_1d {
+            line = line.replace(&format!("texture({name}, "), &format!("{name}_1d("));
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// `source` as GLSL 1.20: `in` and `out` declarations become attributes,
+/// varyings and `gl_FragColor` (`gl_FragData` in declaration order if a
+/// fragment shader has several), `layout` qualifiers go (the program binds
+/// attribute locations when linking instead), `texture` calls name the
+/// sampler's dimension, and `textureSize(sampler, 0)` on a 2D sampler reads
+/// a `vec2` uniform named after it with `_size`, which the host has to set.
+/// `isinf` is defined for the floats it can take; integer bit operations
+/// and unsigned integers don't exist, so code using them needs a
+/// `#ifndef LEGACY_GLSL` alternative.
+fn to_glsl120(stage: Stage, source: &str) -> String {
+    let mut samplers = Vec::new();
+    for line in source.lines() {
+        let trimmed = line.trim();
+        for (kind, lookup) in [("sampler1D", "texture1D"), ("sampler2D", "texture2D")] {
+            if let Some(name) = trimmed.strip_prefix("uniform ").and_then(|rest| rest.strip_prefix(kind)) {
+                let name = name.split(';').next().unwrap_or("").trim().to_string();
+                samplers.push((name, kind, lookup));
+            }
+        }
+    }
+    let fragment_outputs = if stage == Stage::Fragment {
+        source.lines().filter(|line| line.trim_start().starts_with("out ") || (line.trim_start().starts_with("layout") && line.contains(") out "))).count()
+    } else {
+        0
+    };
+    let mut outputs = Vec::new();
+    let mut out = String::with_capacity(source.len());
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#version") {
+            out.push_str("#version 120\n#define LEGACY_GLSL\n#define isinf(x) (abs(x) > 3.4e38)\n");
+            continue;
+        }
+        let declaration = match trimmed.find(')').filter(|_| trimmed.starts_with("layout")) {
+            Some(end) => trimmed[end + 1..].trim_start(),
+            None => trimmed,
+        };
+        if let Some(rest) = declaration.strip_prefix("in ") {
+            let qualifier = if stage == Stage::Vertex { "attribute" } else { "varying" };
+            out.push_str(&format!("{qualifier} {rest}\n"));
+            continue;
+        }
+        if let Some(rest) = declaration.strip_prefix("out ") {
+            if stage == Stage::Vertex {
+                out.push_str(&format!("varying {rest}\n"));
+            } else {
+                // `out vec4 NAME;`, which is `gl_FragColor` or `gl_FragData[i]` from now on
+                if let Some(name) = rest.split_whitespace().nth(1) {
+                    let builtin = if fragment_outputs > 1 { format!("gl_FragData[{}]", outputs.len()) } else { "gl_FragColor".to_string() };
+                    outputs.push((name.trim_end_matches(';').to_string(), builtin));
+                }
+                out.push('\n');
+            }
+            continue;
+        }
+        let mut line = line.to_string();
+        for (name, kind, lookup) in &samplers {
+            line = line.replace(&format!("texture({name}, "), &format!("{lookup}({name}, "));
+            if *kind == "sampler2D" {
+                line = line.replace(&format!("textureSize({name}, 0)"), &format!("{name}_size"));
+            }
+        }
+        for (output, builtin) in &outputs {
+            line = replace_word(&line, output, builtin);
+        }
+        out.push_str(&line);
+        out.push('\n');
+        if let Some((name, _, _)) = samplers.iter().find(|(name, kind, _)| *kind == "sampler2D" && trimmed.starts_with(&format!("uniform sampler2D {name};"))) {
+            out.push_str(&format!("uniform vec2 {name}_size;\n"));
+        }
+    }
+    out
+}
+
+/// `line` with every `word` that isn't part of a longer identifier replaced by `with`.
+fn replace_word(line: &str, word: &str, with: &str) -> String {
+    let identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(at) = rest.find(word) {
+        let before = if at > 0 { rest[..at].chars().last() } else { out.chars().last() };
+        let after = rest[at + word.len()..].chars().next();
+        out.push_str(&rest[..at]);
+        if before.is_some_and(identifier) || after.is_some_and(identifier) {
+            out.push_str(word);
+        } else {
+            out.push_str(with);
+        }
+        rest = &rest[at + word.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The whole info log of a shader or program, however long it is.
+unsafe fn info_log(
+    object: GLuint,
+    get: unsafe fn(GLuint, GLenum, *mut GLint),
+    read: unsafe fn(GLuint, GLsizei, *mut GLsizei, *mut GLchar),
+) -> String {
+    let mut length = 0;
+    get(object, gl::INFO_LOG_LENGTH, &mut length);
+    let mut log = vec![0u8; length.max(1) as usize];
+    let mut written = 0;
+    read(object, log.len() as GLsizei, &mut written, log.as_mut_ptr() as *mut GLchar);
+    log.truncate(written.clamp(0, length.max(0)) as usize);
+    String::from_utf8_lossy(&log).into_owned()
+}
+
+/// The line numbers `log` reports errors on, in the order it does, from
+/// the forms drivers write them in: `0:12(5):` (Mesa), `0(12) :` (NVIDIA)
+/// and `ERROR: 0:12:` (AMD, Intel, ANGLE).
+pub fn reported_lines(log: &str) -> Vec<usize> {
+    let mut lines = Vec::new();
+    for entry in log.lines() {
+        let number = ["0:", "0("].iter().find_map(|prefix| {
+            let at = entry.find(prefix)?;
+            let digits: String = entry[at + prefix.len()..].chars().take_while(char::is_ascii_digit).collect();
+            digits.parse::<usize>().ok()
+        });
+        if let Some(number) = number.filter(|number| !lines.contains(number)) {
+            lines.push(number);
+        }
+    }
+    lines
+}
+
+/// The lines of `source` around the first few `log` reports, numbered from
+/// 1 as GLSL counts them, the reported ones marked with `>`; empty when the
+/// log names no line of it.
+pub fn excerpt(source: &str, log: &str) -> String {
+    let source: Vec<&str> = source.lines().collect();
+    let reported: Vec<usize> = reported_lines(log).into_iter().filter(|line| (1..=source.len()).contains(line)).take(EXCERPT_LINES).collect();
+    let mut shown: Vec<usize> = reported
+        .iter()
+        .flat_map(|&line| line.saturating_sub(EXCERPT_CONTEXT).max(1)..=(line + EXCERPT_CONTEXT).min(source.len()))
+        .collect();
+    shown.sort_unstable();
+    shown.dedup();
+
+    let width = shown.last().map_or(1, |last| last.to_string().len());
+    let mut out = String::new();
+    for (index, &line) in shown.iter().enumerate() {
+        if index > 0 && shown[index - 1] + 1 != line {
+            out.push_str("  ...\n");
+        }
+        let marker = if reported.contains(&line) { '>' } else { ' ' };
+        out.push_str(&format!("{marker} {line:>width$} | {}\n", source[line - 1].trim_end()));
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// The shader in `path` with every `#include "file"` line replaced by that
+/// file, preprocessed the same way. Names are relative to the file including
+/// them; a file including itself, however indirectly, is an error, and every
+/// error names the file and line it is on.
+pub fn preprocess(path: &Path) -> Result<String, String> {
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let canonical = fs::canonicalize(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let mut out = String::new();
+    include(path, &source, &mut vec![canonical], &mut out)?;
+    Ok(out)
+}
+
+/// Appends `source`, read from `path`, to `out`; `stack` has the files being
+/// included, `path` last.
+fn include(path: &Path, source: &str, stack: &mut Vec<PathBuf>, out: &mut String) -> Result<(), String> {
+    for (index, line) in source.lines().enumerate() {
+        let Some(rest) = line.trim_start().strip_prefix("#include") else {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+        let at = format!("{}:{}", path.display(), index + 1);
+        let name = rest
+            .trim()
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+            .ok_or_else(|| format!("{at}: expected #include \"file\""))?;
+        let included = path.parent().unwrap_or(Path::new("")).join(name);
+        let canonical = fs::canonicalize(&included).map_err(|err| format!("{at}: cannot include {name}: {err}"))?;
+        if let Some(from) = stack.iter().position(|file| *file == canonical) {
+            let cycle: Vec<String> = stack[from..].iter().chain([&canonical]).map(|file| file.display().to_string()).collect();
+            return Err(format!("{at}: {name} is already being included: {}", cycle.join(" -> ")));
+        }
+        let nested = fs::read_to_string(&included).map_err(|err| format!("{at}: cannot include {name}: {err}"))?;
+        stack.push(canonical);
+        include(&included, &nested, stack, out)?;
+        stack.pop();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines `line 1` to `line n`.
+    fn source(n: usize) -> String {
+        (1..=n).map(|line| format!("line {line}\n")).collect()
+    }
+
+    #[test]
+    fn reads_each_drivers_log() {
+        let mesa = "0:12(3): error: syntax error, unexpected IDENTIFIER\n0:40(1): warning: unused variable";
+        assert_eq!(reported_lines(mesa), [12, 40]);
+        let nvidia = "0(12) : error C0000: syntax error, unexpected identifier\n0(12) : error C1503: undefined variable";
+        assert_eq!(reported_lines(nvidia), [12]);
+        let amd = "ERROR: 0:12: 'foo' : undeclared identifier\nERROR: 0:7: '' : compilation terminated\nERROR: 2 compilation errors.";
+        assert_eq!(reported_lines(amd), [12, 7]);
+        assert!(reported_lines("out of memory").is_empty());
+    }
+
+    #[test]
+    fn excerpt_marks_the_reported_lines() {
+        let excerpt = excerpt(&source(20), "0:10(1): error: oops");
+        assert_eq!(excerpt, "   8 | line 8\n   9 | line 9\n> 10 | line 10\n  11 | line 11\n  12 | line 12");
+    }
+
+    #[test]
+    fn excerpt_stops_at_the_edges() {
+        assert_eq!(excerpt(&source(5), "ERROR: 0:1: first"), "> 1 | line 1\n  2 | line 2\n  3 | line 3");
+        assert_eq!(excerpt(&source(5), "0(5) : error C0000: last"), "  3 | line 3\n  4 | line 4\n> 5 | line 5");
+        // past the end, or line 0, is no line of the source
+        assert_eq!(excerpt(&source(5), "0:6(1): error: past\nERROR: 0:0: none"), "");
+    }
+
+    #[test]
+    fn excerpt_separates_distant_reports() {
+        let excerpt = excerpt(&source(30), "0:3(1): a\n0:20(1): b");
+        assert_eq!(excerpt.lines().filter(|line| *line == "  ...").count(), 1);
+        assert_eq!(excerpt.lines().filter(|line| line.starts_with('>')).count(), 2);
+    }
+}
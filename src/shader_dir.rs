@@ -0,0 +1,152 @@
+// Shaders edited on disk (`--shader-dir`, or `shaders` next to the
+// executable) in place of the built-in ones. While the explorer runs, the
+// directory is watched and the fractal shader rebuilt whenever a file in it
+// changes; a version that doesn't build leaves the last good one drawing, with
+// the compiler's log in a window until a fix builds.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use imgui::{Condition, Ui};
+use log::{info, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::renderer::Renderer;
+use crate::shader;
+
+/// The vertex and fragment shader in a shader directory.
+const SHADER_FILES: [&str; 2] = ["fractal.vert", "fractal.frag"];
+
+/// How long the files have to stay untouched before reloading, as editors
+/// save in several steps.
+const SETTLE: Duration = Duration::from_millis(150);
+
+/// The directory to take shaders from: `dir` if given, which has to exist,
+/// or else `shaders` next to the executable if there is one.
+pub fn find(dir: Option<&Path>) -> Result<Option<PathBuf>, String> {
+    match dir {
+        Some(dir) if !dir.is_dir() => Err(format!("--shader-dir {}: not a directory", dir.display())),
+        Some(dir) => Ok(Some(dir.to_path_buf())),
+        None => Ok(std::env::current_exe().ok().map(|exe| exe.with_file_name("shaders")).filter(|dir| dir.is_dir())),
+    }
+}
+
+/// The vertex and fragment shader in `dir`, includes pasted in, each `None`
+/// where the file isn't there.
+pub fn read(dir: &Path) -> Result<(Option<String>, Option<String>), String> {
+    let [vertex, fragment] = SHADER_FILES.map(|name| {
+        let path = dir.join(name);
+        path.is_file().then(|| shader::preprocess(&path)).transpose()
+    });
+    Ok((vertex?, fragment?))
+}
+
+pub struct ShaderWatch {
+    dir: PathBuf,
+    /// Watches for as long as it lives.
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    /// When the last change came in, while waiting for the files to settle.
+    changed: Option<Instant>,
+    /// Why the last reload failed, until one succeeds.
+    error: Option<String>,
+}
+
+impl ShaderWatch {
+    pub fn new(dir: &Path) -> Result<ShaderWatch, String> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(|err| err.to_string())?;
+        watcher.watch(dir, RecursiveMode::Recursive).map_err(|err| format!("{}: {err}", dir.display()))?;
+        Ok(ShaderWatch { dir: dir.to_path_buf(), _watcher: watcher, events, changed: None, error: None })
+    }
+
+    /// Rebuilds `renderer`'s program once the files have settled after a
+    /// change; says whether it did, so whatever was drawn with the old one
+    /// can be redrawn.
+    pub fn update(&mut self, renderer: &mut Renderer) -> bool {
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) => {
+                    self.changed = Some(Instant::now());
+                }
+                Ok(_) => {}
+                Err(err) => warn!("watching {} failed: {err}", self.dir.display()),
+            }
+        }
+        if self.changed.is_none_or(|at| at.elapsed() < SETTLE) {
+            return false;
+        }
+        self.changed = None;
+
+        let reloaded = read(&self.dir).and_then(|(vertex, fragment)| renderer.reload(vertex, fragment).map_err(String::from));
+        match reloaded {
+            Ok(()) => {
+                info!("reloaded the shaders in {}", self.dir.display());
+                self.error = None;
+                true
+            }
+            Err(err) => {
+                warn!("the shaders in {} did not build, still drawing with the last ones:\n{err}", self.dir.display());
+                self.error = Some(err);
+                false
+            }
+        }
+    }
+
+    /// The log of the last failed reload, if the shaders are still broken.
+    pub fn draw(&self, ui: &Ui) {
+        let Some(error) = &self.error else {
+            return;
+        };
+        ui.window("Shader error")
+            .size([560., 240.], Condition::FirstUseEver)
+            .build(|| {
+                ui.text("still drawing with the last shaders that built");
+                ui.separator();
+                ui.text_wrapped(error);
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for each test.
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mandelplotter-shader-dir-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("a temporary directory");
+        dir
+    }
+
+    #[test]
+    fn a_missing_directory_is_an_error() {
+        let missing = std::env::temp_dir().join("mandelplotter-no-such-shaders");
+        let err = find(Some(&missing)).expect_err("not there");
+        assert_eq!(err, format!("--shader-dir {}: not a directory", missing.display()));
+        let dir = dir("find");
+        assert_eq!(find(Some(&dir)), Ok(Some(dir.clone())));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn files_that_arent_there_are_the_built_in_ones() {
+        let dir = dir("read");
+        assert_eq!(read(&dir), Ok((None, None)));
+        std::fs::write(dir.join("common.glsl"), "float twice(float x) { return 2. * x; }\n").expect("written");
+        std::fs::write(dir.join("fractal.frag"), "#include \"common.glsl\"\nvoid main() {}\n").expect("written");
+        let read = read(&dir);
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(read, Ok((None, Some("float twice(float x) { return 2. * x; }\nvoid main() {}\n".into()))));
+    }
+
+    #[test]
+    fn a_broken_include_names_where_it_is() {
+        let dir = dir("broken");
+        std::fs::write(dir.join("fractal.vert"), "void main() {}\n#include \"missing.glsl\"\n").expect("written");
+        let err = read(&dir).expect_err("nothing to include");
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(err.starts_with(&format!("{}:2: cannot include missing.glsl", dir.join("fractal.vert").display())), "{err}");
+    }
+}
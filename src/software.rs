@@ -0,0 +1,203 @@
+// Fallback for contexts the fractal shader doesn't build in: the CPU renderer
+// draws the frame and GL is only used to put the finished image on screen,
+// which works on any context down to 2.1.
+
+use gl::*;
+use gl::types::*;
+use std::os::raw::c_void;
+
+use image::RgbaImage;
+
+use crate::background::{Background, BackgroundStyle};
+use crate::cpu;
+use crate::gl_object::Texture;
+use crate::mesh::{self, FullscreenTriangle};
+use crate::palette::{ColorMode, Lighting, PaletteTable, SlowTint, Tone};
+use crate::renderer::{self, DrawParams};
+use crate::shader::{Dialect, Program, Shader, Stage};
+use crate::viewport::Viewport;
+
+const BLIT_VERTEX: &str = r#"
+    #version 330 core
+    layout(location = 0) in vec2 in_position;
+    out vec2 uv;
+
+    void main() {
+        gl_Position = vec4(in_position, 0.0, 1.0);
+        uv = vec2(in_position.x, -in_position.y) * 0.5 + 0.5;
+    }
+"#;
+
+const BLIT_FRAGMENT: &str = r#"
+    #version 330 core
+    in vec2 uv;
+    out vec4 FragColor;
+    uniform sampler2D frame;
+
+    void main() {
+        FragColor = texture(frame, uv);
+    }
+"#;
+
+/// Everything that changes the CPU image; a new render only happens when it differs.
+#[derive(Clone, PartialEq)]
+struct FrameKey {
+    viewport: Viewport,
+    substeps: i32,
+    ssaa: i32,
+    seed: u32,
+    julia: Option<(f64, f64)>,
+    morph: f32,
+    transparent_interior: bool,
+    color_mode: ColorMode,
+    color_span: Option<i32>,
+    log_polar: bool,
+    tone: Tone,
+    checkerboard: bool,
+    backdrop: Option<[f32; 3]>,
+    premultiplied: bool,
+    slow_tint: Option<SlowTint>,
+    lighting: Option<Lighting>,
+    background: Option<BackgroundStyle>,
+    palette: PaletteTable,
+}
+
+/// Owns its GL objects, deleted in the order declared when it drops, with the
+/// context it was created in still current.
+pub struct SoftwareRenderer {
+    program: Program,
+    triangle: FullscreenTriangle,
+    texture: Texture,
+    key: Option<FrameKey>,
+    frame: Option<RgbaImage>,
+    /// What `DrawParams::background` composites over, as `Renderer::set_background`.
+    background: Option<Background>,
+}
+
+impl SoftwareRenderer {
+    /// Builds the blit in the current context's GLSL dialect.
+    pub fn new() -> Result<SoftwareRenderer, String> {
+        let dialect = Dialect::current();
+        let shaders = [
+            Shader::compile(Stage::Vertex, &dialect.translate(Stage::Vertex, BLIT_VERTEX))?,
+            Shader::compile(Stage::Fragment, &dialect.translate(Stage::Fragment, BLIT_FRAGMENT))?,
+        ];
+        let program = Program::link(&shaders, &[(mesh::POSITION, c"in_position")])?;
+
+        let triangle = FullscreenTriangle::new(dialect.vertex_arrays())?;
+        let texture = Texture::new(TEXTURE_2D)?;
+        unsafe {
+            TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as GLint);
+            TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST as GLint);
+            TexParameteri(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as GLint);
+            TexParameteri(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as GLint);
+        }
+        Ok(SoftwareRenderer { program, triangle, texture, key: None, frame: None, background: None })
+    }
+
+    /// Re-renders on the CPU if anything changed, then draws the image over the
+    /// bound framebuffer.
+    pub fn draw(&mut self, viewport: &Viewport, params: &DrawParams, palette: &PaletteTable) {
+        let key = FrameKey {
+            viewport: *viewport,
+            substeps: params.substeps,
+            ssaa: params.ssaa,
+            seed: params.seed,
+            julia: params.julia,
+            morph: params.morph,
+            transparent_interior: params.transparent_interior,
+            color_mode: params.color_mode,
+            color_span: params.color_span,
+            log_polar: params.log_polar,
+            tone: params.tone,
+            checkerboard: params.checkerboard,
+            backdrop: params.backdrop,
+            premultiplied: params.premultiplied,
+            slow_tint: params.slow_tint,
+            lighting: params.lighting,
+            background: params.background,
+            palette: palette.clone(),
+        };
+        if self.key.as_ref() != Some(&key) {
+            let shading = cpu::Shading {
+                palette,
+                mode: params.color_mode,
+                transparent_interior: params.transparent_interior,
+                background: self.background(params),
+                morph: params.morph,
+                slow_tint: params.slow_tint,
+                lighting: params.lighting,
+                color_span: params.color_span,
+                log_polar: params.log_polar,
+                tone: params.tone,
+            };
+            let frame = cpu::render_image(viewport, params.substeps, params.ssaa, params.seed, params.julia, &shading);
+            self.upload(&frame, params.checkerboard, params.backdrop, params.premultiplied);
+            self.frame = Some(frame);
+            self.key = Some(key);
+        }
+
+        debug_assert!(self.program.is_linked(), "blitting with program {} that isn't linked in the current context", self.program.id());
+        let [r, g, b] = params.backdrop.unwrap_or([0.; 3]);
+        unsafe {
+            ClearColor(r, g, b, if params.premultiplied { 0. } else { 1. });
+            Clear(COLOR_BUFFER_BIT);
+            UseProgram(self.program.id());
+            ActiveTexture(TEXTURE0);
+            BindTexture(TEXTURE_2D, self.texture.id());
+            Uniform1i(GetUniformLocation(self.program.id(), c"frame".as_ptr()), 0);
+        }
+        self.triangle.draw();
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = Some(background);
+        self.key = None;
+    }
+
+    /// The background to composite over when `params` ask for one.
+    pub fn background(&self, params: &DrawParams) -> Option<&Background> {
+        params.background.and(self.background.as_ref())
+    }
+
+    /// The last CPU render, without the preview checkerboard.
+    pub fn frame(&self) -> Option<&RgbaImage> {
+        self.frame.as_ref()
+    }
+
+    /// Composites as the shader does, over the checkerboard or else
+    /// `backdrop`, or else premultiplies the alpha if `premultiplied` asks.
+    fn upload(&self, frame: &RgbaImage, checkerboard: bool, backdrop: Option<[f32; 3]>, premultiplied: bool) {
+        let mut shown = frame.clone();
+        if checkerboard || backdrop.is_some() {
+            for (x, y, pixel) in shown.enumerate_pixels_mut() {
+                let bg = if checkerboard {
+                    let cell = (x / 8 + y / 8) % 2;
+                    [if cell == 0 { 0.6 } else { 0.85 }; 3]
+                } else {
+                    backdrop.unwrap_or([0.; 3])
+                };
+                let a = pixel[3] as f32 / 255.;
+                for k in 0..3 {
+                    pixel[k] = ((pixel[k] as f32 / 255. * a + bg[k] * (1. - a)) * 255.).round() as u8;
+                }
+                pixel[3] = 255;
+            }
+        } else if premultiplied {
+            for pixel in shown.pixels_mut() {
+                let a = pixel[3] as f32 / 255.;
+                for k in 0..3 {
+                    pixel[k] = (pixel[k] as f32 * a).round() as u8;
+                }
+            }
+        }
+        renderer::tight_rows();
+        unsafe {
+            BindTexture(TEXTURE_2D, self.texture.id());
+            TexImage2D(
+                TEXTURE_2D, 0, RGBA as GLint, shown.width() as GLsizei, shown.height() as GLsizei, 0,
+                RGBA, UNSIGNED_BYTE, shown.as_raw().as_ptr() as *const c_void,
+            );
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// Terminal front end: draws CPU renders as text, either as an ASCII gradient or
+// as 24-bit colored half blocks, and navigates with the keyboard in raw mode.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{cursor, execute, queue, terminal};
+
+use crate::cpu;
+use crate::viewport::Viewport;
+
+/// Characters from darkest to brightest.
+const GRADIENT: &[u8] = b" .:-=+*#%@";
+
+/// Builds one line of text per pair of pixel rows. Terminal cells are roughly
+/// twice as tall as they are wide, so each character covers two pixels stacked
+/// vertically and shows their average.
+pub fn ascii_frame(shades: &[f32], width: usize) -> String {
+    let mut frame = String::new();
+    for rows in shades.chunks(width * 2) {
+        let (top, bottom) = rows.split_at(width.min(rows.len()));
+        for (x, &t) in top.iter().enumerate() {
+            let shade = match bottom.get(x) {
+                Some(b) => (t + b) / 2.,
+                None => t,
+            };
+            let index = (shade.clamp(0., 1.) * (GRADIENT.len() - 1) as f32).round() as usize;
+            frame.push(GRADIENT[index] as char);
+        }
+        frame.push_str("\r\n");
+    }
+    frame
+}
+
+/// Builds one line per pair of pixel rows using the upper half block: the
+/// foreground color paints the top pixel and the background color the bottom one.
+pub fn truecolor_frame(shades: &[f32], width: usize) -> String {
+    let mut frame = String::new();
+    for rows in shades.chunks(width * 2) {
+        let (top, bottom) = rows.split_at(width.min(rows.len()));
+        for (x, &t) in top.iter().enumerate() {
+            let t = grey(t);
+            let b = bottom.get(x).map_or(0, |&s| grey(s));
+            write!(frame, "\x1b[38;2;{t};{t};{t}m\x1b[48;2;{b};{b};{b}m\u{2580}").unwrap();
+        }
+        frame.push_str("\x1b[0m\r\n");
+    }
+    frame
+}
+
+fn grey(shade: f32) -> u8 {
+    (shade.clamp(0., 1.) * 255.).round() as u8
+}
+
+fn supports_truecolor() -> bool {
+    matches!(std::env::var("COLORTERM").as_deref(), Ok("truecolor") | Ok("24bit"))
+}
+
+/// Pixel size of the render that fills the terminal: two pixels per cell
+/// vertically, with the last line kept for the status bar.
+pub fn canvas_size() -> io::Result<(u32, u32)> {
+    let (cols, rows) = terminal::size()?;
+    Ok((cols as u32, rows.saturating_sub(1).max(1) as u32 * 2))
+}
+
+pub fn run(viewport: Viewport, substeps: i32, ascii: bool) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let result = event_loop(&mut stdout, viewport, substeps, !ascii && supports_truecolor());
+
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn event_loop(
+    stdout: &mut io::Stdout,
+    mut viewport: Viewport,
+    mut substeps: i32,
+    mut truecolor: bool,
+) -> io::Result<()> {
+    loop {
+        let (width, height) = canvas_size()?;
+        viewport.resize(width, height);
+
+        let shades = cpu::render(&viewport, substeps);
+        let frame = if truecolor {
+            truecolor_frame(&shades, width as usize)
+        } else {
+            ascii_frame(&shades, width as usize)
+        };
+        queue!(stdout, cursor::MoveTo(0, 0))?;
+        stdout.write_all(frame.as_bytes())?;
+        write!(
+            stdout,
+            "scale {:.3e}  iterations {substeps}  [arrows] pan  [+/-] zoom  [[/]] iterations  [m] mode  [r] reset  [q] quit",
+            viewport.scale
+        )?;
+        queue!(stdout, terminal::Clear(terminal::ClearType::UntilNewLine))?;
+        stdout.flush()?;
+
+        // block until something changes the picture
+        loop {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Up => viewport.pan(0., 0.1),
+                        KeyCode::Down => viewport.pan(0., -0.1),
+                        KeyCode::Right => viewport.pan(0.1, 0.),
+                        KeyCode::Left => viewport.pan(-0.1, 0.),
+                        KeyCode::Char('+') | KeyCode::Char('=') => viewport.zoom_by(0.8),
+                        KeyCode::Char('-') => viewport.zoom_by(1.25),
+                        KeyCode::Char(']') => substeps += 100,
+                        KeyCode::Char('[') => substeps = (substeps - 100).max(0),
+                        KeyCode::Char('m') => truecolor = !truecolor,
+                        KeyCode::Char('r') => viewport.reset(),
+                        _ => continue,
+                    }
+                    break;
+                }
+                Event::Resize(..) => break,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_averages_pairs_of_rows() {
+        // a 3x3 render: two lines, the last row on its own
+        let shades = [0., 1., 0.5, 0., 1., 0.5, 1., 0.2, 0.];
+        assert_eq!(ascii_frame(&shades, 3), " @+\r\n@: \r\n");
+    }
+
+    #[test]
+    fn truecolor_paints_top_and_bottom() {
+        let shades = [0., 1., 0.5, 0.25];
+        assert_eq!(
+            truecolor_frame(&shades, 2),
+            "\x1b[38;2;0;0;0m\x1b[48;2;128;128;128m\u{2580}\x1b[38;2;255;255;255m\x1b[48;2;64;64;64m\u{2580}\x1b[0m\r\n"
+        );
+        // a missing bottom row is black
+        assert_eq!(truecolor_frame(&[1.], 1), "\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m\u{2580}\x1b[0m\r\n");
+    }
+
+    #[test]
+    fn renders_map_to_their_characters() {
+        // deep in the main cardioid every point is inside, and white
+        let inside = Viewport { center: (-0.2, 0.), scale: 0.01, ..Viewport::new(4, 4) };
+        assert_eq!(ascii_frame(&cpu::render(&inside, 100), 4), "@@@@\r\n@@@@\r\n");
+        // far outside they escape at once, as the darkest shade
+        let outside = Viewport { center: (40., 40.), scale: 0.01, ..Viewport::new(4, 2) };
+        assert_eq!(ascii_frame(&cpu::render(&outside, 100), 4), "    \r\n");
+    }
+}
@@ -0,0 +1,161 @@
+// `render --export-tiles DIR`: the view as a pyramid of map tiles, for
+// publishing a deep zoom that people can explore in the browser.
+//
+// The layout is the `{z}/{x}/{y}` one of web maps, so Leaflet, OpenLayers and
+// OpenSeadragon (with a `getTileUrl`) read it as it is. Level 0 is a single
+// square tile showing `--zoom` around `--center` on every side; each tile of a
+// level is split into four at the next, and within a level `x` grows to the
+// right (along the real axis) and `y` downwards (against the imaginary one),
+// from 0 at the top left corner. Levels get deep fast, so only the tiles
+// within a `--size` window around the center are rendered; other tiles are
+// missing and show as blank. Next to the tiles go `tiles.json`, which says
+// what they show, and `index.html`, a Leaflet page to browse them.
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::viewport::Viewport;
+
+/// Deepest level there can be, where a pixel is about 2^-40 of level 0's and
+/// f64 begins to run out of digits.
+pub const MAX_LEVEL: u32 = 40;
+
+/// Which tiles to render, and what they show.
+pub struct Pyramid {
+    pub center: (f64, f64),
+    /// Half the extent of the level 0 tile, in complex units.
+    pub zoom: f64,
+    /// Tile edge in pixels.
+    pub tile: u32,
+    /// Window around the center, in pixels, whose tiles are rendered.
+    pub window: (u32, u32),
+}
+
+/// The tiles rendered of one level.
+pub struct Level {
+    pub level: u32,
+    pub x: Range<u64>,
+    pub y: Range<u64>,
+    pub iterations: i32,
+}
+
+impl Level {
+    pub fn count(&self) -> usize {
+        ((self.x.end - self.x.start) * (self.y.end - self.y.start)) as usize
+    }
+
+    /// Every tile, row by row.
+    pub fn tiles(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.y.clone().flat_map(move |y| self.x.clone().map(move |x| (x, y)))
+    }
+}
+
+impl Pyramid {
+    /// The tiles of `level` within the window, with `iterations` for them.
+    pub fn level(&self, level: u32, iterations: i32) -> Level {
+        let across = 1u64 << level;
+        let half_world = (across * self.tile as u64) as f64 / 2.;
+        let span = |window: u32| {
+            let from = ((half_world - window as f64 / 2.) / self.tile as f64).floor().max(0.) as u64;
+            let to = ((half_world + window as f64 / 2.) / self.tile as f64).ceil() as u64;
+            from..to.min(across)
+        };
+        Level { level, x: span(self.window.0), y: span(self.window.1), iterations }
+    }
+
+    /// Half the extent of a tile of `level`, as `Viewport::scale`.
+    pub fn scale(&self, level: u32) -> f64 {
+        self.zoom / (1u64 << level) as f64
+    }
+
+    /// What tile `(x, y)` of `level` shows.
+    pub fn view(&self, level: u32, (x, y): (u64, u64)) -> Viewport {
+        let across = (1u64 << level) as f64;
+        // the middle of the tile, from -1 to 1 across the level 0 tile
+        let offset = |index: u64| 2. * (index as f64 + 0.5) / across - 1.;
+        let mut view = Viewport::new(self.tile, self.tile);
+        view.center = (self.center.0 + offset(x) * self.zoom, self.center.1 - offset(y) * self.zoom);
+        view.scale = self.scale(level);
+        view
+    }
+}
+
+/// Where tile `(x, y)` of `level` goes in `dir`.
+pub fn tile_path(dir: &Path, level: u32, (x, y): (u64, u64)) -> PathBuf {
+    dir.join(level.to_string()).join(x.to_string()).join(format!("{y}.png"))
+}
+
+#[derive(Serialize)]
+struct Manifest {
+    /// `{z}/{x}/{y}.png`, for viewers to fill in.
+    url: &'static str,
+    tile_size: u32,
+    center: [f64; 2],
+    zoom: f64,
+    levels: Vec<LevelEntry>,
+}
+
+#[derive(Serialize)]
+struct LevelEntry {
+    level: u32,
+    /// The first column or row rendered and the one after the last.
+    x: [u64; 2],
+    y: [u64; 2],
+    iterations: i32,
+}
+
+/// Writes `tiles.json` and `index.html` for the tiles of `levels` into `dir`.
+pub fn write_viewer(dir: &Path, pyramid: &Pyramid, levels: &[Level]) -> Result<(), String> {
+    let manifest = Manifest {
+        url: "{z}/{x}/{y}.png",
+        tile_size: pyramid.tile,
+        center: [pyramid.center.0, pyramid.center.1],
+        zoom: pyramid.zoom,
+        levels: levels
+            .iter()
+            .map(|level| LevelEntry {
+                level: level.level,
+                x: [level.x.start, level.x.end],
+                y: [level.y.start, level.y.end],
+                iterations: level.iterations,
+            })
+            .collect(),
+    };
+    let json = serde_json::to_string_pretty(&manifest).map_err(|err| err.to_string())?;
+    let path = dir.join("tiles.json");
+    fs::write(&path, json + "\n").map_err(|err| format!("{}: {err}", path.display()))?;
+
+    let (min, max) = (levels.first().map_or(0, |level| level.level), levels.last().map_or(0, |level| level.level));
+    let size = pyramid.tile;
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Mandelbrot set at {re} {im}</title>
+<link rel="stylesheet" href="https://unpkg.com/leaflet@1.9.4/dist/leaflet.css">
+<script src="https://unpkg.com/leaflet@1.9.4/dist/leaflet.js"></script>
+<style>html, body, #map {{ height: 100%; margin: 0; background: #000; }}</style>
+</head>
+<body>
+<div id="map"></div>
+<script>
+// level 0 is one {size}px tile, with y growing downwards
+const map = L.map("map", {{ crs: L.CRS.Simple, minZoom: {min}, maxZoom: {max} }});
+const world = [[-{size}, 0], [0, {size}]];
+L.tileLayer("{{z}}/{{x}}/{{y}}.png", {{ tileSize: {size}, minZoom: {min}, maxZoom: {max}, noWrap: true, bounds: world }}).addTo(map);
+map.setView([-{size} / 2, {size} / 2], {min});
+</script>
+</body>
+</html>
+"#,
+        re = pyramid.center.0,
+        im = pyramid.center.1,
+    );
+    let path = dir.join("index.html");
+    fs::write(&path, page).map_err(|err| format!("{}: {err}", path.display()))
+}
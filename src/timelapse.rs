@@ -0,0 +1,158 @@
+// Timelapse capture (Shift+F12): while it runs, a screenshot every few
+// seconds or frames goes into a directory of its own, numbered in order, so a
+// session of exploring can be cut into a video later (e.g. `ffmpeg -i
+// frame_%05d.png`). The frames go through the same PBO readback as F12, so
+// capturing doesn't hitch. `manifest.json` next to them lists the camera of
+// every frame and is rewritten after each one, so it is complete even if the
+// program doesn't exit cleanly.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::capture;
+use crate::session::Camera;
+use crate::viewport::Viewport;
+
+/// Readbacks plus encoder queue of `Capturer`, with a margin.
+const FRAMES_IN_FLIGHT: usize = 8;
+
+/// How often to capture, from the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Every {
+    Seconds(f64),
+    Frames(u32),
+}
+
+/// When a timelapse stops by itself.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub max_images: Option<usize>,
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Frame {
+    file: String,
+    /// Since the timelapse started.
+    seconds: f64,
+    camera: Camera,
+    iterations: i32,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    /// Unix time in milliseconds.
+    started: u128,
+    every: Every,
+    frames: &'a [Frame],
+}
+
+/// What the timelapse wants this frame.
+pub enum Step {
+    Wait,
+    /// Capture the frame into this file.
+    Capture(String),
+    /// A limit was reached; the reason, for the console.
+    Stop(String),
+}
+
+pub struct Timelapse {
+    dir: PathBuf,
+    every: Every,
+    limits: Limits,
+    started: u128,
+    elapsed: f64,
+    /// When the next capture is due, in seconds or frames since the start.
+    next_at: f64,
+    rendered: u64,
+    frames: Vec<Frame>,
+    /// Bytes of the frames found on disk so far, and how many of them.
+    bytes: u64,
+    counted: usize,
+}
+
+impl Timelapse {
+    /// Creates the directory, named after the time, in the working directory.
+    pub fn start(every: Every, limits: Limits) -> Result<Timelapse, String> {
+        let dir = PathBuf::from(capture::capture_name("timelapse"));
+        fs::create_dir(&dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+        Ok(Timelapse {
+            dir,
+            every,
+            limits,
+            started,
+            elapsed: 0.,
+            // the first frame is captured right away
+            next_at: 0.,
+            rendered: 0,
+            frames: Vec::new(),
+            bytes: 0,
+            counted: 0,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Advances by one frame of `dt` seconds showing `viewport`, and says
+    /// whether to capture it.
+    pub fn step(&mut self, dt: f64, viewport: &Viewport, iterations: i32) -> Step {
+        let seconds = self.elapsed;
+        let (now, interval) = match self.every {
+            Every::Seconds(every) => (seconds, every),
+            Every::Frames(frames) => (self.rendered as f64, frames as f64),
+        };
+        self.elapsed += dt;
+        self.rendered += 1;
+        if now < self.next_at {
+            return Step::Wait;
+        }
+        if let Some(reason) = self.limit_reached() {
+            return Step::Stop(reason);
+        }
+        // keep to the schedule, but don't catch up on captures a long frame skipped
+        self.next_at = (self.next_at + interval).max(now);
+
+        let file = format!("frame_{:05}.png", self.frames.len() + 1);
+        self.frames.push(Frame { file: file.clone(), seconds, camera: Camera::from_view(viewport), iterations });
+        if let Err(err) = self.write_manifest() {
+            return Step::Stop(format!("failed to write the manifest: {err}"));
+        }
+        Step::Capture(self.dir.join(file).to_string_lossy().into_owned())
+    }
+
+    fn limit_reached(&mut self) -> Option<String> {
+        if let Some(max) = self.limits.max_images.filter(|&max| self.frames.len() >= max) {
+            return Some(format!("reached {max} images"));
+        }
+        let max = self.limits.max_bytes?;
+        // the newest frames may still be read back or half written, so the
+        // limit can be overshot by that many
+        let settled = self.frames.len().saturating_sub(FRAMES_IN_FLIGHT);
+        while let Some(frame) = self.frames[..settled].get(self.counted) {
+            let Ok(metadata) = fs::metadata(self.dir.join(&frame.file)) else {
+                break;
+            };
+            self.bytes += metadata.len();
+            self.counted += 1;
+        }
+        (self.bytes >= max).then(|| format!("reached {} MB", max as f64 / 1e6))
+    }
+
+    fn write_manifest(&self) -> Result<(), String> {
+        let path = self.dir.join("manifest.json");
+        let manifest = Manifest { started: self.started, every: self.every, frames: &self.frames };
+        let file = File::create(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+        serde_json::to_writer_pretty(file, &manifest).map_err(|err| format!("{}: {err}", path.display()))
+    }
+}
@@ -0,0 +1,168 @@
+// The explorer's clock: what animates (the Julia cross-fade, flights, tour
+// playback, zoom inertia) advances in fixed steps of wall-clock time
+// instead of once per frame, so it moves at the same speed with or without
+// vsync and catches up after a slow frame. A frame runs however many steps
+// fit into the time since the last one and presents the state in between
+// the last two, by how far into the next step it is; the shader's `time`
+// (palette cycling) is the wall-clock time itself. After a frame that took
+// very long, only `MAX_CATCH_UP` worth of steps run and the rest is dropped,
+// so steps that take long themselves can't pile up into a spiral of ever
+// slower frames.
+
+use std::f64::consts::{PI, TAU};
+use std::time::{Duration, Instant};
+
+use crate::viewport::Viewport;
+
+/// Steps per second.
+const RATE: u32 = 120;
+/// The most time one frame catches up on.
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+
+/// What one frame has to do.
+pub struct Tick {
+    /// Since the last frame, for what still goes per frame.
+    pub delta: Duration,
+    /// Fixed steps to run, of `Clock::step` each.
+    pub steps: u32,
+}
+
+pub struct Clock {
+    last: Instant,
+    /// Time the steps run so far haven't covered yet, less than a step.
+    behind: Duration,
+    /// What the steps run so far add up to.
+    stepped: Duration,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock { last: Instant::now(), behind: Duration::ZERO, stepped: Duration::ZERO }
+    }
+
+    pub fn step() -> Duration {
+        Duration::from_secs(1) / RATE
+    }
+
+    /// Starts a frame at `now`.
+    pub fn tick(&mut self, now: Instant) -> Tick {
+        let delta = now.saturating_duration_since(self.last);
+        self.last = now;
+        let (steps, behind) = steps(self.behind + delta);
+        self.behind = behind;
+        self.stepped += Clock::step() * steps;
+        Tick { delta, steps }
+    }
+
+    /// How far into the next step the frame is, from 0 to 1: how much of
+    /// the way from the state before the last step to the one after it to
+    /// present.
+    pub fn alpha(&self) -> f64 {
+        self.behind.as_secs_f64() / Clock::step().as_secs_f64()
+    }
+
+    /// Seconds the explorer has run, less what was dropped after slow frames.
+    pub fn time(&self) -> f64 {
+        (self.stepped + self.behind).as_secs_f64()
+    }
+}
+
+/// The steps that fit into `behind` and what is left over, after dropping
+/// what is past `MAX_CATCH_UP`.
+fn steps(behind: Duration) -> (u32, Duration) {
+    let step = Clock::step();
+    let behind = behind.min(MAX_CATCH_UP);
+    let steps = (behind.as_nanos() / step.as_nanos()) as u32;
+    (steps, behind - step * steps)
+}
+
+/// Between `from` and `to`, `alpha` of the way.
+pub fn lerp(from: f64, to: f64, alpha: f64) -> f64 {
+    from + (to - from) * alpha
+}
+
+/// Between the views `from` and `to`, `alpha` of the way: the scale by equal
+/// factors, as zooming goes, and the rotation the short way round. The size is
+/// `to`'s.
+pub fn lerp_view(from: &Viewport, to: &Viewport, alpha: f64) -> Viewport {
+    let turn = (to.rotation - from.rotation + PI).rem_euclid(TAU) - PI;
+    Viewport {
+        center: (lerp(from.center.0, to.center.0, alpha), lerp(from.center.1, to.center.1, alpha)),
+        scale: from.scale * (to.scale / from.scale).powf(alpha),
+        rotation: (from.rotation + turn * alpha).rem_euclid(TAU),
+        anisotropy: lerp(from.anisotropy, to.anisotropy, alpha),
+        ..*to
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A clock whose last frame was at `start`.
+    fn clock(start: Instant) -> Clock {
+        Clock { last: start, ..Clock::new() }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        // a step is a whole number of nanoseconds, a little short of 1/120 s
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn steps_fit_into_the_frame() {
+        let start = Instant::now();
+        let mut clock = clock(start);
+        let tick = clock.tick(start + Clock::step() * 5 / 2);
+        assert_eq!(tick.steps, 2);
+        assert_eq!(tick.delta, Clock::step() * 5 / 2);
+        assert!(close(clock.alpha(), 0.5));
+        assert!(close(clock.time(), 2.5 / RATE as f64));
+    }
+
+    #[test]
+    fn leftovers_add_up_over_frames() {
+        // at twice the step rate, every other frame runs a step
+        let start = Instant::now();
+        let mut clock = clock(start);
+        let steps: Vec<u32> = (1..=8).map(|frame| clock.tick(start + Clock::step() * frame / 2).steps).collect();
+        assert_eq!(steps, [0, 1, 0, 1, 0, 1, 0, 1]);
+        assert!(close(clock.time(), 4. / RATE as f64));
+    }
+
+    #[test]
+    fn a_long_frame_catches_up_only_so_far() {
+        let start = Instant::now();
+        let mut clock = clock(start);
+        let tick = clock.tick(start + Duration::from_secs(10));
+        let most = (MAX_CATCH_UP.as_nanos() / Clock::step().as_nanos()) as u32;
+        assert_eq!(tick.steps, most);
+        assert!((0. ..1.).contains(&clock.alpha()));
+        // the rest is dropped, not owed to the frames after it
+        assert!(clock.time() <= MAX_CATCH_UP.as_secs_f64());
+        let next = clock.tick(start + Duration::from_secs(10) + Clock::step());
+        assert!(next.steps <= 1);
+    }
+
+    #[test]
+    fn a_frame_before_the_last_runs_nothing() {
+        let start = Instant::now();
+        let mut clock = clock(start + Duration::from_secs(1));
+        let tick = clock.tick(start);
+        assert_eq!((tick.delta, tick.steps), (Duration::ZERO, 0));
+    }
+
+    #[test]
+    fn views_in_between() {
+        let from = Viewport { center: (-1., 0.5), scale: 1e-2, rotation: TAU - 0.1, ..Viewport::new(100, 50) };
+        let to = Viewport { center: (1., -0.5), scale: 1e-4, rotation: 0.1, ..Viewport::new(100, 50) };
+        assert_eq!(lerp_view(&from, &to, 0.), from);
+        let end = lerp_view(&from, &to, 1.);
+        assert!(close(end.scale, to.scale) && close(end.rotation, to.rotation) && end.center == to.center);
+        let half = lerp_view(&from, &to, 0.5);
+        assert_eq!(half.center, (0., 0.));
+        assert!(close(half.scale.log10(), -3.));
+        // through 0, not back the long way round
+        assert!(close(half.rotation, 0.) || close(half.rotation, TAU));
+    }
+}
@@ -0,0 +1,74 @@
+// Orbit trails (Shift+O): a point sweeps across the view on its own, in
+// step with the shader's `time`, and its orbit is drawn over the fractal,
+// fading from the start to the last iteration, to show how orbits behave
+// from place to place. In a Julia set the point is where the orbit starts;
+// otherwise it is `c` and the orbit starts at 0. While it is on, a window
+// holds the sweep speed and the trail length; closing it turns it off.
+
+use imgui::{Condition, Ui};
+
+use crate::cpu;
+use crate::viewport::Viewport;
+
+/// How much of the view the sweep covers, from the middle.
+const REACH: f64 = 0.8;
+
+pub struct OrbitTrail {
+    pub on: bool,
+    /// Radians per second of the sweep's phase.
+    speed: f32,
+    /// Iterations drawn at most.
+    length: i32,
+}
+
+impl OrbitTrail {
+    pub fn new() -> OrbitTrail {
+        OrbitTrail { on: false, speed: 0.3, length: 100 }
+    }
+
+    /// Where the point is at `time` seconds: a Lissajous figure over the
+    /// view, so it stays on screen at any depth.
+    fn point(&self, view: &Viewport, time: f32) -> (f64, f64) {
+        let phase = (time * self.speed) as f64;
+        let (width, height) = (view.width as f64, view.height as f64);
+        let x = width / 2. * (1. + REACH * phase.sin());
+        let y = height / 2. * (1. + REACH * (phase * 1.37 + 0.5).sin());
+        view.screen_to_complex((x, y))
+    }
+
+    /// The window and, over the fractal, the orbit of the point at `time`,
+    /// of the Julia set of `julia` if one is drawn.
+    pub fn draw(&mut self, ui: &Ui, view: &Viewport, time: f32, julia: Option<(f64, f64)>) {
+        if !self.on {
+            return;
+        }
+        let mut open = true;
+        ui.window("Orbit trail")
+            .opened(&mut open)
+            .size([300., 80.], Condition::FirstUseEver)
+            .build(|| {
+                ui.slider("sweep speed", 0., 2., &mut self.speed);
+                ui.slider("trail length", 2, 2000, &mut self.length);
+            });
+        self.on = open;
+
+        let point = self.point(view, time);
+        let orbit = match julia {
+            Some(c) => cpu::orbit(point, c, self.length),
+            None => cpu::orbit((0., 0.), point, self.length),
+        };
+        // framebuffer pixels to the UI's screen coordinates
+        let [scale_x, scale_y] = ui.io().display_framebuffer_scale;
+        let screen = |z: (f64, f64)| {
+            let (x, y) = view.complex_to_screen(z);
+            [x as f32 / scale_x, y as f32 / scale_y]
+        };
+        let draw = ui.get_background_draw_list();
+        let segments = (orbit.len() - 1).max(1) as f32;
+        for (index, pair) in orbit.windows(2).enumerate() {
+            let fade = 1. - 0.85 * index as f32 / segments;
+            draw.add_line(screen(pair[0]), screen(pair[1]), [1., 0.85, 0.3, fade]).thickness(1.5).build();
+        }
+        draw.add_circle(screen(point), 4., [1., 1., 1., 0.9]).filled(true).build();
+    }
+}
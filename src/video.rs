@@ -0,0 +1,52 @@
+// Video files without linking any codec: frames go to an ffmpeg child process
+// as raw RGBA on its stdin and ffmpeg does the encoding.
+
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+
+use image::RgbaImage;
+
+pub struct VideoEncoder {
+    child: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+    frame_bytes: usize,
+}
+
+impl VideoEncoder {
+    /// Starts ffmpeg writing `path` from `width x height` frames at `fps`, with
+    /// `args` (codec, quality, ...) placed between the input and the output.
+    pub fn spawn(path: &Path, width: u32, height: u32, fps: f64, args: &[String]) -> Result<VideoEncoder, String> {
+        let mut child = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{width}x{height}"), "-r", &fps.to_string(), "-i", "-"])
+            .args(args)
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::NotFound => "ffmpeg is not installed (or not on PATH); it is needed to write video".to_string(),
+                _ => format!("could not start ffmpeg: {err}"),
+            })?;
+        let stdin = child.stdin.take().map(BufWriter::new);
+        Ok(VideoEncoder { child, stdin, frame_bytes: width as usize * height as usize * 4 })
+    }
+
+    pub fn write_frame(&mut self, frame: &RgbaImage) -> Result<(), String> {
+        assert_eq!(frame.as_raw().len(), self.frame_bytes, "frames must match the video size");
+        let stdin = self.stdin.as_mut().expect("only taken by finish");
+        // a broken pipe means ffmpeg gave up; its exit status from finish() says why
+        stdin.write_all(frame.as_raw()).map_err(|err| format!("ffmpeg stopped reading frames: {err}"))
+    }
+
+    /// Flushes the last frames, closes ffmpeg's stdin so it finishes the file
+    /// and waits for it to exit.
+    pub fn finish(mut self) -> Result<ExitStatus, String> {
+        if let Some(stdin) = self.stdin.take() {
+            // flush errors only mean ffmpeg already quit, which its status reports
+            let _ = stdin.into_inner().map(drop);
+        }
+        self.child.wait().map_err(|err| format!("waiting for ffmpeg: {err}"))
+    }
+}
@@ -0,0 +1,265 @@
+// A view with everything it is colored with, for rendering on the CPU from
+// other programs without going through `cpu::Shading` by hand. On top of
+// what the explorer colors with, the palette can repeat (`frequency`) and be
+// shifted (`phase`), the exterior's colors can be bent by a gamma, and the
+// interior can be any color, transparent included.
+
+use crate::cpu;
+use crate::palette::{ColorMode, Palette, PaletteTable, Tone, PALETTE_SIZE};
+use crate::viewport::Viewport;
+
+/// A view of the Mandelbrot set, or of a Julia set, and how to color it.
+#[derive(Clone, Debug)]
+pub struct MandelbrotView {
+    viewport: Viewport,
+    iterations: i32,
+    ssaa: i32,
+    seed: u32,
+    julia: Option<(f64, f64)>,
+    palette: Palette,
+    color_mode: ColorMode,
+    color_span: Option<i32>,
+    frequency: f32,
+    phase: f32,
+    gamma: f32,
+    tone: Tone,
+    interior: [u8; 4],
+}
+
+impl MandelbrotView {
+    /// The explorer's starting view at `width x height` pixels: the whole
+    /// Mandelbrot set, 1000 iterations, one sample per pixel, the grayscale
+    /// palette coloring by escape time once over the iterations and a white
+    /// interior.
+    pub fn new(width: u32, height: u32) -> MandelbrotView {
+        MandelbrotView {
+            viewport: Viewport::new(width, height),
+            iterations: 1000,
+            ssaa: 1,
+            seed: 0,
+            julia: None,
+            palette: Palette::curated(0),
+            color_mode: ColorMode::EscapeTime,
+            color_span: None,
+            frequency: 1.,
+            phase: 0.,
+            gamma: 1.,
+            tone: Tone::default(),
+            interior: [255; 4],
+        }
+    }
+
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    /// The point in the middle of the view.
+    pub fn set_center(&mut self, re: f64, im: f64) {
+        self.viewport.center = (re, im);
+    }
+
+    /// Half of the visible height, 1 to start with; smaller is deeper. Must
+    /// be positive.
+    pub fn set_scale(&mut self, scale: f64) {
+        assert!(scale > 0., "the scale must be positive, not {scale}");
+        self.viewport.scale = scale;
+    }
+
+    /// Counter-clockwise, in radians; 0 to start with.
+    pub fn set_rotation(&mut self, rotation: f64) {
+        self.viewport.rotation = rotation.rem_euclid(std::f64::consts::TAU);
+    }
+
+    /// Iterations before a point counts as inside the set, at least 1.
+    pub fn set_iterations(&mut self, iterations: i32) {
+        self.iterations = iterations.max(1);
+    }
+
+    /// `ssaa x ssaa` jittered samples per pixel, from 1 (the default) to 4,
+    /// jittered as picked by `seed`.
+    pub fn set_supersampling(&mut self, ssaa: i32, seed: u32) {
+        self.ssaa = ssaa.clamp(1, 4);
+        self.seed = seed;
+    }
+
+    /// The Julia set of `c` instead of the Mandelbrot set, or back with `None`.
+    pub fn set_julia(&mut self, c: Option<(f64, f64)>) {
+        self.julia = c;
+    }
+
+    pub fn set_color_mode(&mut self, mode: ColorMode) {
+        self.color_mode = mode;
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Iterations the palette spreads over in `ColorMode::EscapeTime` and
+    /// `Blend`, at least 1; `None` (the default) for the iteration count.
+    pub fn set_color_span(&mut self, span: Option<i32>) {
+        self.color_span = span.map(|span| span.max(1));
+    }
+
+    /// How many times the palette repeats over its span, 1 by default;
+    /// fractions stretch it instead. From 1/64 to 64.
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency.clamp(1. / 64., 64.);
+    }
+
+    /// How far along the palette its start is moved, in turns: 0 (the
+    /// default) and 1 are the same, 0.5 starts halfway through it.
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase.rem_euclid(1.);
+    }
+
+    /// Of the exterior's finished colors, as `color^(1/gamma)`: 1 (the
+    /// default) leaves them, higher values brighten the dark ones. From 0.1
+    /// to 10; the interior color stays as it is set.
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.gamma = gamma.clamp(0.1, 10.);
+    }
+
+    /// Brightness (-1 to 1, 0 by default) and contrast (0 to 4, 1 by
+    /// default), before the gamma.
+    pub fn set_tone(&mut self, tone: Tone) {
+        self.tone = Tone { brightness: tone.brightness.clamp(-1., 1.), contrast: tone.contrast.clamp(0., 4.) };
+    }
+
+    /// RGBA of the points inside the set, opaque white by default; an alpha
+    /// of 0 leaves them transparent.
+    pub fn set_interior_color(&mut self, color: [u8; 4]) {
+        self.interior = color;
+    }
+
+    /// The palette as sampled, with the frequency and phase baked in.
+    fn table(&self) -> PaletteTable {
+        let texels = (0..PALETTE_SIZE)
+            .map(|i| {
+                let t = i as f32 / (PALETTE_SIZE - 1) as f32;
+                let u = t * self.frequency + self.phase;
+                // the end of each repetition is the palette's end, not its start
+                let u = if u > 0. && u.fract() == 0. { 1. } else { u.fract() };
+                let [r, g, b] = self.palette.color_at(u);
+                [r, g, b].map(|v| (v.clamp(0., 1.) * 255.).round() as u8)
+            })
+            .map(|[r, g, b]| [r, g, b, 255])
+            .collect();
+        PaletteTable { texels }
+    }
+
+    /// Renders the view into `buffer`: RGBA8, rows from the top, exactly
+    /// `4 * width * height` bytes.
+    pub fn render_to_buffer(&self, buffer: &mut [u8]) -> Result<(), String> {
+        let (width, height) = (self.viewport.width, self.viewport.height);
+        let needed = 4 * width as usize * height as usize;
+        if buffer.len() != needed {
+            return Err(format!("a {width}x{height} view takes {needed} bytes, not {}", buffer.len()));
+        }
+        let palette = self.table();
+        let shading = cpu::Shading {
+            palette: &palette,
+            mode: self.color_mode,
+            // the interior is colored here, through the alpha it leaves
+            transparent_interior: true,
+            background: None,
+            morph: 1.,
+            slow_tint: None,
+            lighting: None,
+            color_span: self.color_span,
+            log_polar: false,
+            tone: self.tone,
+        };
+        let image = cpu::render_image(&self.viewport, self.iterations, self.ssaa, self.seed, self.julia, &shading);
+        let interior = self.interior.map(|v| v as f32 / 255.);
+        for (out, pixel) in buffer.chunks_exact_mut(4).zip(image.pixels()) {
+            let a = pixel[3] as f32 / 255.;
+            // the exterior over the interior color, as far as it covers the pixel
+            let alpha = a + interior[3] * (1. - a);
+            for k in 0..3 {
+                let exterior = (pixel[k] as f32 / 255.).powf(1. / self.gamma);
+                let over = exterior * a + interior[k] * interior[3] * (1. - a);
+                out[k] = if alpha > 0. { (over / alpha * 255.).round() as u8 } else { 0 };
+            }
+            out[3] = (alpha * 255.).round() as u8;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(view: &MandelbrotView) -> Vec<u8> {
+        let mut buffer = vec![0; 4 * view.viewport.width as usize * view.viewport.height as usize];
+        view.render_to_buffer(&mut buffer).expect("sized for the view");
+        buffer
+    }
+
+    /// The pixel at `(x, y)` of a render of `view`.
+    fn pixel(view: &MandelbrotView, buffer: &[u8], x: usize, y: usize) -> [u8; 4] {
+        let at = 4 * (y * view.viewport.width as usize + x);
+        buffer[at..at + 4].try_into().expect("four channels")
+    }
+
+    #[test]
+    fn defaults_match_the_cpu_renderer() {
+        let view = MandelbrotView::new(24, 16);
+        let palette = Palette::curated(0).build();
+        let shading = cpu::Shading {
+            palette: &palette,
+            mode: ColorMode::EscapeTime,
+            transparent_interior: false,
+            background: None,
+            morph: 1.,
+            slow_tint: None,
+            lighting: None,
+            color_span: None,
+            log_polar: false,
+            tone: Tone::default(),
+        };
+        let expected = cpu::render_image(&view.viewport, 1000, 1, 0, None, &shading).into_raw();
+        let got = render(&view);
+        // the palette is baked again from the stops, so allow a level of rounding
+        assert!(got.iter().zip(&expected).all(|(a, b)| a.abs_diff(*b) <= 1));
+    }
+
+    #[test]
+    fn interior_takes_its_color() {
+        let mut view = MandelbrotView::new(9, 9);
+        view.set_scale(0.1);
+        view.set_interior_color([10, 20, 30, 255]);
+        assert_eq!(pixel(&view, &render(&view), 4, 4), [10, 20, 30, 255]);
+        view.set_interior_color([10, 20, 30, 0]);
+        assert_eq!(pixel(&view, &render(&view), 4, 4)[3], 0);
+    }
+
+    #[test]
+    fn a_whole_turn_of_phase_changes_nothing() {
+        let mut view = MandelbrotView::new(32, 24);
+        view.set_palette(Palette::curated(1));
+        view.set_frequency(3.);
+        let before = render(&view);
+        view.set_phase(1.);
+        assert_eq!(render(&view), before);
+        view.set_phase(0.5);
+        assert_ne!(render(&view), before);
+    }
+
+    #[test]
+    fn gamma_brightens_the_exterior() {
+        let mut view = MandelbrotView::new(32, 24);
+        let before = render(&view);
+        view.set_gamma(2.2);
+        let after = render(&view);
+        assert!(after.iter().zip(&before).all(|(a, b)| a >= b));
+        assert_ne!(after, before);
+    }
+
+    #[test]
+    fn buffer_must_fit() {
+        let view = MandelbrotView::new(4, 4);
+        assert!(view.render_to_buffer(&mut [0; 63]).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+// Mapping between framebuffer pixels and the complex plane.
+//
+// Screen coordinates are framebuffer pixels with the origin in the top-left
+// corner and y growing downwards (the way GLFW reports the cursor). The view
+// always shows `2 * scale` complex units vertically; the horizontal extent
+// follows the framebuffer aspect ratio, multiplied by `anisotropy` when the
+// user asks for independently chosen horizontal and vertical scales.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    /// Complex coordinate shown at the middle of the framebuffer.
+    pub center: (f64, f64),
+    /// Half of the visible vertical extent, in complex units.
+    pub scale: f64,
+    /// Counter-clockwise rotation of the view, in radians.
+    pub rotation: f64,
+    /// Horizontal complex units per pixel divided by the vertical ones.
+    /// 1 keeps pixels square.
+    pub anisotropy: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Everything the fragment shader needs to turn its `[-1, 1]` screen position
+/// into a point `c`: `c = offset + rotate(position * scale, rotation)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewUniforms {
+    pub offset: [f32; 2],
+    pub scale: [f32; 2],
+    /// `(cos, sin)` of the view rotation.
+    pub rotation: [f32; 2],
+    /// Size of the whole view in pixels, for mapping from `gl_FragCoord`.
+    pub resolution: [f32; 2],
+    /// Where the render target's bottom-left pixel sits in the whole view, in
+    /// GL pixel coordinates (y up). Zero unless rendering a tile.
+    pub pixel_offset: [f32; 2],
+}
+
+/// Longest image edge in pixels. The shader places tiles with `f32` pixel
+/// offsets, which hold every pixel center (a multiple of 0.5) exactly up to
+/// 2^23, so tiles of larger images could drift by a pixel against each other.
+pub const MAX_EDGE: u32 = 1 << 23;
+
+/// A rectangle of framebuffer pixels, top-left origin like screen coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    pub fn new(width: u32, height: u32) -> Viewport {
+        Viewport {
+            center: (0., 0.),
+            scale: 1.,
+            rotation: 0.,
+            anisotropy: 1.,
+            width: width.max(1),
+            height: height.max(1),
+        }
+    }
+
+    pub fn aspect(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    /// Half extents of the view along the screen axes, in complex units.
+    pub fn half_extents(&self) -> (f64, f64) {
+        (self.scale * self.aspect() * self.anisotropy, self.scale)
+    }
+
+    /// Sets the visible half extents explicitly. A missing value is derived
+    /// from the other one so that pixels stay square.
+    pub fn set_extents(&mut self, scale_x: Option<f64>, scale_y: Option<f64>) {
+        match (scale_x, scale_y) {
+            (Some(x), Some(y)) => {
+                self.scale = y;
+                self.anisotropy = x / (y * self.aspect());
+            }
+            (Some(x), None) => {
+                self.scale = x / self.aspect();
+                self.anisotropy = 1.;
+            }
+            (None, Some(y)) => {
+                self.scale = y;
+                self.anisotropy = 1.;
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Zoom factor relative to the default framing, whose vertical half extent is 1.
+    pub fn magnification(&self) -> f64 {
+        1. / self.scale
+    }
+
+    /// The scale whose magnification is the power of `base` nearest to the
+    /// current one (nearest in log space, so 10x and 100x split at ~31.6x),
+    /// along with that power.
+    pub fn snapped_scale(&self, base: f64) -> (f64, i32) {
+        let exponent = (self.magnification().ln() / base.ln()).round();
+        (base.powf(-exponent), exponent as i32)
+    }
+
+    /// Vertical size of one framebuffer pixel in complex units (the horizontal
+    /// size is this times `anisotropy`).
+    pub fn pixel_size(&self) -> f64 {
+        2. * self.scale / self.height as f64
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width.max(1);
+        self.height = height.max(1);
+    }
+
+    /// Moves the center to the initial framing without touching the framebuffer
+    /// size or the anisotropy.
+    pub fn reset(&mut self) {
+        *self = Viewport {
+            anisotropy: self.anisotropy,
+            ..Viewport::new(self.width, self.height)
+        };
+    }
+
+    pub fn screen_to_complex(&self, px: (f64, f64)) -> (f64, f64) {
+        // normalized device coordinates, y up
+        let ndc_x = px.0 / self.width as f64 * 2. - 1.;
+        let ndc_y = 1. - px.1 / self.height as f64 * 2.;
+        let (half_w, half_h) = self.half_extents();
+        let (x, y) = rotate((ndc_x * half_w, ndc_y * half_h), self.rotation);
+        (self.center.0 + x, self.center.1 + y)
+    }
+
+    pub fn complex_to_screen(&self, c: (f64, f64)) -> (f64, f64) {
+        let (x, y) = rotate((c.0 - self.center.0, c.1 - self.center.1), -self.rotation);
+        let (half_w, half_h) = self.half_extents();
+        let ndc_x = x / half_w;
+        let ndc_y = y / half_h;
+        (
+            (ndc_x + 1.) / 2. * self.width as f64,
+            (1. - ndc_y) / 2. * self.height as f64,
+        )
+    }
+
+    /// Pans along the screen axes. `dx`/`dy` are measured in units of `scale`,
+    /// so the same input moves the image by the same amount at any depth.
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        let (x, y) = rotate((dx * self.scale, dy * self.scale), self.rotation);
+        self.center.0 += x;
+        self.center.1 += y;
+    }
+
+    /// Multiplies the visible extent by `factor` (< 1 zooms in).
+    pub fn zoom_by(&mut self, factor: f64) {
+        self.scale *= factor;
+    }
+
+    /// Zooms by `factor` while keeping the point under `px` fixed on screen.
+    pub fn zoom_at(&mut self, px: (f64, f64), factor: f64) {
+        let before = self.screen_to_complex(px);
+        self.zoom_by(factor);
+        let after = self.screen_to_complex(px);
+        self.center.0 += before.0 - after.0;
+        self.center.1 += before.1 - after.1;
+    }
+
+    /// The part of the view under `rect`, as a view of its own with the
+    /// rect's size: same rotation and pixel shape, so every pixel of it lands
+    /// where it was in this one.
+    pub fn region(&self, rect: PixelRect) -> Viewport {
+        let middle = (rect.x as f64 + rect.width as f64 / 2., rect.y as f64 + rect.height as f64 / 2.);
+        Viewport {
+            center: self.screen_to_complex(middle),
+            scale: self.scale * rect.height as f64 / self.height as f64,
+            width: rect.width.max(1),
+            height: rect.height.max(1),
+            ..*self
+        }
+    }
+
+    /// Where the logarithmic view puts what `screen_to_complex` has at `point`:
+    /// the offset from the center, unrotated and divided by `scale`, is taken
+    /// as `u` and mapped to `scale * exp(pi * u)` as complex numbers. The
+    /// screen's height then goes once around the center and its width out by
+    /// a factor of e^pi per half height, so zooming in scrolls the set along.
+    pub fn log_polar(&self, point: (f64, f64)) -> (f64, f64) {
+        let (x, y) = rotate((point.0 - self.center.0, point.1 - self.center.1), -self.rotation);
+        let (u, v) = (std::f64::consts::PI * x / self.scale, std::f64::consts::PI * y / self.scale);
+        let radius = self.scale * u.exp();
+        let (x, y) = rotate((radius * v.cos(), radius * v.sin()), self.rotation);
+        (self.center.0 + x, self.center.1 + y)
+    }
+
+    pub fn rotate_by(&mut self, angle: f64) {
+        self.rotation = (self.rotation + angle).rem_euclid(std::f64::consts::TAU);
+    }
+
+    pub fn uniforms(&self) -> ViewUniforms {
+        let (half_w, half_h) = self.half_extents();
+        ViewUniforms {
+            offset: [self.center.0 as f32, self.center.1 as f32],
+            scale: [half_w as f32, half_h as f32],
+            rotation: [self.rotation.cos() as f32, self.rotation.sin() as f32],
+            resolution: [self.width as f32, self.height as f32],
+            pixel_offset: [0., 0.],
+        }
+    }
+
+    /// Uniforms for rendering just `rect` of this view into a target of the
+    /// rect's size. Every tile shares the view's offset and scale and only
+    /// shifts whole pixels, so each pixel maps to the same point of the plane
+    /// as in a single pass over the whole view and tiles meet without seams.
+    pub fn tile_uniforms(&self, rect: PixelRect) -> ViewUniforms {
+        debug_assert!(self.width.max(self.height) <= MAX_EDGE, "pixel offsets must stay exact in f32");
+        ViewUniforms {
+            pixel_offset: [rect.x as f32, (self.height - rect.y - rect.height) as f32],
+            ..self.uniforms()
+        }
+    }
+}
+
+fn rotate(p: (f64, f64), angle: f64) -> (f64, f64) {
+    let (sin, cos) = angle.sin_cos();
+    (p.0 * cos - p.1 * sin, p.0 * sin + p.1 * cos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu;
+    use crate::palette::{ColorMode, Palette, Tone};
+
+    fn close(a: (f64, f64), b: (f64, f64), tolerance: f64) -> bool {
+        (a.0 - b.0).abs() <= tolerance && (a.1 - b.1).abs() <= tolerance
+    }
+
+    /// What rounding the center to f64 can move a point by, in pixels.
+    fn rounding(view: &Viewport) -> f64 {
+        4. * f64::EPSILON * view.center.0.abs().max(view.center.1.abs()) / view.pixel_size()
+    }
+
+    /// Square, wide, tall, rotated, stretched and deep views.
+    fn views() -> Vec<Viewport> {
+        let mut views = Vec::new();
+        for (width, height) in [(600, 600), (1920, 1080), (480, 1000), (1, 1)] {
+            let mut view = Viewport::new(width, height);
+            views.push(view);
+            view.center = (-0.743643887037151, 0.131825904205330);
+            view.scale = 1e-9;
+            view.rotate_by(0.7);
+            views.push(view);
+            view.set_extents(Some(3e-9), Some(1e-9));
+            views.push(view);
+        }
+        views
+    }
+
+    #[test]
+    fn points_round_trip() {
+        for view in views() {
+            for px in [(0., 0.), (0.5, 0.5), (view.width as f64, view.height as f64), (123.25, 45.75), (-10., 2000.)] {
+                let back = view.complex_to_screen(view.screen_to_complex(px));
+                let tolerance = 1e-9 * (1. + px.0.abs().max(px.1.abs())) + rounding(&view);
+                assert!(close(back, px, tolerance), "{view:?}: {px:?} came back as {back:?}");
+            }
+            let c = view.center;
+            assert!(close(view.screen_to_complex(view.complex_to_screen(c)), c, view.pixel_size() * 1e-6));
+        }
+    }
+
+    #[test]
+    fn pixel_size_follows_resize() {
+        for mut view in views() {
+            for (width, height) in [(800, 600), (600, 800), (3, 7)] {
+                view.resize(width, height);
+                assert_eq!(view.pixel_size(), 2. * view.scale / height as f64);
+                // one pixel down is one pixel size along the view's vertical axis
+                let top = view.screen_to_complex((1., 1.));
+                let below = view.screen_to_complex((1., 2.));
+                let step = ((top.0 - below.0).hypot(top.1 - below.1) - view.pixel_size()).abs();
+                assert!(step <= view.pixel_size() * (1e-9 + rounding(&view)), "{view:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn half_extents_of_non_square_views() {
+        let wide = Viewport::new(1920, 1080);
+        assert_eq!(wide.half_extents(), (1920. / 1080., 1.));
+        let tall = Viewport { scale: 0.5, ..Viewport::new(500, 1000) };
+        assert_eq!(tall.half_extents(), (0.25, 0.5));
+        // the corners are where the extents say
+        assert!(close(wide.screen_to_complex((0., 0.)), (-1920. / 1080., 1.), 1e-12));
+        assert!(close(tall.screen_to_complex((500., 1000.)), (0.25, -0.5), 1e-12));
+
+        let mut stretched = Viewport::new(800, 400);
+        stretched.set_extents(Some(3.), Some(1.));
+        assert!(close(stretched.half_extents(), (3., 1.), 1e-12));
+        stretched.set_extents(Some(4.), None);
+        assert!(close(stretched.half_extents(), (4., 2.), 1e-12));
+    }
+
+    #[test]
+    fn tiles_stitch_to_a_single_pass() {
+        let palette = Palette::curated(1).build();
+        let shading = cpu::Shading {
+            palette: &palette,
+            mode: ColorMode::Blend,
+            transparent_interior: false,
+            background: None,
+            morph: 1.,
+            slow_tint: None,
+            lighting: None,
+            color_span: None,
+            log_polar: false,
+            tone: Tone::default(),
+        };
+        for view in [Viewport::new(101, 67), Viewport { center: (-0.75, 0.1), scale: 0.2, rotation: 0.3, ..Viewport::new(64, 90) }] {
+            let whole = cpu::render_image(&view, 200, 2, 7, None, &shading);
+            // uneven tiles, as the last ones of an export are
+            let (split_x, split_y) = (view.width / 2 + 3, view.height / 3);
+            let mut stitched = image::RgbaImage::new(view.width, view.height);
+            for (x, width) in [(0, split_x), (split_x, view.width - split_x)] {
+                for (y, height) in [(0, split_y), (split_y, view.height - split_y)] {
+                    let rect = PixelRect { x, y, width, height };
+                    let tile = cpu::render_tile(&view, rect, 200, 2, 7, None, &shading);
+                    image::imageops::replace(&mut stitched, &tile, x as i64, y as i64);
+                    // the GPU's tiles only shift whole pixels, from the bottom as GL counts rows
+                    let uniforms = view.tile_uniforms(rect);
+                    assert_eq!(uniforms.pixel_offset, [x as f32, (view.height - y - height) as f32]);
+                    assert_eq!(ViewUniforms { pixel_offset: [0.; 2], ..uniforms }, view.uniforms());
+                }
+            }
+            assert!(stitched == whole, "{view:?}");
+        }
+    }
+}
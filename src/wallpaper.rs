@@ -0,0 +1,279 @@
+// `--set-wallpaper`: renders a view at the primary monitor's resolution with
+// `--export-ssaa`, saves it to the cache directory and makes it the desktop
+// wallpaper. The view is the one the explorer was left at, or `--load`,
+// `--open`, `--state` or `--location`; with `--daily` it is the view of the day
+// instead, the same all day and different the next, so a daily cron job or
+// scheduled task of `--set-wallpaper --daily` keeps the desktop changing.
+//
+// How the wallpaper is set depends on the platform: SystemParametersInfo on
+// Windows, System Events through `osascript` on macOS, and on Linux
+// `gsettings` under GNOME and Cinnamon, `plasma-apply-wallpaperimage` under
+// KDE and `feh` anywhere else.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cli::{self, Options};
+use crate::cpu;
+use crate::headless::{self, EXIT_BAD_ARGS, EXIT_RENDER_FAILED};
+use crate::load;
+use crate::location;
+use crate::metadata::Fractal;
+use crate::palette::Palette;
+use crate::platform;
+use crate::rng::Rng;
+use crate::session::{self, Camera, Session};
+use crate::viewport::Viewport;
+
+/// Iterations of the view of the day; a point escaping after `EDGE_ESCAPE`
+/// of them is close enough to the set to have detail around it.
+const DAILY_ITERATIONS: i32 = 2500;
+const EDGE_ESCAPE: i32 = 200;
+
+/// Renders the wallpaper, sets it and returns the process exit status.
+pub fn run(options: &Options) -> i32 {
+    let (width, height) = match platform::monitor_size() {
+        Ok(size) => size,
+        Err(err) => {
+            eprintln!("--set-wallpaper: {err}");
+            return EXIT_RENDER_FAILED;
+        }
+    };
+    let session = match view(options) {
+        Ok(session) => session,
+        Err(err) => {
+            eprintln!("--set-wallpaper: {err}");
+            return EXIT_BAD_ARGS;
+        }
+    };
+    let Some(dir) = cache_dir() else {
+        eprintln!("--set-wallpaper: no cache directory (HOME is not set)");
+        return EXIT_RENDER_FAILED;
+    };
+    if let Err(err) = fs::create_dir_all(&dir) {
+        eprintln!("{}: {err}", dir.display());
+        return EXIT_RENDER_FAILED;
+    }
+    // a new name every time, as macOS doesn't reload a picture it already shows
+    let out = dir.join(format!("wallpaper-{}.png", now().as_secs()));
+
+    let mut args = cli::render_defaults(out.clone(), options.quality);
+    args.size = (width, height);
+    args.iter = session.iterations.max(1);
+    args.ssaa = options.export_ssaa;
+    args.color_mode = session.color_mode;
+    args.lighting = session.lighting;
+    args.color_span = session.color_span;
+    args.tone = session.tone;
+    if let Fractal::Julia { c } = session.fractal {
+        args.julia = Some(c.to_vec());
+    }
+    let viewport = match session.camera.view(width, height) {
+        Ok(viewport) => viewport,
+        Err(err) => {
+            eprintln!("--set-wallpaper: {err}");
+            return EXIT_BAD_ARGS;
+        }
+    };
+    println!("rendering a {width}x{height} wallpaper with the {} palette", session.palette.name);
+    let status = headless::render(viewport, &session.palette, &args, options);
+    if status != 0 {
+        return status;
+    }
+
+    match set(&out) {
+        Ok(backend) => {
+            println!("set {} as the wallpaper with {backend}", out.display());
+            remove_older(&dir, &out);
+            0
+        }
+        Err(err) => {
+            eprintln!("could not set the wallpaper, tried {err}; the image is in {}", out.display());
+            EXIT_RENDER_FAILED
+        }
+    }
+}
+
+/// What to render: the view of the day, the one asked for, or where the
+/// last session left off.
+fn view(options: &Options) -> Result<Session, String> {
+    if options.daily {
+        let day = now().as_secs() / (24 * 60 * 60);
+        return Ok(of_the_day(day));
+    }
+    if let Some(what) = &options.load {
+        return load::load(what)?.session();
+    }
+    if let Some(text) = &options.location {
+        return location::decode(text);
+    }
+    if let Some(path) = &options.open {
+        return load::file(path)?.session();
+    }
+    if let Some(path) = &options.state {
+        return session::load(path);
+    }
+    if let Some(session) = options.restored.as_ref().and_then(|restored| restored.session.clone()) {
+        return Ok(session);
+    }
+    let mut viewport = Viewport::new(1, 1);
+    viewport.set_extents(options.scale_x, options.scale_y);
+    Ok(Session { iterations: options.quality.iterations(), ..load::defaults(Camera::from_view(&viewport)) })
+}
+
+/// A point near the edge of the set, zoomed in 10x to 10000x, in one of the
+/// shipped palettes; which ones depends only on `day`.
+fn of_the_day(day: u64) -> Session {
+    let mut rng = Rng::new(day);
+    // the familiar seahorse valley, should the points all land far from the set
+    let mut center = (-0.745, 0.11);
+    for _ in 0..100_000 {
+        let c = (-2. + 2.5 * rng.next_f64(), -1.2 + 2.4 * rng.next_f64());
+        if cpu::escape_time(c, DAILY_ITERATIONS).is_some_and(|escape| escape >= EDGE_ESCAPE) {
+            center = c;
+            break;
+        }
+    }
+    let mut viewport = Viewport::new(1, 1);
+    viewport.center = center;
+    viewport.scale = 10f64.powf(-1. - 3. * rng.next_f64());
+    Session {
+        iterations: DAILY_ITERATIONS,
+        palette: Palette::curated(day as usize),
+        ..load::defaults(Camera::from_view(&viewport))
+    }
+}
+
+fn now() -> std::time::Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// `mandelplotter` in `%LOCALAPPDATA%`, `~/Library/Caches` or
+/// `$XDG_CACHE_HOME` (`~/.cache`), whichever the platform uses.
+fn cache_dir() -> Option<PathBuf> {
+    let home = || env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from);
+    let dir = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Caches"))
+    } else {
+        env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from).or_else(|| Some(home()?.join(".cache")))
+    };
+    Some(dir?.join("mandelplotter"))
+}
+
+/// Removes the wallpapers rendered before `current`, now that it's set.
+fn remove_older(dir: &Path, current: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if path != current && name.starts_with("wallpaper-") && name.ends_with(".png") {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+/// Runs `program` and fails with its stderr if it does.
+fn command(program: &str, args: &[impl AsRef<OsStr>]) -> Result<(), String> {
+    let output = Command::new(program).args(args).output().map_err(|err| format!("{program}: {err}"))?;
+    if !output.status.success() {
+        return Err(format!("{program}: {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// Makes `path` the desktop wallpaper, returning what did it; the error
+/// says what was tried and why it didn't work.
+#[cfg(windows)]
+fn set(path: &Path) -> Result<&'static str, String> {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn SystemParametersInfoW(action: u32, param: u32, value: *mut c_void, flags: u32) -> i32;
+    }
+    const SPI_SETDESKWALLPAPER: u32 = 0x0014;
+    const SPIF_UPDATEINIFILE: u32 = 0x01;
+    const SPIF_SENDCHANGE: u32 = 0x02;
+
+    let mut wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    let done = unsafe { SystemParametersInfoW(SPI_SETDESKWALLPAPER, 0, wide.as_mut_ptr().cast(), SPIF_UPDATEINIFILE | SPIF_SENDCHANGE) };
+    if done == 0 {
+        return Err(format!("SystemParametersInfo: {}", std::io::Error::last_os_error()));
+    }
+    Ok("SystemParametersInfo")
+}
+
+#[cfg(target_os = "macos")]
+fn set(path: &Path) -> Result<&'static str, String> {
+    let path = path.display().to_string().replace('\\', "\\\\").replace('"', "\\\"");
+    let script = format!(r#"tell application "System Events" to tell every desktop to set picture to "{path}""#);
+    command("osascript", &["-e", &script])?;
+    Ok("osascript")
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set(path: &Path) -> Result<&'static str, String> {
+    let desktop = env::var("XDG_CURRENT_DESKTOP").unwrap_or_default().to_ascii_lowercase();
+    if desktop.contains("cinnamon") {
+        command("gsettings", &["set", "org.cinnamon.desktop.background", "picture-uri", &file_uri(path)])?;
+        return Ok("gsettings");
+    }
+    if ["gnome", "unity", "budgie", "pantheon"].iter().any(|name| desktop.contains(name)) {
+        let uri = file_uri(path);
+        command("gsettings", &["set", "org.gnome.desktop.background", "picture-uri", &uri])?;
+        // the dark style has its own, in GNOME 42 and later
+        let _ = command("gsettings", &["set", "org.gnome.desktop.background", "picture-uri-dark", &uri]);
+        return Ok("gsettings");
+    }
+    if desktop.contains("kde") {
+        command("plasma-apply-wallpaperimage", &[path])?;
+        return Ok("plasma-apply-wallpaperimage");
+    }
+    command("feh", &[OsStr::new("--bg-fill"), path.as_os_str()]).map_err(|err| format!("{err} (no GNOME, Cinnamon or KDE in XDG_CURRENT_DESKTOP)"))?;
+    Ok("feh")
+}
+
+/// `path` as a `file://` URI: every byte but the unreserved characters and
+/// slashes percent-encoded, so a space, `#` or `%` in it (or bytes that
+/// aren't UTF-8) point at the same file.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn file_uri(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri += &format!("%{byte:02X}");
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn file_uris_are_percent_encoded() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        use std::path::Path;
+
+        use super::file_uri;
+
+        assert_eq!(file_uri(Path::new("/home/ana/.cache/mandelplotter/wallpaper.png")), "file:///home/ana/.cache/mandelplotter/wallpaper.png");
+        assert_eq!(file_uri(Path::new("/home/ana maria/#1 100%.png")), "file:///home/ana%20maria/%231%20100%25.png");
+        assert_eq!(file_uri(Path::new("/tmp/fractal-é.png")), "file:///tmp/fractal-%C3%A9.png");
+        // not UTF-8, which `display` would have replaced
+        assert_eq!(file_uri(Path::new(OsStr::from_bytes(b"/tmp/\xff.png"))), "file:///tmp/%FF.png");
+    }
+}
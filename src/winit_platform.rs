@@ -0,0 +1,486 @@
+// The explorer's window through winit, with the GL context from glutin: the
+// pure Rust backend (`backend-winit`), for building without a C toolchain.
+//
+// winit wants to own the main loop; here it is pumped once a frame instead,
+// or by the event thread waiting for events, so the loop in `main` stays the
+// same as with GLFW. Once the context is on the render thread, that thread
+// resizes the surface along with the window. winit
+// has no key state to ask for, so the keys held are kept from the events,
+// and dropped when the window loses focus, as their releases go elsewhere.
+// The clipboard is arboard's, as winit has none; it is kept open, as on X11
+// what was copied is only there while it is.
+
+use std::collections::HashSet;
+use std::ffi::{c_void, CString};
+use std::num::NonZeroU32;
+use std::ptr;
+use std::time::Duration;
+
+use glutin::config::{Config, ConfigTemplateBuilder, GlConfig};
+use glutin::context::{
+    ContextApi, ContextAttributesBuilder, GlProfile, NotCurrentContext, NotCurrentGlContext, PossiblyCurrentContext, PossiblyCurrentGlContext, Version,
+};
+use glutin::display::{GetGlDisplay, GlDisplay};
+use glutin::surface::{GlSurface, Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface};
+use glutin_winit::{DisplayBuilder, GlWindow};
+use log::warn;
+use raw_window_handle::HasWindowHandle;
+use winit::application::ApplicationHandler;
+use winit::dpi::{LogicalPosition, LogicalSize, PhysicalSize};
+use winit::event::{ElementState, KeyEvent, MouseScrollDelta, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
+use winit::platform::pump_events::{EventLoopExtPumpEvents, PumpStatus};
+use winit::window::{Fullscreen, WindowAttributes, WindowId, WindowLevel};
+
+use crate::error::StartError;
+use crate::platform::{self, Action, Detached, Event, Key, Modifiers, MouseButton, Window, WindowOptions};
+
+/// What a line of a mouse wheel is in screen coordinates, for touchpads that
+/// scroll by pixels.
+const PIXELS_PER_LINE: f64 = 20.;
+
+pub struct WinitWindow {
+    // the surface and context go before the window they draw into; `None`
+    // once detached
+    gl: Option<Gl>,
+    window: winit::window::Window,
+    event_loop: EventLoop<()>,
+    input: Input,
+    clipboard: Option<arboard::Clipboard>,
+    /// Whether the config picked shows the desktop through.
+    transparent: bool,
+}
+
+struct Gl {
+    surface: Surface<WindowSurface>,
+    context: PossiblyCurrentContext,
+}
+
+/// The context for another thread, not current anywhere.
+struct DetachedGl {
+    surface: Surface<WindowSurface>,
+    context: NotCurrentContext,
+}
+
+/// What the events so far leave behind.
+#[derive(Default)]
+struct Input {
+    /// Not yet handed out by `poll_events`.
+    events: Vec<Event>,
+    held: HashSet<Key>,
+    modifiers: Modifiers,
+    cursor: (f64, f64),
+    should_close: bool,
+}
+
+pub fn open(options: &WindowOptions) -> Result<WinitWindow, StartError> {
+    let (width, height) = options.size;
+    let mut attributes = WindowAttributes::default()
+        .with_title(options.title.as_str())
+        .with_inner_size(LogicalSize::new(width, height))
+        .with_fullscreen(options.fullscreen.then_some(Fullscreen::Borderless(None)))
+        .with_transparent(options.transparent)
+        .with_window_level(if options.always_on_top { WindowLevel::AlwaysOnTop } else { WindowLevel::Normal });
+    if let Some((x, y)) = options.position.filter(|_| !options.fullscreen) {
+        attributes = attributes.with_position(LogicalPosition::new(x, y));
+    }
+    let window = create(attributes, options.msaa, options.vsync, options.gles).map_err(StartError::Winit)?;
+    if options.transparent && !window.transparent {
+        warn!("the desktop can't show through the window here, it stays opaque");
+    }
+    Ok(window)
+}
+
+/// An invisible window, for a context to render offscreen in.
+pub fn hidden() -> Result<WinitWindow, String> {
+    let attributes = WindowAttributes::default().with_visible(false).with_inner_size(PhysicalSize::new(1, 1));
+    create(attributes, 0, false, false)
+}
+
+/// `gles` tries OpenGL ES 3.0 before 3.3 core rather than after it.
+fn create(attributes: WindowAttributes, msaa: u32, vsync: bool, gles: bool) -> Result<WinitWindow, String> {
+    let event_loop = EventLoop::new().map_err(|err| err.to_string())?;
+    event_loop.set_control_flow(ControlFlow::Poll);
+    let transparent = attributes.transparent;
+    let (window, config) = DisplayBuilder::new()
+        .with_window_attributes(Some(attributes))
+        .build(&event_loop, ConfigTemplateBuilder::new().with_transparency(transparent), |configs| closest(configs, msaa, transparent))
+        .map_err(|err| err.to_string())?;
+    let transparent = transparent && config.supports_transparency() == Some(true);
+    let window = window.ok_or("no window was created")?;
+
+    let display = config.display();
+    let handle = window.window_handle().map_err(|err| err.to_string())?.as_raw();
+    let core = ContextAttributesBuilder::new()
+        .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
+        .with_profile(GlProfile::Core)
+        .build(Some(handle));
+    // runs the shaders as well as 3.3 core does
+    let es = ContextAttributesBuilder::new().with_context_api(ContextApi::Gles(Some(Version::new(3, 0)))).build(Some(handle));
+    // neither, take whatever the driver offers and render on the CPU
+    let any = ContextAttributesBuilder::new().build(Some(handle));
+    let attempts = if gles { [es, core, any] } else { [core, es, any] };
+    let mut failure = None;
+    let context = attempts
+        .iter()
+        .find_map(|attributes| unsafe { display.create_context(&config, attributes) }.map_err(|err| failure = Some(err)).ok())
+        .ok_or_else(|| format!("could not create an OpenGL context: {}", failure.expect("something was tried")))?;
+    let surface_attributes = window.build_surface_attributes(SurfaceAttributesBuilder::new()).map_err(|err| err.to_string())?;
+    let surface = unsafe { display.create_window_surface(&config, &surface_attributes) }.map_err(|err| err.to_string())?;
+    let context = context.make_current(&surface).map_err(|err| err.to_string())?;
+
+    let interval = if vsync { SwapInterval::Wait(NonZeroU32::MIN) } else { SwapInterval::DontWait };
+    if let Err(err) = surface.set_swap_interval(&context, interval) {
+        warn!("could not set the swap interval: {err}");
+    }
+    Ok(WinitWindow { gl: Some(Gl { surface, context }), window, event_loop, input: Input::default(), clipboard: None, transparent })
+}
+
+/// The config with the most samples up to `msaa`, or the fewest past it if
+/// none has so few, of those the desktop shows through if `transparent` asks
+/// for one and there are any; drivers list them in no particular order.
+fn closest(configs: Box<dyn Iterator<Item = Config> + '_>, msaa: u32, transparent: bool) -> Config {
+    configs
+        .max_by_key(|config| {
+            let see_through = !transparent || config.supports_transparency() == Some(true);
+            let samples = config.num_samples() as u32;
+            if samples <= msaa.max(1) {
+                (see_through, true, samples as i64)
+            } else {
+                (see_through, false, -(samples as i64))
+            }
+        })
+        .expect("glutin fails before offering no configs")
+}
+
+/// The primary monitor's size in pixels, or that of the first one there is
+/// where there is no primary (Wayland).
+pub fn monitor_size() -> Result<(u32, u32), String> {
+    #[derive(Default)]
+    struct Probe(Option<PhysicalSize<u32>>);
+
+    impl ApplicationHandler for Probe {
+        fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+            let monitor = event_loop.primary_monitor().or_else(|| event_loop.available_monitors().next());
+            self.0 = monitor.map(|monitor| monitor.size());
+        }
+
+        fn window_event(&mut self, _: &ActiveEventLoop, _: WindowId, _: WindowEvent) {}
+    }
+
+    let mut event_loop = EventLoop::new().map_err(|err| err.to_string())?;
+    let mut probe = Probe::default();
+    event_loop.pump_app_events(Some(Duration::ZERO), &mut probe);
+    let size = probe.0.ok_or("no monitor to size the wallpaper for")?;
+    Ok((size.width, size.height))
+}
+
+impl Window for WinitWindow {
+    fn poll_events(&mut self) -> Vec<Event> {
+        self.wait_events(Duration::ZERO)
+    }
+
+    fn wait_events(&mut self, timeout: Duration) -> Vec<Event> {
+        let mut pump = Pump { window: &self.window, gl: self.gl.as_ref(), input: &mut self.input };
+        if let PumpStatus::Exit(_) = self.event_loop.pump_app_events(Some(timeout), &mut pump) {
+            self.input.should_close = true;
+        }
+        std::mem::take(&mut self.input.events)
+    }
+
+    fn is_held(&self, key: Key) -> bool {
+        self.input.held.contains(&key)
+    }
+
+    fn held_keys(&self) -> Vec<Key> {
+        self.input.held.iter().copied().collect()
+    }
+
+    fn cursor_pos(&self) -> (f64, f64) {
+        self.input.cursor
+    }
+
+    fn size(&self) -> (i32, i32) {
+        let size = self.window.inner_size().to_logical::<f64>(self.window.scale_factor());
+        (size.width.round() as i32, size.height.round() as i32)
+    }
+
+    fn position(&self) -> (i32, i32) {
+        // Wayland doesn't tell
+        self.window.outer_position().map_or((0, 0), |position| {
+            let position = position.to_logical::<f64>(self.window.scale_factor());
+            (position.x.round() as i32, position.y.round() as i32)
+        })
+    }
+
+    fn framebuffer_size(&self) -> (i32, i32) {
+        let size = self.window.inner_size();
+        (size.width as i32, size.height as i32)
+    }
+
+    fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    fn should_close(&self) -> bool {
+        self.input.should_close
+    }
+
+    fn set_should_close(&mut self, close: bool) {
+        self.input.should_close = close;
+    }
+
+    fn swap_buffers(&mut self) {
+        if let Some(gl) = &self.gl {
+            gl.swap_buffers();
+        }
+    }
+
+    fn clipboard_text(&mut self) -> Option<String> {
+        self.clipboard().ok()?.get_text().ok()
+    }
+
+    fn set_clipboard_text(&mut self, text: &str) {
+        if let Err(err) = self.clipboard().and_then(|clipboard| clipboard.set_text(text)) {
+            warn!("could not copy to the clipboard: {err}");
+        }
+    }
+
+    fn proc_address(&mut self, name: &str) -> *const c_void {
+        self.gl.as_ref().map_or(ptr::null(), |gl| gl.proc_address(name))
+    }
+
+    fn context_version(&self) -> (u32, u32) {
+        context_version()
+    }
+
+    fn detach(&mut self) -> Result<Box<dyn Detached>, String> {
+        let Gl { surface, context } = self.gl.take().ok_or("the context is already detached")?;
+        let context = context.make_not_current().map_err(|err| err.to_string())?;
+        Ok(Box::new(DetachedGl { surface, context }))
+    }
+}
+
+impl Gl {
+    fn swap_buffers(&self) {
+        if let Err(err) = self.surface.swap_buffers(&self.context) {
+            warn!("could not swap buffers: {err}");
+        }
+    }
+
+    fn proc_address(&self, name: &str) -> *const c_void {
+        CString::new(name).map_or(ptr::null(), |name| self.context.display().get_proc_address(&name))
+    }
+}
+
+impl Detached for DetachedGl {
+    fn make_current(self: Box<Self>) -> Result<Box<dyn platform::Surface>, String> {
+        let DetachedGl { surface, context } = *self;
+        let context = context.make_current(&surface).map_err(|err| err.to_string())?;
+        Ok(Box::new(Gl { surface, context }))
+    }
+}
+
+impl platform::Surface for Gl {
+    fn swap_buffers(&mut self) {
+        Gl::swap_buffers(self);
+    }
+
+    fn proc_address(&mut self, name: &str) -> *const c_void {
+        Gl::proc_address(self, name)
+    }
+
+    fn context_version(&self) -> (u32, u32) {
+        context_version()
+    }
+
+    fn resize(&mut self, width: i32, height: i32) {
+        if let (Some(width), Some(height)) = (NonZeroU32::new(width.max(0) as u32), NonZeroU32::new(height.max(0) as u32)) {
+            self.surface.resize(&self.context, width, height);
+        }
+    }
+}
+
+/// Of the current context, as probed.
+fn context_version() -> (u32, u32) {
+    mandelplotter::caps::GpuCapabilities::current().version
+}
+
+impl WinitWindow {
+    fn clipboard(&mut self) -> Result<&mut arboard::Clipboard, arboard::Error> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(arboard::Clipboard::new()?);
+        }
+        Ok(self.clipboard.as_mut().expect("just opened"))
+    }
+}
+
+/// Takes the events of one pump into `input`.
+struct Pump<'a> {
+    window: &'a winit::window::Window,
+    /// Unless it is on the render thread.
+    gl: Option<&'a Gl>,
+    input: &'a mut Input,
+}
+
+impl ApplicationHandler for Pump<'_> {
+    // the window is made before the first pump
+    fn resumed(&mut self, _: &ActiveEventLoop) {}
+
+    fn window_event(&mut self, _: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let scale = self.window.scale_factor();
+        let input = &mut *self.input;
+        match event {
+            WindowEvent::CloseRequested => input.should_close = true,
+            WindowEvent::Resized(size) => {
+                if let Some(gl) = self.gl {
+                    self.window.resize_surface(&gl.surface, &gl.context);
+                }
+                input.events.push(Event::FramebufferSize(size.width as i32, size.height as i32));
+            }
+            WindowEvent::Focused(false) => input.held.clear(),
+            WindowEvent::ModifiersChanged(modifiers) => input.modifiers = modifiers_of(modifiers.state()),
+            WindowEvent::KeyboardInput { event, .. } => input.key(event),
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = position.to_logical::<f64>(scale);
+                input.cursor = (position.x, position.y);
+                input.events.push(Event::CursorPos(position.x, position.y));
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    winit::event::MouseButton::Left => MouseButton::Left,
+                    winit::event::MouseButton::Right => MouseButton::Right,
+                    winit::event::MouseButton::Middle => MouseButton::Middle,
+                    _ => return,
+                };
+                let action = if state == ElementState::Pressed { Action::Press } else { Action::Release };
+                input.events.push(Event::MouseButton(button, action, input.modifiers));
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (x as f64, y as f64),
+                    MouseScrollDelta::PixelDelta(pixels) => {
+                        let moved = pixels.to_logical::<f64>(scale);
+                        (moved.x / PIXELS_PER_LINE, moved.y / PIXELS_PER_LINE)
+                    }
+                };
+                input.events.push(Event::Scroll(x, y));
+            }
+            WindowEvent::DroppedFile(path) => input.events.push(Event::FileDrop(vec![path])),
+            _ => {}
+        }
+    }
+}
+
+impl Input {
+    /// The key as GLFW would send it, then the text it typed.
+    fn key(&mut self, event: KeyEvent) {
+        if let Some(key) = match event.physical_key {
+            PhysicalKey::Code(code) => key_of(code),
+            PhysicalKey::Unidentified(_) => None,
+        } {
+            let action = match (event.state, event.repeat) {
+                (ElementState::Released, _) => Action::Release,
+                (ElementState::Pressed, true) => Action::Repeat,
+                (ElementState::Pressed, false) => Action::Press,
+            };
+            if action == Action::Release {
+                self.held.remove(&key);
+            } else {
+                self.held.insert(key);
+            }
+            self.events.push(Event::Key(key, action, self.modifiers));
+        }
+        if event.state == ElementState::Pressed {
+            let text = event.text.iter().flat_map(|text| text.chars()).filter(|c| !c.is_control());
+            self.events.extend(text.map(Event::Char));
+        }
+    }
+}
+
+fn modifiers_of(state: ModifiersState) -> Modifiers {
+    [
+        (state.shift_key(), Modifiers::SHIFT),
+        (state.control_key(), Modifiers::CONTROL),
+        (state.alt_key(), Modifiers::ALT),
+        (state.super_key(), Modifiers::SUPER),
+    ]
+    .into_iter()
+    .filter(|&(held, _)| held)
+    .fold(Modifiers::default(), |held, (_, modifier)| held | modifier)
+}
+
+fn key_of(code: KeyCode) -> Option<Key> {
+    Some(match code {
+        KeyCode::KeyA => Key::A,
+        KeyCode::KeyB => Key::B,
+        KeyCode::KeyC => Key::C,
+        KeyCode::KeyD => Key::D,
+        KeyCode::KeyE => Key::E,
+        KeyCode::KeyF => Key::F,
+        KeyCode::KeyG => Key::G,
+        KeyCode::KeyH => Key::H,
+        KeyCode::KeyI => Key::I,
+        KeyCode::KeyJ => Key::J,
+        KeyCode::KeyK => Key::K,
+        KeyCode::KeyL => Key::L,
+        KeyCode::KeyM => Key::M,
+        KeyCode::KeyN => Key::N,
+        KeyCode::KeyO => Key::O,
+        KeyCode::KeyP => Key::P,
+        KeyCode::KeyQ => Key::Q,
+        KeyCode::KeyR => Key::R,
+        KeyCode::KeyS => Key::S,
+        KeyCode::KeyT => Key::T,
+        KeyCode::KeyU => Key::U,
+        KeyCode::KeyV => Key::V,
+        KeyCode::KeyW => Key::W,
+        KeyCode::KeyX => Key::X,
+        KeyCode::KeyY => Key::Y,
+        KeyCode::KeyZ => Key::Z,
+        KeyCode::F1 => Key::F1,
+        KeyCode::F2 => Key::F2,
+        KeyCode::F3 => Key::F3,
+        KeyCode::F4 => Key::F4,
+        KeyCode::F5 => Key::F5,
+        KeyCode::F6 => Key::F6,
+        KeyCode::F7 => Key::F7,
+        KeyCode::F8 => Key::F8,
+        KeyCode::F9 => Key::F9,
+        KeyCode::F10 => Key::F10,
+        KeyCode::F11 => Key::F11,
+        KeyCode::F12 => Key::F12,
+        KeyCode::Space => Key::Space,
+        KeyCode::Comma => Key::Comma,
+        KeyCode::Period => Key::Period,
+        KeyCode::BracketLeft => Key::LeftBracket,
+        KeyCode::BracketRight => Key::RightBracket,
+        KeyCode::Backslash => Key::Backslash,
+        KeyCode::Escape => Key::Escape,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::NumpadEnter => Key::KpEnter,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::ArrowLeft => Key::Left,
+        KeyCode::ArrowRight => Key::Right,
+        KeyCode::ArrowUp => Key::Up,
+        KeyCode::ArrowDown => Key::Down,
+        KeyCode::ShiftLeft => Key::LeftShift,
+        KeyCode::ShiftRight => Key::RightShift,
+        KeyCode::ControlLeft => Key::LeftControl,
+        KeyCode::ControlRight => Key::RightControl,
+        KeyCode::AltLeft => Key::LeftAlt,
+        KeyCode::AltRight => Key::RightAlt,
+        KeyCode::SuperLeft => Key::LeftSuper,
+        KeyCode::SuperRight => Key::RightSuper,
+        _ => return None,
+    })
+}
@@ -0,0 +1,170 @@
+// The `render` subcommand, `--batch` and `--export-palette` as a user runs
+// them: the binary, headless, on the CPU.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A path of its own in the temporary directory, for each test.
+fn temp(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("mandelplotter-render-{}-{name}.png", std::process::id()))
+}
+
+/// Runs `render` with `args` into `out` and opens the PNG it wrote.
+fn render_png(out: &PathBuf, args: &[&str]) -> image::DynamicImage {
+    let status = Command::new(env!("CARGO_BIN_EXE_Mandelplotter1"))
+        .arg("render")
+        .args(args)
+        .arg("--out")
+        .arg(out)
+        .status()
+        .expect("the binary runs");
+    assert!(status.success(), "render exited with {status}");
+    let image = image::open(out).expect("a PNG");
+    std::fs::remove_file(out).ok();
+    image
+}
+
+/// The same, at 8 bits per channel.
+fn render(out: &PathBuf, args: &[&str]) -> image::RgbaImage {
+    render_png(out, args).to_rgba8()
+}
+
+#[test]
+fn writes_a_png_of_the_requested_size() {
+    let image = render(&temp("size"), &["--size", "64x48", "--iter", "200"]);
+    assert_eq!(image.dimensions(), (64, 48));
+    // the origin is inside the set, in white; the corners escape
+    assert_eq!(image.get_pixel(32, 24).0, [255; 4]);
+    for (x, y) in [(0, 0), (63, 0), (0, 47), (63, 47)] {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        assert!(r < 128 && g < 128 && b < 128 && a == 255, "corner ({x}, {y}) is {:?}", [r, g, b, a]);
+    }
+}
+
+#[test]
+fn renders_are_repeatable() {
+    let args = ["--size", "40x30", "--center", "-0.75", "0.1", "--zoom", "0.05", "--iter", "300"];
+    let first = render(&temp("first"), &args);
+    let second = render(&temp("second"), &args);
+    assert_eq!(first, second);
+    // a deeper spot, and not a flat image
+    assert!(first.pixels().any(|p| p != first.get_pixel(0, 0)));
+}
+
+#[test]
+fn sixteen_bit_renders_have_finer_levels() {
+    // blend shades smoothly, with no bands to hold the count of levels down
+    let args = ["--size", "64x48", "--center", "-0.75", "0.1", "--zoom", "0.05", "--color-mode", "blend", "--bit-depth", "16"];
+    let image::DynamicImage::ImageRgba16(image) = render_png(&temp("sixteen"), &args) else {
+        panic!("not a 16-bit RGBA PNG");
+    };
+    let levels: std::collections::HashSet<u16> = image.pixels().map(|p| p.0[0]).collect();
+    // 8 bits can't hold more than 256
+    assert!(levels.len() > 256, "{} levels of red", levels.len());
+}
+
+#[test]
+fn batch_jobs_keep_the_tone_of_their_session() {
+    let dir = std::env::temp_dir();
+    let (plain, toned) = (temp("batch-plain"), temp("batch-toned"));
+    let session = |out: &PathBuf, tone: &str| {
+        let camera = r#"{"center": ["-0.75", "0.1"], "scale": "0.05"}"#;
+        format!(r#"{{"location": {{"version": 1, "camera": {camera}, "iterations": 200{tone}}}, "out": {:?}, "size": "40x30"}}"#, out)
+    };
+    let jobs = dir.join(format!("mandelplotter-render-{}-jobs.json", std::process::id()));
+    let toned_job = session(&toned, r#", "tone": {"brightness": 0.25, "contrast": 1}"#);
+    std::fs::write(&jobs, format!("[{}, {toned_job}]", session(&plain, ""))).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_Mandelplotter1"))
+        .args(["--fresh", "--batch"])
+        .arg(&jobs)
+        .status()
+        .expect("the binary runs");
+    std::fs::remove_file(&jobs).ok();
+    std::fs::remove_file(jobs.with_extension("summary.json")).ok();
+    assert!(status.success(), "the batch exited with {status}");
+    let open = |out: &PathBuf| {
+        let image = image::open(out).expect("a PNG").to_rgba8();
+        std::fs::remove_file(out).ok();
+        image
+    };
+    let (plain, toned) = (open(&plain), open(&toned));
+    // brighter everywhere the plain render isn't already white
+    for (a, b) in plain.pixels().zip(toned.pixels()) {
+        assert!(a.0[..3].iter().zip(&b.0[..3]).all(|(a, b)| a == b && *a == 255 || a < b), "{a:?} against {b:?}");
+    }
+}
+
+#[test]
+fn contours_of_the_whole_set_close_round_it() {
+    let out = std::env::temp_dir().join(format!("mandelplotter-render-{}-contours.svg", std::process::id()));
+    let status = Command::new(env!("CARGO_BIN_EXE_Mandelplotter1"))
+        .args(["render", "--size", "120x90", "--center", "-0.6", "0", "--zoom", "1.5", "--iter", "100", "--contour-levels", "3,100"])
+        .arg("--export-contours")
+        .arg(&out)
+        .status()
+        .expect("the binary runs");
+    assert!(status.success(), "render exited with {status}");
+    let svg = std::fs::read_to_string(&out).expect("an SVG");
+    std::fs::remove_file(&out).ok();
+    assert!(svg.starts_with("<?xml") && svg.trim_end().ends_with("</svg>"));
+    assert_eq!(svg.matches("<g ").count(), svg.matches("</g>").count());
+    // the whole set is in view, so its outline at `--iter` comes back round to where it started
+    let outline = svg.split(r#"<g id="level-100""#).nth(1).expect("a group for the outline");
+    let paths: Vec<&str> = outline.lines().filter(|line| line.trim_start().starts_with("<path")).collect();
+    assert!(!paths.is_empty());
+    assert!(paths.iter().all(|path| path.ends_with(r#" Z"/>"#)), "{outline}");
+}
+
+#[test]
+fn the_exported_palette_is_the_one_given() {
+    let palette_file = std::env::temp_dir().join(format!("mandelplotter-render-{}-palette.json", std::process::id()));
+    let palette = mandelplotter::palette::Palette::curated(1);
+    palette.save(&palette_file).expect("written");
+    let out = temp("palette-strip");
+    let status = Command::new(env!("CARGO_BIN_EXE_Mandelplotter1"))
+        .arg("--palette")
+        .arg(&palette_file)
+        .arg("--export-palette")
+        .arg(&out)
+        .status()
+        .expect("the binary runs");
+    std::fs::remove_file(&palette_file).ok();
+    assert!(status.success(), "--export-palette exited with {status}");
+    let strip = image::open(&out).expect("a PNG").to_rgba8();
+    std::fs::remove_file(&out).ok();
+    let texels = palette.build().texels;
+    assert_eq!(strip.width() as usize, texels.len());
+    assert!(strip.enumerate_pixels().all(|(x, _, pixel)| pixel.0 == texels[x as usize]));
+}
+
+#[test]
+#[ignore = "needs a GL context; run with --ignored on a machine with one"]
+fn odd_widths_read_back_unskewed() {
+    // at an odd width, rows read back padded or with a stale row length shear the image
+    let args = ["--size", "601x41", "--center", "-0.75", "0.1", "--zoom", "0.05", "--iter", "200"];
+    let gpu = render(&temp("gpu"), &[&["--gpu"][..], &args].concat());
+    let cpu = render(&temp("cpu"), &args);
+    assert_eq!(gpu.dimensions(), (601, 41));
+    // f32 on the GPU against f64 on the CPU differ near the boundary only
+    let close = gpu.pixels().zip(cpu.pixels()).filter(|(a, b)| a.0.iter().zip(b.0).all(|(x, y)| x.abs_diff(y) <= 8)).count();
+    assert!(close as f64 >= 0.9 * (601 * 41) as f64, "{close} of {} pixels match", 601 * 41);
+    // and each row matches where it is, not where a sheared row would be
+    for row in [0, 20, 40] {
+        let matching = (0..601).filter(|&x| gpu.get_pixel(x, row).0.iter().zip(cpu.get_pixel(x, row).0).all(|(a, b)| a.abs_diff(b) <= 8)).count();
+        assert!(matching >= 500, "row {row}: {matching} of 601 pixels match");
+    }
+}
+
+#[test]
+#[ignore = "needs a GL context; run with --ignored on a machine with one"]
+fn gpu_tiles_meet_without_a_seam() {
+    // past 1024 pixels the GPU renders in tiles, each placed by its pixel
+    // offset; the view puts fine detail at the seam, where columns differ
+    let args = ["--size", "1100x24", "--center", "-1.1386", "0.1318", "--zoom", "0.01", "--iter", "200"];
+    let gpu = render(&temp("gpu-tiles"), &[&["--gpu"][..], &args].concat());
+    let cpu = render(&temp("cpu-tiles"), &args);
+    let matches = |x: u32| (0..24).filter(|&y| gpu.get_pixel(x, y).0.iter().zip(cpu.get_pixel(x, y).0).all(|(a, b)| a.abs_diff(b) <= 8)).count();
+    // a tile off by a pixel would match its neighbour's column on the CPU instead
+    let seam: usize = (1016..1032).map(matches).sum();
+    assert!(seam >= 16 * 24 * 9 / 10, "{seam} of {} pixels by the seam match", 16 * 24);
+}
@@ -0,0 +1,38 @@
+// How the binary fails when it can't start: a message on stderr saying what
+// failed, and 2 for what was asked being wrong or 1 for the rest, before it
+// ever needs a display.
+
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_Mandelplotter1")).args(args).output().expect("the binary runs")
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn a_missing_shader_directory_is_a_bad_argument() {
+    let missing = std::env::temp_dir().join("mandelplotter-start-no-such-shaders");
+    let output = run(&["--shader-dir", missing.to_str().expect("UTF-8")]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains(&format!("--shader-dir {}: not a directory", missing.display())), "{}", stderr(&output));
+}
+
+#[test]
+fn a_view_that_doesnt_load_is_a_bad_argument() {
+    let missing = std::env::temp_dir().join("mandelplotter-start-no-such-view.kfr");
+    let output = run(&["--load", missing.to_str().expect("UTF-8")]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains(&format!("--load {}: ", missing.display())), "{}", stderr(&output));
+}
+
+#[test]
+fn a_file_that_cant_be_written_fails() {
+    let out = std::env::temp_dir().join("mandelplotter-start-no-such-dir").join("palette.png");
+    let output = run(&["--export-palette", out.to_str().expect("UTF-8")]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains(&format!("failed to write {}: ", out.display())), "{}", stderr(&output));
+    assert!(!out.exists());
+}